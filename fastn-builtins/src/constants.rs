@@ -289,6 +289,7 @@ pub const FTD_SPECIAL_VALUE: &str = "$VALUE";
 pub const FTD_SPECIAL_CHECKED: &str = "$CHECKED";
 pub const FTD_INHERITED: &str = "inherited";
 pub const FTD_LOOP_COUNTER: &str = "LOOP.COUNTER";
+pub const FTD_LOOP_KEY: &str = "LOOP.KEY";
 pub const FTD_DEFAULT_TYPES: &str = "default-types";
 pub const FTD_DEFAULT_COLORS: &str = "default-colors";
 pub const FTD_NONE: &str = "none";
@@ -0,0 +1,624 @@
+//! The `datetime` and `date` kinds, and their functions for expressions: `ftd.format_date`,
+//! `ftd.format_time`, `ftd.format_datetime` and `ftd.relative_time`.
+//!
+//! A `datetime` is kept in UTC in RFC 3339, like `2024-01-02T03:04:05+00:00` or
+//! `2024-01-02T03:04:05.123+00:00`, and a `date` like `2024-01-02`, so two of them compare with
+//! `<`, `>` and `==` as strings do. Both are parsed from ISO 8601: `2024-01-02`,
+//! `2024-01-02T03:04`, `2024-01-02 03:04:05.123` and `2024-01-02T03:04:05+05:30`, without an
+//! offset the time is in UTC. JSON and SQL numbers are seconds since the Unix epoch.
+//!
+//! `fastn-js/js/ftd.js` parses and formats them the same way in the browser, and during server
+//! side rendering, which has no `Intl`, so neither uses it: the month names and patterns of each
+//! language are in `LOCALES` here and `locales` there, keep the two in sync. Both format in the
+//! language of the page, unless the last argument of a function names one, and in the
+//! `time-zone` of the package, UTC by default, which the formatted time ends with.
+
+use fastn_resolved::evalexpr::{EvalexprError, EvalexprResult, Value};
+
+/// What the functions format in, from the page: `language` unless an argument names one, and
+/// the time of a `datetime` at `utc_offset`, in minutes east of UTC.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Settings {
+    pub language: Option<String>,
+    pub utc_offset: i32,
+}
+
+static ISO_8601: std::sync::LazyLock<regex::Regex> = std::sync::LazyLock::new(|| {
+    regex::Regex::new(
+        r"^(\d{4})-(\d{2})-(\d{2})(?:[Tt ](\d{2}):(\d{2})(?::(\d{2})(?:\.(\d+))?)?)?\s*([Zz]|[+-]\d{2}:?\d{2})?$",
+    )
+    .unwrap()
+});
+
+/// `s` as the value of a `datetime`, `None` if `s` is not ISO 8601.
+pub fn parse_datetime(s: &str) -> Option<String> {
+    parse(s).map(|(datetime, _)| canonical(datetime))
+}
+
+/// `s` as the value of a `date`, the day of a `datetime` in UTC.
+pub fn parse_date(s: &str) -> Option<String> {
+    parse(s).map(|(datetime, _)| datetime.format("%Y-%m-%d").to_string())
+}
+
+/// The `datetime` `seconds` after the Unix epoch.
+pub fn datetime_from_timestamp(seconds: i64) -> Option<String> {
+    chrono::DateTime::from_timestamp(seconds, 0).map(|datetime| canonical(datetime.naive_utc()))
+}
+
+/// The `time-zone` of a package, `UTC` or an offset like `+05:30`, `-0800` or `+05`, in minutes
+/// east of UTC.
+pub fn parse_utc_offset(s: &str) -> Option<i32> {
+    let s = s.trim();
+    let offset = s
+        .strip_prefix("UTC")
+        .or_else(|| s.strip_prefix("utc"))
+        .unwrap_or(s);
+    if offset.is_empty() || offset.eq_ignore_ascii_case("z") {
+        return Some(0);
+    }
+
+    let sign = match offset.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let digits = offset[1..].replacen(':', "", 1);
+    if !digits.chars().all(|c| c.is_ascii_digit()) || ![2, 4].contains(&digits.len()) {
+        return None;
+    }
+    let hours: i32 = digits[..2].parse().ok()?;
+    let minutes: i32 = match digits.len() {
+        4 => digits[2..].parse().ok()?,
+        _ => 0,
+    };
+    (hours <= 14 && minutes < 60).then_some(sign * (hours * 60 + minutes))
+}
+
+/// `UTC`, or `UTC+05:30` for an offset of 330 minutes.
+fn zone(utc_offset: i32) -> String {
+    if utc_offset == 0 {
+        return "UTC".to_string();
+    }
+    let sign = if utc_offset < 0 { '-' } else { '+' };
+    let minutes = utc_offset.abs();
+    format!("UTC{sign}{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// RFC 3339 in UTC with the fraction of the second if there is one, the one of `chrono` is in
+/// milli, micro or nanoseconds. `+` sorts before `.` and the digits, so a time without a
+/// fraction is before the ones with.
+fn canonical(datetime: chrono::NaiveDateTime) -> String {
+    datetime
+        .and_utc()
+        .to_rfc3339_opts(chrono::SecondsFormat::AutoSi, false)
+}
+
+/// The time in UTC, and if `s` has one, a `date` does not.
+fn parse(s: &str) -> Option<(chrono::NaiveDateTime, bool)> {
+    let captures = ISO_8601.captures(s.trim())?;
+    let number = |i: usize| -> Option<u32> {
+        captures
+            .get(i)
+            .map(|m| m.as_str().parse().ok())
+            .unwrap_or(Some(0))
+    };
+    // the first nine digits, in nanoseconds
+    let nanoseconds = match captures.get(7) {
+        None => 0,
+        Some(fraction) => format!("{:0<9.9}", fraction.as_str()).parse().ok()?,
+    };
+
+    let date = chrono::NaiveDate::from_ymd_opt(number(1)? as i32, number(2)?, number(3)?)?;
+    let time =
+        chrono::NaiveTime::from_hms_nano_opt(number(4)?, number(5)?, number(6)?, nanoseconds)?;
+    let offset = match captures.get(8).map(|m| m.as_str()) {
+        None | Some("Z") | Some("z") => 0,
+        Some(offset) => {
+            let sign = if offset.starts_with('-') { -1 } else { 1 };
+            let digits = offset[1..].replace(':', "");
+            let hours: i64 = digits[..2].parse().ok()?;
+            let minutes: i64 = digits[2..].parse().ok()?;
+            sign * (hours * 60 + minutes)
+        }
+    };
+
+    date.and_time(time)
+        .checked_sub_signed(chrono::Duration::minutes(offset))
+        .map(|datetime| (datetime, captures.get(4).is_some()))
+}
+
+struct Locale {
+    months: [&'static str; 12],
+    /// From Sunday
+    weekdays: [&'static str; 7],
+    short_date: &'static str,
+    long_date: &'static str,
+    full_date: &'static str,
+    time: &'static str,
+    am_pm: [&'static str; 2],
+    datetime: &'static str,
+}
+
+/// The patterns have `{day}`, `{dd}` (with a leading zero), `{month}`, `{mm}`, `{month_name}`,
+/// `{year}`, `{weekday}`, `{hour}` (from 00 to 23), `{hour12}`, `{minute}`, `{am_pm}`, `{zone}`
+/// (like `UTC` or `UTC+05:30`), and `{date}` and `{time}` in `datetime`.
+const LOCALES: &[(&str, Locale)] = &[
+    (
+        "en",
+        Locale {
+            months: [
+                "January",
+                "February",
+                "March",
+                "April",
+                "May",
+                "June",
+                "July",
+                "August",
+                "September",
+                "October",
+                "November",
+                "December",
+            ],
+            weekdays: [
+                "Sunday",
+                "Monday",
+                "Tuesday",
+                "Wednesday",
+                "Thursday",
+                "Friday",
+                "Saturday",
+            ],
+            short_date: "{month}/{day}/{year}",
+            long_date: "{month_name} {day}, {year}",
+            full_date: "{weekday}, {month_name} {day}, {year}",
+            time: "{hour12}:{minute} {am_pm} {zone}",
+            am_pm: ["AM", "PM"],
+            datetime: "{date}, {time}",
+        },
+    ),
+    (
+        "es",
+        Locale {
+            months: [
+                "enero",
+                "febrero",
+                "marzo",
+                "abril",
+                "mayo",
+                "junio",
+                "julio",
+                "agosto",
+                "septiembre",
+                "octubre",
+                "noviembre",
+                "diciembre",
+            ],
+            weekdays: [
+                "domingo",
+                "lunes",
+                "martes",
+                "miércoles",
+                "jueves",
+                "viernes",
+                "sábado",
+            ],
+            short_date: "{day}/{month}/{year}",
+            long_date: "{day} de {month_name} de {year}",
+            full_date: "{weekday}, {day} de {month_name} de {year}",
+            time: "{hour}:{minute} {zone}",
+            am_pm: ["a. m.", "p. m."],
+            datetime: "{date}, {time}",
+        },
+    ),
+    (
+        "fr",
+        Locale {
+            months: [
+                "janvier",
+                "février",
+                "mars",
+                "avril",
+                "mai",
+                "juin",
+                "juillet",
+                "août",
+                "septembre",
+                "octobre",
+                "novembre",
+                "décembre",
+            ],
+            weekdays: [
+                "dimanche", "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi",
+            ],
+            short_date: "{dd}/{mm}/{year}",
+            long_date: "{day} {month_name} {year}",
+            full_date: "{weekday} {day} {month_name} {year}",
+            time: "{hour}:{minute} {zone}",
+            am_pm: ["AM", "PM"],
+            datetime: "{date} à {time}",
+        },
+    ),
+    (
+        "de",
+        Locale {
+            months: [
+                "Januar",
+                "Februar",
+                "März",
+                "April",
+                "Mai",
+                "Juni",
+                "Juli",
+                "August",
+                "September",
+                "Oktober",
+                "November",
+                "Dezember",
+            ],
+            weekdays: [
+                "Sonntag",
+                "Montag",
+                "Dienstag",
+                "Mittwoch",
+                "Donnerstag",
+                "Freitag",
+                "Samstag",
+            ],
+            short_date: "{dd}.{mm}.{year}",
+            long_date: "{day}. {month_name} {year}",
+            full_date: "{weekday}, {day}. {month_name} {year}",
+            time: "{hour}:{minute} {zone}",
+            am_pm: ["AM", "PM"],
+            datetime: "{date}, {time}",
+        },
+    ),
+    (
+        "hi",
+        Locale {
+            months: [
+                "जनवरी",
+                "फ़रवरी",
+                "मार्च",
+                "अप्रैल",
+                "मई",
+                "जून",
+                "जुलाई",
+                "अगस्त",
+                "सितंबर",
+                "अक्तूबर",
+                "नवंबर",
+                "दिसंबर",
+            ],
+            weekdays: [
+                "रविवार",
+                "सोमवार",
+                "मंगलवार",
+                "बुधवार",
+                "गुरुवार",
+                "शुक्रवार",
+                "शनिवार",
+            ],
+            short_date: "{day}/{month}/{year}",
+            long_date: "{day} {month_name} {year}",
+            full_date: "{weekday}, {day} {month_name} {year}",
+            time: "{hour12}:{minute} {am_pm} {zone}",
+            am_pm: ["am", "pm"],
+            datetime: "{date}, {time}",
+        },
+    ),
+];
+
+/// The locale of `language`, like `hi` or `en-US`, English if there is none.
+fn locale(language: Option<&str>) -> &'static Locale {
+    let language = language
+        .map(|l| {
+            l.split(['-', '_'])
+                .next()
+                .unwrap_or_default()
+                .to_lowercase()
+        })
+        .unwrap_or_default();
+    &LOCALES
+        .iter()
+        .find(|(code, _)| *code == language)
+        .unwrap_or(&LOCALES[0])
+        .1
+}
+
+fn format(
+    pattern: &str,
+    datetime: &chrono::NaiveDateTime,
+    utc_offset: i32,
+    locale: &Locale,
+) -> String {
+    use chrono::{Datelike, Timelike};
+
+    let hour12 = match datetime.hour() % 12 {
+        0 => 12,
+        h => h,
+    };
+    pattern
+        .replace("{month_name}", locale.months[datetime.month0() as usize])
+        .replace(
+            "{weekday}",
+            locale.weekdays[datetime.weekday().num_days_from_sunday() as usize],
+        )
+        .replace("{dd}", format!("{:02}", datetime.day()).as_str())
+        .replace("{day}", datetime.day().to_string().as_str())
+        .replace("{mm}", format!("{:02}", datetime.month()).as_str())
+        .replace("{month}", datetime.month().to_string().as_str())
+        .replace("{year}", datetime.year().to_string().as_str())
+        .replace("{hour12}", hour12.to_string().as_str())
+        .replace("{hour}", format!("{:02}", datetime.hour()).as_str())
+        .replace("{minute}", format!("{:02}", datetime.minute()).as_str())
+        .replace("{am_pm}", locale.am_pm[(datetime.hour() / 12) as usize])
+        .replace("{zone}", zone(utc_offset).as_str())
+}
+
+fn date_pattern(style: &str, locale: &Locale) -> EvalexprResult<&'static str> {
+    match style {
+        "short" => Ok(locale.short_date),
+        "long" => Ok(locale.long_date),
+        "full" => Ok(locale.full_date),
+        style => Err(EvalexprError::CustomMessage(format!(
+            "unknown date style `{style}`, expected `short`, `long` or `full`"
+        ))),
+    }
+}
+
+/// The `datetime` or `date` at the offset of `settings`, the offset, the locale of the last of
+/// the string arguments after it if there are `max` of them, and the others.
+fn arguments(
+    argument: &Value,
+    max: usize,
+    settings: &Settings,
+) -> EvalexprResult<(chrono::NaiveDateTime, i32, &'static Locale, Vec<String>)> {
+    let arguments = match argument {
+        Value::Tuple(arguments) => arguments.clone(),
+        argument => vec![argument.clone()],
+    };
+    if arguments.is_empty() || arguments.len() > max + 1 {
+        return Err(EvalexprError::WrongFunctionArgumentAmount {
+            expected: max + 1,
+            actual: arguments.len(),
+        });
+    }
+
+    let value = arguments[0].as_string()?;
+    let (datetime, has_time) = parse(value.as_str()).ok_or_else(|| {
+        EvalexprError::CustomMessage(format!("`{value}` is not a datetime or a date"))
+    })?;
+    // a `date` is a day, not a time in UTC
+    let utc_offset = if has_time { settings.utc_offset } else { 0 };
+    let mut rest = arguments[1..]
+        .iter()
+        .map(Value::as_string)
+        .collect::<EvalexprResult<Vec<_>>>()?;
+    let language = (rest.len() == max)
+        .then(|| rest.pop())
+        .flatten()
+        .filter(|language| !language.is_empty())
+        .or_else(|| settings.language.clone());
+    Ok((
+        datetime + chrono::Duration::minutes(utc_offset.into()),
+        utc_offset,
+        locale(language.as_deref()),
+        rest,
+    ))
+}
+
+/// `ftd.format_date(d)`, `ftd.format_date(d, style)` or `ftd.format_date(d, style, language)`,
+/// the style is `short`, `long` (the default) or `full`.
+pub fn format_date(
+    settings: &Settings,
+) -> impl Fn(&Value) -> EvalexprResult<Value> + Send + Sync + Clone + 'static {
+    let settings = settings.clone();
+    move |argument| {
+        let (datetime, utc_offset, locale, rest) = arguments(argument, 2, &settings)?;
+        let pattern = date_pattern(rest.first().map(String::as_str).unwrap_or("long"), locale)?;
+        Ok(Value::String(format(
+            pattern, &datetime, utc_offset, locale,
+        )))
+    }
+}
+
+/// `ftd.format_time(d)` or `ftd.format_time(d, language)`
+pub fn format_time(
+    settings: &Settings,
+) -> impl Fn(&Value) -> EvalexprResult<Value> + Send + Sync + Clone + 'static {
+    let settings = settings.clone();
+    move |argument| {
+        let (datetime, utc_offset, locale, _) = arguments(argument, 1, &settings)?;
+        Ok(Value::String(format(
+            locale.time,
+            &datetime,
+            utc_offset,
+            locale,
+        )))
+    }
+}
+
+/// `ftd.format_datetime(d)`, `ftd.format_datetime(d, style)` or
+/// `ftd.format_datetime(d, style, language)`, the style is the one of the date.
+pub fn format_datetime(
+    settings: &Settings,
+) -> impl Fn(&Value) -> EvalexprResult<Value> + Send + Sync + Clone + 'static {
+    let settings = settings.clone();
+    move |argument| {
+        let (datetime, utc_offset, locale, rest) = arguments(argument, 2, &settings)?;
+        let pattern = date_pattern(rest.first().map(String::as_str).unwrap_or("long"), locale)?;
+        let pattern = locale
+            .datetime
+            .replace("{date}", pattern)
+            .replace("{time}", locale.time);
+        Ok(Value::String(format(
+            pattern.as_str(),
+            &datetime,
+            utc_offset,
+            locale,
+        )))
+    }
+}
+
+/// `ftd.relative_time(d)` or `ftd.relative_time(d, language)`, like `3 days ago` in the browser.
+/// The server does not know when the page is read, so here, and in server side rendering, it is
+/// `ftd.format_datetime(d)`, which the browser replaces once the page is loaded.
+pub fn relative_time(
+    settings: &Settings,
+) -> impl Fn(&Value) -> EvalexprResult<Value> + Send + Sync + Clone + 'static {
+    let settings = settings.clone();
+    move |argument| {
+        let (datetime, utc_offset, locale, _) = arguments(argument, 1, &settings)?;
+        let pattern = locale
+            .datetime
+            .replace("{date}", locale.long_date)
+            .replace("{time}", locale.time);
+        Ok(Value::String(format(
+            pattern.as_str(),
+            &datetime,
+            utc_offset,
+            locale,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fastn_resolved::evalexpr::{EvalexprResult, Value};
+
+    fn s(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    #[test]
+    fn parse() {
+        let p = super::parse_datetime;
+        assert_eq!(
+            p("2024-01-02"),
+            Some("2024-01-02T00:00:00+00:00".to_string())
+        );
+        assert_eq!(
+            p("2024-01-02T03:04"),
+            Some("2024-01-02T03:04:00+00:00".to_string())
+        );
+        assert_eq!(
+            p("2024-01-02 03:04:05.123"),
+            Some("2024-01-02T03:04:05.123+00:00".to_string())
+        );
+        assert_eq!(
+            p("2024-01-02T03:04:05.1234567891Z"),
+            Some("2024-01-02T03:04:05.123456789+00:00".to_string())
+        );
+        assert_eq!(
+            p("2024-01-02T03:04:05+05:30"),
+            Some("2024-01-01T21:34:05+00:00".to_string())
+        );
+        assert_eq!(
+            p("2024-01-02T23:04:05-0100"),
+            Some("2024-01-03T00:04:05+00:00".to_string())
+        );
+        assert!(p("2024-01-02T03:04:05Z") < p("2024-01-02T03:04:05.5Z"));
+        assert!(p("2024-01-02T03:04:05.25Z") < p("2024-01-02T03:04:05.5Z"));
+        assert_eq!(p("2024-02-30"), None);
+        assert_eq!(p("yesterday"), None);
+        assert_eq!(
+            super::parse_date("2024-01-02T23:04:05-01:00"),
+            Some("2024-01-03".to_string())
+        );
+        assert_eq!(
+            super::datetime_from_timestamp(1704164645),
+            Some("2024-01-02T03:04:05+00:00".to_string())
+        );
+
+        let o = super::parse_utc_offset;
+        assert_eq!(o("UTC"), Some(0));
+        assert_eq!(o("+05:30"), Some(330));
+        assert_eq!(o("UTC-0800"), Some(-480));
+        assert_eq!(o("+05"), Some(300));
+        assert_eq!(o("05:30"), None);
+        assert_eq!(o("Asia/Kolkata"), None);
+    }
+
+    #[test]
+    fn format() {
+        let d = s("2024-01-02T15:04:05Z");
+        let en = super::Settings::default();
+        let f = |function: &dyn Fn(&Value) -> EvalexprResult<Value>, arguments: &[&str]| {
+            let mut arguments = arguments.iter().map(|a| s(a)).collect::<Vec<_>>();
+            arguments.insert(0, d.clone());
+            function(&Value::Tuple(arguments)).unwrap()
+        };
+
+        assert_eq!(f(&super::format_date(&en), &[]), s("January 2, 2024"));
+        assert_eq!(f(&super::format_date(&en), &["short"]), s("1/2/2024"));
+        assert_eq!(
+            f(&super::format_date(&en), &["full"]),
+            s("Tuesday, January 2, 2024")
+        );
+        assert_eq!(
+            f(&super::format_date(&en), &["short", "fr"]),
+            s("02/01/2024")
+        );
+        assert_eq!(
+            f(&super::format_date(&en), &["long", "de-DE"]),
+            s("2. Januar 2024")
+        );
+        assert_eq!(
+            f(&super::format_date(&en), &["full", "es"]),
+            s("martes, 2 de enero de 2024")
+        );
+        assert_eq!(f(&super::format_time(&en), &[]), s("3:04 PM UTC"));
+        assert_eq!(f(&super::format_time(&en), &["de"]), s("15:04 UTC"));
+        assert_eq!(
+            f(&super::format_datetime(&en), &[]),
+            s("January 2, 2024, 3:04 PM UTC")
+        );
+        assert_eq!(
+            f(&super::format_datetime(&en), &["long", "fr"]),
+            s("2 janvier 2024 à 15:04 UTC")
+        );
+        assert_eq!(
+            f(&super::relative_time(&en), &[]),
+            s("January 2, 2024, 3:04 PM UTC")
+        );
+        assert_eq!(
+            f(&super::format_date(&en), &["long", "xx"]),
+            s("January 2, 2024")
+        );
+        let format_date = super::format_date(&en);
+        assert!(format_date(&Value::Tuple(vec![d.clone(), s("medium")])).is_err());
+        assert!(format_date(&s("soon")).is_err());
+    }
+
+    #[test]
+    fn settings() {
+        let settings = super::Settings {
+            language: Some("hi".to_string()),
+            utc_offset: 330,
+        };
+        let d = s("2024-01-02T20:04:05Z");
+
+        assert_eq!(
+            super::format_datetime(&settings)(&d).unwrap(),
+            s("3 जनवरी 2024, 1:34 am UTC+05:30")
+        );
+        assert_eq!(
+            super::format_time(&settings)(&Value::Tuple(vec![d.clone(), s("en")])).unwrap(),
+            s("1:34 AM UTC+05:30")
+        );
+        assert_eq!(
+            super::format_date(&settings)(&Value::Tuple(vec![d, s("short"), s("")])).unwrap(),
+            s("3/1/2024")
+        );
+        // a date is not moved to another day
+        let settings = super::Settings {
+            language: None,
+            utc_offset: -480,
+        };
+        assert_eq!(
+            super::format_date(&settings)(&s("2024-01-02")).unwrap(),
+            s("January 2, 2024")
+        );
+    }
+}
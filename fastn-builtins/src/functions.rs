@@ -0,0 +1,393 @@
+//! The string, list, map and number functions of `ftd`, like `ftd.upper(name)`, for the expressions
+//! the interpreter evaluates. `fastn-js/js/ftd.js` has the same functions for the browser, keep
+//! the two in sync: `ftd/t/functions.json` has the cases both are tested against.
+//!
+//! Strings are indexed by character, lists by item. A negative index counts from the end and
+//! indices past either end are clamped, like `slice` of javascript.
+
+use fastn_resolved::evalexpr::{EvalexprError, EvalexprResult, Value};
+
+pub fn upper(argument: &Value) -> EvalexprResult<Value> {
+    Ok(Value::String(string(argument)?.to_uppercase()))
+}
+
+pub fn lower(argument: &Value) -> EvalexprResult<Value> {
+    Ok(Value::String(string(argument)?.to_lowercase()))
+}
+
+pub fn trim(argument: &Value) -> EvalexprResult<Value> {
+    Ok(Value::String(string(argument)?.trim().to_string()))
+}
+
+/// `ftd.split(s, separator)`, an empty separator splits `s` into its characters.
+pub fn split(argument: &Value) -> EvalexprResult<Value> {
+    let arguments = argument.as_fixed_len_tuple(2)?;
+    let (s, separator) = (string(&arguments[0])?, string(&arguments[1])?);
+    let parts: Vec<Value> = if separator.is_empty() {
+        s.chars().map(|c| Value::String(c.to_string())).collect()
+    } else {
+        s.split(separator.as_str())
+            .map(|part| Value::String(part.to_string()))
+            .collect()
+    };
+    Ok(Value::Tuple(parts))
+}
+
+/// `ftd.join(list, separator)`
+pub fn join(argument: &Value) -> EvalexprResult<Value> {
+    let arguments = argument.as_fixed_len_tuple(2)?;
+    let separator = string(&arguments[1])?;
+    let items = list(&arguments[0])?
+        .iter()
+        .map(string)
+        .collect::<EvalexprResult<Vec<_>>>()?;
+    Ok(Value::String(items.join(separator.as_str())))
+}
+
+/// `ftd.replace(s, from, to)` replaces every `from`, an empty `from` replaces nothing.
+pub fn replace(argument: &Value) -> EvalexprResult<Value> {
+    let arguments = argument.as_fixed_len_tuple(3)?;
+    let (s, from) = (string(&arguments[0])?, string(&arguments[1])?);
+    if from.is_empty() {
+        return Ok(Value::String(s));
+    }
+    Ok(Value::String(
+        s.replace(from.as_str(), string(&arguments[2])?.as_str()),
+    ))
+}
+
+/// `ftd.substring(s, start)` or `ftd.substring(s, start, end)`
+pub fn substring(argument: &Value) -> EvalexprResult<Value> {
+    let (s, start, end) = sliced(argument)?;
+    let chars = string(&s)?.chars().collect::<Vec<_>>();
+    let (start, end) = range(chars.len(), start, end);
+    Ok(Value::String(chars[start..end].iter().collect()))
+}
+
+/// `ftd.contains(s, part)`, or `ftd.contains(list, item)`
+pub fn contains(argument: &Value) -> EvalexprResult<Value> {
+    let arguments = argument.as_fixed_len_tuple(2)?;
+    if let Value::String(s) = &arguments[0] {
+        return Ok(Value::Boolean(s.contains(string(&arguments[1])?.as_str())));
+    }
+    Ok(Value::Boolean(
+        index_of_(&arguments[0], &arguments[1])?.is_some(),
+    ))
+}
+
+pub fn starts_with(argument: &Value) -> EvalexprResult<Value> {
+    let arguments = argument.as_fixed_len_tuple(2)?;
+    Ok(Value::Boolean(
+        string(&arguments[0])?.starts_with(string(&arguments[1])?.as_str()),
+    ))
+}
+
+pub fn ends_with(argument: &Value) -> EvalexprResult<Value> {
+    let arguments = argument.as_fixed_len_tuple(2)?;
+    Ok(Value::Boolean(
+        string(&arguments[0])?.ends_with(string(&arguments[1])?.as_str()),
+    ))
+}
+
+/// `ftd.format_number(n)` groups the digits of the integer part by thousands with `,`,
+/// `ftd.format_number(n, decimals)` rounds to `decimals` digits, halves away from zero, first.
+/// Numbers are never written with an exponent, `1e21` is `1,000,000,000,000,000,000,000`.
+pub fn format_number(argument: &Value) -> EvalexprResult<Value> {
+    let (number, decimals) = match argument {
+        Value::Tuple(_) => {
+            let arguments = argument.as_fixed_len_tuple(2)?;
+            let decimals = arguments[1].as_int()?;
+            if decimals < 0 {
+                return Err(EvalexprError::CustomMessage(format!(
+                    "ftd.format_number: decimals can not be negative, found {decimals}"
+                )));
+            }
+            (arguments[0].clone(), Some(decimals as usize))
+        }
+        number => (number.clone(), None),
+    };
+
+    let n = number.as_number()?;
+    if !n.is_finite() {
+        return Err(EvalexprError::CustomMessage(format!(
+            "ftd.format_number: can not format {n}"
+        )));
+    }
+    // the shortest digits that read back as the number, never an exponent, like `to_string`
+    let formatted = match (number, decimals) {
+        (Value::Int(n), None) => n.to_string(),
+        (_, None) => n.to_string(),
+        (_, Some(decimals)) => {
+            let scale = 10f64.powi(decimals as i32);
+            let rounded = if (n.abs() * scale).is_finite() {
+                n.signum() * (n.abs() * scale).round() / scale
+            } else {
+                n
+            };
+            let rounded = rounded.to_string();
+            match rounded.split_once('.') {
+                _ if decimals == 0 => rounded,
+                Some((integer, fraction)) => format!("{integer}.{fraction:0<decimals$}"),
+                None => format!("{rounded}.{}", "0".repeat(decimals)),
+            }
+        }
+    };
+
+    let (sign, digits) = match formatted.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", formatted.as_str()),
+    };
+    let (integer, fraction) = match digits.split_once('.') {
+        Some((integer, fraction)) => (integer, format!(".{fraction}")),
+        None => (digits, String::new()),
+    };
+    let mut grouped = String::new();
+    for (i, c) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    // `-0` after rounding a small negative number
+    let is_zero = !grouped
+        .chars()
+        .chain(fraction.chars())
+        .any(|c| c.is_ascii_digit() && c != '0');
+    let sign = if is_zero { "" } else { sign };
+    Ok(Value::String(format!("{sign}{grouped}{fraction}")))
+}
+
+/// `ftd.slice(list, start)` or `ftd.slice(list, start, end)`
+pub fn slice(argument: &Value) -> EvalexprResult<Value> {
+    let (items, start, end) = sliced(argument)?;
+    let items = list(&items)?;
+    let (start, end) = range(items.len(), start, end);
+    Ok(Value::Tuple(items[start..end].to_vec()))
+}
+
+pub fn reverse(argument: &Value) -> EvalexprResult<Value> {
+    let mut items = list(argument)?;
+    items.reverse();
+    Ok(Value::Tuple(items))
+}
+
+/// Sorts strings, or numbers, in ascending order.
+pub fn sort(argument: &Value) -> EvalexprResult<Value> {
+    let mut items = list(argument)?;
+    if items.iter().all(Value::is_number) {
+        items.sort_by(|a, b| {
+            a.as_number()
+                .unwrap()
+                .partial_cmp(&b.as_number().unwrap())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+    } else if items.iter().all(Value::is_string) {
+        items.sort_by_key(|v| v.as_string().unwrap());
+    } else {
+        return Err(EvalexprError::CustomMessage(
+            "ftd.sort: can only sort a list of strings or of numbers".to_string(),
+        ));
+    }
+    Ok(Value::Tuple(items))
+}
+
+/// `ftd.index_of(list, item)`, `-1` if `item` is not in `list`.
+pub fn index_of(argument: &Value) -> EvalexprResult<Value> {
+    let arguments = argument.as_fixed_len_tuple(2)?;
+    Ok(Value::Int(
+        index_of_(&arguments[0], &arguments[1])?
+            .map(|i| i as i64)
+            .unwrap_or(-1),
+    ))
+}
+
+fn index_of_(items: &Value, item: &Value) -> EvalexprResult<Option<usize>> {
+    Ok(list(items)?.iter().position(|v| same(v, item)))
+}
+
+/// `1` and `1.0` are the same, like they are in javascript.
+fn same(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (a, b) if a.is_number() && b.is_number() => a.as_number().ok() == b.as_number().ok(),
+        (a, b) => a == b,
+    }
+}
+
+/// `ftd.get(map, key)`, `NULL` if `map` has no `key`.
+pub fn get(argument: &Value) -> EvalexprResult<Value> {
+    let arguments = argument.as_fixed_len_tuple(2)?;
+    let key = arguments[1].as_string()?;
+    Ok(map(&arguments[0])?
+        .into_iter()
+        .find_map(|(k, v)| k.eq(&key).then_some(v))
+        .unwrap_or(Value::Empty))
+}
+
+/// `ftd.has_key(map, key)`
+pub fn has_key(argument: &Value) -> EvalexprResult<Value> {
+    let arguments = argument.as_fixed_len_tuple(2)?;
+    let key = arguments[1].as_string()?;
+    Ok(Value::Boolean(
+        map(&arguments[0])?.iter().any(|(k, _)| k.eq(&key)),
+    ))
+}
+
+/// `ftd.keys(map)`, in the order of the entries.
+pub fn keys(argument: &Value) -> EvalexprResult<Value> {
+    Ok(Value::Tuple(
+        map(argument)?
+            .into_iter()
+            .map(|(k, _)| Value::String(k))
+            .collect(),
+    ))
+}
+
+/// A map is a tuple of `(key, value)` tuples, `NULL` is the empty map.
+fn map(value: &Value) -> EvalexprResult<Vec<(String, Value)>> {
+    list(value)?
+        .iter()
+        .map(|entry| {
+            let entry = entry.as_fixed_len_tuple(2)?;
+            Ok((entry[0].as_string()?, entry[1].clone()))
+        })
+        .collect()
+}
+
+/// A list is a tuple, `NULL` is the empty list.
+fn list(value: &Value) -> EvalexprResult<Vec<Value>> {
+    match value {
+        Value::Empty => Ok(vec![]),
+        value => value.as_tuple(),
+    }
+}
+
+/// A number or a boolean as a string, `NULL` is the empty string.
+fn string(value: &Value) -> EvalexprResult<String> {
+    match value {
+        Value::String(s) => Ok(s.to_string()),
+        Value::Empty => Ok(String::new()),
+        Value::Int(_) | Value::Float(_) | Value::Boolean(_) => Ok(value.to_string()),
+        value => Err(EvalexprError::expected_string(value.clone())),
+    }
+}
+
+/// The subject, start and end of `ftd.substring` and `ftd.slice`.
+fn sliced(argument: &Value) -> EvalexprResult<(Value, i64, Option<i64>)> {
+    let arguments = argument.as_tuple()?;
+    match arguments.as_slice() {
+        [subject, start] => Ok((subject.clone(), start.as_int()?, None)),
+        [subject, start, end] => Ok((subject.clone(), start.as_int()?, Some(end.as_int()?))),
+        _ => Err(EvalexprError::WrongFunctionArgumentAmount {
+            expected: 3,
+            actual: arguments.len(),
+        }),
+    }
+}
+
+fn range(len: usize, start: i64, end: Option<i64>) -> (usize, usize) {
+    let index = |i: i64| {
+        if i < 0 {
+            (len as i64 + i).max(0) as usize
+        } else {
+            (i as usize).min(len)
+        }
+    };
+    let start = index(start);
+    let end = end.map(index).unwrap_or(len);
+    (start, end.max(start))
+}
+
+#[cfg(test)]
+mod tests {
+    use fastn_resolved::evalexpr::{ContextWithMutableFunctions, HashMapContext, Value};
+
+    #[track_caller]
+    fn p(expression: &str, expected: Value) {
+        let mut context = HashMapContext::new();
+        for (key, function) in fastn_builtins::default_functions() {
+            context.set_function(key, function).unwrap();
+        }
+        assert_eq!(
+            fastn_resolved::evalexpr::eval_with_context_mut(expression, &mut context).unwrap(),
+            expected,
+            "{expression}"
+        );
+    }
+
+    fn s(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    fn strings(v: &[&str]) -> Value {
+        Value::Tuple(v.iter().map(|v| s(v)).collect())
+    }
+
+    #[test]
+    fn strings_() {
+        p(r#"ftd.upper("Hello")"#, s("HELLO"));
+        p(r#"ftd.lower("Hello")"#, s("hello"));
+        p(r#"ftd.trim("  a b ")"#, s("a b"));
+        p(r#"ftd.split("a,b,,c", ",")"#, strings(&["a", "b", "", "c"]));
+        p(
+            r#"ftd.split("héllo", "")"#,
+            strings(&["h", "é", "l", "l", "o"]),
+        );
+        p(r#"ftd.join(("a", 1, true), "-")"#, s("a-1-true"));
+        p(r#"ftd.replace("a-b-c", "-", "+")"#, s("a+b+c"));
+        p(r#"ftd.substring("héllo", 1, 3)"#, s("él"));
+        p(r#"ftd.substring("hello", -3)"#, s("llo"));
+        p(r#"ftd.substring("hello", 3, 1)"#, s(""));
+        p(r#"ftd.substring("hello", 2, 100)"#, s("llo"));
+        p(r#"ftd.contains("hello", "ell")"#, Value::Boolean(true));
+        p(r#"ftd.starts_with("hello", "he")"#, Value::Boolean(true));
+        p(r#"ftd.ends_with("hello", "he")"#, Value::Boolean(false));
+    }
+
+    #[test]
+    fn format_number() {
+        p("ftd.format_number(1234567)", s("1,234,567"));
+        p("ftd.format_number(-1234.5)", s("-1,234.5"));
+        p("ftd.format_number(999)", s("999"));
+        p("ftd.format_number(1234.5678, 2)", s("1,234.57"));
+        p("ftd.format_number(2.5, 0)", s("3"));
+        p("ftd.format_number(-2.5, 0)", s("-3"));
+        p("ftd.format_number(-0.001, 2)", s("0.00"));
+        p("ftd.format_number(1000000, 2)", s("1,000,000.00"));
+    }
+
+    #[test]
+    fn lists() {
+        p(
+            r#"ftd.slice(("a", "b", "c", "d"), 1, 3)"#,
+            strings(&["b", "c"]),
+        );
+        p(r#"ftd.slice(("a", "b", "c", "d"), -1)"#, strings(&["d"]));
+        p(r#"ftd.reverse(("a", "b", "c"))"#, strings(&["c", "b", "a"]));
+        p(r#"ftd.sort(("b", "c", "a"))"#, strings(&["a", "b", "c"]));
+        p(
+            "ftd.sort((10, 2.5, 1))",
+            Value::Tuple(vec![Value::Int(1), Value::Float(2.5), Value::Int(10)]),
+        );
+        p(r#"ftd.index_of(("a", "b"), "b")"#, Value::Int(1));
+        p(r#"ftd.index_of(("a", "b"), "c")"#, Value::Int(-1));
+        p("ftd.contains((1, 2), 2.0)", Value::Boolean(true));
+        p("ftd.reverse(())", Value::Tuple(vec![]));
+    }
+
+    #[test]
+    fn maps() {
+        let labels = r#"(("en", "Hello"), ("hi", "Namaste"))"#;
+        p(&format!(r#"ftd.get({labels}, "hi")"#), s("Namaste"));
+        p(&format!(r#"ftd.get({labels}, "fr")"#), Value::Empty);
+        p(
+            &format!(r#"ftd.has_key({labels}, "en")"#),
+            Value::Boolean(true),
+        );
+        p(
+            &format!(r#"ftd.has_key({labels}, "fr")"#),
+            Value::Boolean(false),
+        );
+        p(&format!("ftd.keys({labels})"), strings(&["en", "hi"]));
+        p(r#"ftd.has_key((), "en")"#, Value::Boolean(false));
+    }
+}
@@ -4,6 +4,8 @@
 extern crate self as fastn_builtins;
 
 pub mod constants;
+pub mod datetime;
+pub mod functions;
 
 pub type Map<T> = std::collections::BTreeMap<String, T>;
 use fastn_resolved::evalexpr::ContextWithMutableFunctions;
@@ -37,8 +39,23 @@ enable light mode in the application.
 4. `enable_system_mode` - This function takes no arguments and returns an empty value. It is used to
 enable system mode in the application, which means the application will use the system's default
 color scheme.
+
+5. `upper`, `lower`, `trim`, `split`, `join`, `replace`, `substring`, `contains`, `starts_with`,
+`ends_with`, `format_number`, `slice`, `reverse`, `sort` and `index_of` - The string, list and
+number functions of `fastn_builtins::functions`, which `fastn-js/js/ftd.js` implements for the
+browser.
+
+6. `format_date`, `format_time`, `format_datetime` and `relative_time` - The functions of the
+`datetime` and `date` kinds, see `fastn_builtins::datetime`. They format in the language and
+the time zone of `settings`.
 */
 pub fn default_functions() -> Map<fastn_resolved::evalexpr::Function> {
+    default_functions_with(&Default::default())
+}
+
+pub fn default_functions_with(
+    settings: &fastn_builtins::datetime::Settings,
+) -> Map<fastn_resolved::evalexpr::Function> {
     use fastn_resolved::evalexpr::*;
 
     std::iter::IntoIterator::into_iter([
@@ -122,6 +139,94 @@ pub fn default_functions() -> Map<fastn_resolved::evalexpr::Function> {
                 }
             }),
         ),
+        (
+            "ftd.upper".to_string(),
+            Function::new(fastn_builtins::functions::upper),
+        ),
+        (
+            "ftd.lower".to_string(),
+            Function::new(fastn_builtins::functions::lower),
+        ),
+        (
+            "ftd.trim".to_string(),
+            Function::new(fastn_builtins::functions::trim),
+        ),
+        (
+            "ftd.split".to_string(),
+            Function::new(fastn_builtins::functions::split),
+        ),
+        (
+            "ftd.join".to_string(),
+            Function::new(fastn_builtins::functions::join),
+        ),
+        (
+            "ftd.replace".to_string(),
+            Function::new(fastn_builtins::functions::replace),
+        ),
+        (
+            "ftd.substring".to_string(),
+            Function::new(fastn_builtins::functions::substring),
+        ),
+        (
+            "ftd.contains".to_string(),
+            Function::new(fastn_builtins::functions::contains),
+        ),
+        (
+            "ftd.starts_with".to_string(),
+            Function::new(fastn_builtins::functions::starts_with),
+        ),
+        (
+            "ftd.ends_with".to_string(),
+            Function::new(fastn_builtins::functions::ends_with),
+        ),
+        (
+            "ftd.format_number".to_string(),
+            Function::new(fastn_builtins::functions::format_number),
+        ),
+        (
+            "ftd.slice".to_string(),
+            Function::new(fastn_builtins::functions::slice),
+        ),
+        (
+            "ftd.reverse".to_string(),
+            Function::new(fastn_builtins::functions::reverse),
+        ),
+        (
+            "ftd.sort".to_string(),
+            Function::new(fastn_builtins::functions::sort),
+        ),
+        (
+            "ftd.index_of".to_string(),
+            Function::new(fastn_builtins::functions::index_of),
+        ),
+        (
+            "ftd.get".to_string(),
+            Function::new(fastn_builtins::functions::get),
+        ),
+        (
+            "ftd.has_key".to_string(),
+            Function::new(fastn_builtins::functions::has_key),
+        ),
+        (
+            "ftd.keys".to_string(),
+            Function::new(fastn_builtins::functions::keys),
+        ),
+        (
+            "ftd.format_date".to_string(),
+            Function::new(fastn_builtins::datetime::format_date(settings)),
+        ),
+        (
+            "ftd.format_time".to_string(),
+            Function::new(fastn_builtins::datetime::format_time(settings)),
+        ),
+        (
+            "ftd.format_datetime".to_string(),
+            Function::new(fastn_builtins::datetime::format_datetime(settings)),
+        ),
+        (
+            "ftd.relative_time".to_string(),
+            Function::new(fastn_builtins::datetime::relative_time(settings)),
+        ),
         (
             "enable_dark_mode".to_string(),
             Function::new(|_| Ok(Value::Empty)),
@@ -139,9 +244,15 @@ pub fn default_functions() -> Map<fastn_resolved::evalexpr::Function> {
 }
 
 pub fn default_context(
+) -> Result<fastn_resolved::evalexpr::HashMapContext, fastn_resolved::evalexpr::EvalexprError> {
+    default_context_with(&Default::default())
+}
+
+pub fn default_context_with(
+    settings: &fastn_builtins::datetime::Settings,
 ) -> Result<fastn_resolved::evalexpr::HashMapContext, fastn_resolved::evalexpr::EvalexprError> {
     let mut context = fastn_resolved::evalexpr::HashMapContext::new();
-    for (key, function) in default_functions() {
+    for (key, function) in default_functions_with(settings) {
         context.set_function(key, function)?;
     }
     Ok(context)
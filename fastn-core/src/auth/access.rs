@@ -0,0 +1,310 @@
+//! `readers` of the sitemap and dynamic urls, see `fastn_core::sitemap::readers`, enforced by
+//! `fastn serve`.
+//!
+//! A reader is an identity of a user: one of its verified emails. `everyone` lets anyone read,
+//! to make a document public in a section with readers, and `logged-in` anyone who is logged in.
+//! `fastn serve --identities alice@example.com,bob` serves every request as a user with these
+//! identities, for tests.
+//!
+//! The readers of a url apply to its `/-/<package-name>/` alias too, and to the document it is
+//! rendered from, whatever url that is read at. `sitemap.xml` and `fastn build` only list what
+//! everyone can read, feeds and search results in `fastn serve` what the requester can read.
+
+pub const EVERYONE: &str = "everyone";
+pub const LOGGED_IN: &str = "logged-in";
+
+#[derive(Debug, PartialEq)]
+pub enum Access {
+    Allowed,
+    /// Nobody is logged in
+    LoginRequired,
+    Denied,
+}
+
+/// `identities` is `None` if nobody is logged in.
+pub fn access(readers: &[String], identities: Option<&[String]>) -> Access {
+    if readers.is_empty() || readers.iter().any(|r| r == EVERYONE) {
+        return Access::Allowed;
+    }
+
+    let Some(identities) = identities else {
+        return Access::LoginRequired;
+    };
+    if readers
+        .iter()
+        .any(|r| r == LOGGED_IN || identities.contains(r))
+    {
+        Access::Allowed
+    } else {
+        Access::Denied
+    }
+}
+
+/// Anyone, logged in or not, can read `path`.
+pub fn is_public(package: &fastn_core::Package, path: &str) -> bool {
+    access(&url_readers(package, path), None) == Access::Allowed
+}
+
+/// Anyone can read `module`, the name of a document like `example.com/guide/install`: at its url
+/// and as the document of any url. The modules of dependencies are not protected by readers.
+pub fn is_public_module(package: &fastn_core::Package, module: &str) -> bool {
+    let Some(path) = module
+        .strip_prefix(package.name.trim_end_matches('/'))
+        .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+        .map(|rest| rest.trim_matches('/'))
+    else {
+        return true;
+    };
+
+    let path = match path {
+        "index" => "",
+        path => path.strip_suffix("/index").unwrap_or(path),
+    };
+    let (url, documents) = match path {
+        "" => ("/".to_string(), vec!["index.ftd".to_string()]),
+        path => (
+            format!("/{path}/"),
+            vec![format!("{path}.ftd"), format!("{path}/index.ftd")],
+        ),
+    };
+    is_public(package, url.as_str())
+        && documents.iter().all(|document| {
+            fastn_core::sitemap::document_readers(package, document)
+                .iter()
+                .all(|readers| access(readers, None) == Access::Allowed)
+        })
+}
+
+/// `None` if the request can read `path` and the `document` it is rendered from, or the
+/// response to send instead: a redirect to the login page if nobody is logged in and the
+/// package has `fastn.auth`, 401 if it does not, and 403 if the user is not a reader.
+///
+/// `document` is the path of the file in the package, like `guide/install.ftd`, `None` if it is
+/// not a document of the package. The response of a request with readers is never cached.
+pub(crate) async fn check(
+    req_config: &mut fastn_core::RequestConfig,
+    path: &str,
+    document: Option<&str>,
+) -> fastn_core::Result<Option<fastn_core::http::Response>> {
+    let package = &req_config.config.package;
+    let mut readers = vec![url_readers(package, path)];
+    if let Some(document) = document {
+        readers.push(url_readers(
+            package,
+            fastn_core::utils::id_to_path(document).as_str(),
+        ));
+        readers.extend(fastn_core::sitemap::document_readers(package, document));
+    }
+    readers.retain(|readers| !readers.is_empty());
+    if readers.is_empty() {
+        return Ok(None);
+    }
+    req_config.response_is_cacheable = false;
+
+    let identities = identities(req_config).await?;
+    let access = readers
+        .iter()
+        .map(|readers| access(readers, identities))
+        .find(|access| *access != Access::Allowed)
+        .unwrap_or(Access::Allowed);
+    if access == Access::Allowed {
+        return Ok(None);
+    }
+
+    tracing::warn!(
+        msg = "access denied",
+        path = path,
+        document = document,
+        readers = ?readers,
+        identities = ?identities,
+        access = ?access,
+    );
+
+    Ok(Some(match access {
+        Access::LoginRequired if req_config.config.package.auth.is_some() => {
+            fastn_core::http::redirect_with_code(
+                format!(
+                    "{}login/?{}",
+                    fastn_core::auth::PREFIX,
+                    url::form_urlencoded::Serializer::new(String::new())
+                        .append_pair("next", req_config.request.full_path().as_str())
+                        .finish()
+                ),
+                307,
+            )
+        }
+        Access::LoginRequired => {
+            actix_web::HttpResponse::Unauthorized().body(format!("log in to read {path}\n"))
+        }
+        _ => actix_web::HttpResponse::Forbidden().body(format!("you can not read {path}\n")),
+    }))
+}
+
+/// Keeps the `items` whose `url` the request can read, or everyone can if there is no
+/// `req_config`, like in `fastn build`.
+pub(crate) async fn retain_readable<T>(
+    package: &fastn_core::Package,
+    req_config: Option<&fastn_core::RequestConfig>,
+    items: &mut Vec<T>,
+    url: impl Fn(&T) -> &str,
+) -> fastn_core::Result<()> {
+    let readers: Vec<Vec<String>> = items
+        .iter()
+        .map(|item| url_readers(package, url(item)))
+        .collect();
+    if readers.iter().all(Vec::is_empty) {
+        return Ok(());
+    }
+
+    let identities = match req_config {
+        Some(req_config) => identities(req_config).await?,
+        None => None,
+    };
+    let mut readers = readers.into_iter();
+    items.retain(|_| {
+        readers
+            .next()
+            .is_some_and(|readers| access(&readers, identities) == Access::Allowed)
+    });
+    Ok(())
+}
+
+/// The readers of `path`, an url of the package or its `/-/<package-name>/` alias.
+fn url_readers(package: &fastn_core::Package, path: &str) -> Vec<String> {
+    let path = path.trim_start_matches('/');
+    let alias = format!("-/{}", package.name.trim_matches('/'));
+    let path = match path.strip_prefix(alias.as_str()) {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => rest,
+        _ => path,
+    };
+    fastn_core::sitemap::readers(
+        package,
+        format!("/{}", path.trim_start_matches('/')).as_str(),
+    )
+}
+
+/// The verified emails of the user of the request, `None` if nobody is logged in. Read once per
+/// request, the first time a url with readers is asked for.
+async fn identities(
+    req_config: &fastn_core::RequestConfig,
+) -> fastn_core::Result<Option<&[String]>> {
+    req_config
+        .identities
+        .get_or_try_init(|| async {
+            if let Some(identities) = fastn_core::utils::parse_from_cli("--identities") {
+                return Ok(Some(
+                    identities
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|i| !i.is_empty())
+                        .map(str::to_string)
+                        .collect(),
+                ));
+            }
+
+            let Some(sid) = req_config.session_id() else {
+                return Ok(None);
+            };
+            let rows = req_config
+                .config
+                .ds
+                .sql_query(
+                    req_config.config.get_db_url().await.as_str(),
+                    r#"
+                    SELECT fastn_user.id, fastn_user.data FROM fastn_user
+                    JOIN fastn_session ON fastn_session.uid = fastn_user.id
+                    WHERE
+                        fastn_session.id = $1
+                        AND (fastn_session.expires_at IS NULL OR fastn_session.expires_at > $2)
+                    "#,
+                    vec![
+                        sid.as_str().into(),
+                        ft_sys_shared::SqliteRawValue::Integer(super::now()),
+                    ],
+                )
+                .await
+                .map_err(super::AuthError::from)?;
+
+            let user = rows
+                .into_iter()
+                .next()
+                .map(super::User::from_row)
+                .transpose()?;
+            Ok::<_, fastn_core::Error>(user.map(|user| user.data.email.verified_emails))
+        })
+        .await
+        .map(Option::as_deref)
+}
+
+#[cfg(test)]
+mod tests {
+    fn strings(v: &[&str]) -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn access() {
+        use super::Access;
+
+        let alice = strings(&["alice", "alice@example.com"]);
+        let bob = strings(&["bob"]);
+        let team = strings(&["alice@example.com"]);
+
+        assert_eq!(super::access(&[], None), Access::Allowed);
+        assert_eq!(super::access(&team, None), Access::LoginRequired);
+        assert_eq!(super::access(&team, Some(&alice)), Access::Allowed);
+        assert_eq!(super::access(&team, Some(&bob)), Access::Denied);
+        assert_eq!(
+            super::access(&strings(&["logged-in"]), Some(&bob)),
+            Access::Allowed
+        );
+        assert_eq!(
+            super::access(&strings(&["logged-in"]), None),
+            Access::LoginRequired
+        );
+        assert_eq!(
+            super::access(&strings(&["everyone"]), None),
+            Access::Allowed
+        );
+    }
+
+    #[test]
+    fn url_readers() {
+        let mut package = fastn_core::Package::new("example.com");
+        package.sitemap = Some(fastn_core::sitemap::Sitemap {
+            sections: vec![fastn_core::sitemap::section::Section {
+                id: "/team/".to_string(),
+                readers: strings(&["alice@example.com"]),
+                subsections: vec![fastn_core::sitemap::section::Subsection {
+                    toc: vec![fastn_core::sitemap::toc::TocItem {
+                        id: "/team/notes/".to_string(),
+                        document: Some("notes.ftd".to_string()),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            readers: vec![],
+            writers: vec![],
+        });
+
+        let team = strings(&["alice@example.com"]);
+        assert_eq!(super::url_readers(&package, "/team/"), team);
+        assert_eq!(super::url_readers(&package, "/-/example.com/team/"), team);
+        assert_eq!(super::url_readers(&package, "-/example.com/team/"), team);
+        assert!(super::url_readers(&package, "/-/example.company/team/").is_empty());
+        assert!(super::is_public(&package, "/"));
+        assert!(!super::is_public(&package, "team/"));
+
+        assert!(super::is_public_module(&package, "example.com"));
+        assert!(super::is_public_module(&package, "example.com/index"));
+        assert!(super::is_public_module(&package, "example.com/guide"));
+        assert!(!super::is_public_module(&package, "example.com/team"));
+        assert!(!super::is_public_module(&package, "example.com/team/index"));
+        // read at /team/notes/
+        assert!(!super::is_public_module(&package, "example.com/notes"));
+        assert!(super::is_public_module(&package, "example.com/reindex"));
+        assert!(super::is_public_module(&package, "example.company/team"));
+    }
+}
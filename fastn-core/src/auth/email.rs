@@ -0,0 +1,149 @@
+//! Where the emails of `fastn_core::auth` go.
+//!
+//! `fastn_core::Config::add_email_sink` sets the sink, without one `FASTN_EMAIL_SINK` picks it:
+//! `queue` inserts them into `fastn_email_queue` for a mailer to send, `stdout` prints them, for
+//! development, and anything else is the path of a file the emails are appended to. Without a
+//! sink no email is sent, the pages that would send one fail instead.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Email {
+    pub from_address: String,
+    pub from_name: String,
+    pub to_address: String,
+    pub subject: String,
+    pub body_text: String,
+    pub body_html: String,
+    /// `verify-email` or `magic-link`
+    pub kind: String,
+}
+
+impl Email {
+    /// The email as the stdout and file sinks write it.
+    pub fn to_text(&self) -> String {
+        format!(
+            "From: {} <{}>\nTo: {}\nSubject: {}\n\n{}\n",
+            self.from_name, self.from_address, self.to_address, self.subject, self.body_text
+        )
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+pub trait EmailSink: std::fmt::Debug + Send + Sync {
+    async fn send(&self, config: &fastn_core::Config, email: &Email) -> Result<(), EmailError>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum EmailError {
+    #[error("io error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("sql error: {0}")]
+    SqlError(#[from] fastn_utils::SqlError),
+    #[error("no email sink, set FASTN_EMAIL_SINK to `queue`, `stdout` or the path of a file")]
+    NoSink,
+}
+
+#[derive(Debug)]
+pub struct Stdout;
+
+#[async_trait::async_trait(?Send)]
+impl EmailSink for Stdout {
+    async fn send(&self, _config: &fastn_core::Config, email: &Email) -> Result<(), EmailError> {
+        println!("{}", email.to_text());
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct File {
+    pub path: std::path::PathBuf,
+}
+
+#[async_trait::async_trait(?Send)]
+impl EmailSink for File {
+    async fn send(&self, _config: &fastn_core::Config, email: &Email) -> Result<(), EmailError> {
+        use std::io::Write;
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path.as_path())?;
+        writeln!(file, "{}", email.to_text())?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct Queue;
+
+#[async_trait::async_trait(?Send)]
+impl EmailSink for Queue {
+    async fn send(&self, config: &fastn_core::Config, email: &Email) -> Result<(), EmailError> {
+        let now = chrono::Utc::now().timestamp_nanos_opt().unwrap();
+        config
+            .ds
+            .sql_execute(
+                config.get_db_url().await.as_str(),
+                r#"
+                INSERT INTO fastn_email_queue
+                (
+                    from_address, from_name, to_address, subject, body_text, body_html,
+                    created_at, updated_at, sent_at, mkind, status
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $7, 0, $8, 'pending')
+                "#,
+                vec![
+                    email.from_address.as_str().into(),
+                    email.from_name.as_str().into(),
+                    email.to_address.as_str().into(),
+                    email.subject.as_str().into(),
+                    email.body_text.as_str().into(),
+                    email.body_html.as_str().into(),
+                    ft_sys_shared::SqliteRawValue::Integer(now),
+                    email.kind.as_str().into(),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+pub(crate) async fn sink(
+    config: &fastn_core::Config,
+) -> Result<std::sync::Arc<dyn EmailSink>, EmailError> {
+    if let Some(sink) = config.email_sink.as_ref() {
+        return Ok(sink.clone());
+    }
+
+    match config.ds.env("FASTN_EMAIL_SINK").await {
+        Ok(sink) if sink == "queue" => Ok(std::sync::Arc::new(Queue)),
+        Ok(sink) if sink == "stdout" => Ok(std::sync::Arc::new(Stdout)),
+        Ok(sink) if !sink.is_empty() => Ok(std::sync::Arc::new(File { path: sink.into() })),
+        _ => Err(EmailError::NoSink),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn to_text() {
+        let email = super::Email {
+            from_address: "noreply@localhost".to_string(),
+            from_name: "fastn".to_string(),
+            to_address: "alice@example.com".to_string(),
+            subject: "Verify your email".to_string(),
+            body_text: "https://example.com/-/auth/verify-email/?code=abc".to_string(),
+            body_html: String::new(),
+            kind: "verify-email".to_string(),
+        };
+        pretty_assertions::assert_eq!(
+            email.to_text(),
+            indoc::indoc! {"
+                From: fastn <noreply@localhost>
+                To: alice@example.com
+                Subject: Verify your email
+
+                https://example.com/-/auth/verify-email/?code=abc
+            "}
+        );
+    }
+}
@@ -0,0 +1,927 @@
+//! Sign-up and login, for packages with `-- fastn.auth:` in `FASTN.ftd`. The pages are at
+//! `/-/auth/`:
+//!
+//! - `signup/` creates a `fastn_user` and emails a link to `verify-email/?code=`, which marks
+//!   the email as verified and logs in
+//! - `login/` takes the email or username and the password, of a user with a verified email
+//! - `magic-link/` emails a link to `magic-link/?code=`, which logs in without the password
+//! - `logout/` ends the session, it works without `fastn.auth` too
+//!
+//! Logging in creates a `fastn_session`, its id is the session cookie, which
+//! `fastn_ds::DocumentStore::ud` reads the user of. The emailed links are to the
+//! `canonical-url` of the package, never to the host the request was sent to.
+//!
+//! The pages are plain html forms. The same fields can be posted as json, the response is json
+//! then: the `redirect` to follow, or the errors of each field, see `fastn_core::http::user_err`.
+
+pub mod access;
+pub mod email;
+mod password;
+
+pub const PREFIX: &str = "/-/auth/";
+
+const VERIFY_EMAIL: &str = "verify-email";
+const MAGIC_LINK: &str = "magic-link";
+const VERIFY_EMAIL_MINUTES: i64 = 24 * 60;
+const MAGIC_LINK_MINUTES: i64 = 15;
+const MIN_PASSWORD_LENGTH: usize = 8;
+const NANOS_PER_SECOND: i64 = 1_000_000_000;
+
+#[derive(thiserror::Error, Debug)]
+pub enum AuthError {
+    #[error("sql error: {0}")]
+    SqlError(#[from] fastn_utils::SqlError),
+    #[error("serde error: {0}")]
+    SerdeError(#[from] serde_json::Error),
+    #[error("email error: {0}")]
+    EmailError(#[from] email::EmailError),
+    #[error("user {0} not found")]
+    UserNotFound(String),
+}
+
+pub async fn handle(
+    config: &fastn_core::Config,
+    req: &fastn_core::http::Request,
+) -> Option<fastn_core::Result<fastn_core::http::Response>> {
+    let route = req.path().strip_prefix(PREFIX)?.trim_end_matches('/');
+    if route == "logout" {
+        return Some(logout(config, req).await);
+    }
+
+    let auth = config.package.auth.as_ref()?;
+    let json = req
+        .content_type()
+        .is_some_and(|mime| mime.essence_str() == mime_guess::mime::APPLICATION_JSON);
+    let context = Context {
+        config,
+        req,
+        auth,
+        db: config.get_db_url().await,
+        fields: fields(req, json),
+        json,
+    };
+
+    Some(match (route, req.method()) {
+        ("signup", "GET") if auth.signup => Ok(context.form(&SIGNUP, &[])),
+        ("signup", "POST") if auth.signup => context.signup().await,
+        ("login", "GET") => Ok(context.form(&LOGIN, &[])),
+        ("login", "POST") => context.login().await,
+        (MAGIC_LINK, "GET") if auth.magic_link && context.query("code").is_some() => {
+            context.magic_link_login().await
+        }
+        (MAGIC_LINK, "GET") if auth.magic_link => Ok(context.form(&SEND_MAGIC_LINK, &[])),
+        (MAGIC_LINK, "POST") if auth.magic_link => context.send_magic_link().await,
+        (VERIFY_EMAIL, "GET") => context.verify_email().await,
+        _ => return None,
+    })
+}
+
+async fn logout(
+    config: &fastn_core::Config,
+    req: &fastn_core::http::Request,
+) -> fastn_core::Result<fastn_core::http::Response> {
+    if let (Some(_), Some(sid)) = (
+        config.package.auth.as_ref(),
+        req.cookie(fastn_core::http::SESSION_COOKIE_NAME),
+    ) {
+        config
+            .ds
+            .sql_execute(
+                config.get_db_url().await.as_str(),
+                "DELETE FROM fastn_session WHERE id = $1",
+                vec![sid.as_str().into()],
+            )
+            .await
+            .map_err(AuthError::from)?;
+    }
+
+    Ok(fastn_core::commands::serve::clear_sid2(req))
+}
+
+struct Form {
+    path: &'static str,
+    title: &'static str,
+    submit: &'static str,
+    fields: &'static [Field],
+}
+
+struct Field {
+    name: &'static str,
+    label: &'static str,
+    kind: &'static str,
+    required: bool,
+}
+
+const SIGNUP: Form = Form {
+    path: "signup",
+    title: "Create an account",
+    submit: "Sign up",
+    fields: &[
+        Field {
+            name: "name",
+            label: "Name",
+            kind: "text",
+            required: true,
+        },
+        Field {
+            name: "email",
+            label: "Email",
+            kind: "email",
+            required: true,
+        },
+        Field {
+            name: "username",
+            label: "Username (optional)",
+            kind: "text",
+            required: false,
+        },
+        Field {
+            name: "password",
+            label: "Password",
+            kind: "password",
+            required: true,
+        },
+    ],
+};
+
+const LOGIN: Form = Form {
+    path: "login",
+    title: "Log in",
+    submit: "Log in",
+    fields: &[
+        Field {
+            name: "username",
+            label: "Email or username",
+            kind: "text",
+            required: true,
+        },
+        Field {
+            name: "password",
+            label: "Password",
+            kind: "password",
+            required: true,
+        },
+    ],
+};
+
+const SEND_MAGIC_LINK: Form = Form {
+    path: MAGIC_LINK,
+    title: "Log in with a link",
+    submit: "Email me a link",
+    fields: &[Field {
+        name: "email",
+        label: "Email",
+        kind: "email",
+        required: true,
+    }],
+};
+
+/// `data` of `fastn_user`, the keys other systems store in it are kept.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct UserData {
+    #[serde(default)]
+    email: Emails,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    password: Option<String>,
+    #[serde(flatten)]
+    other: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct Emails {
+    #[serde(default)]
+    emails: Vec<String>,
+    #[serde(default)]
+    verified_emails: Vec<String>,
+}
+
+struct User {
+    id: i64,
+    data: UserData,
+}
+
+impl User {
+    /// From the `id, data` columns of `fastn_user`.
+    fn from_row(row: Vec<serde_json::Value>) -> Result<User, AuthError> {
+        let mut row = row.into_iter();
+        let id = serde_json::from_value(row.next().unwrap_or_default())?;
+        let data: String = serde_json::from_value(row.next().unwrap_or_default())?;
+        Ok(User {
+            id,
+            data: serde_json::from_str(data.as_str())?,
+        })
+    }
+}
+
+struct Context<'a> {
+    config: &'a fastn_core::Config,
+    req: &'a fastn_core::http::Request,
+    auth: &'a fastn_core::package::Auth,
+    db: String,
+    /// The posted form
+    fields: std::collections::HashMap<String, String>,
+    /// The form was posted as json, the response is json too
+    json: bool,
+}
+
+impl Context<'_> {
+    async fn signup(&self) -> fastn_core::Result<fastn_core::http::Response> {
+        let name = self.field("name").trim();
+        let email = self.field("email").trim().to_lowercase();
+        let username = self.field("username").trim();
+        let password = self.field("password");
+
+        let mut errors = vec![];
+        if name.is_empty() {
+            errors.push(("name", "Enter your name".to_string()));
+        }
+        if !is_email(email.as_str()) {
+            errors.push(("email", "Enter a valid email address".to_string()));
+        } else if self.find_user(email.as_str()).await?.is_some() {
+            errors.push(("email", "This email is already registered".to_string()));
+        }
+        // a username could be someone else's email otherwise
+        if username.contains('@') {
+            errors.push(("username", "A username can not contain @".to_string()));
+        } else if !username.is_empty() && self.find_user(username).await?.is_some() {
+            errors.push(("username", "This username is taken".to_string()));
+        }
+        if password.chars().count() < MIN_PASSWORD_LENGTH {
+            errors.push((
+                "password",
+                format!("Use at least {MIN_PASSWORD_LENGTH} characters"),
+            ));
+        }
+        if !errors.is_empty() {
+            return self.errors(&SIGNUP, errors);
+        }
+        let sink = email::sink(self.config).await.map_err(AuthError::from)?;
+
+        let identity = if username.is_empty() {
+            email.as_str()
+        } else {
+            username
+        };
+        let data = UserData {
+            email: Emails {
+                emails: vec![email.clone()],
+                verified_emails: vec![],
+            },
+            password: Some(password::hash(password)),
+            other: Default::default(),
+        };
+        self.config
+            .ds
+            .sql_execute(
+                self.db.as_str(),
+                r#"
+                INSERT INTO fastn_user (name, identity, data, created_at, updated_at)
+                VALUES ($1, $2, $3, $4, $4)
+                "#,
+                vec![
+                    name.into(),
+                    identity.into(),
+                    serde_json::to_string(&data)?.as_str().into(),
+                    ft_sys_shared::SqliteRawValue::Integer(now()),
+                ],
+            )
+            .await
+            .map_err(AuthError::from)?;
+
+        let user = self
+            .find_user(identity)
+            .await?
+            .ok_or_else(|| AuthError::UserNotFound(identity.to_string()))?;
+        self.send_link(sink.as_ref(), user.id, email.as_str(), VERIFY_EMAIL)
+            .await?;
+        // no session until the email is verified, whoever signed up may not own it
+        self.message(
+            fastn_core::http::StatusCode::OK,
+            "Check your email",
+            format!("We have sent a link to {email}, open it to verify your email and log in.")
+                .as_str(),
+        )
+    }
+
+    async fn login(&self) -> fastn_core::Result<fastn_core::http::Response> {
+        let login = self.field("username").trim();
+        let login = if login.contains('@') {
+            login.to_lowercase()
+        } else {
+            login.to_string()
+        };
+
+        match self.find_user(login.as_str()).await? {
+            Some(user)
+                if user
+                    .data
+                    .password
+                    .as_deref()
+                    .is_some_and(|hashed| password::verify(self.field("password"), hashed)) =>
+            {
+                if user.data.email.verified_emails.is_empty() {
+                    return self.errors(
+                        &LOGIN,
+                        vec![(
+                            "username",
+                            "Verify your email first, open the link we have emailed you"
+                                .to_string(),
+                        )],
+                    );
+                }
+                self.logged_in(user.id).await
+            }
+            _ => self.errors(
+                &LOGIN,
+                vec![(
+                    "password",
+                    "The email, username or password is incorrect".to_string(),
+                )],
+            ),
+        }
+    }
+
+    async fn send_magic_link(&self) -> fastn_core::Result<fastn_core::http::Response> {
+        let email = self.field("email").trim().to_lowercase();
+        if !is_email(email.as_str()) {
+            return self.errors(
+                &SEND_MAGIC_LINK,
+                vec![("email", "Enter a valid email address".to_string())],
+            );
+        }
+
+        // the response is the same either way, so it does not tell who has an account
+        let sink = email::sink(self.config).await.map_err(AuthError::from)?;
+        if let Some(user) = self.find_user(email.as_str()).await? {
+            self.send_link(sink.as_ref(), user.id, email.as_str(), MAGIC_LINK)
+                .await?;
+        }
+
+        self.message(
+            fastn_core::http::StatusCode::OK,
+            "Check your email",
+            format!("If {email} has an account, we have sent it a link to log in.").as_str(),
+        )
+    }
+
+    async fn magic_link_login(&self) -> fastn_core::Result<fastn_core::http::Response> {
+        match self.use_code(MAGIC_LINK).await? {
+            // the link was sent to the email, so it is verified now
+            Some((uid, email)) => {
+                self.verify(uid, email.as_str(), MAGIC_LINK).await?;
+                self.logged_in(uid).await
+            }
+            None => self.invalid_link(),
+        }
+    }
+
+    async fn verify_email(&self) -> fastn_core::Result<fastn_core::http::Response> {
+        match self.use_code(VERIFY_EMAIL).await? {
+            Some((uid, email)) => {
+                self.verify(uid, email.as_str(), VERIFY_EMAIL).await?;
+                self.logged_in(uid).await
+            }
+            None => self.invalid_link(),
+        }
+    }
+
+    /// `identity` or the (first) email of a user. `data` is matched with `LIKE` so that this works
+    /// on every database, the users it finds are then checked here.
+    async fn find_user(&self, login: &str) -> Result<Option<User>, AuthError> {
+        let rows = self
+            .config
+            .ds
+            .sql_query(
+                self.db.as_str(),
+                r#"
+                SELECT id, data, identity FROM fastn_user
+                WHERE identity = $1 OR data LIKE $2
+                "#,
+                vec![
+                    login.into(),
+                    format!("%{}%", serde_json::to_string(login)?)
+                        .as_str()
+                        .into(),
+                ],
+            )
+            .await?;
+
+        for mut row in rows {
+            let identity = row.pop();
+            let user = User::from_row(row)?;
+            if identity.as_ref().and_then(|v| v.as_str()) == Some(login)
+                || user.data.email.emails.first().map(String::as_str) == Some(login)
+            {
+                return Ok(Some(user));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Marks `email` verified. The password of a user whose first email is verified by a magic
+    /// link is removed: it was set by whoever signed up, who may not own the email.
+    async fn verify(&self, uid: i64, email: &str, kind: &str) -> Result<(), AuthError> {
+        let rows = self
+            .config
+            .ds
+            .sql_query(
+                self.db.as_str(),
+                "SELECT id, data FROM fastn_user WHERE id = $1",
+                vec![ft_sys_shared::SqliteRawValue::Integer(uid)],
+            )
+            .await?;
+        let mut user = rows
+            .into_iter()
+            .next()
+            .map(User::from_row)
+            .transpose()?
+            .ok_or_else(|| AuthError::UserNotFound(uid.to_string()))?;
+        if user.data.email.verified_emails.iter().any(|e| e == email) {
+            return Ok(());
+        }
+
+        if kind == MAGIC_LINK && user.data.email.verified_emails.is_empty() {
+            user.data.password = None;
+        }
+        user.data.email.verified_emails.push(email.to_string());
+        self.config
+            .ds
+            .sql_execute(
+                self.db.as_str(),
+                "UPDATE fastn_user SET data = $1, updated_at = $2 WHERE id = $3",
+                vec![
+                    serde_json::to_string(&user.data)?.as_str().into(),
+                    ft_sys_shared::SqliteRawValue::Integer(now()),
+                    ft_sys_shared::SqliteRawValue::Integer(uid),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Emails a link with a new code of `kind`, only the hash of the code is stored.
+    async fn send_link(
+        &self,
+        sink: &dyn email::EmailSink,
+        uid: i64,
+        email: &str,
+        kind: &str,
+    ) -> Result<(), AuthError> {
+        let code = password::random_hex(32);
+        let minutes = match kind {
+            MAGIC_LINK => MAGIC_LINK_MINUTES,
+            _ => VERIFY_EMAIL_MINUTES,
+        };
+        let now = now();
+        self.config
+            .ds
+            .sql_execute(
+                self.db.as_str(),
+                r#"
+                INSERT INTO fastn_auth_token (id, uid, kind, email, created_at, expires_at)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                "#,
+                vec![
+                    fastn_core::utils::generate_hash(code.as_str())
+                        .as_str()
+                        .into(),
+                    ft_sys_shared::SqliteRawValue::Integer(uid),
+                    kind.into(),
+                    email.into(),
+                    ft_sys_shared::SqliteRawValue::Integer(now),
+                    ft_sys_shared::SqliteRawValue::Integer(now + minutes * 60 * NANOS_PER_SECOND),
+                ],
+            )
+            .await?;
+
+        // not the `Host` of the request, anyone can send one that makes the link theirs
+        let base_url = fastn_core::sitemap::xml::base_url(&self.config.package);
+        let link = format!(
+            "{}{PREFIX}{kind}/?{}",
+            base_url.trim_end_matches('/'),
+            url::form_urlencoded::Serializer::new(String::new())
+                .append_pair("code", code.as_str())
+                .append_pair("next", self.next().as_str())
+                .finish()
+        );
+        let host = base_url
+            .split_once("://")
+            .map_or(base_url.as_str(), |(_, v)| v)
+            .trim_end_matches('/');
+        let (subject, text) = match kind {
+            MAGIC_LINK => (
+                "Your login link",
+                format!(
+                    "Open this link to log in to {host}, it works once, in the next \
+                    {MAGIC_LINK_MINUTES} minutes:"
+                ),
+            ),
+            _ => (
+                "Verify your email",
+                format!("Open this link to verify your email for {host}:"),
+            ),
+        };
+
+        let email = email::Email {
+            from_address: self.auth.from_address.to_string(),
+            from_name: self.auth.from_name.to_string(),
+            to_address: email.to_string(),
+            subject: subject.to_string(),
+            body_text: format!("{text}\n\n{link}"),
+            body_html: format!(
+                r#"<p>{}</p><p><a href="{link}">{link}</a></p>"#,
+                fastn_core::sitemap::xml::escape(text.as_str()),
+                link = fastn_core::sitemap::xml::escape(link.as_str())
+            ),
+            kind: kind.to_string(),
+        };
+        sink.send(self.config, &email).await?;
+        Ok(())
+    }
+
+    /// The user and the email of the `code` of an emailed link, a code works only once.
+    async fn use_code(&self, kind: &str) -> Result<Option<(i64, String)>, AuthError> {
+        let Some(code) = self.query("code") else {
+            return Ok(None);
+        };
+        let id = fastn_core::utils::generate_hash(code);
+
+        let rows = self
+            .config
+            .ds
+            .sql_query(
+                self.db.as_str(),
+                r#"
+                SELECT uid, email FROM fastn_auth_token
+                WHERE id = $1 AND kind = $2 AND expires_at > $3
+                "#,
+                vec![
+                    id.as_str().into(),
+                    kind.into(),
+                    ft_sys_shared::SqliteRawValue::Integer(now()),
+                ],
+            )
+            .await?;
+        let Some(row) = rows.into_iter().next() else {
+            return Ok(None);
+        };
+
+        // Two requests with the same code can both find it, only the one that deletes it uses it.
+        let deleted = self
+            .config
+            .ds
+            .sql_execute(
+                self.db.as_str(),
+                "DELETE FROM fastn_auth_token WHERE id = $1",
+                vec![id.as_str().into()],
+            )
+            .await?;
+        if deleted.first().and_then(|row| row.first()) != Some(&serde_json::json!(1)) {
+            return Ok(None);
+        }
+
+        let mut row = row.into_iter();
+        Ok(Some((
+            serde_json::from_value(row.next().unwrap_or_default())?,
+            serde_json::from_value(row.next().unwrap_or_default())?,
+        )))
+    }
+
+    async fn logged_in(&self, uid: i64) -> fastn_core::Result<fastn_core::http::Response> {
+        let sid = password::random_hex(32);
+        let max_age = self.auth.session_days * 24 * 60 * 60;
+        let now = now();
+        self.config
+            .ds
+            .sql_execute(
+                self.db.as_str(),
+                r#"
+                INSERT INTO fastn_session (id, uid, data, created_at, updated_at, expires_at)
+                VALUES ($1, $2, '{}', $3, $3, $4)
+                "#,
+                vec![
+                    sid.as_str().into(),
+                    ft_sys_shared::SqliteRawValue::Integer(uid),
+                    ft_sys_shared::SqliteRawValue::Integer(now),
+                    ft_sys_shared::SqliteRawValue::Integer(now + max_age * NANOS_PER_SECOND),
+                ],
+            )
+            .await
+            .map_err(AuthError::from)?;
+
+        // `Lax`, so the session works when following a link from another site, like the magic
+        // link in an email. No `Domain`, the cookie is for this host only, and it is `Secure`
+        // except for local development over http.
+        let cookie = actix_web::cookie::Cookie::build(ft_sys_shared::SESSION_KEY, sid)
+            .path("/")
+            .http_only(true)
+            .secure(!is_local(self.req.connection_info.host()))
+            .max_age(actix_web::cookie::time::Duration::seconds(max_age))
+            .same_site(actix_web::cookie::SameSite::Lax)
+            .finish();
+
+        self.redirect(Some(cookie))
+    }
+
+    /// To `next`. Safari ignores a cookie set by a redirect, so it is a meta-refresh if there is
+    /// a `cookie` to set, see `clear_sid2()`.
+    fn redirect(
+        &self,
+        cookie: Option<actix_web::cookie::Cookie>,
+    ) -> fastn_core::Result<fastn_core::http::Response> {
+        let next = self.next();
+        let mut resp = if self.json {
+            fastn_core::http::api_ok(serde_json::json!({ "redirect": next }))?
+        } else if cookie.is_some() {
+            actix_web::HttpResponse::Ok()
+                .content_type(mime_guess::mime::TEXT_HTML_UTF_8)
+                .body(format!(
+                    r#"<meta http-equiv="refresh" content="0; url={}" />"#,
+                    fastn_core::sitemap::xml::escape(next.as_str())
+                ))
+        } else {
+            fastn_core::http::redirect_with_code(next, 303)
+        };
+
+        if let Some(cookie) = cookie {
+            resp.headers_mut().append(
+                actix_web::http::header::SET_COOKIE,
+                actix_web::http::header::HeaderValue::from_str(cookie.to_string().as_str())
+                    .unwrap(),
+            );
+        }
+        Ok(resp)
+    }
+
+    fn invalid_link(&self) -> fastn_core::Result<fastn_core::http::Response> {
+        self.message(
+            fastn_core::http::StatusCode::BAD_REQUEST,
+            "Invalid link",
+            "This link has expired or was already used.",
+        )
+    }
+
+    fn message(
+        &self,
+        status: fastn_core::http::StatusCode,
+        title: &str,
+        message: &str,
+    ) -> fastn_core::Result<fastn_core::http::Response> {
+        if self.json && status.is_success() {
+            return Ok(fastn_core::http::api_ok(
+                serde_json::json!({ "message": message }),
+            )?);
+        }
+        if self.json {
+            return fastn_core::http::user_err(
+                vec![("message".to_string(), vec![message.to_string()])],
+                status,
+            );
+        }
+
+        Ok(page(
+            status,
+            title,
+            format!("<p>{}</p>", fastn_core::sitemap::xml::escape(message)).as_str(),
+        ))
+    }
+
+    fn errors(
+        &self,
+        form: &Form,
+        errors: Vec<(&str, String)>,
+    ) -> fastn_core::Result<fastn_core::http::Response> {
+        if self.json {
+            let mut by_field: Vec<(String, Vec<String>)> = vec![];
+            for (field, error) in errors {
+                match by_field.iter_mut().find(|(f, _)| f.as_str() == field) {
+                    Some((_, messages)) => messages.push(error),
+                    None => by_field.push((field.to_string(), vec![error])),
+                }
+            }
+            return fastn_core::http::user_err(by_field, fastn_core::http::StatusCode::BAD_REQUEST);
+        }
+
+        let mut resp = self.form(form, errors.as_slice());
+        *resp.status_mut() = fastn_core::http::StatusCode::BAD_REQUEST;
+        Ok(resp)
+    }
+
+    /// The form, filled with what was posted, except the password.
+    fn form(&self, form: &Form, errors: &[(&str, String)]) -> fastn_core::http::Response {
+        use fastn_core::sitemap::xml::escape;
+
+        let mut html = format!(r#"<form method="post" action="{PREFIX}{}/">"#, form.path);
+        for field in form.fields {
+            let value = match field.kind {
+                "password" => "",
+                _ => self.field(field.name),
+            };
+            html.push_str(
+                format!(
+                    r#"<label>{}<input name="{}" type="{}" value="{}"{}></label>"#,
+                    field.label,
+                    field.name,
+                    field.kind,
+                    escape(value),
+                    if field.required { " required" } else { "" },
+                )
+                .as_str(),
+            );
+            for (_, error) in errors.iter().filter(|(name, _)| *name == field.name) {
+                html.push_str(format!(r#"<p class="error">{}</p>"#, escape(error)).as_str());
+            }
+        }
+        html.push_str(
+            format!(
+                r#"<input name="next" type="hidden" value="{}"><button>{}</button></form>"#,
+                escape(self.next().as_str()),
+                form.submit
+            )
+            .as_str(),
+        );
+
+        let next = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("next", self.next().as_str())
+            .finish();
+        for (path, label, shown) in [
+            ("login", "Log in with a password", true),
+            ("signup", "Create an account", self.auth.signup),
+            (MAGIC_LINK, "Log in with a link", self.auth.magic_link),
+        ] {
+            if shown && path != form.path {
+                html.push_str(
+                    format!(
+                        r#"<p><a href="{PREFIX}{path}/?{}">{label}</a></p>"#,
+                        escape(&next)
+                    )
+                    .as_str(),
+                );
+            }
+        }
+
+        page(fastn_core::http::StatusCode::OK, form.title, html.as_str())
+    }
+
+    fn field(&self, name: &str) -> &str {
+        self.fields
+            .get(name)
+            .map(String::as_str)
+            .unwrap_or_default()
+    }
+
+    fn query(&self, name: &str) -> Option<&str> {
+        self.req.query().get(name).and_then(|v| v.as_str())
+    }
+
+    /// Where to go after logging in, only a path on this site.
+    fn next(&self) -> String {
+        let next = match self.fields.get("next") {
+            Some(next) => next.as_str(),
+            None => self.query("next").unwrap_or_default(),
+        };
+        safe_next(next).to_string()
+    }
+}
+
+fn fields(
+    req: &fastn_core::http::Request,
+    json: bool,
+) -> std::collections::HashMap<String, String> {
+    if !json {
+        return url::form_urlencoded::parse(req.body())
+            .into_owned()
+            .collect();
+    }
+
+    serde_json::from_slice::<std::collections::HashMap<String, serde_json::Value>>(req.body())
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(k, v)| match v {
+            serde_json::Value::String(v) => Some((k, v)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// `/` instead of a url of another site, `//example.com` included.
+fn safe_next(next: &str) -> &str {
+    if next.starts_with('/') && !next.starts_with("//") && !next.starts_with("/\\") {
+        next
+    } else {
+        "/"
+    }
+}
+
+/// `localhost` or a loopback address, with or without a port.
+fn is_local(host: &str) -> bool {
+    let host = match host.rsplit_once(':') {
+        Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => host,
+        _ => host,
+    };
+    host == "localhost"
+        || host.ends_with(".localhost")
+        || host
+            .trim_matches(['[', ']'])
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+fn is_email(email: &str) -> bool {
+    match email.split_once('@') {
+        Some((user, domain)) => {
+            !user.is_empty()
+                && !domain.contains('@')
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !email.contains(char::is_whitespace)
+        }
+        None => false,
+    }
+}
+
+fn page(
+    status: fastn_core::http::StatusCode,
+    title: &str,
+    content: &str,
+) -> fastn_core::http::Response {
+    actix_web::HttpResponse::build(status)
+        .content_type(mime_guess::mime::TEXT_HTML_UTF_8)
+        .body(format!(
+            r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<style>
+body {{ font-family: sans-serif; display: flex; justify-content: center; padding: 48px 16px; }}
+main {{ width: 100%; max-width: 360px; }}
+label {{ display: block; margin-bottom: 16px; }}
+input {{ display: block; box-sizing: border-box; width: 100%; padding: 8px; margin-top: 4px; }}
+button {{ width: 100%; padding: 8px; margin-bottom: 16px; }}
+.error {{ color: #b00020; margin-top: -8px; }}
+</style>
+</head>
+<body><main><h1>{title}</h1>{content}</main></body>
+</html>
+"#
+        ))
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp_nanos_opt().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn safe_next() {
+        assert_eq!(super::safe_next("/blog/?page=2"), "/blog/?page=2");
+        assert_eq!(super::safe_next(""), "/");
+        assert_eq!(super::safe_next("https://example.com/"), "/");
+        assert_eq!(super::safe_next("//example.com/"), "/");
+        assert_eq!(super::safe_next("/\\example.com/"), "/");
+    }
+
+    #[test]
+    fn is_local() {
+        assert!(super::is_local("localhost:8000"));
+        assert!(super::is_local("127.0.0.1:8000"));
+        assert!(super::is_local("[::1]:8000"));
+        assert!(!super::is_local("example.com"));
+        assert!(!super::is_local("localhost.example.com"));
+    }
+
+    #[test]
+    fn is_email() {
+        assert!(super::is_email("alice@example.com"));
+        assert!(!super::is_email("alice"));
+        assert!(!super::is_email("@example.com"));
+        assert!(!super::is_email("alice@localhost"));
+        assert!(!super::is_email("alice@example.com."));
+        assert!(!super::is_email("alice smith@example.com"));
+    }
+
+    #[test]
+    fn user_data() {
+        let mut data: super::UserData = serde_json::from_str(
+            r#"{"email": {"emails": ["alice@example.com"]}, "github": {"login": "alice"}}"#,
+        )
+        .unwrap();
+        data.email
+            .verified_emails
+            .push("alice@example.com".to_string());
+        assert_eq!(
+            serde_json::to_value(&data).unwrap(),
+            serde_json::json!({
+                "email": {
+                    "emails": ["alice@example.com"],
+                    "verified_emails": ["alice@example.com"],
+                },
+                "github": {"login": "alice"},
+            })
+        );
+    }
+}
@@ -0,0 +1,84 @@
+//! PBKDF2-HMAC-SHA256 password hashes, stored as `pbkdf2_sha256$<iterations>$<salt>$<hash>`, and
+//! the random ids of sessions and emailed codes.
+
+const ALGORITHM: &str = "pbkdf2_sha256";
+const ITERATIONS: u32 = 100_000;
+
+pub(crate) fn hash(password: &str) -> String {
+    hash_with(password, random_hex(16).as_str(), ITERATIONS)
+}
+
+/// `false` for a hash in any other format too.
+pub(crate) fn verify(password: &str, hashed: &str) -> bool {
+    let mut parts = hashed.split('$');
+    let (Some(ALGORITHM), Some(iterations), Some(salt), Some(_), None) = (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) else {
+        return false;
+    };
+    let Ok(iterations @ 1..) = iterations.parse() else {
+        return false;
+    };
+
+    constant_time_eq(
+        hash_with(password, salt, iterations).as_bytes(),
+        hashed.as_bytes(),
+    )
+}
+
+/// `bytes` random bytes, hex encoded.
+pub(crate) fn random_hex(bytes: usize) -> String {
+    use rand::RngCore;
+
+    let mut buffer = vec![0; bytes];
+    rand::thread_rng().fill_bytes(buffer.as_mut_slice());
+    hex(buffer.as_slice())
+}
+
+fn hash_with(password: &str, salt: &str, iterations: u32) -> String {
+    let mut hash = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<sha2::Sha256>(
+        password.as_bytes(),
+        salt.as_bytes(),
+        iterations,
+        &mut hash,
+    );
+    format!("{ALGORITHM}${iterations}${salt}${}", hex(hash.as_slice()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn hash_with() {
+        // the first block of the PBKDF2-HMAC-SHA256 test vector of RFC 7914
+        assert_eq!(
+            super::hash_with("passwd", "salt", 1),
+            "pbkdf2_sha256$1$salt$55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc"
+        );
+    }
+
+    #[test]
+    fn verify() {
+        let hashed = super::hash_with("hunter22", "abcd", 10);
+        assert!(hashed.starts_with("pbkdf2_sha256$10$abcd$"));
+        assert!(super::verify("hunter22", hashed.as_str()));
+        assert!(!super::verify("hunter23", hashed.as_str()));
+        assert!(!super::verify("hunter22", "hunter22"));
+        assert!(!super::verify(
+            "hunter22",
+            hashed.replace("$10$", "$11$").as_str()
+        ));
+    }
+}
@@ -80,6 +80,11 @@ pub async fn build(
     }
 
     if !test {
+        // Left out of test builds, which compare `.build` with a checked in copy: `lastmod` in
+        // sitemap.xml and undated feed items come from file modification times.
+        fastn_core::sitemap::xml::write(config, &build_dir, preview_session_id).await?;
+        fastn_core::feed::write(config, &build_dir, preview_session_id).await?;
+        fastn_core::search::write(config, &build_dir, preview_session_id).await?;
         config.download_fonts(&None).await?;
     }
 
@@ -759,7 +764,7 @@ pub async fn default_build_files(
     ds: &fastn_ds::DocumentStore,
 ) -> fastn_core::Result<()> {
     if ftd_edition.is_2023() {
-        let default_ftd_js_content = ftd::js::all_js_without_test(package_name);
+        let default_ftd_js_content = fastn_core::shared_js::default_ftd_js(package_name);
         let hashed_ftd_js_name = fastn_core::utils::hashed_default_ftd_js(package_name);
         let save_default_ftd_js = base_path.join(hashed_ftd_js_name);
         fastn_core::utils::update(&save_default_ftd_js, default_ftd_js_content.as_bytes(), ds)
@@ -1,3 +1,7 @@
+//! `fastn check`, a lint pass over the source of the package: links and images that go
+//! nowhere, imports and definitions nothing uses, and a sitemap that does not match the files.
+//! `post_build_check` is the check of `.build` that `fastn build --check-build` runs.
+
 pub const INDEX_FILE: &str = "index.html";
 pub const BUILD_FOLDER: &str = ".build";
 pub const IGNORED_DIRECTORIES: [&str; 4] = ["-", "images", "static", "assets"];
@@ -68,3 +72,790 @@ async fn check_index_in_folders(
 fn is_ignored_directory(path: &camino::Utf8PathBuf) -> bool {
     IGNORED_DIRECTORIES.iter().any(|dir| path.ends_with(dir))
 }
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Format {
+    #[default]
+    Human,
+    Json,
+}
+
+impl std::str::FromStr for Format {
+    type Err = fastn_core::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Format::Human),
+            "json" => Ok(Format::Json),
+            t => fastn_core::usage_error(format!(
+                "Unknown check format {t}, use one of: human, json"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Lint {
+    /// The document does not parse, nothing else is checked in it.
+    InvalidDocument,
+    /// A `link:` or a markdown link to a url of the package with no document, file or sitemap
+    /// entry.
+    BrokenLink,
+    /// An `ftd.image` `src` that is not a file of the package.
+    MissingAsset,
+    UnusedImport,
+    /// A component or variable nothing in the package uses.
+    UnusedDefinition,
+    /// A sitemap or dynamic urls entry whose document is not in the package.
+    MissingDocument,
+    /// A document that is not imported anywhere and has no sitemap entry.
+    NotInSitemap,
+}
+
+impl Lint {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Lint::InvalidDocument
+            | Lint::BrokenLink
+            | Lint::MissingAsset
+            | Lint::MissingDocument => Severity::Error,
+            Lint::UnusedImport | Lint::UnusedDefinition | Lint::NotInSitemap => Severity::Warning,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Problem {
+    pub file: String,
+    pub line: Option<usize>,
+    pub lint: Lint,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Problem {
+    fn new(lint: Lint, file: &str, line: Option<usize>, message: String) -> Problem {
+        Problem {
+            file: file.to_string(),
+            line,
+            lint,
+            severity: lint.severity(),
+            message,
+        }
+    }
+}
+
+/// `fastn check`, prints the problems `lint` finds, as text or as a json list. It is an error if
+/// any of them is, warnings are only printed.
+pub async fn check(config: &fastn_core::Config, format: Format) -> fastn_core::Result<()> {
+    let problems = lint(config, &None).await?;
+    match format {
+        Format::Human => print_problems(&problems),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&problems)?),
+    }
+
+    let errors = problems
+        .iter()
+        .filter(|p| p.severity == Severity::Error)
+        .count();
+    if errors > 0 {
+        return fastn_core::assert_error(format!("fastn check found {errors} error(s)"));
+    }
+
+    Ok(())
+}
+
+/// The problems in the package, by file and line. `config` is expected to be read without
+/// resolving the sitemap, which fails on the first entry without a document.
+pub async fn lint(
+    config: &fastn_core::Config,
+    session_id: &Option<String>,
+) -> fastn_core::Result<Vec<Problem>> {
+    let files = config.get_files(&config.package, session_id).await?;
+    let package = Package::new(config, files.as_slice());
+
+    let mut problems = vec![];
+    let mut modules = vec![];
+    for file in files.iter() {
+        let fastn_core::File::Ftd(document) = file else {
+            continue;
+        };
+        if document.id == "FASTN.ftd" {
+            continue;
+        }
+        match fastn_core::commands::lsp::analysis::parse(document) {
+            Ok(()) => modules.push(Module::new(config, document)),
+            Err(e) => problems.push(Problem::new(
+                Lint::InvalidDocument,
+                document.id.as_str(),
+                None,
+                e.to_string(),
+            )),
+        }
+    }
+
+    for module in modules.iter() {
+        problems.extend(package.broken_targets(module));
+        problems.extend(unused_imports(module));
+    }
+    problems.extend(unused_definitions(config, modules.as_slice()));
+    problems.extend(package.sitemap_problems(modules.as_slice()));
+
+    problems.sort_by(|a, b| (a.file.as_str(), a.line).cmp(&(b.file.as_str(), b.line)));
+    // a url can be both a section and its first sub section of the sitemap
+    problems.dedup();
+    Ok(problems)
+}
+
+fn print_problems(problems: &[Problem]) {
+    use colored::Colorize;
+
+    for problem in problems {
+        let location = match problem.line {
+            Some(line) => format!("{}:{line}", problem.file),
+            None => problem.file.clone(),
+        };
+        let lint = serde_json::to_value(problem.lint)
+            .ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_default();
+        let severity = match problem.severity {
+            Severity::Error => format!("error[{lint}]").red(),
+            Severity::Warning => format!("warning[{lint}]").yellow(),
+        };
+        println!("{location}: {severity}: {}", problem.message);
+    }
+
+    let errors = problems
+        .iter()
+        .filter(|p| p.severity == Severity::Error)
+        .count();
+    let summary = format!(
+        "\n{errors} error(s), {} warning(s)",
+        problems.len() - errors
+    );
+    if errors > 0 {
+        println!("{}", summary.red());
+    } else {
+        println!("{}", summary.green());
+    }
+}
+
+/// A document of the package that parses.
+struct Module<'a> {
+    document: &'a fastn_core::Document,
+    /// `pkg/foo`, as it is imported
+    name: String,
+    sections: Vec<ftd_p1::Section>,
+    definitions: Vec<ftd_ast::Ast>,
+    aliases: std::collections::BTreeMap<String, String>,
+    /// (line number, word) of the lines that are not comments or `-- import:`s
+    words: Vec<(usize, String)>,
+}
+
+impl<'a> Module<'a> {
+    fn new(config: &fastn_core::Config, document: &'a fastn_core::Document) -> Module<'a> {
+        use fastn_core::commands::lsp::analysis;
+
+        let name = analysis::module_name(document);
+        let content = document.content.as_str();
+        let sections = ftd_p1::parse(content, name.as_str())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|section| section.remove_comments())
+            .collect();
+        let definitions = analysis::definitions(content, name.as_str());
+        let aliases = analysis::aliases(config, definitions.as_slice());
+
+        Module {
+            document,
+            name,
+            sections,
+            definitions,
+            aliases,
+            words: words(content),
+        }
+    }
+
+    fn imports(&self) -> impl Iterator<Item = &ftd_ast::Import> {
+        self.definitions.iter().filter_map(|v| match v {
+            ftd_ast::Ast::Import(import) => Some(import),
+            _ => None,
+        })
+    }
+}
+
+/// What the links in a package can go to.
+struct Package<'a> {
+    package: &'a fastn_core::Package,
+    /// the id of every file
+    files: std::collections::HashSet<&'a str>,
+    /// the entries of `-- fastn.sitemap:` and `-- fastn.dynamic-urls:`
+    entries: Vec<Entry<'a>>,
+    /// `FASTN.ftd`, for the line numbers of the sitemap entries
+    fastn_ftd: &'a str,
+}
+
+struct Entry<'a> {
+    id: &'a str,
+    document: Option<&'a str>,
+    path_parameters: &'a [fastn_core::sitemap::PathParams],
+}
+
+impl<'a> Package<'a> {
+    fn new(config: &'a fastn_core::Config, files: &'a [fastn_core::File]) -> Package<'a> {
+        let package = &config.package;
+        let mut entries = vec![];
+        let sections = package
+            .sitemap
+            .iter()
+            .flat_map(|v| v.sections.iter())
+            .chain(package.dynamic_urls.iter().flat_map(|v| v.sections.iter()));
+        for section in sections {
+            add_entries(&mut entries, section);
+        }
+
+        Package {
+            package,
+            files: files.iter().map(|v| v.get_id()).collect(),
+            entries,
+            fastn_ftd: files
+                .iter()
+                .find_map(|v| match v {
+                    fastn_core::File::Ftd(doc) if doc.id == "FASTN.ftd" => {
+                        Some(doc.content.as_str())
+                    }
+                    _ => None,
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    /// The links and image sources of `module` that do not go to anything in the package.
+    fn broken_targets(&self, module: &Module) -> Vec<Problem> {
+        let lines: Vec<&str> = module.document.content.lines().collect();
+        let mut targets = vec![];
+        for section in module.sections.iter() {
+            add_targets(&mut targets, section, lines.as_slice());
+        }
+
+        targets
+            .into_iter()
+            .filter_map(|(lint, line, value)| {
+                let path = match lint {
+                    Lint::MissingAsset => asset_path(value.as_str(), module, self.package)
+                        .or_else(|| self.internal_path(value.as_str()).map(str::to_string)),
+                    _ => self.internal_path(value.as_str()).map(str::to_string),
+                }?;
+                if self.resolves(path.as_str()) {
+                    return None;
+                }
+                let message = match lint {
+                    Lint::MissingAsset => format!("`{value}` is not a file of the package"),
+                    _ => format!("`{value}` does not go to any document, file or sitemap entry"),
+                };
+                Some(Problem::new(
+                    lint,
+                    module.document.id.as_str(),
+                    Some(line),
+                    message,
+                ))
+            })
+            .collect()
+    }
+
+    /// The path of a link to this package: `/foo/` for `/foo/#bar` and `/-/<package>/foo/`.
+    /// `None` for external links, links to dependencies and relative links, which are not
+    /// checked.
+    fn internal_path<'v>(&self, value: &'v str) -> Option<&'v str> {
+        let path = value.split(['#', '?']).next()?;
+        if !path.starts_with('/') || path.starts_with("//") {
+            return None;
+        }
+        match path.strip_prefix("/-/") {
+            Some(path) => match path.strip_prefix(self.package.name.as_str())? {
+                "" => Some("/"),
+                path => path.starts_with('/').then_some(path),
+            },
+            None => Some(path),
+        }
+    }
+
+    fn resolves(&self, path: &str) -> bool {
+        if self.has_file(path) {
+            return true;
+        }
+
+        let trimmed = path.trim_start_matches('/');
+        self.entries.iter().any(|entry| {
+            if entry.path_parameters.is_empty() {
+                fastn_core::utils::ids_matches(entry.id, path)
+            } else {
+                fastn_core::sitemap::utils::url_match(path, entry.path_parameters)
+                    .is_ok_and(|(matches, _)| matches)
+            }
+        }) || self
+            .package
+            .redirects
+            .iter()
+            .flatten()
+            .any(|(from, _)| fastn_core::utils::ids_matches(from, path))
+            || self
+                .package
+                .apps
+                .iter()
+                .map(|app| app.mount_point.as_str())
+                .chain(self.package.endpoints.iter().map(|e| e.mountpoint.as_str()))
+                .any(|mount_point| trimmed.starts_with(mount_point.trim_start_matches('/')))
+    }
+
+    /// If `id` is a file of the package or a document with that url, the way `fastn serve`
+    /// finds them, an image also by its `-dark` variant.
+    fn has_file(&self, id: &str) -> bool {
+        if fastn_core::package::package_doc::file_id_to_names(id)
+            .iter()
+            .any(|name| self.files.contains(name.as_str()))
+        {
+            return true;
+        }
+
+        let id = id.trim_matches('/');
+        let Some((name, ext)) = id.rsplit_once('.') else {
+            return false;
+        };
+        let other = match name.strip_suffix("-dark") {
+            Some(name) => format!("{name}.{ext}"),
+            None => format!("{name}-dark.{ext}"),
+        };
+        is_image(ext) && self.files.contains(other.as_str())
+    }
+
+    /// Sitemap entries without a document, and documents that are not in the sitemap, if there
+    /// is one. Documents imported by others are modules, not pages, and are not expected in it.
+    fn sitemap_problems(&self, modules: &[Module]) -> Vec<Problem> {
+        let mut problems = vec![];
+        let mut documents = std::collections::HashSet::new();
+        for entry in self.entries.iter() {
+            let id = entry.id.trim();
+            if id.is_empty() || id.contains("://") || id.trim_start_matches('/').starts_with("-/") {
+                continue;
+            }
+            let document = entry
+                .document
+                .unwrap_or_else(|| id.rsplit_once('#').map(|v| v.0).unwrap_or(id));
+            match fastn_core::package::package_doc::file_id_to_names(document)
+                .into_iter()
+                .find(|name| self.files.contains(name.as_str()))
+            {
+                Some(name) => {
+                    documents.insert(name);
+                }
+                // dynamic urls have no document of their own
+                None if !entry.path_parameters.is_empty() && entry.document.is_none() => {}
+                None => problems.push(Problem::new(
+                    Lint::MissingDocument,
+                    "FASTN.ftd",
+                    line_of(self.fastn_ftd, 0, id.trim_matches('/')),
+                    format!("the sitemap entry `{id}` has no document, `{document}` is not in the package"),
+                )),
+            }
+        }
+
+        if self.package.sitemap.is_none() {
+            return problems;
+        }
+
+        let imported: std::collections::HashSet<&str> = modules
+            .iter()
+            .flat_map(|m| m.imports().map(|i| i.module.as_str()))
+            .chain(self.package.auto_import.iter().map(|a| a.path.as_str()))
+            .collect();
+        for module in modules {
+            let id = module.document.id.as_str();
+            if id == "404.ftd" || documents.contains(id) || imported.contains(module.name.as_str())
+            {
+                continue;
+            }
+            problems.push(Problem::new(
+                Lint::NotInSitemap,
+                id,
+                None,
+                format!("`{id}` is not in the sitemap and not imported by any document"),
+            ));
+        }
+
+        problems
+    }
+}
+
+fn add_entries<'a>(
+    entries: &mut Vec<Entry<'a>>,
+    section: &'a fastn_core::sitemap::section::Section,
+) {
+    entries.push(Entry {
+        id: section.id.as_str(),
+        document: section.document.as_deref(),
+        path_parameters: section.path_parameters.as_slice(),
+    });
+    for subsection in section.subsections.iter() {
+        if let Some(id) = subsection.id.as_deref() {
+            entries.push(Entry {
+                id,
+                document: subsection.document.as_deref(),
+                path_parameters: subsection.path_parameters.as_slice(),
+            });
+        }
+        add_toc_entries(entries, subsection.toc.as_slice());
+    }
+}
+
+fn add_toc_entries<'a>(entries: &mut Vec<Entry<'a>>, toc: &'a [fastn_core::sitemap::toc::TocItem]) {
+    for item in toc {
+        entries.push(Entry {
+            id: item.id.as_str(),
+            document: item.document.as_deref(),
+            path_parameters: item.path_parameters.as_slice(),
+        });
+        add_toc_entries(entries, item.children.as_slice());
+    }
+}
+
+/// (lint, line number, value) of the `link:`s, the links in the markdown of `ftd.text` and
+/// `*.markdown` sections, and the `src`s of `ftd.image`s and `ftd.image-src` variables in
+/// `section`. Values that are references or expressions are left out.
+fn add_targets(
+    targets: &mut Vec<(Lint, usize, String)>,
+    section: &ftd_p1::Section,
+    lines: &[&str],
+) {
+    let is_image = section.kind.is_none() && section.name.ends_with("image");
+    let is_image_src = section.kind.as_deref() == Some("ftd.image-src");
+
+    for header in section.headers.0.iter() {
+        match header {
+            ftd_p1::Header::KV(kv) => {
+                let lint = match kv.key.as_str() {
+                    "link" if !is_image_src => Lint::BrokenLink,
+                    "src" if is_image => Lint::MissingAsset,
+                    "light" | "dark" if is_image_src => Lint::MissingAsset,
+                    _ => continue,
+                };
+                if let Some(value) = kv.value.as_deref().filter(|v| !v.contains('{')) {
+                    targets.push((lint, kv.line_number, value.trim().to_string()));
+                }
+            }
+            ftd_p1::Header::BlockRecordHeader(record) if is_image && record.key == "src" => {
+                for field in record.fields.iter() {
+                    if let ftd_p1::Header::KV(ftd_p1::KV {
+                        value: Some(value),
+                        line_number,
+                        ..
+                    }) = field
+                    {
+                        targets.push((Lint::MissingAsset, *line_number, value.trim().to_string()));
+                    }
+                }
+            }
+            ftd_p1::Header::Section(header) => {
+                for section in header.section.iter() {
+                    add_targets(targets, section, lines);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if section.kind.is_none() && (section.name == "ftd.text" || section.name.ends_with("markdown"))
+    {
+        let caption = section.caption.as_ref().and_then(|v| match v {
+            ftd_p1::Header::KV(kv) => kv.value.as_deref(),
+            _ => None,
+        });
+        for text in caption
+            .into_iter()
+            .chain(section.body.iter().map(|v| v.value.as_str()))
+        {
+            for link in markdown_links(text) {
+                let line = line_of_in(lines, section.line_number, format!("]({link}").as_str())
+                    .unwrap_or(section.line_number);
+                targets.push((Lint::BrokenLink, line, link.to_string()));
+            }
+        }
+    }
+
+    for sub_section in section.sub_sections.iter() {
+        add_targets(targets, sub_section, lines);
+    }
+}
+
+/// The targets of the `[text](target)` links in `text`.
+fn markdown_links(text: &str) -> Vec<&str> {
+    text.match_indices("](")
+        .filter_map(|(i, _)| {
+            let rest = &text[i + 2..];
+            let end = rest.find([')', ' ', '\n'])?;
+            rest[end..].starts_with(')').then_some(&rest[..end])
+        })
+        .filter(|link| !link.is_empty())
+        .collect()
+}
+
+/// `/images/logo.svg` for `$assets.files.images.logo.svg` (or `.light`/`.dark` of it), where
+/// `assets` is an import of the `assets` module of this package.
+fn asset_path(value: &str, module: &Module, package: &fastn_core::Package) -> Option<String> {
+    let (alias, files) = value.strip_prefix('$')?.split_once(".files.")?;
+    if module.aliases.get(alias)? != &format!("{}/assets", package.name) {
+        return None;
+    }
+    let files = files
+        .strip_suffix(".light")
+        .or_else(|| files.strip_suffix(".dark"))
+        .unwrap_or(files);
+    let (file, ext) = files.rsplit_once('.')?;
+    Some(format!("/{}.{ext}", file.replace('.', "/")))
+}
+
+fn is_image(ext: &str) -> bool {
+    mime_guess::MimeGuess::from_ext(ext)
+        .first_or_octet_stream()
+        .to_string()
+        .starts_with("image/")
+}
+
+fn unused_imports(module: &Module) -> Vec<Problem> {
+    module
+        .imports()
+        .filter(|import| {
+            let exposed = match import.exposing.as_ref() {
+                // an import that is re-exported is used by whoever imports this module
+                _ if import.exports.is_some() => return false,
+                Some(ftd_ast::Exposing::All) => return false,
+                Some(ftd_ast::Exposing::Things(things)) => things.as_slice(),
+                None => &[],
+            };
+            !module.words.iter().any(|(_, word)| {
+                let first = first_name(word);
+                first == import.alias || exposed.iter().any(|v| v == first)
+            })
+        })
+        .map(|import| {
+            Problem::new(
+                Lint::UnusedImport,
+                module.document.id.as_str(),
+                Some(import.line_number),
+                format!(
+                    "`{}` is imported as `{}` but never used",
+                    import.module, import.alias
+                ),
+            )
+        })
+        .collect()
+}
+
+/// Components and variables whose name is not used in any document, other than on the line it
+/// is defined on. Any word that could refer to one counts, so nothing used is reported, but a
+/// definition whose name is also an argument somewhere is not reported either.
+fn unused_definitions(config: &fastn_core::Config, modules: &[Module]) -> Vec<Problem> {
+    use fastn_core::commands::lsp::analysis;
+
+    let mut used = std::collections::HashSet::new();
+    for module in modules {
+        // (name, module) of the names this document can use without an alias
+        let exposed: Vec<(&str, &str)> = module
+            .imports()
+            .flat_map(|import| match import.exposing.as_ref() {
+                Some(ftd_ast::Exposing::Things(things)) => things
+                    .iter()
+                    .map(|thing| (thing.as_str(), import.module.as_str()))
+                    .collect(),
+                _ => vec![],
+            })
+            .chain(config.package.auto_import.iter().flat_map(|auto_import| {
+                auto_import
+                    .exposing
+                    .iter()
+                    .map(|thing| (thing.as_str(), auto_import.path.as_str()))
+            }))
+            .collect();
+        let mut defined_on: std::collections::HashMap<usize, String> = module
+            .definitions
+            .iter()
+            .filter_map(|v| Some((v.line_number(), v.get_definition_name()?)))
+            .collect();
+
+        for (line, word) in module.words.iter() {
+            let first = first_name(word);
+            if defined_on.get(line).is_some_and(|name| name == first) {
+                defined_on.remove(line);
+                continue;
+            }
+            used.insert(analysis::resolve(
+                word,
+                module.name.as_str(),
+                &module.aliases,
+            ));
+            for (name, exposed_by) in exposed.iter() {
+                if *name == first {
+                    used.insert((exposed_by.to_string(), name.to_string()));
+                }
+            }
+        }
+    }
+
+    let mut problems = vec![];
+    for module in modules {
+        for definition in module.definitions.iter() {
+            let is_checked = match definition {
+                ftd_ast::Ast::ComponentDefinition(_) | ftd_ast::Ast::WebComponentDefinition(_) => {
+                    true
+                }
+                ftd_ast::Ast::VariableDefinition(_) => {
+                    !definition.is_always_included_variable_definition()
+                }
+                _ => false,
+            };
+            let name = definition.name();
+            if is_checked && !used.contains(&(module.name.clone(), name.clone())) {
+                problems.push(Problem::new(
+                    Lint::UnusedDefinition,
+                    module.document.id.as_str(),
+                    Some(definition.line_number()),
+                    format!("`{name}` is defined but never used"),
+                ));
+            }
+        }
+    }
+    problems
+}
+
+/// `foo` for `$foo.bar` and `*$foo`.
+fn first_name(word: &str) -> &str {
+    let word = word.trim_start_matches(['$', '*']);
+    word.split('.').next().unwrap_or(word)
+}
+
+/// (line number, word) for every word in `content`, leaving out comments and `-- import:`s,
+/// whose alias and `exposing` are not a use of what they import.
+fn words(content: &str) -> Vec<(usize, String)> {
+    let is_word = |c: char| c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '#' | '$' | '*');
+
+    let mut words = vec![];
+    let mut in_import = false;
+    for (i, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("-- import:") {
+            in_import = true;
+            continue;
+        }
+        if trimmed.is_empty() || trimmed.starts_with("-- ") || trimmed.starts_with("/-- ") {
+            in_import = false;
+        }
+        if in_import || trimmed.starts_with(";;") || trimmed.starts_with("/-- ") {
+            continue;
+        }
+        words.extend(
+            line.split(|c| !is_word(c))
+                .map(|word| word.trim_start_matches('-').trim_end_matches('.'))
+                .filter(|word| !word.is_empty())
+                .map(|word| (i + 1, word.to_string())),
+        );
+    }
+    words
+}
+
+/// The first line from `from` on that contains `needle`.
+fn line_of(content: &str, from: usize, needle: &str) -> Option<usize> {
+    let lines: Vec<&str> = content.lines().collect();
+    line_of_in(lines.as_slice(), from, needle)
+}
+
+fn line_of_in(lines: &[&str], from: usize, needle: &str) -> Option<usize> {
+    (from.saturating_sub(1)..lines.len())
+        .find(|i| lines[*i].contains(needle))
+        .map(|i| i + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    fn targets(content: &str) -> Vec<(super::Lint, usize, String)> {
+        let lines: Vec<&str> = content.lines().collect();
+        let mut targets = vec![];
+        for section in ftd_p1::parse(content, "foo").unwrap() {
+            super::add_targets(&mut targets, &section, lines.as_slice());
+        }
+        targets
+    }
+
+    #[test]
+    fn add_targets() {
+        use super::Lint::{BrokenLink, MissingAsset};
+
+        let content = indoc::indoc! {"
+            -- ftd.text: Hello
+            link: /foo/
+
+            -- ftd.image:
+            src: /-/foo.com/images/logo.png
+            link if { flag }: $url
+
+            -- ftd.image-src logo:
+            light: /images/logo.svg
+            dark: /images/logo-dark.svg
+
+            -- ftd.column:
+
+            -- ftd.text:
+
+            See [the docs](/docs/#intro) and [github](https://github.com).
+
+            -- end: ftd.column
+        "};
+        assert_eq!(
+            targets(content),
+            vec![
+                (BrokenLink, 2, "/foo/".to_string()),
+                (MissingAsset, 5, "/-/foo.com/images/logo.png".to_string()),
+                (BrokenLink, 6, "$url".to_string()),
+                (MissingAsset, 9, "/images/logo.svg".to_string()),
+                (MissingAsset, 10, "/images/logo-dark.svg".to_string()),
+                (BrokenLink, 16, "/docs/#intro".to_string()),
+                (BrokenLink, 16, "https://github.com".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn markdown_links() {
+        assert_eq!(
+            super::markdown_links("[a](/a/) ![b](/b.png) [c](not a link) [d]()"),
+            vec!["/a/", "/b.png"]
+        );
+    }
+
+    #[test]
+    fn words() {
+        let content = indoc::indoc! {"
+            -- import: foo.com/lib
+            exposing: card
+
+            ;; -- lib.hero:
+
+            -- lib.page: $title
+            -- card:
+        "};
+        let words = super::words(content);
+        let words: Vec<(usize, &str)> = words
+            .iter()
+            .map(|(line, word)| (*line, word.as_str()))
+            .collect();
+        assert_eq!(words, vec![(6, "lib.page"), (6, "$title"), (7, "card")]);
+    }
+}
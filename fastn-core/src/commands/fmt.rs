@@ -1,7 +1,11 @@
+/// Formats the ftd files of the package, or only the ones matching `files`. A file matches by its
+/// id, its path or a glob on its id (`*`, `**` and `?`). With `check` nothing is written, the
+/// diff of every file that is not formatted is printed and it is an error if there is any.
 pub async fn fmt(
     config: &fastn_core::Config,
-    file: Option<&str>,
+    files: &[String],
     no_indentation: bool,
+    check: bool,
 ) -> fastn_core::Result<()> {
     use colored::Colorize;
     use itertools::Itertools;
@@ -11,350 +15,1070 @@ pub async fn fmt(
         .await?
         .into_iter()
         .filter_map(|v| v.get_ftd_document())
+        .filter(|v| files.is_empty() || files.iter().any(|f| matches_file(f, v)))
         .collect_vec();
 
+    let mut unformatted = vec![];
     for ftd_document in documents {
-        if let Some(file) = file {
-            if !ftd_document.id.eq(file) {
-                continue;
-            }
+        let formatted = format(
+            ftd_document.content.as_str(),
+            ftd_document.id.as_str(),
+            !no_indentation,
+        )?;
+        if formatted == ftd_document.content {
+            continue;
         }
 
-        print!("Formatting {} ... ", ftd_document.id);
+        if check {
+            print_diff(
+                ftd_document.id.as_str(),
+                ftd_document.content.as_str(),
+                formatted.as_str(),
+            );
+            unformatted.push(ftd_document.id);
+            continue;
+        }
 
-        let parsed_content =
-            parsed_to_sections(format!("{}\n\n", ftd_document.content.as_str()).as_str());
-        let format_sections = format_sections(parsed_content, !no_indentation);
+        print!("Formatting {} ... ", ftd_document.id);
         config
             .ds
-            .write_content(&ftd_document.get_full_path(), &format_sections.into_bytes())
+            .write_content(&ftd_document.get_full_path(), &formatted.into_bytes())
             .await?;
         println!("{}", "Done".green())
     }
 
+    if !unformatted.is_empty() {
+        return fastn_core::assert_error(format!(
+            "{} file(s) not formatted: {}",
+            unformatted.len(),
+            unformatted.join(", ")
+        ));
+    }
+
+    Ok(())
+}
+
+/// `fastn fmt -`, formats stdin to stdout, for editors. With `check` only the diff is printed.
+pub fn fmt_stdin(no_indentation: bool, check: bool) -> fastn_core::Result<()> {
+    use std::io::Read;
+
+    let mut content = String::new();
+    std::io::stdin().read_to_string(&mut content)?;
+    let formatted = format(content.as_str(), STDIN_ID, !no_indentation)?;
+
+    if !check {
+        print!("{formatted}");
+        return Ok(());
+    }
+
+    if formatted != content {
+        print_diff(STDIN_ID, content.as_str(), formatted.as_str());
+        return fastn_core::assert_error(format!("{STDIN_ID} is not formatted"));
+    }
+
     Ok(())
 }
 
-#[derive(Debug)]
-struct Section {
-    value: String,
-    kind: SectionKind,
+const STDIN_ID: &str = "<stdin>";
+
+fn matches_file(file: &str, document: &fastn_core::Document) -> bool {
+    let id = document.id.as_str();
+    if file.trim_start_matches("./") == id || document.get_full_path().to_string() == file {
+        return true;
+    }
+
+    file.contains(['*', '?'])
+        && fastn_core::utils::glob_match(
+            &file.chars().collect::<Vec<_>>(),
+            &id.chars().collect::<Vec<_>>(),
+        )
+}
+
+fn print_diff(id: &str, content: &str, formatted: &str) {
+    let patch = diffy::create_patch(content, formatted);
+    println!("Diff in {id}:");
+    println!(
+        "{}",
+        diffy::PatchFormatter::new().with_color().fmt_patch(&patch)
+    );
 }
 
-#[derive(Debug)]
-enum SectionKind {
-    Comment,
-    Empty,
-    Section {
-        name: String,
-        end: bool,
-        sub_sections: Vec<Section>,
-    },
+#[derive(thiserror::Error, Debug)]
+pub enum FormatError {
+    #[error("{0}")]
+    Parse(#[from] ftd_p1::Error),
+    #[error("formatting {doc_id} would change its sections, it is left as it is")]
+    ChangesSections { doc_id: String },
+    #[error("formatting {doc_id} would drop the comment `{comment}`, it is left as it is")]
+    DropsComment { doc_id: String, comment: String },
 }
 
-impl Section {
-    fn new_comment(value: &str) -> Section {
-        Section {
-            value: value.to_string(),
-            kind: SectionKind::Comment,
-        }
+/// `content` formatted, built from its `ftd_p1` section tree. One blank line between sections,
+/// the headers that fit on a line right below the section line, the other ones as
+/// `-- <section>.<header>:` blocks after them, then the body, and `-- end:` only where the parser
+/// needs it. Headers are sorted by key, `for` and `if` first, the ones with the same key stay in
+/// the order they are in the file, and so do the fields and arguments of definitions. Sub
+/// sections from the second level on are indented with a tab per level, unless `indentation` is
+/// false.
+///
+/// Comments stay above what they are above of in `content`, and bodies are written as they are
+/// in `content`, only re-indented, so that their comments and escapes are kept. It is an error if
+/// the formatted content does not parse to the same sections or has lost a comment.
+pub fn format(content: &str, doc_id: &str, indentation: bool) -> Result<String, FormatError> {
+    let sections = ftd_p1::parse(content, doc_id)?;
+
+    let mut formatter = Formatter::new(content, sections.as_slice(), indentation);
+    for section in sections.iter() {
+        formatter.section(section, 0, None);
     }
+    let formatted = formatter.finish();
 
-    fn new_empty(value: &str) -> Section {
-        Section {
-            value: value.to_string(),
-            kind: SectionKind::Empty,
-        }
+    if !matches!(
+        ftd_p1::parse(formatted.as_str(), doc_id),
+        Ok(v) if normalise(v.as_slice()) == normalise(sections.as_slice())
+    ) {
+        return Err(FormatError::ChangesSections {
+            doc_id: doc_id.to_string(),
+        });
     }
 
-    fn new_section(name: &str, value: &str) -> Section {
-        Section {
-            value: value.to_string(),
-            kind: SectionKind::Section {
-                name: name.to_string(),
-                end: false,
-                sub_sections: vec![],
-            },
+    let mut kept = comments(formatted.as_str());
+    for comment in comments(content) {
+        match kept.iter().position(|v| *v == comment) {
+            Some(i) => {
+                kept.remove(i);
+            }
+            None => {
+                return Err(FormatError::DropsComment {
+                    doc_id: doc_id.to_string(),
+                    comment,
+                })
+            }
         }
     }
+
+    Ok(formatted)
 }
 
-fn format_sections(sections: Vec<Section>, indentation: bool) -> String {
-    let mut output = vec![];
-    for section in sections {
-        output.push(format_section(
-            &section,
-            if indentation { Some(-1) } else { None },
-        ))
-    }
-    format!("{}\n", output.join("\n").trim_end())
+struct Formatter<'a> {
+    lines: Vec<&'a str>,
+    indentation: bool,
+    /// The comments on their own line, by the line of the section, header or body they are above
+    /// of. `usize::MAX` has the ones after the last section.
+    comments: std::collections::HashMap<usize, Vec<Comment>>,
+    /// Bodies as they are in `lines`, by the line their section or header starts on.
+    bodies: std::collections::HashMap<usize, Vec<String>>,
+    /// The line of the `-- end:`, by the line its section or header starts on.
+    ends: std::collections::HashMap<usize, usize>,
+    output: Vec<String>,
 }
 
-fn format_section(section: &Section, indentation: Option<i32>) -> String {
-    match &section.kind {
-        SectionKind::Comment => add_indentation(section.value.as_str(), indentation),
-        SectionKind::Empty => section.value.to_string(),
-        SectionKind::Section {
-            name,
-            end,
-            sub_sections,
-        } => format_section_kind(
-            name.as_str(),
-            *end,
-            sub_sections.as_slice(),
-            section.value.as_str(),
-            indentation,
-        ),
-    }
+struct Comment {
+    text: String,
+    blank_line_after: bool,
 }
 
-fn format_section_kind(
-    section_name: &str,
-    end: bool,
-    sub_sections: &[Section],
-    value: &str,
-    indentation: Option<i32>,
-) -> String {
-    let mut output = vec![add_indentation(value, indentation)];
-    for section in sub_sections {
-        output.push(format_section(section, indentation.map(|v| v + 1)));
-    }
-    if end {
-        output.push(add_indentation(
-            format!("-- end: {section_name}").as_str(),
+impl<'a> Formatter<'a> {
+    fn new(content: &'a str, sections: &[ftd_p1::Section], indentation: bool) -> Formatter<'a> {
+        let mut formatter = Formatter {
+            lines: content.lines().collect(),
             indentation,
-        ));
+            comments: Default::default(),
+            bodies: Default::default(),
+            ends: Default::default(),
+            output: vec![],
+        };
+
+        let mut anchors = std::collections::BTreeSet::new();
+        let mut in_bodies = std::collections::HashSet::new();
+        for section in sections {
+            formatter.collect(section, None, &mut anchors, &mut in_bodies);
+        }
+
+        for (i, line) in formatter.lines.iter().enumerate() {
+            let line_number = i + 1;
+            let text = line.trim();
+            if !text.starts_with(";;") || in_bodies.contains(&line_number) {
+                continue;
+            }
+            let anchor = anchors
+                .range(line_number..)
+                .next()
+                .copied()
+                .unwrap_or(usize::MAX);
+            formatter.comments.entry(anchor).or_default().push(Comment {
+                text: text.to_string(),
+                blank_line_after: formatter
+                    .lines
+                    .get(i + 1)
+                    .is_some_and(|v| v.trim().is_empty()),
+            });
+        }
+
+        formatter
     }
-    output.join("\n")
-}
 
-fn add_indentation(input: &str, indentation: Option<i32>) -> String {
-    let indentation = match indentation {
-        Some(indentation) if indentation > 0 => indentation,
-        _ => {
-            return input.to_string();
+    /// Finds the lines comments can be above of, and the bodies to keep as they are. Returns the
+    /// last line of `section`.
+    fn collect(
+        &mut self,
+        section: &ftd_p1::Section,
+        parent: Option<&str>,
+        anchors: &mut std::collections::BTreeSet<usize>,
+        in_bodies: &mut std::collections::HashSet<usize>,
+    ) -> usize {
+        anchors.insert(section.line_number);
+        let mut last = section.line_number;
+
+        if let Some(caption) = section.caption.as_ref() {
+            let start = self.header_start(caption);
+            anchors.insert(start);
+            last = last.max(caption.get_line_number());
+            if caption_block(caption).is_some() {
+                self.collect_body(start, in_bodies);
+            }
         }
-    };
-    let mut value = vec![];
-    for i in input.split('\n') {
-        value.push(format!("{}{}", "\t".repeat(indentation as usize), i));
+
+        for header in section.headers.0.iter() {
+            let start = self.header_start(header);
+            anchors.insert(start);
+            last = last.max(header.get_line_number());
+            match header {
+                ftd_p1::Header::KV(ftd_p1::KV {
+                    source: ftd_p1::KVSource::Body,
+                    value: Some(_),
+                    ..
+                }) => self.collect_body(start, in_bodies),
+                ftd_p1::Header::Section(header) => {
+                    for section in header.section.iter() {
+                        last = last.max(self.collect(section, None, anchors, in_bodies));
+                    }
+                    let name = format!("{}.{}", section.name, header.key);
+                    last = self.collect_end(start, last, name.as_str(), anchors);
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(body) = section.body.as_ref() {
+            last = last.max(body.line_number);
+            if let Some((start, anchor)) = self.body_start(section, body) {
+                anchors.insert(anchor);
+                self.collect_body(start, in_bodies);
+            }
+        }
+
+        for sub_section in section.sub_sections.iter() {
+            last = last.max(self.collect(
+                sub_section,
+                Some(section.name.as_str()),
+                anchors,
+                in_bodies,
+            ));
+        }
+
+        if section.sub_sections.is_empty() && parent != Some(section.name.as_str()) {
+            return last;
+        }
+        self.collect_end(section.line_number, last, section.name.as_str(), anchors)
     }
-    value.join("\n")
-}
 
-fn parsed_to_sections(input: &str) -> Vec<Section> {
-    let mut sections = vec![];
-    let mut input = input.to_string();
-    while !input.is_empty() {
-        if end_section(&mut input, &mut sections) {
-            continue;
-        } else if let Some(empty_section) = empty_section(&mut input) {
-            sections.push(empty_section);
-        } else if let Some(comment_section) = comment_section(&mut input) {
-            sections.push(comment_section);
-        } else if let Some(section) = section(&mut input) {
-            sections.push(section);
-        } else {
-            panic!(
-                "`{}`: can't parse",
-                input
-                    .split_once('\n')
-                    .map(|(v, _)| v.to_string())
-                    .unwrap_or_else(|| input.to_string())
-            );
+    /// Finds the `-- end: <name>` after `last`, for what starts on `start`.
+    fn collect_end(
+        &mut self,
+        start: usize,
+        last: usize,
+        name: &str,
+        anchors: &mut std::collections::BTreeSet<usize>,
+    ) -> usize {
+        for (i, line) in self.lines.iter().enumerate().skip(last) {
+            let Some(rest) = line.trim_start().strip_prefix("-- ") else {
+                continue;
+            };
+            match rest.split_once(':') {
+                Some((end, v)) if end.trim() == "end" && v.trim() == name => {
+                    anchors.insert(i + 1);
+                    self.ends.insert(start, i + 1);
+                    return i + 1;
+                }
+                Some((end, _)) if end.trim() == "end" => {}
+                _ => break,
+            }
         }
+        last
     }
 
-    sections
-}
+    /// The body after the `-- ` line `start`, past the headers and up to the next `-- ` line. The
+    /// comments after the last line of the body are left out, they are above the next section.
+    fn collect_body(&mut self, start: usize, in_bodies: &mut std::collections::HashSet<usize>) {
+        let is_start = |line: &str| {
+            let line = line.trim_start();
+            line.starts_with("-- ") || line.starts_with("/-- ")
+        };
+        let is_text = |line: &str| !line.trim().is_empty() && !line.trim().starts_with(";;");
 
-fn end_section(input: &mut String, sections: &mut Vec<Section>) -> bool {
-    let mut remaining = None;
-    let first_line = if let Some((first_line, rem)) = input.split_once('\n') {
-        remaining = Some(rem.to_string());
-        first_line.to_string()
-    } else {
-        input.to_string()
-    };
+        let mut first = start;
+        while first < self.lines.len() && !self.lines[first].trim().is_empty() {
+            first += 1;
+        }
+        let mut last = first;
+        while last < self.lines.len() && !is_start(self.lines[last]) {
+            last += 1;
+        }
+        // `first` and `last` are indices in `lines`, the body is `lines[first..last]`
+        let body = &self.lines[first..last];
+        let (Some(from), Some(to)) = (
+            body.iter().position(|v| !v.trim().is_empty()),
+            body.iter().rposition(|v| is_text(v)),
+        ) else {
+            return;
+        };
 
-    let section_name = if let Some(section_name) = end_section_name(first_line.as_str()) {
-        section_name
-    } else {
-        return false;
-    };
+        let indentation = body[from..=to]
+            .iter()
+            .filter(|v| is_text(v))
+            .map(|v| v.len() - v.trim_start().len())
+            .min()
+            .unwrap_or(0);
+        let lines = body[from..=to]
+            .iter()
+            .map(|v| {
+                let leading = v.len() - v.trim_start().len();
+                v[leading.min(indentation)..].trim_end().to_string()
+            })
+            .collect();
 
-    *input = remaining.unwrap_or_default();
-
-    let mut sub_sections = vec![];
-    while let Some(mut section) = sections.pop() {
-        match &mut section.kind {
-            SectionKind::Section {
-                name,
-                end,
-                sub_sections: s,
-            } if section_name
-                .trim_start_matches('$')
-                .eq(name.trim_start_matches('$'))
-                && !*end =>
-            {
-                *end = true;
-                *s = sub_sections;
-                sections.push(section);
-                return true;
+        in_bodies.extend((first + from + 1)..=(first + to + 1));
+        self.bodies.insert(start, lines);
+    }
+
+    fn section(&mut self, section: &ftd_p1::Section, depth: usize, parent: Option<&str>) {
+        self.blank();
+        self.comments(section.line_number, depth, false);
+
+        let mut line = format!(
+            "{}-- {}",
+            if section.is_commented { "/" } else { "" },
+            kind_and_name(section.kind.as_deref(), section.name.as_str()),
+        );
+        line.push(':');
+        let caption_block = section.caption.as_ref().and_then(caption_block);
+        if let (Some(ftd_p1::Header::KV(kv)), None) = (section.caption.as_ref(), caption_block) {
+            if let Some(caption) = kv.value.as_deref() {
+                line.push(' ');
+                line.push_str(escape_comments(caption).as_str());
             }
-            _ => {
-                sub_sections.insert(0, section);
+        }
+        self.line_with_comment(depth, line, section.line_number);
+
+        let mut inline: Vec<&ftd_p1::KV> = vec![];
+        let mut block: Vec<&ftd_p1::Header> = vec![];
+        for header in section.headers.0.iter() {
+            match header {
+                ftd_p1::Header::KV(kv)
+                    if is_inline(kv) && !block.iter().any(|v| v.get_key() == kv.key) =>
+                {
+                    inline.push(kv)
+                }
+                _ => block.push(header),
             }
         }
-    }
+        if !is_definition(section) {
+            inline.sort_by(|a, b| header_order(&a.key, &b.key));
+            block.sort_by(|a, b| header_order(&a.get_key(), &b.get_key()));
+        }
 
-    panic!("cannot find section {} to end", section_name)
-}
+        for kv in inline {
+            let start = self.header_start_of_kv(kv);
+            self.comments(start, depth, true);
+            let line = kv_line(kv, true);
+            if kv.source == ftd_p1::KVSource::Header {
+                self.line_with_comment(depth, line, start);
+            } else {
+                self.line(depth, line.as_str());
+            }
+        }
 
-fn end_section_name(input: &str) -> Option<String> {
-    use itertools::Itertools;
+        let block_body = caption_block.is_some() || !block.is_empty();
+        if let (Some(caption), Some(header)) = (caption_block, section.caption.as_ref()) {
+            let start = self.header_start(header);
+            self.blank();
+            self.comments(start, depth, false);
+            self.line(depth, format!("-- {}.caption:", section.name).as_str());
+            self.text(start, caption, depth);
+        }
 
-    let input = input.split_whitespace().join(" ");
-    input.strip_prefix("-- end:").map(|v| v.trim().to_string())
-}
+        for header in block {
+            self.blank();
+            self.block_header(section, header, depth);
+        }
 
-fn section(input: &mut String) -> Option<Section> {
-    let section_name = get_section_name(input)?;
-    let mut value = vec![];
-    let mut first_time_encounter_section = true;
-    let mut leading_spaces_count = 0;
-    while !input.is_empty() {
-        let (first_line, remaining) = match input.split_once('\n') {
-            Some((first_line, remaining)) => (first_line.to_string(), remaining.to_string()),
-            None => (input.to_string(), String::new()),
-        };
-        let mut trimmed_line = first_line.trim_start().to_string();
-        let current_leading_spaces_count = first_line.len() - trimmed_line.len();
-
-        if trimmed_line.starts_with("-- ") || trimmed_line.starts_with("/-- ") {
-            // If the first_time_encounter_section then store the indentation here in the
-            // `leading_spaces_count`
-            // Also, don't break code, for this is section definition line.
-            if first_time_encounter_section {
-                first_time_encounter_section = false;
-                *input = remaining.to_string();
-                value.push(trimmed_line.trim().to_string());
-                leading_spaces_count = current_leading_spaces_count;
-                continue;
+        if let Some(body) = section.body.as_ref() {
+            let start = self.body_start(section, body);
+            self.blank();
+            if let Some((_, anchor)) = start {
+                self.comments(anchor, depth, false);
             }
-            *input = format!("{first_line}\n{remaining}");
-            break;
-        }
-
-        // Use the indentation saved in `leading_spaces_count` and add indentation upto the
-        // extra space left when deducting the `leading_spaces_count`. This ensures the section body
-        // keeps the indentation as intended by user
-        if !trimmed_line.is_empty() {
-            trimmed_line = format!(
-                "{}{}",
-                " ".repeat(current_leading_spaces_count.saturating_sub(leading_spaces_count)),
-                trimmed_line.trim()
-            );
+            if block_body {
+                self.line(depth, format!("-- {}.body:", section.name).as_str());
+                self.blank();
+            }
+            match start.and_then(|(start, _)| self.bodies.remove(&start)) {
+                Some(text) => self.lines(depth, text.iter().map(String::as_str)),
+                None => self.escaped_lines(depth, body.value.as_str()),
+            }
+        }
+
+        for sub_section in section.sub_sections.iter() {
+            self.section(sub_section, depth + 1, Some(section.name.as_str()));
+        }
+
+        // without it the `-- end:` of the parent would end this section
+        if !section.sub_sections.is_empty() || parent == Some(section.name.as_str()) {
+            self.end(section.line_number, section.name.as_str(), depth);
         }
-        value.push(trimmed_line);
-        *input = remaining.to_string();
     }
 
-    if !value.is_empty() {
-        let mut value = value.join("\n").to_string();
-        remove_comment_from_section_value_if_its_comment_for_other_section(input, &mut value);
-        Some(Section::new_section(section_name.as_str(), value.as_str()))
-    } else {
-        None
+    fn end(&mut self, start: usize, name: &str, depth: usize) {
+        self.blank();
+        if let Some(end) = self.ends.get(&start).copied() {
+            self.comments(end, depth, false);
+        }
+        self.line(depth, format!("-- end: {name}").as_str());
     }
-}
 
-fn remove_comment_from_section_value_if_its_comment_for_other_section(
-    input: &mut String,
-    value: &mut String,
-) {
-    if !value.trim().is_empty() {
-        if let Some((v, probable_comment_section)) = value.rsplit_once("\n\n") {
-            let mut probable_comment_section = probable_comment_section.to_string();
-            if let Some(comment_section) = comment_section(&mut probable_comment_section) {
-                if probable_comment_section.trim().is_empty() {
-                    *input = format!("{}\n{}", comment_section.value, input);
-                    *value = format!("{v}\n");
+    fn block_header(&mut self, section: &ftd_p1::Section, header: &ftd_p1::Header, depth: usize) {
+        let start = self.header_start(header);
+        self.comments(start, depth, false);
+
+        match header {
+            ftd_p1::Header::KV(kv) => {
+                let mut line = format!(
+                    "-- {}:",
+                    kind_and_name(
+                        kind_with_access_modifier(kv).as_deref(),
+                        format!("{}.{}", section.name, kv.key).as_str()
+                    )
+                );
+                match (&kv.source, kv.value.as_deref()) {
+                    (ftd_p1::KVSource::Body, Some(value)) => {
+                        self.line(depth, line.as_str());
+                        self.condition(kv.condition.as_deref(), depth);
+                        self.text(start, value, depth);
+                    }
+                    (_, value) => {
+                        if let Some(value) = value {
+                            line.push(' ');
+                            line.push_str(value);
+                        }
+                        self.line(depth, line.as_str());
+                        self.condition(kv.condition.as_deref(), depth);
+                    }
+                }
+            }
+            ftd_p1::Header::BlockRecordHeader(record) => {
+                let mut line = format!(
+                    "-- {}:",
+                    kind_and_name(
+                        record.kind.as_deref(),
+                        format!("{}.{}", section.name, record.key).as_str()
+                    )
+                );
+                if let Some(caption) = record.caption.as_deref() {
+                    line.push(' ');
+                    line.push_str(caption);
+                }
+                self.line(depth, line.as_str());
+                self.condition(record.condition.as_deref(), depth);
+                // the parser sorts the fields of a record block, the ones from
+                // `-- <section>.<key>.<field>:` lines are in the order they come
+                let mut fields: Vec<_> = record.fields.iter().collect();
+                fields.sort_by_key(|v| v.get_key());
+                for field in fields {
+                    if let ftd_p1::Header::KV(kv) = field {
+                        self.line(depth, kv_line(kv, false).as_str());
+                    }
                 }
+                if let Some(body) = record.body.0.as_deref() {
+                    self.blank();
+                    self.escaped_lines(depth, body);
+                }
+            }
+            ftd_p1::Header::Section(header) => {
+                let key = format!("{}.{}", section.name, header.key);
+                self.line(
+                    depth,
+                    format!(
+                        "-- {}:",
+                        kind_and_name(header.kind.as_deref(), key.as_str())
+                    )
+                    .as_str(),
+                );
+                self.condition(header.condition.as_deref(), depth);
+                for section in header.section.iter() {
+                    self.section(section, depth + 1, None);
+                }
+                self.end(start, key.as_str(), depth);
             }
         }
     }
+
+    /// A multi-line value after its `-- ` line `start`, as it is in the file if it can be.
+    fn text(&mut self, start: usize, value: &str, depth: usize) {
+        self.blank();
+        match self.bodies.remove(&start) {
+            Some(text) => self.lines(depth, text.iter().map(String::as_str)),
+            None => self.escaped_lines(depth, value),
+        }
+    }
+
+    fn condition(&mut self, condition: Option<&str>, depth: usize) {
+        if let Some(condition) = condition {
+            self.line(
+                depth,
+                format!("{}: {}", ftd_p1::utils::IF, escape_comments(condition)).as_str(),
+            );
+        }
+    }
+
+    fn comments(&mut self, anchor: usize, depth: usize, in_headers: bool) {
+        for comment in self.comments.remove(&anchor).unwrap_or_default() {
+            self.line(depth, comment.text.as_str());
+            // a blank line would end the headers
+            if comment.blank_line_after && !in_headers {
+                self.output.push(String::new());
+            }
+        }
+    }
+
+    fn line_with_comment(&mut self, depth: usize, mut line: String, line_number: usize) {
+        if let Some(comment) = self
+            .lines
+            .get(line_number.wrapping_sub(1))
+            .and_then(|v| inline_comment(v))
+        {
+            line.push(' ');
+            line.push_str(comment);
+        }
+        self.line(depth, line.as_str());
+    }
+
+    fn escaped_lines(&mut self, depth: usize, value: &str) {
+        let lines: Vec<String> = value.lines().map(escape).collect();
+        self.lines(depth, lines.iter().map(String::as_str));
+    }
+
+    fn lines<'b>(&mut self, depth: usize, lines: impl Iterator<Item = &'b str>) {
+        for line in lines {
+            self.line(depth, line);
+        }
+    }
+
+    fn line(&mut self, depth: usize, line: &str) {
+        let line = line.trim_end();
+        if line.is_empty() {
+            self.output.push(String::new());
+        } else if self.indentation && depth > 1 {
+            self.output
+                .push(format!("{}{line}", "\t".repeat(depth - 1)));
+        } else {
+            self.output.push(line.to_string());
+        }
+    }
+
+    fn blank(&mut self) {
+        if self.output.last().is_some_and(|v| !v.is_empty()) {
+            self.output.push(String::new());
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.blank();
+        self.comments(usize::MAX, 0, false);
+        while self.output.last().is_some_and(|v| v.is_empty()) {
+            self.output.pop();
+        }
+        if self.output.is_empty() {
+            return String::new();
+        }
+        format!("{}\n", self.output.join("\n"))
+    }
+
+    /// The line `header` starts on. The parser keeps the line of the value for some headers, and
+    /// the last one for headers with sections, so this looks for the `-- ` line before it.
+    fn header_start(&self, header: &ftd_p1::Header) -> usize {
+        match header {
+            ftd_p1::Header::KV(kv) => self.header_start_of_kv(kv),
+            ftd_p1::Header::Section(header) => self.start_of(
+                header
+                    .section
+                    .first()
+                    .map(|v| v.line_number.saturating_sub(1))
+                    .unwrap_or(header.line_number),
+            ),
+            ftd_p1::Header::BlockRecordHeader(record) => self.start_of(record.line_number),
+        }
+    }
+
+    fn header_start_of_kv(&self, kv: &ftd_p1::KV) -> usize {
+        match kv.source {
+            ftd_p1::KVSource::Header => kv.line_number,
+            _ => self.start_of(kv.line_number),
+        }
+    }
+
+    /// The `-- ` line the body of `section` comes after, the section line or `-- <section>.body:`,
+    /// and the line comments above the body are above of: that one, or the blank line after the
+    /// headers for a body right after them.
+    fn body_start(&self, section: &ftd_p1::Section, body: &ftd_p1::Body) -> Option<(usize, usize)> {
+        let start = self.start_of(body.line_number);
+        if start == section.line_number {
+            let blank = (start..self.lines.len()).find(|i| self.lines[*i].trim().is_empty())?;
+            return Some((start, blank + 1));
+        }
+        let line = self.lines.get(start.wrapping_sub(1))?.trim_start();
+        line.starts_with(&format!("-- {}.body:", section.name))
+            .then_some((start, start))
+    }
+
+    /// The closest line at or before `line_number` that starts a section or a header.
+    fn start_of(&self, line_number: usize) -> usize {
+        (1..=line_number.min(self.lines.len()))
+            .rev()
+            .find(|v| {
+                let line = self.lines[v - 1].trim_start();
+                line.starts_with("-- ") || line.starts_with("/-- ")
+            })
+            .unwrap_or(line_number)
+    }
 }
 
-fn get_section_name(input: &str) -> Option<String> {
-    use itertools::Itertools;
+/// The headers of a definition are its fields or arguments, their order is the one they are
+/// shown in.
+fn is_definition(section: &ftd_p1::Section) -> bool {
+    ftd_ast::ComponentDefinition::is_component_definition(section)
+        || ftd_ast::WebComponentDefinition::is_web_component_definition(section)
+        || section.kind.as_deref() == Some("record")
+}
 
-    let first_line = if let Some((first_line, _)) = input.split_once('\n') {
-        first_line.trim().to_string()
-    } else {
-        input.trim().to_string()
+fn header_order(a: &str, b: &str) -> std::cmp::Ordering {
+    let rank = |key: &str| match key {
+        ftd_ast::utils::FOR => 0,
+        ftd_p1::utils::IF => 1,
+        _ => 2,
     };
-    if !first_line.starts_with("-- ") && !first_line.starts_with("/-- ") {
-        None
-    } else if let Some((section_name_kind, _)) = first_line.split_once(':') {
-        Some(
-            section_name_kind
-                .split_whitespace()
-                .join(" ")
-                .rsplit_once(' ')
-                .map(|(_, v)| v.to_string())
-                .unwrap_or_else(|| section_name_kind.to_string()),
-        )
-    } else {
-        None
+    rank(a).cmp(&rank(b)).then_with(|| a.cmp(b))
+}
+
+/// The caption, if it has to be a `-- <section>.caption:` block.
+fn caption_block(caption: &ftd_p1::Header) -> Option<&str> {
+    match caption {
+        ftd_p1::Header::KV(kv) => kv
+            .value
+            .as_deref()
+            .filter(|v| v.is_empty() || v.contains('\n')),
+        _ => None,
     }
 }
 
-fn empty_section(input: &mut String) -> Option<Section> {
-    let mut value = vec![];
-    while !input.is_empty() {
-        let (first_line, remaining) = match input.split_once('\n') {
-            Some((first_line, remaining)) => (first_line.to_string(), remaining.to_string()),
-            None => (input.to_string(), String::new()),
-        };
-        let trimmed_line = first_line.trim().to_string();
-        if !trimmed_line.is_empty() {
-            *input = format!("{first_line}\n{remaining}");
-            break;
+fn is_inline(kv: &ftd_p1::KV) -> bool {
+    let fits = |v: &Option<String>| v.as_deref().is_none_or(|v| !v.contains(':'));
+    match kv.source {
+        ftd_p1::KVSource::Header => true,
+        // `-- <section>.<header>: <value>`, comments are not dropped from such lines
+        ftd_p1::KVSource::Caption => {
+            fits(&kv.kind)
+                && fits(&kv.condition)
+                && kv.value.as_deref().is_none_or(|v| !v.contains(";;"))
         }
-        value.push(trimmed_line);
-        *input = remaining.to_string();
+        ftd_p1::KVSource::Body => kv.value.is_none() && fits(&kv.kind) && fits(&kv.condition),
     }
+}
 
-    if !value.is_empty() {
-        Some(Section::new_empty(value.join("\n").as_str()))
+fn kv_line(kv: &ftd_p1::KV, escaped: bool) -> String {
+    let mut line = kind_and_name(kind_with_access_modifier(kv).as_deref(), kv.key.as_str());
+    if let Some(condition) = kv.condition.as_deref() {
+        line.push_str(ftd_p1::utils::INLINE_IF);
+        line.push_str(condition);
+    }
+    line.push(':');
+    if let Some(value) = kv.value.as_deref() {
+        line.push(' ');
+        line.push_str(value);
+    }
+    if escaped {
+        escape(line.as_str())
     } else {
-        None
+        line
     }
 }
 
-fn comment_section(input: &mut String) -> Option<Section> {
-    let mut value = vec![];
+/// The parser takes `private` out of the kind of a header.
+fn kind_with_access_modifier(kv: &ftd_p1::KV) -> Option<String> {
+    if kv.access_modifier.is_public() {
+        return kv.kind.clone();
+    }
+    Some(
+        ["private", kv.kind.as_deref().unwrap_or_default()]
+            .join(" ")
+            .trim_end()
+            .to_string(),
+    )
+}
 
-    while !input.is_empty() {
-        let (first_line, remaining) = match input.split_once('\n') {
-            Some((first_line, remaining)) => (first_line.to_string(), remaining.to_string()),
-            None => (input.to_string(), String::new()),
-        };
-        let trimmed_line = first_line.trim().to_string();
-        if trimmed_line.starts_with("-- ")
-            || trimmed_line.starts_with("/-- ")
-            || !trimmed_line.starts_with(";;")
-        {
-            *input = format!("{first_line}\n{remaining}");
-            break;
+fn kind_and_name(kind: Option<&str>, name: &str) -> String {
+    match kind.filter(|v| !v.is_empty()) {
+        Some(kind) => format!("{kind} {name}"),
+        None => name.to_string(),
+    }
+}
+
+/// `line` written so that the parser reads it back as `line`: a leading `;;` or `-- ` is
+/// escaped, and so is any `;;` after it, which the parser would take for a comment.
+fn escape(line: &str) -> String {
+    let trimmed = line.trim_start();
+    let indentation = &line[..line.len() - trimmed.len()];
+    if trimmed.starts_with(";;") || trimmed.starts_with("-- ") {
+        return format!("{indentation}\\{trimmed}");
+    }
+    format!("{indentation}{}", escape_comments(trimmed))
+}
+
+fn escape_comments(value: &str) -> String {
+    if value.contains("<hl>") {
+        return value.to_string();
+    }
+    value.replace(";;", "\\;;")
+}
+
+/// The `;; comment` the parser drops from the end of `line`, as `remove_inline_comments` in
+/// `ftd_p1` does.
+fn inline_comment(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+    if line.contains("<hl>")
+        || trimmed.starts_with(";;")
+        || trimmed.starts_with("\\;;")
+        || trimmed.starts_with("\\-- ")
+    {
+        return None;
+    }
+
+    let mut chars = line.char_indices().peekable();
+    let mut escape = false;
+    let mut count = 0;
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' {
+            escape = true;
+            count += 1;
+            match chars.peek() {
+                Some((_, ';')) => {
+                    chars.next();
+                    continue;
+                }
+                Some((_, '\\')) => {}
+                _ => {
+                    escape = false;
+                    count = 0;
+                }
+            }
+        }
+
+        if c == ';' {
+            if escape {
+                if count % 2 == 0 {
+                    return None;
+                }
+                escape = false;
+                count = 0;
+            } else if let Some((_, ';')) = chars.peek() {
+                return Some(&line[i..]);
+            }
+        }
+
+        if escape {
+            escape = false;
+            count = 0;
         }
-        value.push(trimmed_line);
-        *input = remaining.to_string();
     }
-    if !value.is_empty() {
-        Some(Section::new_comment(value.join("\n").as_str()))
-    } else {
-        None
+
+    None
+}
+
+/// All the comments in `content`, the ones on their own line and the ones at the end of a line.
+fn comments(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let trimmed = line.trim();
+            if trimmed.starts_with(";;") {
+                return Some(trimmed.to_string());
+            }
+            inline_comment(line).map(|v| v.trim().to_string())
+        })
+        .collect()
+}
+
+/// The sections without what formatting changes: line numbers, which headers are blocks, the
+/// order of headers with different keys and spaces in values that the parser does not drop.
+fn normalise(sections: &[ftd_p1::Section]) -> Vec<ftd_p1::Section> {
+    sections.iter().map(normalise_section).collect()
+}
+
+fn normalise_section(section: &ftd_p1::Section) -> ftd_p1::Section {
+    ftd_p1::Section {
+        name: section.name.clone(),
+        kind: section.kind.clone(),
+        caption: section.caption.as_ref().map(normalise_header),
+        headers: ftd_p1::Headers(normalise_headers(section.headers.0.as_slice())),
+        body: section.body.as_ref().map(|v| ftd_p1::Body {
+            line_number: 0,
+            value: trim_lines(v.value.as_str()),
+        }),
+        sub_sections: normalise(section.sub_sections.as_slice()),
+        is_commented: section.is_commented,
+        line_number: 0,
+        block_body: false,
+    }
+}
+
+fn normalise_headers(headers: &[ftd_p1::Header]) -> Vec<ftd_p1::Header> {
+    let mut headers: Vec<_> = headers.iter().map(normalise_header).collect();
+    headers.sort_by_key(|v| v.get_key());
+    headers
+}
+
+fn normalise_header(header: &ftd_p1::Header) -> ftd_p1::Header {
+    match header {
+        ftd_p1::Header::KV(kv) => ftd_p1::Header::KV(ftd_p1::KV {
+            line_number: 0,
+            value: kv.value.as_deref().map(trim_lines),
+            source: Default::default(),
+            ..kv.clone()
+        }),
+        ftd_p1::Header::Section(header) => ftd_p1::Header::Section(ftd_p1::SectionHeader {
+            line_number: 0,
+            section: normalise(header.section.as_slice()),
+            ..header.clone()
+        }),
+        ftd_p1::Header::BlockRecordHeader(record) => {
+            ftd_p1::Header::BlockRecordHeader(ftd_p1::BlockRecordHeader {
+                line_number: 0,
+                body: (record.body.0.as_deref().map(trim_lines), None),
+                fields: normalise_headers(record.fields.as_slice()),
+                ..record.clone()
+            })
+        }
+    }
+}
+
+/// A line with only spaces counts when the parser takes the indentation out of a value, so the
+/// lines after the first may have more of it left than the formatted value has.
+fn trim_lines(value: &str) -> String {
+    let lines: Vec<&str> = value.lines().map(str::trim_end).collect();
+    let indentation = lines
+        .iter()
+        .skip(1)
+        .filter(|v| !v.is_empty())
+        .map(|v| v.len() - v.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, v)| {
+            if i == 0 || v.is_empty() {
+                v
+            } else {
+                &v[indentation..]
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    #[track_caller]
+    fn p(content: &str, expected: &str) {
+        let formatted = super::format(content, "foo", true).unwrap();
+        pretty_assertions::assert_eq!(formatted, expected);
+        // formatting is idempotent
+        pretty_assertions::assert_eq!(super::format(expected, "foo", true).unwrap(), expected);
+    }
+
+    #[test]
+    fn sections() {
+        p(
+            indoc::indoc!(
+                "
+                ;; the page
+                -- ftd.column:
+                   padding.px: 10
+                -- ftd.text: hello   ;; greeting
+                -- end: ftd.column
+
+
+
+                -- ftd.text:
+                color: red
+
+
+
+                Some text
+
+                  indented
+                "
+            ),
+            indoc::indoc!(
+                "
+                ;; the page
+                -- ftd.column:
+                padding.px: 10
+
+                -- ftd.text: hello ;; greeting
+
+                -- end: ftd.column
+
+                -- ftd.text:
+                color: red
+
+                Some text
+
+                  indented
+                "
+            ),
+        );
+    }
+
+    #[test]
+    fn end_only_where_needed() {
+        p(
+            indoc::indoc!(
+                "
+                -- ftd.text: hello
+                -- end: ftd.text
+
+                -- ftd.column:
+                -- ftd.row:
+                -- ftd.column:
+                -- ftd.text: deep
+                -- end: ftd.column
+                -- end: ftd.row
+                -- end: ftd.column
+                "
+            ),
+            indoc::indoc!(
+                "
+                -- ftd.text: hello
+
+                -- ftd.column:
+
+                -- ftd.row:
+
+                \t-- ftd.column:
+
+                \t\t-- ftd.text: deep
+
+                \t-- end: ftd.column
+
+                -- end: ftd.row
+
+                -- end: ftd.column
+                "
+            ),
+        );
+    }
+
+    #[test]
+    fn block_headers() {
+        p(
+            indoc::indoc!(
+                "
+                -- component foo:
+                caption title:
+
+                -- string foo.subtitle: hello
+
+                -- foo.body:
+
+                the body
+                ;; inside the body
+                with \\;; escaped
+
+                -- string foo.text:
+
+                multi
+                line
+
+                -- end: foo
+                "
+            ),
+            indoc::indoc!(
+                "
+                -- component foo:
+                caption title:
+                string subtitle: hello
+
+                -- string foo.text:
+
+                multi
+                line
+
+                -- foo.body:
+
+                the body
+                ;; inside the body
+                with \\;; escaped
+                "
+            ),
+        );
+    }
+
+    #[test]
+    fn header_order() {
+        p(
+            indoc::indoc!(
+                "
+                -- ftd.text: hello
+                color: red
+                if: { show }
+                align-self: center
+                padding.px if { wide }: 20
+                padding.px: 10
+
+                -- record person:
+                caption name:
+                integer age:
+                "
+            ),
+            indoc::indoc!(
+                "
+                -- ftd.text: hello
+                if: { show }
+                align-self: center
+                color: red
+                padding.px if { wide }: 20
+                padding.px: 10
+
+                -- record person:
+                caption name:
+                integer age:
+                "
+            ),
+        );
+    }
+
+    #[test]
+    fn comments_are_kept() {
+        let content = indoc::indoc!(
+            "
+            -- ftd.text: a
+            ;; about b
+
+            -- ftd.text: b
+            "
+        );
+        let formatted = super::format(content, "foo", true).unwrap();
+        assert!(formatted.contains(";; about b\n\n-- ftd.text: b"));
     }
 }
@@ -0,0 +1,170 @@
+//! `fastn serve --watch`: reload open pages when the files they were rendered from change.
+//!
+//! A [`LiveReload`] lives in `fastn_core::Config`. The watcher started by [`watch`] polls the
+//! package root through `fastn_ds::DocumentStore::watch` and broadcasts the changed modules to
+//! every page listening on [`EVENTS_PATH`]. Each served page carries the list of modules it
+//! was rendered from (`RequestConfig::dependencies_during_render`), and only reloads itself if
+//! one of them changed. The same watcher also invalidates the `--cache-size` response cache and
+//! the search index.
+
+/// The server-sent events endpoint pages subscribe to.
+pub const EVENTS_PATH: &str = "/-/live-reload/";
+
+/// Sent instead of a module name when every open page should reload, e.g. when `FASTN.ftd` or
+/// a static asset changes.
+pub(crate) const RELOAD_ALL: &str = "*";
+
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+#[derive(Debug, Clone)]
+pub struct LiveReload {
+    sender: tokio::sync::broadcast::Sender<Vec<String>>,
+}
+
+impl Default for LiveReload {
+    fn default() -> Self {
+        let (sender, _) = tokio::sync::broadcast::channel(16);
+        LiveReload { sender }
+    }
+}
+
+impl LiveReload {
+    /// Streams every batch of changed modules as a server-sent event.
+    pub fn events(&self) -> fastn_core::http::Response {
+        use futures_util::StreamExt;
+
+        let stream = futures::stream::unfold(self.sender.subscribe(), |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(modules) => return Some((modules, receiver)),
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(tokio::sync::broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+        .map(|modules| {
+            Ok::<_, actix_web::Error>(bytes::Bytes::from(format!(
+                "data: {}\n\n",
+                serde_json::to_string(&modules).unwrap()
+            )))
+        });
+
+        actix_web::HttpResponse::Ok()
+            .content_type("text/event-stream")
+            .append_header(("Cache-Control", "no-cache"))
+            // the compress middleware buffers the body, which would hold back the events
+            .append_header(("Content-Encoding", "identity"))
+            .streaming(stream)
+    }
+}
+
+/// Starts watching the package root, skipping `.packages` and `.build`, if live reload or the
+/// response cache is enabled.
+pub fn watch(config: &fastn_core::Config) {
+    let live_reload = config.live_reload.clone();
+    let response_cache = config.response_cache.clone();
+    if live_reload.is_none() && response_cache.is_none() {
+        return;
+    }
+
+    let search_index = config.search_index.clone();
+    let package_name = config.package.name.clone();
+    let mut receiver = config.ds.watch(
+        &config.ds.root(),
+        vec![".packages".to_string(), ".build".to_string()],
+        POLL_INTERVAL,
+    );
+
+    tokio::spawn(async move {
+        while let Some(paths) = receiver.recv().await {
+            let modules: Vec<String> = paths
+                .iter()
+                .map(|path| changed_module(package_name.as_str(), path.to_string().as_str()))
+                .collect();
+            tracing::info!(msg = "files changed", ?modules);
+            if let Some(response_cache) = response_cache.as_ref() {
+                response_cache.invalidate(modules.as_slice());
+            }
+            search_index.invalidate();
+            if let Some(live_reload) = live_reload.as_ref() {
+                // sending only fails when no page is listening
+                live_reload.sender.send(modules).ok();
+            }
+        }
+    });
+}
+
+/// `foo/bar.ftd` -> `<package>/foo/bar`, `FASTN.ftd` and non-ftd files -> [`RELOAD_ALL`].
+fn changed_module(package_name: &str, path: &str) -> String {
+    let path = path.replace(std::path::MAIN_SEPARATOR, "/");
+    match path.strip_suffix(".ftd") {
+        Some(id) if path != "FASTN.ftd" => module_name(format!("{package_name}/{id}").as_str()),
+        _ => RELOAD_ALL.to_string(),
+    }
+}
+
+/// Normalises the different spellings of a module (`pkg/foo/`, `pkg/foo/index.ftd`, `pkg/foo`)
+/// so the modules a page depends on can be compared with the modules that changed.
+pub(crate) fn module_name(module: &str) -> String {
+    let module = module.trim_end_matches('/');
+    let module = module.strip_suffix(".ftd").unwrap_or(module);
+    module.strip_suffix("/index").unwrap_or(module).to_string()
+}
+
+/// The script added to every page served with `--watch`. `dependencies` are the modules the
+/// page was rendered from.
+pub fn script(dependencies: &[String]) -> String {
+    let modules: Vec<String> = dependencies.iter().map(|d| module_name(d)).collect();
+
+    format!(
+        r#"<script>
+    (function() {{
+        let modules = {modules};
+        let events = new EventSource("{EVENTS_PATH}");
+        events.onmessage = function(event) {{
+            let changed = JSON.parse(event.data);
+            if (changed.some(m => m === "{RELOAD_ALL}" || modules.includes(m))) {{
+                window.location.reload();
+            }}
+        }};
+    }})();
+</script>"#,
+        modules = serde_json::to_string(&modules).unwrap(),
+    )
+}
+
+/// Adds the live reload `script` right before `</body>`, or at the end of the page.
+pub fn inject(html: Vec<u8>, script: &str) -> Vec<u8> {
+    let html = String::from_utf8_lossy(&html);
+    match html.rfind("</body>") {
+        Some(index) => format!("{}{script}{}", &html[..index], &html[index..]),
+        None => format!("{html}{script}"),
+    }
+    .into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn changed_module() {
+        assert_eq!(
+            super::changed_module("foo.com", "bar/baz.ftd"),
+            "foo.com/bar/baz"
+        );
+        assert_eq!(
+            super::changed_module("foo.com", "bar/index.ftd"),
+            "foo.com/bar"
+        );
+        assert_eq!(super::changed_module("foo.com", "index.ftd"), "foo.com");
+        assert_eq!(super::changed_module("foo.com", "FASTN.ftd"), "*");
+        assert_eq!(super::changed_module("foo.com", "static/a.css"), "*");
+    }
+
+    #[test]
+    fn module_name() {
+        assert_eq!(super::module_name("foo.com/bar/"), "foo.com/bar");
+        assert_eq!(super::module_name("foo.com/index.ftd"), "foo.com");
+        assert_eq!(super::module_name("foo.com/"), "foo.com");
+        assert_eq!(super::module_name("foo.com/bar/index.ftd"), "foo.com/bar");
+    }
+}
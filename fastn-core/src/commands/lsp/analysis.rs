@@ -0,0 +1,557 @@
+//! What the server knows about `.ftd` source: words, sections, imports and definitions.
+
+// `CompletionItemKind`s
+pub(crate) const FUNCTION: u8 = 3;
+pub(crate) const VARIABLE: u8 = 6;
+pub(crate) const CLASS: u8 = 7;
+pub(crate) const PROPERTY: u8 = 10;
+pub(crate) const ENUM: u8 = 13;
+pub(crate) const KEYWORD: u8 = 14;
+pub(crate) const STRUCT: u8 = 22;
+
+pub(crate) const KEYWORDS: &[&str] = &[
+    "import",
+    "component",
+    "record",
+    "or-type",
+    "web-component",
+    "end",
+    "string",
+    "integer",
+    "decimal",
+    "boolean",
+    "datetime",
+    "date",
+    "optional",
+];
+
+pub(crate) fn uri_to_path(uri: &str) -> Option<fastn_ds::Path> {
+    let path = url::Url::parse(uri).ok()?.to_file_path().ok()?;
+    Some(fastn_ds::Path::new(path.to_str()?))
+}
+
+pub(crate) fn path_to_uri(path: &fastn_ds::Path) -> String {
+    url::Url::from_file_path(path.to_string())
+        .map(|uri| uri.to_string())
+        .unwrap_or_else(|_| format!("file://{path}"))
+}
+
+/// `pkg/foo` for `foo.ftd`, `pkg` for `index.ftd`.
+pub(crate) fn module_name(document: &fastn_core::Document) -> String {
+    document.id_with_package().trim_end_matches('/').to_string()
+}
+
+/// Errors `ftd_p1` and `ftd_ast` find in the document, without interpreting it.
+pub(crate) fn parse(document: &fastn_core::Document) -> fastn_core::Result<()> {
+    let id = document.id_with_package();
+    let sections = ftd_p1::parse(document.content.as_str(), id.as_str())?;
+    ftd_ast::Ast::from_sections(sections.as_slice(), id.as_str())?;
+    Ok(())
+}
+
+/// An LSP `Diagnostic` for `error`. Errors in an imported module are reported on the first line.
+pub(crate) fn diagnostic(error: &fastn_core::Error, doc_id: &str) -> serde_json::Value {
+    let (error_doc_id, line_number, message) =
+        location(error).unwrap_or_else(|| (None, 0, error.to_string()));
+    let (line, message) = match error_doc_id {
+        Some(id) if id.trim_end_matches('/') != doc_id.trim_end_matches('/') => {
+            (0, format!("{id}: {message}"))
+        }
+        _ => (line_number.saturating_sub(1), message),
+    };
+    // information, not an error, the processor is fine, see `Config::skip_side_effects`
+    let severity = if message.ends_with(fastn_core::library2022::SKIPPED_PROCESSOR) {
+        3
+    } else {
+        1
+    };
+
+    serde_json::json!({
+        "range": {
+            "start": {"line": line, "character": 0},
+            "end": {"line": line + 1, "character": 0},
+        },
+        "severity": severity,
+        "source": "fastn",
+        "message": message,
+    })
+}
+
+/// (document, line number, message) of the error.
+fn location(error: &fastn_core::Error) -> Option<(Option<String>, usize, String)> {
+    match error {
+        fastn_core::Error::FTDP1Error(e) => p1_location(e),
+        fastn_core::Error::FTDAstError(e) => ast_location(e),
+        fastn_core::Error::FTDInterpreterError(e) => interpreter_location(e),
+        _ => None,
+    }
+}
+
+fn interpreter_location(
+    error: &ftd::interpreter::Error,
+) -> Option<(Option<String>, usize, String)> {
+    use ftd::interpreter::Error;
+
+    match error {
+        Error::P1Error(e) => p1_location(e),
+        Error::ASTError(e) => ast_location(e),
+        Error::InvalidKind {
+            doc_id,
+            line_number,
+            message,
+        }
+        | Error::ValueNotFound {
+            doc_id,
+            line_number,
+            message,
+        }
+        | Error::ParseError {
+            doc_id,
+            line_number,
+            message,
+        } => Some((Some(doc_id.clone()), *line_number, message.clone())),
+        Error::InvalidAccessError {
+            line_number,
+            message,
+        }
+        | Error::FoundCycle {
+            line_number,
+            message,
+        } => Some((None, *line_number, message.clone())),
+        _ => None,
+    }
+}
+
+fn ast_location(error: &ftd_ast::Error) -> Option<(Option<String>, usize, String)> {
+    match error {
+        ftd_ast::Error::P1(e) => p1_location(e),
+        ftd_ast::Error::Parse {
+            doc_id,
+            line_number,
+            message,
+        } => Some((Some(doc_id.clone()), *line_number, message.clone())),
+        _ => None,
+    }
+}
+
+fn p1_location(error: &ftd_p1::Error) -> Option<(Option<String>, usize, String)> {
+    use ftd_p1::Error;
+
+    let (doc_id, line_number) = match error {
+        Error::SectionNotFound {
+            doc_id,
+            line_number,
+        }
+        | Error::MoreThanOneCaption {
+            doc_id,
+            line_number,
+        }
+        | Error::ParseError {
+            doc_id,
+            line_number,
+            ..
+        }
+        | Error::MoreThanOneHeader {
+            doc_id,
+            line_number,
+            ..
+        }
+        | Error::HeaderNotFound {
+            doc_id,
+            line_number,
+            ..
+        } => (doc_id, line_number),
+    };
+
+    // the message without the `<doc_id>:<line_number> -> ` prefix
+    let message = error.to_string();
+    let message = match message.split_once(" -> ") {
+        Some((_, message)) => message.to_string(),
+        None => message.clone(),
+    };
+
+    Some((Some(doc_id.clone()), *line_number, message))
+}
+
+/// Every section of `text` that parses, so a section being typed does not hide the others.
+pub(crate) fn definitions(text: &str, module: &str) -> Vec<ftd_ast::Ast> {
+    match ftd_p1::parse(text, module) {
+        Ok(sections) => sections
+            .iter()
+            .filter_map(|section| section.remove_comments())
+            .filter_map(|section| ftd_ast::Ast::from_section(&section, module).ok())
+            .collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// alias -> module, from the `-- import:`s in the document and the auto imports of the package.
+pub(crate) fn aliases(
+    config: &fastn_core::Config,
+    definitions: &[ftd_ast::Ast],
+) -> std::collections::BTreeMap<String, String> {
+    let mut aliases = std::collections::BTreeMap::from([("ftd".to_string(), "ftd".to_string())]);
+    for auto_import in config.package.auto_import.iter() {
+        let alias = auto_import.alias.clone().unwrap_or_else(|| {
+            auto_import
+                .path
+                .rsplit('/')
+                .next()
+                .unwrap_or(auto_import.path.as_str())
+                .to_string()
+        });
+        aliases.insert(alias, auto_import.path.clone());
+    }
+    for definition in definitions {
+        if let ftd_ast::Ast::Import(import) = definition {
+            aliases.insert(import.alias.clone(), import.module.clone());
+        }
+    }
+    aliases
+}
+
+/// (module, name) `word` refers to: `ftd.text` -> (`ftd`, `text`), `$person.name` -> (`module`,
+/// `person`).
+pub(crate) fn resolve(
+    word: &str,
+    module: &str,
+    aliases: &std::collections::BTreeMap<String, String>,
+) -> (String, String) {
+    fn first(name: &str) -> String {
+        name.split('.').next().unwrap_or(name).to_string()
+    }
+
+    let word = word.trim_start_matches(['$', '*']);
+    if let Some((module, name)) = word.split_once('#') {
+        return (module.to_string(), first(name));
+    }
+    if let Some((alias, name)) = word.split_once('.') {
+        if let Some(module) = aliases.get(alias) {
+            return (module.to_string(), first(name));
+        }
+    }
+    (module.to_string(), first(word))
+}
+
+/// The module of an `-- import:` line.
+pub(crate) fn imported_module(line: &str) -> Option<&str> {
+    let module = line.trim_start().strip_prefix("-- import:")?;
+    let module = module.split(" as ").next().unwrap_or(module).trim();
+    (!module.is_empty()).then_some(module)
+}
+
+/// The byte index of `character`, which LSP counts in utf-16 code units.
+pub(crate) fn byte_index(line: &str, character: usize) -> usize {
+    let mut units = 0;
+    for (index, c) in line.char_indices() {
+        if units >= character {
+            return index;
+        }
+        units += c.len_utf16();
+    }
+    line.len()
+}
+
+/// The name, reference or module qualified name at `character`.
+pub(crate) fn word_at(line: &str, character: usize) -> Option<&str> {
+    let is_word = |c: char| c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '#' | '$' | '*');
+
+    let index = byte_index(line, character);
+    let start = line[..index]
+        .char_indices()
+        .rev()
+        .find(|(_, c)| !is_word(*c))
+        .map(|(i, c)| i + c.len_utf8())
+        .unwrap_or(0);
+    let end = line[index..]
+        .find(|c: char| !is_word(c))
+        .map(|i| index + i)
+        .unwrap_or(line.len());
+
+    let word = line[start..end]
+        .trim_start_matches('-')
+        .trim_end_matches('.');
+    (!word.is_empty()).then_some(word)
+}
+
+pub(crate) enum Context<'a> {
+    /// `-- <cursor>`
+    Section,
+    /// `<cursor>` in the headers of `-- <section>:`
+    Header {
+        section: &'a str,
+    },
+    /// `$<cursor>`
+    Reference,
+    Other,
+}
+
+/// What is being typed, `prefix` is the text of the line before the cursor.
+pub(crate) fn context<'a>(lines: &[&'a str], line: usize, prefix: &str) -> Context<'a> {
+    if prefix
+        .rsplit(char::is_whitespace)
+        .next()
+        .is_some_and(|word| word.starts_with('$'))
+    {
+        return Context::Reference;
+    }
+
+    let trimmed = prefix.trim_start();
+    if trimmed.starts_with("--") {
+        return if trimmed.contains(':') {
+            Context::Other
+        } else {
+            Context::Section
+        };
+    }
+
+    match header_section(lines, line, prefix) {
+        Some(section) => Context::Header { section },
+        None => Context::Other,
+    }
+}
+
+/// The component whose headers the cursor is in, if it is before the `:` of a header. Sections
+/// that define things (`-- component foo:`, `-- string name:`) have no such component.
+pub(crate) fn header_section<'a>(lines: &[&'a str], line: usize, prefix: &str) -> Option<&'a str> {
+    let trimmed = prefix.trim_start();
+    if trimmed.starts_with("--") || trimmed.starts_with(';') || trimmed.contains(':') {
+        return None;
+    }
+
+    for previous in lines.get(..line)?.iter().rev().copied() {
+        let previous = previous.trim_start();
+        if previous.is_empty() {
+            // the body of the section
+            return None;
+        }
+        if let Some(section) = previous.strip_prefix("-- ") {
+            let name = section.split(':').next()?.trim();
+            if name == "end" || name.contains(char::is_whitespace) {
+                return None;
+            }
+            return Some(name);
+        }
+    }
+
+    None
+}
+
+pub(crate) fn section_item_kind(thing: &fastn_resolved::Definition) -> Option<u8> {
+    match thing {
+        fastn_resolved::Definition::Component(_) | fastn_resolved::Definition::WebComponent(_) => {
+            Some(CLASS)
+        }
+        fastn_resolved::Definition::Record(_) => Some(STRUCT),
+        fastn_resolved::Definition::OrType(_) => Some(ENUM),
+        _ => None,
+    }
+}
+
+pub(crate) fn ast_section_item_kind(definition: &ftd_ast::Ast) -> Option<u8> {
+    match definition {
+        ftd_ast::Ast::ComponentDefinition(_) | ftd_ast::Ast::WebComponentDefinition(_) => {
+            Some(CLASS)
+        }
+        ftd_ast::Ast::Record(_) => Some(STRUCT),
+        ftd_ast::Ast::OrType(_) => Some(ENUM),
+        ftd_ast::Ast::FunctionDefinition(_) => Some(FUNCTION),
+        _ => None,
+    }
+}
+
+/// The kind as it is written in `ftd`: `optional caption string`, `ftd.color list`.
+pub(crate) fn kind_data(kind: &fastn_resolved::KindData) -> String {
+    let (optional, inner) = match &kind.kind {
+        fastn_resolved::Kind::Optional { kind } => ("optional ", kind.as_ref()),
+        kind => ("", kind),
+    };
+    let source = match (kind.caption, kind.body) {
+        (true, true) => "caption or body ",
+        (true, false) => "caption ",
+        (false, true) => "body ",
+        (false, false) => "",
+    };
+    format!("{optional}{source}{}", kind_name(inner))
+}
+
+fn kind_name(kind: &fastn_resolved::Kind) -> String {
+    use fastn_resolved::Kind;
+
+    match kind {
+        Kind::String => "string".to_string(),
+        Kind::Integer => "integer".to_string(),
+        Kind::Decimal => "decimal".to_string(),
+        Kind::Boolean => "boolean".to_string(),
+        Kind::DateTime => "datetime".to_string(),
+        Kind::Date => "date".to_string(),
+        Kind::Object => "object".to_string(),
+        Kind::Record { name } | Kind::OrType { name, .. } => name.replace('#', "."),
+        Kind::List { kind } => format!("{} list", kind_name(kind)),
+        Kind::Map { kind } => format!("{} map", kind_name(kind)),
+        Kind::Optional { kind } => format!("optional {}", kind_name(kind)),
+        Kind::Constant { kind } => format!("constant {}", kind_name(kind)),
+        Kind::UI { .. } => "ftd.ui".to_string(),
+        Kind::Void => "void".to_string(),
+        Kind::Module => "module".to_string(),
+        Kind::KwArgs => "kw-args".to_string(),
+    }
+}
+
+pub(crate) fn ast_kind(kind: &ftd_ast::VariableKind) -> String {
+    match kind.modifier {
+        Some(ftd_ast::VariableModifier::List) => format!("{} list", kind.kind),
+        Some(ftd_ast::VariableModifier::Map) => format!("{} map", kind.kind),
+        Some(ftd_ast::VariableModifier::Optional) => format!("optional {}", kind.kind),
+        Some(ftd_ast::VariableModifier::Constant) => format!("constant {}", kind.kind),
+        None => kind.kind.clone(),
+    }
+}
+
+/// (name, kind) of the arguments of a component, or the fields of a record.
+pub(crate) fn arguments(thing: &fastn_resolved::Definition) -> Vec<(String, String)> {
+    let arguments = match thing {
+        fastn_resolved::Definition::Component(c) => &c.arguments,
+        fastn_resolved::Definition::WebComponent(w) => &w.arguments,
+        fastn_resolved::Definition::Record(r) => &r.fields,
+        _ => return vec![],
+    };
+    arguments
+        .iter()
+        .map(|a| (a.name.clone(), kind_data(&a.kind)))
+        .collect()
+}
+
+pub(crate) fn ast_arguments(definition: &ftd_ast::Ast) -> Vec<(String, String)> {
+    let arguments = match definition {
+        ftd_ast::Ast::ComponentDefinition(c) => &c.arguments,
+        ftd_ast::Ast::WebComponentDefinition(w) => &w.arguments,
+        ftd_ast::Ast::Record(r) => &r.fields,
+        ftd_ast::Ast::FunctionDefinition(f) => &f.arguments,
+        _ => return vec![],
+    };
+    arguments
+        .iter()
+        .map(|a| (a.name.clone(), ast_kind(&a.kind)))
+        .collect()
+}
+
+/// The definition as `ftd` source, `name` as it is written where it is used.
+pub(crate) fn hover_text(name: &str, thing: &fastn_resolved::Definition) -> Option<String> {
+    let header = match thing {
+        fastn_resolved::Definition::Variable(v) => {
+            let mutable = if v.mutable { "$" } else { "" };
+            return Some(format!("-- {} {mutable}{name}:", kind_data(&v.kind)));
+        }
+        fastn_resolved::Definition::Component(_) => format!("-- component {name}:"),
+        fastn_resolved::Definition::WebComponent(_) => format!("-- web-component {name}:"),
+        fastn_resolved::Definition::Record(_) => format!("-- record {name}:"),
+        fastn_resolved::Definition::OrType(_) => return Some(format!("-- or-type {name}:")),
+        fastn_resolved::Definition::Function(f) => {
+            let arguments: Vec<(String, String)> = f
+                .arguments
+                .iter()
+                .map(|a| (a.name.clone(), kind_data(&a.kind)))
+                .collect();
+            return Some(with_arguments(
+                function_header(name, kind_data(&f.return_kind), &arguments),
+                arguments,
+            ));
+        }
+        _ => return None,
+    };
+    Some(with_arguments(header, arguments(thing)))
+}
+
+pub(crate) fn ast_hover_text(name: &str, definition: &ftd_ast::Ast) -> Option<String> {
+    let header = match definition {
+        ftd_ast::Ast::VariableDefinition(v) => {
+            let mutable = if v.mutable { "$" } else { "" };
+            return Some(format!("-- {} {mutable}{name}:", ast_kind(&v.kind)));
+        }
+        ftd_ast::Ast::ComponentDefinition(_) => format!("-- component {name}:"),
+        ftd_ast::Ast::WebComponentDefinition(_) => format!("-- web-component {name}:"),
+        ftd_ast::Ast::Record(_) => format!("-- record {name}:"),
+        ftd_ast::Ast::OrType(_) => return Some(format!("-- or-type {name}:")),
+        ftd_ast::Ast::FunctionDefinition(f) => {
+            function_header(name, ast_kind(&f.kind), &ast_arguments(definition))
+        }
+        _ => return None,
+    };
+    Some(with_arguments(header, ast_arguments(definition)))
+}
+
+/// `-- integer sum(a,b):`
+fn function_header(name: &str, kind: String, arguments: &[(String, String)]) -> String {
+    let names: Vec<&str> = arguments.iter().map(|(name, _)| name.as_str()).collect();
+    format!("-- {kind} {name}({}):", names.join(","))
+}
+
+fn with_arguments(header: String, arguments: Vec<(String, String)>) -> String {
+    arguments.into_iter().fold(header, |text, (name, kind)| {
+        format!("{text}\n{kind} {name}:")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn word_at() {
+        let line = "-- ftd.text: $person.name";
+        assert_eq!(super::word_at(line, 0), None);
+        assert_eq!(super::word_at(line, 5), Some("ftd.text"));
+        assert_eq!(super::word_at(line, 11), Some("ftd.text"));
+        assert_eq!(super::word_at(line, 16), Some("$person.name"));
+        assert_eq!(super::word_at("a 😀 foo", 5), Some("foo"));
+    }
+
+    #[test]
+    fn resolve() {
+        let aliases = std::collections::BTreeMap::from([
+            ("ftd".to_string(), "ftd".to_string()),
+            ("lib".to_string(), "foo.com/lib".to_string()),
+        ]);
+        let resolve = |word| super::resolve(word, "foo.com/index", &aliases);
+        assert_eq!(resolve("ftd.text"), ("ftd".to_string(), "text".to_string()));
+        assert_eq!(
+            resolve("$lib.person.name"),
+            ("foo.com/lib".to_string(), "person".to_string())
+        );
+        assert_eq!(
+            resolve("$person.name"),
+            ("foo.com/index".to_string(), "person".to_string())
+        );
+    }
+
+    #[test]
+    fn header_section() {
+        let lines = vec![
+            "-- ftd.column:",
+            "padding.px: 10",
+            "",
+            "-- component foo:",
+            "",
+        ];
+        assert_eq!(super::header_section(&lines, 1, "pad"), Some("ftd.column"));
+        assert_eq!(super::header_section(&lines, 1, "padding.px: 1"), None);
+        assert_eq!(super::header_section(&lines, 3, "body"), None);
+        assert_eq!(super::header_section(&lines, 4, "cap"), None);
+    }
+
+    #[test]
+    fn kind_data() {
+        let mut kind = fastn_resolved::KindData::new(fastn_resolved::Kind::Optional {
+            kind: Box::new(fastn_resolved::Kind::String),
+        });
+        kind.caption = true;
+        kind.body = true;
+        assert_eq!(super::kind_data(&kind), "optional caption or body string");
+
+        let kind = fastn_resolved::KindData::new(fastn_resolved::Kind::List {
+            kind: Box::new(fastn_resolved::Kind::Record {
+                name: "ftd#color".to_string(),
+            }),
+        });
+        assert_eq!(super::kind_data(&kind), "ftd.color list");
+    }
+}
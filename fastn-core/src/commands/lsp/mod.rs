@@ -0,0 +1,669 @@
+//! `fastn lsp`: a language server for `.ftd` files, speaking the Language Server Protocol over
+//! stdio.
+//!
+//! Diagnostics come from parsing the document on every change, and from interpreting it when it
+//! is opened or saved. Interpreting runs processors (`sql-query` and such), so it is not done on
+//! every keystroke. Processors with side effects are not run (see `Config::skip_side_effects`),
+//! the document is only checked up to the first one. Stdout is the protocol, everything else
+//! `fastn` prints goes to stderr. Go-to-definition and completions work on the `ftd_ast` of the
+//! document and of the modules it imports, so they keep working while the document has errors.
+//! Hovers show the `fastn_resolved::Kind` from the last successful interpretation of the
+//! document. `ftd.*` comes from `fastn_builtins::default_bag()`.
+
+pub(crate) mod analysis;
+
+pub async fn lsp(config: &fastn_core::Config) -> fastn_core::Result<()> {
+    let mut stdin = tokio::io::BufReader::new(tokio::io::stdin());
+    let mut server = Server::new(config);
+
+    while let Some(message) = read_message(&mut stdin).await? {
+        let message = match message {
+            Ok(message) => message,
+            Err(e) => {
+                server
+                    .send(serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": null,
+                        "error": {"code": e.code(), "message": e.to_string()},
+                    }))
+                    .await?;
+                continue;
+            }
+        };
+        if !server.handle(message).await? {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+struct Server<'a> {
+    config: &'a fastn_core::Config,
+    /// uri -> text of the documents open in the editor
+    open: std::collections::HashMap<String, String>,
+    /// uri -> last successful interpretation of the document
+    interpreted: std::collections::HashMap<String, ftd::interpreter::Document>,
+    builtins: std::collections::HashMap<String, fastn_resolved::Definition>,
+    stdout: tokio::io::Stdout,
+}
+
+impl<'a> Server<'a> {
+    fn new(config: &'a fastn_core::Config) -> Self {
+        Server {
+            config,
+            open: Default::default(),
+            interpreted: Default::default(),
+            builtins: fastn_builtins::default_bag().into_iter().collect(),
+            stdout: tokio::io::stdout(),
+        }
+    }
+
+    /// Returns `false` once the client asks the server to exit.
+    async fn handle(&mut self, message: serde_json::Value) -> std::io::Result<bool> {
+        let method = match message.get("method").and_then(|m| m.as_str()) {
+            Some(method) => method,
+            // a response, the server does not send requests
+            None => return Ok(true),
+        };
+        let params = message.get("params").cloned().unwrap_or_default();
+        let uri = params
+            .pointer("/textDocument/uri")
+            .and_then(|uri| uri.as_str())
+            .unwrap_or_default()
+            .to_string();
+
+        match method {
+            "exit" => return Ok(false),
+            "textDocument/didOpen" => {
+                if let Some(text) = params
+                    .pointer("/textDocument/text")
+                    .and_then(|t| t.as_str())
+                {
+                    self.open.insert(uri.clone(), text.to_string());
+                    self.diagnose(uri.as_str(), true).await?;
+                }
+            }
+            "textDocument/didChange" => {
+                // full sync, the last change has the whole text
+                if let Some(text) = params
+                    .get("contentChanges")
+                    .and_then(|c| c.as_array())
+                    .and_then(|c| c.last())
+                    .and_then(|c| c.get("text"))
+                    .and_then(|t| t.as_str())
+                {
+                    self.open.insert(uri.clone(), text.to_string());
+                    self.diagnose(uri.as_str(), false).await?;
+                }
+            }
+            "textDocument/didSave" => self.diagnose(uri.as_str(), true).await?,
+            "textDocument/didClose" => {
+                self.open.remove(&uri);
+                self.interpreted.remove(&uri);
+                self.publish_diagnostics(uri.as_str(), vec![]).await?;
+            }
+            _ => {}
+        }
+
+        let id = match message.get("id") {
+            Some(id) => id.clone(),
+            None => return Ok(true),
+        };
+
+        let position = Position::from_params(&params);
+        let result = match method {
+            "initialize" => capabilities(),
+            "shutdown" => serde_json::Value::Null,
+            "textDocument/definition" => self.definition(uri.as_str(), position).await,
+            "textDocument/hover" => self.hover(uri.as_str(), position).await,
+            "textDocument/completion" => self.completion(uri.as_str(), position).await,
+            _ => {
+                return self
+                    .send(serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": {
+                            "code": METHOD_NOT_FOUND,
+                            "message": format!("{method} is not supported"),
+                        },
+                    }))
+                    .await
+                    .map(|_| true);
+            }
+        };
+
+        self.send(serde_json::json!({"jsonrpc": "2.0", "id": id, "result": result}))
+            .await?;
+        Ok(true)
+    }
+
+    async fn diagnose(&mut self, uri: &str, interpret: bool) -> std::io::Result<()> {
+        use futures_util::FutureExt;
+
+        let document = match self.open.get(uri).and_then(|text| self.document(uri, text)) {
+            Some(document) => document,
+            None => return Ok(()),
+        };
+
+        let error = match analysis::parse(&document) {
+            Err(e) => Some(e),
+            Ok(()) if !interpret => None,
+            Ok(()) => match std::panic::AssertUnwindSafe(fastn_core::doc::interpret_document(
+                self.config,
+                &document,
+                &None,
+            ))
+            .catch_unwind()
+            .await
+            {
+                Ok(Ok(doc)) => {
+                    self.interpreted.insert(uri.to_string(), doc);
+                    None
+                }
+                Ok(Err(e)) => Some(e),
+                Err(_) => Some(fastn_core::Error::GenericError(
+                    "interpreter panicked, see the server log".to_string(),
+                )),
+            },
+        };
+
+        let diagnostics = error
+            .map(|e| {
+                vec![analysis::diagnostic(
+                    &e,
+                    document.id_with_package().as_str(),
+                )]
+            })
+            .unwrap_or_default();
+        self.publish_diagnostics(uri, diagnostics).await
+    }
+
+    async fn publish_diagnostics(
+        &mut self,
+        uri: &str,
+        diagnostics: Vec<serde_json::Value>,
+    ) -> std::io::Result<()> {
+        self.send(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": {"uri": uri, "diagnostics": diagnostics},
+        }))
+        .await
+    }
+
+    async fn definition(&self, uri: &str, position: Position) -> serde_json::Value {
+        let (text, document) = match self.text_and_document(uri).await {
+            Some(v) => v,
+            None => return serde_json::Value::Null,
+        };
+        let line = match text.split('\n').nth(position.line) {
+            Some(line) => line,
+            None => return serde_json::Value::Null,
+        };
+
+        // `-- import: <module>` goes to the module itself
+        if let Some(module) = analysis::imported_module(line) {
+            return match self.module_file(module).await {
+                Some(path) => location(analysis::path_to_uri(&path), 0),
+                None => serde_json::Value::Null,
+            };
+        }
+
+        let word = match analysis::word_at(line, position.character) {
+            Some(word) => word,
+            None => return serde_json::Value::Null,
+        };
+        let module = analysis::module_name(&document);
+        let aliases = analysis::aliases(self.config, &analysis::definitions(&text, &module));
+        let (target, name) = analysis::resolve(word, module.as_str(), &aliases);
+
+        let (target_uri, target_text) = if target == module {
+            (uri.to_string(), text)
+        } else {
+            let path = match self.module_file(target.as_str()).await {
+                Some(path) => path,
+                None => return serde_json::Value::Null,
+            };
+            let target_uri = analysis::path_to_uri(&path);
+            match self.text(target_uri.as_str()).await {
+                Some(text) => (target_uri, text),
+                None => return serde_json::Value::Null,
+            }
+        };
+
+        match analysis::definitions(&target_text, &target)
+            .iter()
+            .find(|d| d.get_definition_name().as_deref() == Some(name.as_str()))
+        {
+            Some(definition) => location(target_uri, definition.line_number()),
+            None => serde_json::Value::Null,
+        }
+    }
+
+    async fn hover(&self, uri: &str, position: Position) -> serde_json::Value {
+        let (text, document) = match self.text_and_document(uri).await {
+            Some(v) => v,
+            None => return serde_json::Value::Null,
+        };
+        let lines: Vec<&str> = text.split('\n').collect();
+        let line = match lines.get(position.line) {
+            Some(line) => *line,
+            None => return serde_json::Value::Null,
+        };
+        let word = match analysis::word_at(line, position.character) {
+            Some(word) => word,
+            None => return serde_json::Value::Null,
+        };
+        let module = analysis::module_name(&document);
+        let aliases = analysis::aliases(self.config, &analysis::definitions(&text, &module));
+
+        // `<argument>: value` in a component invocation
+        let index = analysis::byte_index(line, position.character);
+        if let Some(section) = analysis::header_section(&lines, position.line, &line[..index]) {
+            let argument = word.split('.').next().unwrap_or(word);
+            return match self
+                .arguments(section, &module, &text, &aliases)
+                .await
+                .into_iter()
+                .find(|(name, _)| name == argument)
+            {
+                Some((name, kind)) => hover(format!("{kind} {name}:")),
+                None => serde_json::Value::Null,
+            };
+        }
+
+        let (target, name) = analysis::resolve(word, module.as_str(), &aliases);
+        let full_name = format!("{target}#{name}");
+        let display = word.trim_start_matches(['$', '*']);
+
+        if let Some(thing) = self.builtins.get(&full_name).or_else(|| {
+            self.interpreted
+                .get(uri)
+                .and_then(|doc| doc.data.get(&full_name))
+        }) {
+            return analysis::hover_text(display, thing)
+                .map(hover)
+                .unwrap_or_default();
+        }
+
+        match self
+            .module_definitions(&target, &module, &text)
+            .await
+            .iter()
+            .find(|d| d.get_definition_name().as_deref() == Some(name.as_str()))
+        {
+            Some(definition) => analysis::ast_hover_text(display, definition)
+                .map(hover)
+                .unwrap_or_default(),
+            None => serde_json::Value::Null,
+        }
+    }
+
+    async fn completion(&self, uri: &str, position: Position) -> serde_json::Value {
+        let (text, document) = match self.text_and_document(uri).await {
+            Some(v) => v,
+            None => return serde_json::Value::Null,
+        };
+        let lines: Vec<&str> = text.split('\n').collect();
+        let line = match lines.get(position.line) {
+            Some(line) => *line,
+            None => return serde_json::Value::Null,
+        };
+        let prefix = &line[..analysis::byte_index(line, position.character)];
+        let module = analysis::module_name(&document);
+        let definitions = analysis::definitions(&text, &module);
+        let aliases = analysis::aliases(self.config, &definitions);
+
+        let mut items = vec![];
+        match analysis::context(&lines, position.line, prefix) {
+            analysis::Context::Section => {
+                for keyword in analysis::KEYWORDS {
+                    items.push(completion_item(keyword, analysis::KEYWORD, None));
+                }
+                for (name, thing) in self.builtins.iter() {
+                    if let Some(kind) = analysis::section_item_kind(thing) {
+                        items.push(completion_item(&name.replace('#', "."), kind, None));
+                    }
+                }
+                for (prefix, definitions) in self.imported_definitions(definitions, &aliases).await
+                {
+                    for definition in definitions.iter() {
+                        if let (Some(name), Some(kind)) = (
+                            definition.get_definition_name(),
+                            analysis::ast_section_item_kind(definition),
+                        ) {
+                            items.push(completion_item(&format!("{prefix}{name}"), kind, None));
+                        }
+                    }
+                }
+            }
+            analysis::Context::Header { section } => {
+                for (name, kind) in self.arguments(section, &module, &text, &aliases).await {
+                    items.push(completion_item(&name, analysis::PROPERTY, Some(kind)));
+                }
+            }
+            analysis::Context::Reference => {
+                for (name, thing) in self.builtins.iter() {
+                    if let fastn_resolved::Definition::Variable(v) = thing {
+                        items.push(completion_item(
+                            &format!("${}", name.replace('#', ".")),
+                            analysis::VARIABLE,
+                            Some(analysis::kind_data(&v.kind)),
+                        ));
+                    }
+                }
+                for (prefix, definitions) in self.imported_definitions(definitions, &aliases).await
+                {
+                    for definition in definitions.iter() {
+                        if let ftd_ast::Ast::VariableDefinition(v) = definition {
+                            items.push(completion_item(
+                                &format!("${prefix}{}", v.name),
+                                analysis::VARIABLE,
+                                Some(analysis::ast_kind(&v.kind)),
+                            ));
+                        }
+                    }
+                }
+            }
+            analysis::Context::Other => {}
+        }
+
+        serde_json::Value::Array(items)
+    }
+
+    /// The definitions of the document (with an empty prefix) and of every module it imports
+    /// (prefixed with `<alias>.`).
+    async fn imported_definitions(
+        &self,
+        definitions: Vec<ftd_ast::Ast>,
+        aliases: &std::collections::BTreeMap<String, String>,
+    ) -> Vec<(String, Vec<ftd_ast::Ast>)> {
+        let mut all = vec![(String::new(), definitions)];
+        for (alias, module) in aliases.iter() {
+            if module == "ftd" {
+                continue;
+            }
+            if let Some(text) = self.module_text(module).await {
+                all.push((format!("{alias}."), analysis::definitions(&text, module)));
+            }
+        }
+        all
+    }
+
+    /// (name, kind) of every argument of the component `section` (as written in the document).
+    async fn arguments(
+        &self,
+        section: &str,
+        module: &str,
+        text: &str,
+        aliases: &std::collections::BTreeMap<String, String>,
+    ) -> Vec<(String, String)> {
+        let (target, name) = analysis::resolve(section, module, aliases);
+        if let Some(thing) = self.builtins.get(&format!("{target}#{name}")) {
+            return analysis::arguments(thing);
+        }
+
+        self.module_definitions(&target, module, text)
+            .await
+            .iter()
+            .find(|d| d.get_definition_name().as_deref() == Some(name.as_str()))
+            .map(analysis::ast_arguments)
+            .unwrap_or_default()
+    }
+
+    async fn module_definitions(
+        &self,
+        target: &str,
+        module: &str,
+        text: &str,
+    ) -> Vec<ftd_ast::Ast> {
+        if target == module {
+            return analysis::definitions(text, module);
+        }
+
+        match self.module_text(target).await {
+            Some(text) => analysis::definitions(&text, target),
+            None => vec![],
+        }
+    }
+
+    async fn module_text(&self, module: &str) -> Option<String> {
+        let path = self.module_file(module).await?;
+        self.text(analysis::path_to_uri(&path).as_str()).await
+    }
+
+    /// The file `module` is read from: a document of this package, or of a dependency in
+    /// `.packages`.
+    async fn module_file(&self, module: &str) -> Option<fastn_ds::Path> {
+        let mut module = module.trim_matches('/').to_string();
+        for dependency in self.config.package.dependencies.iter() {
+            if let Some(alias) = dependency.alias.as_ref() {
+                if module == *alias || module.starts_with(format!("{alias}/").as_str()) {
+                    module = module.replacen(alias.as_str(), dependency.package.name.as_str(), 1);
+                }
+            }
+        }
+
+        let root = self.config.ds.root();
+        let package = self.config.package.name.as_str();
+        let (base, path) = if module == package {
+            (root, "")
+        } else if let Some(path) = module.strip_prefix(format!("{package}/").as_str()) {
+            (root, path)
+        } else {
+            (root.join(".packages"), module.as_str())
+        };
+
+        let candidates = if path.is_empty() {
+            vec!["index.ftd".to_string()]
+        } else {
+            vec![format!("{path}.ftd"), format!("{path}/index.ftd")]
+        };
+        for candidate in candidates {
+            let file = base.join(candidate);
+            if self.config.ds.exists(&file, &None).await {
+                return Some(file);
+            }
+        }
+
+        None
+    }
+
+    /// The open text of `uri`, or the file on disk.
+    async fn text(&self, uri: &str) -> Option<String> {
+        if let Some(text) = self.open.get(uri) {
+            return Some(text.clone());
+        }
+        let path = analysis::uri_to_path(uri)?;
+        self.config.ds.read_to_string(&path, &None).await.ok()
+    }
+
+    async fn text_and_document(&self, uri: &str) -> Option<(String, fastn_core::Document)> {
+        let text = self.text(uri).await?;
+        let document = self.document(uri, &text)?;
+        Some((text, document))
+    }
+
+    /// `None` for files outside the package.
+    fn document(&self, uri: &str, text: &str) -> Option<fastn_core::Document> {
+        let root = self.config.ds.root();
+        let id = analysis::uri_to_path(uri)?.strip_prefix(&root)?.to_string();
+        Some(fastn_core::Document {
+            package_name: self.config.package.name.clone(),
+            id,
+            content: text.to_string(),
+            parent_path: root,
+        })
+    }
+
+    async fn send(&mut self, message: serde_json::Value) -> std::io::Result<()> {
+        use tokio::io::AsyncWriteExt;
+
+        let body = message.to_string();
+        self.stdout
+            .write_all(format!("Content-Length: {}\r\n\r\n{body}", body.len()).as_bytes())
+            .await?;
+        self.stdout.flush().await
+    }
+}
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Larger messages are skipped, a document is sent whole on every change but is far smaller.
+const MAX_MESSAGE_LENGTH: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Position {
+    line: usize,
+    /// utf-16 code units, see `analysis::byte_index`
+    character: usize,
+}
+
+impl Position {
+    fn from_params(params: &serde_json::Value) -> Position {
+        let get = |pointer: &str| {
+            params
+                .pointer(pointer)
+                .and_then(|v| v.as_u64())
+                .unwrap_or_default() as usize
+        };
+        Position {
+            line: get("/position/line"),
+            character: get("/position/character"),
+        }
+    }
+}
+
+fn capabilities() -> serde_json::Value {
+    serde_json::json!({
+        "capabilities": {
+            "textDocumentSync": {
+                "openClose": true,
+                // full text on every change
+                "change": 1,
+                "save": true,
+            },
+            "definitionProvider": true,
+            "hoverProvider": true,
+            "completionProvider": {"triggerCharacters": ["-", ".", "$"]},
+        },
+        "serverInfo": {"name": "fastn", "version": env!("CARGO_PKG_VERSION")},
+    })
+}
+
+/// `line_number` is 1 based, as in `ftd_p1`.
+fn location(uri: String, line_number: usize) -> serde_json::Value {
+    let position = serde_json::json!({"line": line_number.saturating_sub(1), "character": 0});
+    serde_json::json!({"uri": uri, "range": {"start": position, "end": position}})
+}
+
+fn hover(ftd: String) -> serde_json::Value {
+    serde_json::json!({
+        "contents": {"kind": "markdown", "value": format!("```ftd\n{ftd}\n```")},
+    })
+}
+
+fn completion_item(label: &str, kind: u8, detail: Option<String>) -> serde_json::Value {
+    let mut item = serde_json::json!({"label": label, "kind": kind});
+    if let Some(detail) = detail {
+        item["detail"] = serde_json::Value::String(detail);
+    }
+    item
+}
+
+#[derive(thiserror::Error, Debug)]
+enum MessageError {
+    #[error("{0}")]
+    Json(#[from] serde_json::Error),
+    #[error("the message has {0} bytes, more than the {MAX_MESSAGE_LENGTH} allowed")]
+    TooLarge(usize),
+}
+
+impl MessageError {
+    fn code(&self) -> i64 {
+        match self {
+            MessageError::Json(_) => PARSE_ERROR,
+            MessageError::TooLarge(_) => INVALID_REQUEST,
+        }
+    }
+}
+
+/// Reads one `Content-Length` framed message, `None` once the client closes stdin. A message
+/// that is not JSON, or is too large, is an `Err` the server answers, it does not stop the server.
+async fn read_message<R>(
+    reader: &mut R,
+) -> std::io::Result<Option<Result<serde_json::Value, MessageError>>>
+where
+    R: tokio::io::AsyncBufRead + Unpin,
+{
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() && length.is_some() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let length = length.unwrap_or_default();
+    if length > MAX_MESSAGE_LENGTH {
+        tokio::io::copy(
+            &mut (&mut *reader).take(length as u64),
+            &mut tokio::io::sink(),
+        )
+        .await?;
+        return Ok(Some(Err(MessageError::TooLarge(length))));
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(
+        serde_json::from_slice(&body).map_err(MessageError::from),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    #[tokio::test]
+    async fn read_message() {
+        let input = "Content-Length: 17\r\n\r\n{\"method\":\"exit\"}Content-Length: 2\r\n\r\n{]\
+            Content-Length: 2\r\n\r\n{}";
+        let mut reader = tokio::io::BufReader::new(input.as_bytes());
+        assert_eq!(
+            next(&mut reader).await,
+            Some(Some(serde_json::json!({"method": "exit"})))
+        );
+        assert_eq!(next(&mut reader).await, Some(None));
+        assert_eq!(next(&mut reader).await, Some(Some(serde_json::json!({}))));
+        assert_eq!(next(&mut reader).await, None);
+
+        // a too large message is skipped, the next one is read
+        let length = super::MAX_MESSAGE_LENGTH + 1;
+        let input = format!(
+            "Content-Length: {length}\r\n\r\n{}Content-Length: 2\r\n\r\n{{}}",
+            " ".repeat(length)
+        );
+        let mut reader = tokio::io::BufReader::new(input.as_bytes());
+        assert!(matches!(
+            super::read_message(&mut reader).await.unwrap(),
+            Some(Err(super::MessageError::TooLarge(l))) if l == length
+        ));
+        assert_eq!(next(&mut reader).await, Some(Some(serde_json::json!({}))));
+    }
+
+    /// The next message, `Some(None)` if it is not JSON.
+    async fn next(reader: &mut tokio::io::BufReader<&[u8]>) -> Option<Option<serde_json::Value>> {
+        super::read_message(reader).await.unwrap().map(|m| m.ok())
+    }
+}
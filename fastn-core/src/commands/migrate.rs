@@ -0,0 +1,127 @@
+/// What `fastn migrate` does with the migrations of the package.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Status,
+    /// Applies the pending migrations, up to and including `to`.
+    Up {
+        to: Option<i64>,
+    },
+    /// Reverts the applied migrations after `to`, only the latest one if `to` is not given.
+    Down {
+        to: Option<i64>,
+    },
+}
+
+/// With `dry_run`, prints the SQL `up` and `down` would run instead of running it, and writes
+/// nothing to the database.
+pub async fn migrate(
+    config: &fastn_core::Config,
+    action: Action,
+    dry_run: bool,
+) -> fastn_core::Result<()> {
+    let app_name = config.package.name.as_str();
+    let migrations = config.package.migrations.as_slice();
+
+    match action {
+        Action::Status => status(config).await,
+        Action::Up { to } => {
+            let now = chrono::Utc::now().timestamp_nanos_opt().unwrap();
+            if !dry_run {
+                fastn_core::migrations::create_migration_table(config)
+                    .await
+                    .map_err(fastn_core::migrations::MigrationError::from)?;
+                fastn_core::migrations::migrate_fastn(config, now).await?;
+            }
+
+            let pending = fastn_core::migrations::pending_migrations(
+                config, migrations, app_name, to, dry_run,
+            )
+            .await?;
+            if pending.is_empty() {
+                println!("No migrations to apply.");
+            }
+            for migration in pending {
+                if dry_run {
+                    print_sql("Would apply", &migration, migration.content.as_str());
+                    continue;
+                }
+                println!("Applying Migration for {app_name}: {}", migration.name);
+                fastn_core::migrations::apply_migration(config, app_name, &migration, now).await?;
+            }
+            Ok(())
+        }
+        Action::Down { to } => {
+            if !dry_run {
+                fastn_core::migrations::create_migration_table(config)
+                    .await
+                    .map_err(fastn_core::migrations::MigrationError::from)?;
+            }
+
+            let to_revert =
+                fastn_core::migrations::migrations_to_revert(config, migrations, app_name, to)
+                    .await?;
+            if to_revert.is_empty() {
+                println!("No migrations to revert.");
+            }
+            for migration in to_revert {
+                if dry_run {
+                    print_sql(
+                        "Would revert",
+                        &migration,
+                        migration.down.as_deref().unwrap_or_default(),
+                    );
+                    continue;
+                }
+                println!("Reverting Migration for {app_name}: {}", migration.name);
+                fastn_core::migrations::revert_migration(config, app_name, &migration).await?;
+            }
+            Ok(())
+        }
+    }
+}
+
+async fn status(config: &fastn_core::Config) -> fastn_core::Result<()> {
+    use colored::Colorize;
+
+    let statuses = fastn_core::migrations::migration_status(config).await?;
+    if statuses.is_empty() {
+        println!("No migrations in FASTN.ftd.");
+        return Ok(());
+    }
+
+    for status in statuses {
+        let state = match status.state {
+            fastn_core::migrations::MigrationState::Pending => "pending".yellow(),
+            fastn_core::migrations::MigrationState::Applied { applied_on } => {
+                format!("applied on {}", format_time(applied_on)).green()
+            }
+            fastn_core::migrations::MigrationState::Edited { applied_on } => {
+                format!("applied on {}, edited since", format_time(applied_on)).red()
+            }
+            fastn_core::migrations::MigrationState::Deleted { applied_on } => format!(
+                "applied on {}, not in FASTN.ftd anymore",
+                format_time(applied_on)
+            )
+            .red(),
+        };
+        let down = if status.has_down { "" } else { " (no down)" };
+        println!("{:>4} {} {state}{down}", status.number, status.name);
+    }
+
+    Ok(())
+}
+
+fn print_sql(what: &str, migration: &fastn_core::package::MigrationData, sql: &str) {
+    println!(
+        "-- {what} {} {}\n{}\n",
+        migration.number,
+        migration.name,
+        sql.trim()
+    );
+}
+
+fn format_time(nanos: i64) -> String {
+    chrono::DateTime::from_timestamp_nanos(nanos)
+        .format("%Y-%m-%d %H:%M:%S UTC")
+        .to_string()
+}
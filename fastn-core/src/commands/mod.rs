@@ -1,7 +1,11 @@
 pub mod build;
 pub mod check;
 pub mod fmt;
+pub mod live_reload;
+pub mod lsp;
+pub mod migrate;
 pub mod query;
+pub mod response_cache;
 pub mod serve;
 pub mod test;
 pub mod translation_status;
@@ -1,9 +1,48 @@
+/// A stage of turning an ftd document into a page, `fastn query --stage` dumps the document as it
+/// is after it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stage {
+    P1,
+    Ast,
+    /// `ftd::interpreter::Document`, everything resolved
+    Interpreter,
+    /// the element tree of `ftd::executor`
+    Executor,
+    /// the `ftd::node` tree the html of `ftd 0.3` is rendered from
+    Node,
+    /// the script `fastn-js` generates for the document
+    Js,
+    /// the page, as `fastn serve` sends it
+    Html,
+}
+
+impl std::str::FromStr for Stage {
+    type Err = fastn_core::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "p1" => Ok(Stage::P1),
+            "ast" => Ok(Stage::Ast),
+            "interpreter" => Ok(Stage::Interpreter),
+            "executor" => Ok(Stage::Executor),
+            "node" => Ok(Stage::Node),
+            "js" => Ok(Stage::Js),
+            "html" => Ok(Stage::Html),
+            t => fastn_core::usage_error(format!(
+                "Unknown stage {t}, use one of: p1, ast, interpreter, executor, node, js, html"
+            )),
+        }
+    }
+}
+
 pub async fn query(
     config: &fastn_core::Config,
     stage: &str,
     path: Option<&str>,
     with_null: bool,
+    definition: Option<&str>,
 ) -> fastn_core::Result<()> {
+    let stage: Stage = stage.parse()?;
     let documents = std::collections::BTreeMap::from_iter(
         config
             .get_files(&config.package, &None)
@@ -19,43 +58,48 @@ pub async fn query(
             },
         )?;
 
-        let value = get_ftd_json(file, stage)?;
-        println!(
-            "{}",
-            if with_null {
-                fastn_core::utils::value_to_colored_string(&value, 1)
-            } else {
-                fastn_core::utils::value_to_colored_string_without_null(&value, 1)
-            }
-        );
+        let value = get_ftd_json(config, file, stage, definition).await?;
+        // the script and the page are printed as they are, not as a json string
+        match value {
+            serde_json::Value::String(text) => println!("{text}"),
+            value => println!("{}", to_colored_string(&value, with_null)),
+        }
 
         return Ok(());
     }
     let mut values: serde_json::Map<String, serde_json::Value> = serde_json::Map::new();
     for file in documents.values() {
-        if file.is_ftd() {
-            let value = get_ftd_json(file, stage)?;
+        if file.is_ftd() && file.get_id() != "FASTN.ftd" {
+            let value = get_ftd_json(config, file, stage, definition).await?;
             values.insert(file.get_id().to_string(), value);
         }
     }
 
     let value = serde_json::Value::Object(values);
 
-    println!(
-        "{}",
-        if with_null {
-            fastn_core::utils::value_to_colored_string(&value, 1)
-        } else {
-            fastn_core::utils::value_to_colored_string_without_null(&value, 1)
-        }
-    );
+    println!("{}", to_colored_string(&value, with_null));
 
     Ok(())
 }
 
-pub(crate) fn get_ftd_json(
+fn to_colored_string(value: &serde_json::Value, with_null: bool) -> String {
+    if with_null {
+        fastn_core::utils::value_to_colored_string(value, 1)
+    } else {
+        fastn_core::utils::value_to_colored_string_without_null(value, 1)
+    }
+}
+
+/// `file` after `stage`, only the component, record, variable etc. named `definition` of it if
+/// that is given, which works for the `ast` and `interpreter` stages.
+///
+/// The `query` processor calls this, and interpreting the document can run the processor again.
+#[async_recursion::async_recursion(?Send)]
+pub(crate) async fn get_ftd_json(
+    config: &fastn_core::Config,
     file: &fastn_core::File,
-    stage: &str,
+    stage: Stage,
+    definition: Option<&str>,
 ) -> fastn_core::Result<serde_json::Value> {
     let document = if let fastn_core::File::Ftd(document) = file {
         document
@@ -65,10 +109,42 @@ pub(crate) fn get_ftd_json(
         });
     };
 
+    if definition.is_some() && ![Stage::Ast, Stage::Interpreter].contains(&stage) {
+        return fastn_core::usage_error(
+            "--definition works with the ast and interpreter stages".to_string(),
+        );
+    }
+
     match stage {
-        "p1" => get_p1_json(document),
-        "ast" => get_ast_json(document),
-        _ => unimplemented!(),
+        Stage::P1 => get_p1_json(document),
+        Stage::Ast => get_ast_json(document, definition),
+        Stage::Interpreter => {
+            let interpreted = fastn_core::doc::interpret_document(config, document, &None).await?;
+            match definition {
+                Some(name) => get_definition_json(&interpreted, name),
+                None => Ok(interpreter_json(&interpreted)?),
+            }
+        }
+        Stage::Executor => {
+            let interpreted = fastn_core::doc::interpret_document(config, document, &None).await?;
+            let rt = ftd::executor::ExecuteDoc::from_interpreter(interpreted)?;
+            Ok(sort_js_and_css(serde_json::to_value(rt)?))
+        }
+        Stage::Node => {
+            let interpreted = fastn_core::doc::interpret_document(config, document, &None).await?;
+            let rt = ftd::executor::ExecuteDoc::from_interpreter(interpreted)?;
+            let node = ftd::node::NodeData::from_rt(rt);
+            Ok(sort_js_and_css(serde_json::to_value(node)?))
+        }
+        Stage::Js => {
+            let interpreted = fastn_core::doc::interpret_document(config, document, &None).await?;
+            let js_ast_data = ftd::js::document_into_js_ast(interpreted);
+            Ok(serde_json::Value::String(fastn_js::to_js(
+                js_ast_data.asts.as_slice(),
+                config.package.name.as_str(),
+            )))
+        }
+        Stage::Html => get_html_json(config, document).await,
     }
 }
 
@@ -82,12 +158,126 @@ fn get_p1_json(document: &fastn_core::Document) -> fastn_core::Result<serde_json
     Ok(value)
 }
 
-fn get_ast_json(document: &fastn_core::Document) -> fastn_core::Result<serde_json::Value> {
+fn get_ast_json(
+    document: &fastn_core::Document,
+    definition: Option<&str>,
+) -> fastn_core::Result<serde_json::Value> {
     let id = document.id_with_package();
     let p1 = ftd_p1::parse(document.content.as_str(), id.as_str())?;
 
     let ast = ftd_ast::Ast::from_sections(p1.as_slice(), id.as_str())?;
-    let value = serde_json::to_value(ast)?;
+    let Some(name) = definition else {
+        return Ok(serde_json::to_value(ast)?);
+    };
 
-    Ok(value)
+    match ast
+        .into_iter()
+        .find(|v| v.get_definition_name().as_deref() == Some(name))
+    {
+        Some(ast) => Ok(serde_json::to_value(ast)?),
+        None => fastn_core::usage_error(format!("{name} is not defined in {}", document.id)),
+    }
+}
+
+/// The interpreted document, with its definitions ordered by name and its `js` and `css` sorted,
+/// so the json is the same every time.
+fn interpreter_json(
+    document: &ftd::interpreter::Document,
+) -> serde_json::Result<serde_json::Value> {
+    #[derive(serde::Serialize)]
+    struct Interpreted<'a> {
+        name: &'a str,
+        aliases: &'a ftd::Map<String>,
+        data: std::collections::BTreeMap<&'a str, &'a ftd::interpreter::Thing>,
+        tree: &'a [fastn_resolved::ComponentInvocation],
+        js: std::collections::BTreeSet<&'a str>,
+        css: std::collections::BTreeSet<&'a str>,
+    }
+
+    serde_json::to_value(Interpreted {
+        name: document.name.as_str(),
+        aliases: &document.aliases,
+        data: document.data.iter().map(|(k, v)| (k.as_str(), v)).collect(),
+        tree: document.tree.as_slice(),
+        js: document.js.iter().map(String::as_str).collect(),
+        css: document.css.iter().map(String::as_str).collect(),
+    })
+}
+
+/// The definition `name` of the document, `name` is as it would be written in the document:
+/// `foo`, `lib.foo` for an import or `<module>#foo`.
+fn get_definition_json(
+    document: &ftd::interpreter::Document,
+    name: &str,
+) -> fastn_core::Result<serde_json::Value> {
+    let resolved =
+        ftd::interpreter::utils::resolve_name(name, document.name.as_str(), &document.aliases);
+    match document.data.get(&resolved) {
+        Some(thing) => Ok(serde_json::to_value(thing)?),
+        None => fastn_core::usage_error(format!("{name} is not defined in {}", document.name)),
+    }
+}
+
+/// `js` and `css` of the executor and node stages are sets, in no particular order.
+fn sort_js_and_css(mut value: serde_json::Value) -> serde_json::Value {
+    for key in ["js", "css"] {
+        if let Some(serde_json::Value::Array(items)) = value.get_mut(key) {
+            items.sort_by_key(|v| v.to_string());
+        }
+    }
+    value
+}
+
+/// The page `fastn serve` would send for `document`, or where it redirects to.
+async fn get_html_json(
+    config: &fastn_core::Config,
+    document: &fastn_core::Document,
+) -> fastn_core::Result<serde_json::Value> {
+    let req = fastn_core::http::Request::default();
+    let mut req_config = fastn_core::RequestConfig::new(config, &req, document.id.as_str(), "/");
+    req_config.current_document = Some(document.id.to_string());
+
+    match fastn_core::package::package_doc::read_ftd_2023(
+        &mut req_config,
+        document,
+        "/",
+        false,
+        false,
+        &None,
+    )
+    .await?
+    {
+        fastn_core::package::package_doc::FTDResult::Html(html) => Ok(serde_json::Value::String(
+            String::from_utf8_lossy(html.as_slice()).to_string(),
+        )),
+        fastn_core::package::package_doc::FTDResult::Redirect { url, code } => {
+            Ok(serde_json::json!({"redirect": url, "code": code}))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn sort_js_and_css() {
+        let value = serde_json::json!({
+            "js": ["b.js", "a.js"],
+            "css": ["b.css", "a.css"],
+            "tree": ["b", "a"],
+        });
+        assert_eq!(
+            super::sort_js_and_css(value),
+            serde_json::json!({
+                "js": ["a.js", "b.js"],
+                "css": ["a.css", "b.css"],
+                "tree": ["b", "a"],
+            })
+        );
+    }
+
+    #[test]
+    fn stage() {
+        assert_eq!("js".parse::<super::Stage>().ok(), Some(super::Stage::Js));
+        assert!("p2".parse::<super::Stage>().is_err());
+    }
 }
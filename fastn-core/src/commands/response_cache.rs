@@ -0,0 +1,244 @@
+//! In-memory cache of rendered pages for `fastn serve --cache-size <N>`.
+//!
+//! Only responses whose `RequestConfig::response_is_cacheable` is still set after rendering are
+//! stored, i.e. pages that did not use the `http`, `sql-*`, `request-data` and other dynamic
+//! processors. Every entry remembers the modules the page was rendered from, and is dropped by
+//! the file watcher (`fastn_core::commands::live_reload::watch`) as soon as one of them changes.
+//! Requests of logged in readers are never cached, their pages can show `user-details` and other
+//! data of that reader.
+
+/// Admin endpoint that drops every cached page, needs `Authorization: Bearer <token>` with the
+/// token in `FASTN_CACHE_PURGE_TOKEN`, and is disabled if that is not set.
+pub const PURGE_PATH: &str = "/-/cache/purge/";
+pub const PURGE_TOKEN_ENV: &str = "FASTN_CACHE_PURGE_TOKEN";
+
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    inner: std::sync::Arc<antidote::Mutex<Inner>>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    capacity: usize,
+    entries: std::collections::HashMap<String, Entry>,
+    /// incremented on every lookup, entries with the smallest `last_used` get evicted first
+    clock: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    status: actix_web::http::StatusCode,
+    headers: actix_web::http::header::HeaderMap,
+    body: bytes::Bytes,
+    dependencies: Vec<String>,
+    last_used: u64,
+}
+
+impl ResponseCache {
+    pub fn new(capacity: usize) -> Self {
+        ResponseCache {
+            inner: std::sync::Arc::new(antidote::Mutex::new(Inner {
+                capacity,
+                entries: Default::default(),
+                clock: 0,
+            })),
+        }
+    }
+
+    /// Requests that render differently for the same url (language cookie, bots getting server
+    /// side rendered html) get their own entries. Requests with a session are not cached.
+    pub fn key(req: &fastn_core::http::Request, only_js: bool) -> Option<String> {
+        if req.method() != "GET"
+            || req.cookie(ft_sys_shared::SESSION_KEY).is_some()
+            || req.cookie(fastn_core::http::SESSION_COOKIE_NAME).is_some()
+        {
+            return None;
+        }
+
+        Some(format!(
+            "{only_js}|{}|{}|{}",
+            req.is_bot(),
+            req.cookie("fastn-lang").unwrap_or_default(),
+            req.full_path()
+        ))
+    }
+
+    pub fn get(&self, key: &str) -> Option<fastn_core::http::Response> {
+        let mut inner = self.inner.lock();
+        inner.clock += 1;
+        let clock = inner.clock;
+        let entry = inner.entries.get_mut(key)?;
+        entry.last_used = clock;
+
+        let mut response = actix_web::HttpResponse::with_body(entry.status, entry.body.clone());
+        *response.headers_mut() = entry.headers.clone();
+        Some(response.map_into_boxed_body())
+    }
+
+    /// Stores `response` and hands it back, `dependencies` are the modules the page was
+    /// rendered from.
+    pub async fn insert(
+        &self,
+        key: String,
+        response: fastn_core::http::Response,
+        dependencies: Vec<String>,
+    ) -> fastn_core::Result<fastn_core::http::Response> {
+        if !response.status().is_success()
+            || response
+                .headers()
+                .contains_key(actix_web::http::header::SET_COOKIE)
+        {
+            return Ok(response);
+        }
+
+        let (response, body) = response.into_parts();
+        let body = actix_web::body::to_bytes(body)
+            .await
+            .map_err(|e| fastn_core::Error::GenericError(format!("failed to read body: {e}")))?;
+
+        {
+            let mut inner = self.inner.lock();
+            if inner.entries.len() >= inner.capacity && !inner.entries.contains_key(&key) {
+                if let Some(oldest) = inner
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .map(|(key, _)| key.to_string())
+                {
+                    inner.entries.remove(&oldest);
+                }
+            }
+
+            let last_used = inner.clock;
+            inner.entries.insert(
+                key,
+                Entry {
+                    status: response.status(),
+                    headers: response.headers().clone(),
+                    body: body.clone(),
+                    dependencies: dependencies
+                        .iter()
+                        .map(|d| fastn_core::commands::live_reload::module_name(d))
+                        .collect(),
+                    last_used,
+                },
+            );
+        }
+
+        Ok(response.set_body(body).map_into_boxed_body())
+    }
+
+    /// Drops every entry that depends on one of the `modules` (as sent by the file watcher).
+    pub fn invalidate(&self, modules: &[String]) {
+        let mut inner = self.inner.lock();
+        if modules
+            .iter()
+            .any(|m| m == fastn_core::commands::live_reload::RELOAD_ALL)
+        {
+            inner.entries.clear();
+            return;
+        }
+
+        inner
+            .entries
+            .retain(|_, entry| !entry.dependencies.iter().any(|d| modules.contains(d)));
+    }
+
+    /// Drops everything, returns the number of entries dropped.
+    pub fn purge(&self) -> usize {
+        let mut inner = self.inner.lock();
+        let count = inner.entries.len();
+        inner.entries.clear();
+        count
+    }
+}
+
+pub async fn purge(
+    config: &fastn_core::Config,
+    cache: &ResponseCache,
+    req: &fastn_core::http::Request,
+) -> fastn_core::http::Response {
+    let token = match config.ds.env(PURGE_TOKEN_ENV).await {
+        Ok(token) if !token.is_empty() => token,
+        _ => return fastn_core::not_found!("{PURGE_PATH} is disabled, set {PURGE_TOKEN_ENV}"),
+    };
+
+    if req.method() != "POST" {
+        return actix_web::HttpResponse::MethodNotAllowed()
+            .append_header(("Allow", "POST"))
+            .finish();
+    }
+
+    if !is_authorised(req, token.as_str()) {
+        return fastn_core::unauthorised!("{PURGE_PATH} needs the {PURGE_TOKEN_ENV} bearer token");
+    }
+
+    let purged = cache.purge();
+    tracing::info!(msg = "cache purged", purged);
+    fastn_core::http::api_ok(serde_json::json!({ "purged": purged }))
+        .unwrap_or_else(|e| fastn_core::server_error!("failed to serialize response: {e:?}"))
+}
+
+fn is_authorised(req: &fastn_core::http::Request, token: &str) -> bool {
+    let given = match req
+        .headers()
+        .get(actix_web::http::header::AUTHORIZATION.as_str())
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+    {
+        Some(given) => given.trim(),
+        None => return false,
+    };
+
+    // compares every byte, so the time taken does not tell how much of the token matched
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    async fn page(cache: &super::ResponseCache, key: &str, dependencies: &[&str]) {
+        cache
+            .insert(
+                key.to_string(),
+                fastn_core::http::ok_with_content_type(
+                    key.as_bytes().to_vec(),
+                    mime_guess::mime::TEXT_HTML_UTF_8,
+                ),
+                dependencies.iter().map(|d| d.to_string()).collect(),
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn evicts_least_recently_used() {
+        let cache = super::ResponseCache::new(2);
+        page(&cache, "a", &[]).await;
+        page(&cache, "b", &[]).await;
+        assert!(cache.get("a").is_some());
+        page(&cache, "c", &[]).await;
+
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("c").is_some());
+    }
+
+    #[tokio::test]
+    async fn invalidate() {
+        let cache = super::ResponseCache::new(10);
+        page(&cache, "a", &["foo.com/", "foo.com/lib/"]).await;
+        page(&cache, "b", &["foo.com/b/"]).await;
+
+        cache.invalidate(&["foo.com/lib".to_string()]);
+        assert!(cache.get("a").is_none());
+        assert!(cache.get("b").is_some());
+
+        cache.invalidate(&["*".to_string()]);
+        assert!(cache.get("b").is_none());
+    }
+}
@@ -57,6 +57,18 @@ async fn serve_file(
         }
     };
 
+    // the document of the package, whatever url it is read at
+    let document = config
+        .config
+        .path_without_root(&f.get_full_path())
+        .ok()
+        .filter(|document| !document.starts_with(".packages/"));
+    match fastn_core::auth::access::check(config, path.as_str(), document.as_deref()).await {
+        Ok(None) => {}
+        Ok(Some(r)) => return r,
+        Err(e) => return fastn_core::server_error!("fastn-Error: path: {}, {:?}", path, e),
+    }
+
     if let fastn_core::File::Code(doc) = f {
         let path = doc.get_full_path().to_string();
         let mime = mime_guess::from_path(path).first_or_text_plain();
@@ -72,6 +84,10 @@ async fn serve_file(
         }
     };
 
+    config
+        .dependencies_during_render
+        .push(main_document.id_with_package());
+
     match fastn_core::package::package_doc::read_ftd_(
         config,
         &main_document,
@@ -85,6 +101,17 @@ async fn serve_file(
     {
         Ok(val) => match val {
             fastn_core::package::package_doc::FTDResult::Html(body) => {
+                let body = if config.config.live_reload.is_some() && !only_js {
+                    fastn_core::commands::live_reload::inject(
+                        body,
+                        fastn_core::commands::live_reload::script(
+                            config.dependencies_during_render.as_slice(),
+                        )
+                        .as_str(),
+                    )
+                } else {
+                    body
+                };
                 fastn_core::http::ok_with_content_type(body, mime_guess::mime::TEXT_HTML_UTF_8)
             }
             fastn_core::package::package_doc::FTDResult::Redirect { url, code } => {
@@ -163,30 +190,95 @@ pub async fn serve(
     only_js: bool,
     preview_session_id: &Option<String>,
 ) -> fastn_core::Result<(fastn_core::http::Response, bool)> {
-    let mut req_config = fastn_core::RequestConfig::new(config, &req, "", "/");
+    let (mut resp, cacheable) = serve_(config, &req, only_js, preview_session_id).await?;
 
-    if req.path() == "/-/auth/logout/" {
-        return Ok((clear_sid2(&req), false));
+    // explicitly set headers, like the long max-age of hashed default files, take precedence, and
+    // responses that depend on who is asking, see `auth::access::check`, are never shared
+    if let Some(cache_control) = config
+        .package
+        .cache_control(req.path())
+        .filter(|_| cacheable)
+        .and_then(|v| actix_web::http::header::HeaderValue::from_str(v).ok())
+    {
+        if resp.status().is_success()
+            && !resp
+                .headers()
+                .contains_key(actix_web::http::header::CACHE_CONTROL)
+        {
+            resp.headers_mut()
+                .insert(actix_web::http::header::CACHE_CONTROL, cache_control);
+        }
     }
 
-    if let Some(endpoint_response) = handle_endpoints(config, &req, preview_session_id).await {
+    Ok((fastn_core::http::not_modified(&req, resp), cacheable))
+}
+
+async fn serve_(
+    config: &fastn_core::Config,
+    req: &fastn_core::http::Request,
+    only_js: bool,
+    preview_session_id: &Option<String>,
+) -> fastn_core::Result<(fastn_core::http::Response, bool)> {
+    let mut req_config = fastn_core::RequestConfig::new(config, req, "", "/");
+
+    if let Some(r) = fastn_core::auth::handle(config, req).await {
+        return r.map(|r| (r, false));
+    }
+
+    if let Some(live_reload) = config.live_reload.as_ref() {
+        if req.path() == fastn_core::commands::live_reload::EVENTS_PATH {
+            return Ok((live_reload.events(), false));
+        }
+    }
+
+    if let Some(response_cache) = config.response_cache.as_ref() {
+        if req.path() == fastn_core::commands::response_cache::PURGE_PATH {
+            return Ok((
+                fastn_core::commands::response_cache::purge(config, response_cache, req).await,
+                false,
+            ));
+        }
+    }
+
+    if let Some(endpoint_response) = handle_endpoints(config, req, preview_session_id).await {
         return endpoint_response.map(|r| (r, false));
     }
 
-    if let Some(app_response) = handle_apps(config, &req).await {
+    if let Some(app_response) = handle_apps(config, req).await {
         return app_response.map(|r| (r, false));
     }
 
-    if let Some(default_response) = handle_default_route(&req, config.package.name.as_str()) {
+    if let Some(default_response) = handle_default_route(req, config.package.name.as_str()) {
         return default_response.map(|r| (r, true));
     }
 
+    if let Some(r) = fastn_core::shared_js::handle(config, req.path(), preview_session_id).await {
+        return r.map(|r| (r, true));
+    }
+
     let path: camino::Utf8PathBuf = req.path().replacen('/', "", 1).parse()?;
 
     if let Some(r) = handle_redirect(config, &path) {
         return Ok((r, false));
     }
 
+    if let Some(r) = fastn_core::auth::access::check(&mut req_config, req.path(), None).await? {
+        return Ok((r, false));
+    }
+
+    if let Some(r) = fastn_core::sitemap::xml::handle(config, req.path(), preview_session_id).await
+    {
+        return r.map(|r| (r, false));
+    }
+
+    if let Some(r) = fastn_core::feed::handle(&req_config, preview_session_id).await {
+        return r.map(|r| (r, false));
+    }
+
+    if let Some(r) = fastn_core::search::handle(&req_config, preview_session_id).await {
+        return r.map(|r| (r, false));
+    }
+
     if fastn_core::utils::is_static_path(req.path()) {
         return handle_static_route(
             req.path(),
@@ -195,12 +287,37 @@ pub async fn serve(
             preview_session_id,
         )
         .await
-        .map(|r| (r, true));
+        .map(|r| (r, req_config.response_is_cacheable));
     }
 
-    serve_helper(&mut req_config, only_js, path, preview_session_id)
-        .await
-        .map(|r| (r, req_config.response_is_cacheable))
+    // the cache does not know who is asking, `access::check` marks responses with readers
+    let cache_key = config
+        .response_cache
+        .as_ref()
+        .filter(|_| req_config.response_is_cacheable)
+        .and_then(|_| fastn_core::commands::response_cache::ResponseCache::key(req, only_js));
+
+    if let (Some(response_cache), Some(key)) = (config.response_cache.as_ref(), cache_key.as_ref())
+    {
+        if let Some(r) = response_cache.get(key) {
+            return Ok((r, true));
+        }
+    }
+
+    let mut resp = serve_helper(&mut req_config, only_js, path, preview_session_id).await?;
+    if req_config.response_is_cacheable {
+        resp = fastn_core::http::with_etag(resp).await?;
+    }
+
+    match (config.response_cache.as_ref(), cache_key) {
+        (Some(response_cache), Some(key)) if req_config.response_is_cacheable => {
+            let resp = response_cache
+                .insert(key, resp, req_config.dependencies_during_render)
+                .await?;
+            Ok((resp, true))
+        }
+        _ => Ok((resp, req_config.response_is_cacheable)),
+    }
 }
 
 #[tracing::instrument(skip_all)]
@@ -340,7 +457,7 @@ pub fn handle_default_route(
         return Some(Ok(actix_web::HttpResponse::Ok()
             .content_type(mime_guess::mime::TEXT_JAVASCRIPT)
             .append_header(("Cache-Control", "public, max-age=31536000"))
-            .body(ftd::js::all_js_without_test(package_name))));
+            .body(fastn_core::shared_js::default_ftd_js(package_name))));
     } else if req
         .path()
         .ends_with(fastn_core::utils::hashed_markdown_js())
@@ -484,14 +601,24 @@ async fn handle_static_route(
         path: &str,
         session_id: &Option<String>,
     ) -> Result<fastn_core::http::Response, fastn_ds::ReadError> {
-        ds.read_content(&fastn_ds::Path::new(path), session_id)
-            .await
-            .map(|r| {
-                fastn_core::http::ok_with_content_type(
-                    r,
-                    guess_mime_type(path.to_string().as_str()),
-                )
-            })
+        let file = fastn_ds::Path::new(path);
+        let content = ds.read_content(&file, session_id).await?;
+
+        let mut response = actix_web::HttpResponse::Ok();
+        response
+            .content_type(guess_mime_type(path.to_string().as_str()))
+            .insert_header((
+                actix_web::http::header::ETAG,
+                fastn_core::http::etag(&content),
+            ));
+        if let Ok(modified) = ds.modified(&file, session_id).await {
+            response.insert_header((
+                actix_web::http::header::LAST_MODIFIED,
+                actix_web::http::header::HttpDate::from(modified).to_string(),
+            ));
+        }
+
+        Ok(response.body(content))
     }
 }
 
@@ -643,6 +770,8 @@ You can try without providing port, it will automatically pick unused port."#,
         }
     };
 
+    fastn_core::commands::live_reload::watch(&config);
+
     let app = move || {
         actix_web::App::new()
             .app_data(actix_web::web::Data::new(std::sync::Arc::clone(&config)))
@@ -21,6 +21,15 @@ pub(crate) const HTTP_REDIRECT_HEADER: &str = "http-redirect";
 pub(crate) const HTTP_STATUS_HEADER: &str = "http-status";
 pub(crate) const HTTP_LOCATION_HEADER: &str = "http-location";
 
+// fastn.mock parameters
+pub(crate) const MOCK_URL_HEADER: &str = "url";
+pub(crate) const MOCK_METHOD_HEADER: &str = "method";
+pub(crate) const MOCK_REQUEST_BODY_HEADER: &str = "request-body";
+pub(crate) const MOCK_STATUS_HEADER: &str = "status";
+pub(crate) const MOCK_CONTENT_TYPE_HEADER: &str = "content-type";
+pub(crate) const MOCK_RESPONSE_HEADER: &str = "response";
+pub(crate) const MOCK_TIMES_HEADER: &str = "times";
+
 macro_rules! log_variable {
     // When verbose is true, debug variables
     ($verbose:expr, $($variable:expr),*) => {
@@ -34,7 +43,7 @@ macro_rules! log_message {
     // When verbose is true, print message
     ($verbose:expr, $($message:expr),*) => {
         if $verbose {
-            $(std::println!($message);)*
+            $(fastn_core::progress!($message);)*
         }
     };
 }
@@ -60,37 +69,454 @@ impl TestParameters {
     }
 }
 
+/// How `fastn test` reports the results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    Human,
+    Junit,
+    Json,
+    Tap,
+}
+
+impl std::str::FromStr for Format {
+    type Err = fastn_core::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Format::Human),
+            "junit" => Ok(Format::Junit),
+            "json" => Ok(Format::Json),
+            "tap" => Ok(Format::Tap),
+            t => fastn_core::usage_error(format!(
+                "Unknown test format {t}, use one of: human, junit, json, tap"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status {
+    Passed,
+    Failed(String),
+    /// Not run, because an instruction before it in the same file failed.
+    Skipped,
+    /// `--script` writes the test to a file instead of running it.
+    ScriptCreated,
+}
+
+#[derive(Debug, Clone)]
+pub struct InstructionResult {
+    pub title: String,
+    pub line_number: usize,
+    pub status: Status,
+    pub duration: std::time::Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct TestFileResult {
+    pub id: String,
+    /// `title` of the `fastn.test`
+    pub title: Option<String>,
+    pub instructions: Vec<InstructionResult>,
+    /// The file could not be interpreted, none of its instructions ran.
+    pub error: Option<String>,
+    pub duration: std::time::Duration,
+}
+
+impl TestFileResult {
+    pub fn is_failed(&self) -> bool {
+        self.error.is_some()
+            || self
+                .instructions
+                .iter()
+                .any(|i| matches!(i.status, Status::Failed(_)))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TestReport {
+    pub files: Vec<TestFileResult>,
+    pub duration: std::time::Duration,
+}
+
+impl TestReport {
+    fn count(&self, f: impl Fn(&Status) -> bool) -> usize {
+        self.files
+            .iter()
+            .flat_map(|file| file.instructions.iter())
+            .filter(|i| f(&i.status))
+            .count()
+    }
+
+    pub fn passed(&self) -> usize {
+        self.count(|s| matches!(s, Status::Passed | Status::ScriptCreated))
+    }
+
+    /// Failed instructions, and files that could not be interpreted.
+    pub fn failed(&self) -> usize {
+        self.count(|s| matches!(s, Status::Failed(_)))
+            + self.files.iter().filter(|f| f.error.is_some()).count()
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.count(|s| matches!(s, Status::Skipped))
+    }
+}
+
+/// Runs the test files matching `filters` (all of them if there are none), `jobs` files at a
+/// time. The files share the package's database, so more than one job is only for test files
+/// that do not depend on each other's data. Each file runs its instructions in order, with its
+/// own cookies, and stops at the first failure. Returns an error if any test failed, after
+/// reporting all of them.
+#[allow(clippy::too_many_arguments)]
 pub async fn test(
     config: &fastn_core::Config,
-    only_id: Option<&str>,
+    filters: &[String],
     _base_url: &str,
     headless: bool,
     script: bool,
     verbose: bool,
+    jobs: usize,
+    format: Format,
 ) -> fastn_core::Result<()> {
-    use colored::Colorize;
+    use futures::StreamExt;
 
     if !headless {
         return fastn_core::usage_error(
             "Currently headless mode is only supported, use: --headless flag".to_string(),
         );
     }
-    let ftd_documents = config.get_test_files().await?;
+    let ftd_documents: Vec<fastn_core::Document> = config
+        .get_test_files()
+        .await?
+        .into_iter()
+        .filter(|document| {
+            filters.is_empty()
+                || filters
+                    .iter()
+                    .any(|filter| matches_filter(filter, document.id.as_str()))
+        })
+        .collect();
+
+    if ftd_documents.is_empty() && !filters.is_empty() {
+        return fastn_core::usage_error(format!("No test file matches {}", filters.join(", ")));
+    }
 
-    for document in ftd_documents {
-        if let Some(id) = only_id {
-            if !document.id.contains(id) {
-                continue;
-            }
+    // verbose logs are printed as the tests run, they are only readable one file at a time
+    let jobs = if verbose { 1 } else { jobs.max(1) };
+
+    let start = std::time::Instant::now();
+    let mut results = futures::stream::iter(ftd_documents)
+        .map(|document| run_test_file(document, config, script, verbose))
+        .buffered(jobs);
+
+    let mut files = vec![];
+    while let Some(result) = results.next().await {
+        if format == Format::Human {
+            print_test_file(&result);
         }
-        let mut test_parameters = TestParameters::new(script, verbose);
-        println!("Running test file: {}", document.id.magenta());
-        read_ftd_test_file(document, config, &mut test_parameters).await?;
+        files.push(result);
+    }
+
+    let report = TestReport {
+        files,
+        duration: start.elapsed(),
+    };
+    match format {
+        Format::Human => print_summary(&report),
+        Format::Junit => println!("{}", junit(&report)),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&json(&report))?),
+        Format::Tap => print!("{}", tap(&report)),
+    }
+
+    if report.failed() > 0 {
+        return fastn_core::assert_error(format!(
+            "{} of {} tests failed",
+            report.failed(),
+            report.failed() + report.passed() + report.skipped()
+        ));
     }
 
     Ok(())
 }
 
+/// A filter with `*` or `?` is a glob, matched against the id of the test file, with or without
+/// the `_tests/` prefix. Any other filter matches the ids it is part of.
+fn matches_filter(filter: &str, id: &str) -> bool {
+    if !filter.contains(['*', '?']) {
+        return id.contains(filter);
+    }
+
+    let pattern: Vec<char> = filter.chars().collect();
+    let test_folder = format!("{TEST_FOLDER}/");
+    [Some(id), id.strip_prefix(test_folder.as_str())]
+        .into_iter()
+        .flatten()
+        .any(|id| fastn_core::utils::glob_match(&pattern, &id.chars().collect::<Vec<_>>()))
+}
+
+async fn run_test_file(
+    document: fastn_core::Document,
+    config: &fastn_core::Config,
+    script: bool,
+    verbose: bool,
+) -> TestFileResult {
+    let start = std::time::Instant::now();
+    // every file gets its own mocks, see `fastn.mock`
+    let http_mock = fastn_ds::http_mock::HttpMock::default();
+    let mut config = config.clone();
+    config.ds = config.ds.add_http_mock(http_mock.clone());
+
+    let mut result = TestFileResult {
+        id: document.id.to_string(),
+        title: None,
+        instructions: vec![],
+        error: None,
+        duration: Default::default(),
+    };
+
+    let mut test_parameters = TestParameters::new(script, verbose);
+    if let Err(e) = read_ftd_test_file(document, &config, &mut test_parameters, &mut result).await {
+        result.error = Some(e.to_string());
+    }
+    assert_mock_calls(&http_mock, &mut result);
+    result.duration = start.elapsed();
+    result
+}
+
+fn print_test_file(result: &TestFileResult) {
+    use colored::Colorize;
+
+    println!("Running test file: {}", result.id.magenta());
+    if let Some(title) = result.title.as_ref() {
+        println!("Test: {}", title);
+    }
+    if let Some(error) = result.error.as_ref() {
+        println!("{}: {}", "Error".red(), error);
+    }
+    for instruction in result.instructions.iter() {
+        let status = match &instruction.status {
+            Status::Passed => "Test Passed".green(),
+            Status::Failed(message) => format!("Test Failed: {message}").red(),
+            Status::Skipped => "Test Skipped".yellow(),
+            Status::ScriptCreated => "Script file created".green(),
+        };
+        println!(
+            "Test: {} {} ({} ms)",
+            instruction.title.yellow(),
+            status,
+            instruction.duration.as_millis()
+        );
+    }
+}
+
+fn print_summary(report: &TestReport) {
+    use colored::Colorize;
+
+    let failures: Vec<String> = report
+        .files
+        .iter()
+        .flat_map(|file| {
+            let error = file.error.iter().map(move |e| format!("{}: {e}", file.id));
+            let failed = file
+                .instructions
+                .iter()
+                .filter_map(move |i| match &i.status {
+                    Status::Failed(message) => Some(format!(
+                        "{}:{} {}: {message}",
+                        file.id, i.line_number, i.title
+                    )),
+                    _ => None,
+                });
+            error.chain(failed)
+        })
+        .collect();
+
+    if !failures.is_empty() {
+        println!("\n{}", "Failures:".red());
+        for failure in failures {
+            println!("    {failure}");
+        }
+    }
+
+    let summary = format!(
+        "\n{} passed, {} failed, {} skipped in {:.2}s",
+        report.passed(),
+        report.failed(),
+        report.skipped(),
+        report.duration.as_secs_f64()
+    );
+    if report.failed() > 0 {
+        println!("{}", summary.red());
+    } else {
+        println!("{}", summary.green());
+    }
+}
+
+fn junit(report: &TestReport) -> String {
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites tests=\"{}\" failures=\"{}\" \
+        skipped=\"{}\" time=\"{:.3}\">\n",
+        report.passed() + report.failed() + report.skipped(),
+        report.failed(),
+        report.skipped(),
+        report.duration.as_secs_f64()
+    );
+
+    for file in report.files.iter() {
+        let count =
+            |f: fn(&Status) -> bool| file.instructions.iter().filter(|i| f(&i.status)).count();
+        xml.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" \
+            time=\"{:.3}\">\n",
+            xml_escape(file.id.as_str()),
+            file.instructions.len() + usize::from(file.error.is_some()),
+            count(|s| matches!(s, Status::Failed(_))),
+            usize::from(file.error.is_some()),
+            count(|s| matches!(s, Status::Skipped)),
+            file.duration.as_secs_f64()
+        ));
+
+        if let Some(error) = file.error.as_ref() {
+            xml.push_str(&format!(
+                "    <testcase name=\"{id}\" classname=\"{id}\" time=\"0.000\">\n      \
+                <error message=\"{}\"/>\n    </testcase>\n",
+                xml_escape(error),
+                id = xml_escape(file.id.as_str())
+            ));
+        }
+
+        for instruction in file.instructions.iter() {
+            let name = xml_escape(instruction.title.as_str());
+            let classname = xml_escape(file.id.as_str());
+            let time = instruction.duration.as_secs_f64();
+            match &instruction.status {
+                Status::Passed | Status::ScriptCreated => xml.push_str(&format!(
+                    "    <testcase name=\"{name}\" classname=\"{classname}\" time=\"{time:.3}\"/>\n"
+                )),
+                Status::Failed(message) => xml.push_str(&format!(
+                    "    <testcase name=\"{name}\" classname=\"{classname}\" time=\"{time:.3}\">\n      \
+                    <failure message=\"{}\"/>\n    </testcase>\n",
+                    xml_escape(message)
+                )),
+                Status::Skipped => xml.push_str(&format!(
+                    "    <testcase name=\"{name}\" classname=\"{classname}\" time=\"{time:.3}\">\n      \
+                    <skipped/>\n    </testcase>\n"
+                )),
+            }
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+
+    xml.push_str("</testsuites>");
+    xml
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn json(report: &TestReport) -> serde_json::Value {
+    let files: Vec<serde_json::Value> = report
+        .files
+        .iter()
+        .map(|file| {
+            let instructions: Vec<serde_json::Value> = file
+                .instructions
+                .iter()
+                .map(|instruction| {
+                    let (status, message) = match &instruction.status {
+                        Status::Passed => ("passed", None),
+                        Status::Failed(message) => ("failed", Some(message)),
+                        Status::Skipped => ("skipped", None),
+                        Status::ScriptCreated => ("script-created", None),
+                    };
+                    serde_json::json!({
+                        "title": instruction.title,
+                        "line-number": instruction.line_number,
+                        "status": status,
+                        "message": message,
+                        "duration-ms": instruction.duration.as_millis() as u64,
+                    })
+                })
+                .collect();
+
+            serde_json::json!({
+                "id": file.id,
+                "title": file.title,
+                "passed": !file.is_failed(),
+                "error": file.error,
+                "duration-ms": file.duration.as_millis() as u64,
+                "instructions": instructions,
+            })
+        })
+        .collect();
+
+    serde_json::json!({
+        "passed": report.passed(),
+        "failed": report.failed(),
+        "skipped": report.skipped(),
+        "duration-ms": report.duration.as_millis() as u64,
+        "files": files,
+    })
+}
+
+/// [TAP 13](https://testanything.org/tap-version-13-specification.html), one test point per
+/// instruction, and one for each file that could not be interpreted.
+fn tap(report: &TestReport) -> String {
+    let mut points = vec![];
+    for file in report.files.iter() {
+        if let Some(error) = file.error.as_ref() {
+            points.push((false, file.id.clone(), None, Some(error.as_str()), None));
+        }
+        for instruction in file.instructions.iter() {
+            let (ok, directive, message) = match &instruction.status {
+                Status::Passed => (true, None, None),
+                Status::Failed(message) => (false, None, Some(message.as_str())),
+                Status::Skipped => (true, Some("# SKIP"), None),
+                Status::ScriptCreated => (true, Some("# SKIP script created"), None),
+            };
+            points.push((
+                ok,
+                format!("{} - {}", file.id, instruction.title),
+                directive,
+                message,
+                Some(instruction.duration),
+            ));
+        }
+    }
+
+    let mut tap = format!("TAP version 13\n1..{}\n", points.len());
+    for (i, (ok, description, directive, message, duration)) in points.into_iter().enumerate() {
+        tap.push_str(&format!(
+            "{} {} - {}",
+            if ok { "ok" } else { "not ok" },
+            i + 1,
+            description.replace('#', "\\#")
+        ));
+        if let Some(directive) = directive {
+            tap.push_str(&format!(" {directive}"));
+        }
+        tap.push('\n');
+        if message.is_some() || duration.is_some() {
+            tap.push_str("  ---\n");
+            if let Some(message) = message {
+                tap.push_str(&format!("  message: {}\n", serde_json::json!(message)));
+            }
+            if let Some(duration) = duration {
+                tap.push_str(&format!("  duration_ms: {}\n", duration.as_millis()));
+            }
+            tap.push_str("  ...\n");
+        }
+    }
+    tap
+}
+
 impl fastn_core::Config {
     /**
     Returns the list of all fixture files with extension of `<file name>.test.ftd`
@@ -208,6 +634,7 @@ async fn read_ftd_test_file(
     ftd_document: fastn_core::Document,
     config: &fastn_core::Config,
     test_parameters: &mut TestParameters,
+    result: &mut TestFileResult,
 ) -> fastn_core::Result<()> {
     let req = fastn_core::http::Request::default();
     let mut saved_cookies: std::collections::HashMap<String, String> =
@@ -231,26 +658,166 @@ async fn read_ftd_test_file(
     bag.extend(ftd::interpreter::default::default_test_bag());
 
     let doc = ftd::interpreter::TDoc::new(&main_ftd_doc.name, &main_ftd_doc.aliases, &bag);
+    result.title = get_test_title(&main_ftd_doc.tree, &doc)?;
     let all_instructions = get_all_instructions(&main_ftd_doc.tree, &doc, config).await?;
-    let mut instruction_number = 1;
-    for instruction in all_instructions.iter() {
-        test_parameters.instruction_number = instruction_number;
-        if !execute_instruction(
+    let mut failed = false;
+    for (instruction_number, instruction) in all_instructions.iter().enumerate() {
+        if instruction.name.eq("fastn#mock") {
+            add_mock(instruction, &doc, &config.ds)?;
+            continue;
+        }
+
+        let title = get_instruction_title(instruction, &doc);
+        if failed {
+            result.instructions.push(InstructionResult {
+                title,
+                line_number: instruction.line_number,
+                status: Status::Skipped,
+                duration: Default::default(),
+            });
+            continue;
+        }
+
+        test_parameters.instruction_number = instruction_number as i64 + 1;
+        let start = std::time::Instant::now();
+        let status = execute_instruction(
             instruction,
             &doc,
             config,
             &mut saved_cookies,
             test_parameters,
         )
-        .await?
-        {
-            break;
+        .await
+        .unwrap_or_else(|e| Status::Failed(e.to_string()));
+        failed = matches!(status, Status::Failed(_));
+        result.instructions.push(InstructionResult {
+            title,
+            line_number: instruction.line_number,
+            status,
+            duration: start.elapsed(),
+        });
+    }
+    Ok(())
+}
+
+/// Registers the response of a `fastn.mock`, for the rest of the test file.
+fn add_mock(
+    instruction: &fastn_resolved::ComponentInvocation,
+    doc: &ftd::interpreter::TDoc<'_>,
+    ds: &fastn_ds::DocumentStore,
+) -> fastn_core::Result<()> {
+    let property_values = instruction.get_interpreter_property_value_of_all_arguments(doc)?;
+
+    let url = get_value_ok(MOCK_URL_HEADER, &property_values, instruction.line_number)?
+        .to_json_string(doc, false)?
+        .unwrap();
+    let number = |header: &str| -> fastn_core::Result<Option<usize>> {
+        match get_optional_value_string(header, &property_values, doc)? {
+            Some(value) => match value.parse() {
+                Ok(value) => Ok(Some(value)),
+                Err(_) => fastn_core::usage_error(format!(
+                    "Invalid {header}: {value}, doc: {} line_number: {}",
+                    doc.name, instruction.line_number
+                )),
+            },
+            None => Ok(None),
         }
-        instruction_number += 1;
+    };
+
+    let status = number(MOCK_STATUS_HEADER)?.unwrap_or(200);
+    let Ok(status) = u16::try_from(status) else {
+        return fastn_core::usage_error(format!(
+            "Invalid {MOCK_STATUS_HEADER}: {status}, doc: {} line_number: {}",
+            doc.name, instruction.line_number
+        ));
+    };
+
+    let mock = fastn_ds::http_mock::Mock {
+        method: get_optional_value_string(MOCK_METHOD_HEADER, &property_values, doc)?,
+        url,
+        body: get_optional_value_string(MOCK_REQUEST_BODY_HEADER, &property_values, doc)?,
+        status,
+        content_type: get_optional_value_string(MOCK_CONTENT_TYPE_HEADER, &property_values, doc)?
+            .unwrap_or_else(|| "application/json".to_string()),
+        response: get_optional_value_string(MOCK_RESPONSE_HEADER, &property_values, doc)?
+            .unwrap_or_default()
+            .into(),
+        times: number(MOCK_TIMES_HEADER)?,
+    };
+
+    if let Some(http_mock) = ds.http_mock.as_ref() {
+        http_mock.add(mock);
     }
     Ok(())
 }
 
+/// One result for each `fastn.mock` with `times`, after the instructions of the file. Skipped if
+/// the file failed before all its instructions ran.
+fn assert_mock_calls(http_mock: &fastn_ds::http_mock::HttpMock, result: &mut TestFileResult) {
+    let completed = !result.is_failed();
+    for (mock, times, calls) in http_mock.expectations() {
+        let status = if !completed {
+            Status::Skipped
+        } else if calls == times {
+            Status::Passed
+        } else {
+            Status::Failed(format!(
+                "expected {times} requests to {}, found {calls}",
+                mock.url
+            ))
+        };
+        result.instructions.push(InstructionResult {
+            title: format!("fastn.mock: {}", mock.url),
+            line_number: 0,
+            status,
+            duration: Default::default(),
+        });
+    }
+}
+
+/// `title` of the `fastn.test` in the test file.
+fn get_test_title(
+    instructions: &[fastn_resolved::ComponentInvocation],
+    doc: &ftd::interpreter::TDoc<'_>,
+) -> fastn_core::Result<Option<String>> {
+    let Some(test) = instructions.iter().find(|i| i.name.eq("fastn#test")) else {
+        return Ok(None);
+    };
+    let property_values = test.get_interpreter_property_value_of_all_arguments(doc)?;
+    Ok(get_optional_value_string(
+        TEST_TITLE_HEADER,
+        &property_values,
+        doc,
+    )?)
+}
+
+/// `title` of a `fastn.get` or `fastn.post`, where a `fastn.redirect` redirects from and to.
+fn get_instruction_title(
+    instruction: &fastn_resolved::ComponentInvocation,
+    doc: &ftd::interpreter::TDoc<'_>,
+) -> String {
+    let is_redirect = instruction.name.eq("fastn#redirect");
+    let header = if is_redirect {
+        HTTP_REDIRECT_HEADER
+    } else {
+        TEST_TITLE_HEADER
+    };
+    let title = instruction
+        .get_interpreter_property_value_of_all_arguments(doc)
+        .ok()
+        .and_then(|property_values| {
+            get_optional_value_string(header, &property_values, doc)
+                .ok()
+                .flatten()
+        });
+
+    match title {
+        Some(redirect) if is_redirect => format!("Redirecting from {}", redirect.trim()),
+        Some(title) => title,
+        None => format!("{} (line {})", instruction.name, instruction.line_number),
+    }
+}
+
 // This will give all overall set of instructions for a test file
 // including instructions from fixture and other test instructions
 async fn get_all_instructions(
@@ -280,7 +847,7 @@ async fn get_all_instructions(
                         .await?,
                 );
             }
-            "fastn#get" | "fastn#post" | "fastn#redirect" => {
+            "fastn#get" | "fastn#post" | "fastn#redirect" | "fastn#mock" => {
                 if !found_test_component {
                     return fastn_core::usage_error(format!(
                         "fastn.test doesn't exist for this test, doc: {} \
@@ -312,7 +879,7 @@ async fn execute_instruction(
     config: &fastn_core::Config,
     saved_cookies: &mut std::collections::HashMap<String, String>,
     test_parameters: &mut TestParameters,
-) -> fastn_core::Result<bool> {
+) -> fastn_core::Result<Status> {
     match instruction.name.as_str() {
         "fastn#get" => {
             execute_get_instruction(instruction, doc, config, saved_cookies, test_parameters).await
@@ -339,10 +906,6 @@ async fn get_instructions_from_test(
 ) -> fastn_core::Result<Vec<fastn_resolved::ComponentInvocation>> {
     let property_values = instruction.get_interpreter_property_value_of_all_arguments(doc)?;
 
-    if let Some(title) = get_optional_value_string(TEST_TITLE_HEADER, &property_values, doc)? {
-        println!("Test: {}", title);
-    }
-
     let fixtures =
         if let Some(fixtures) = get_optional_value_list(FIXTURE_HEADER, &property_values, doc)? {
             let mut resolved_fixtures = vec![];
@@ -409,16 +972,14 @@ async fn execute_post_instruction(
     config: &fastn_core::Config,
     saved_cookies: &mut std::collections::HashMap<String, String>,
     test_parameters: &mut TestParameters,
-) -> fastn_core::Result<bool> {
+) -> fastn_core::Result<Status> {
     let property_values = instruction.get_interpreter_property_value_of_all_arguments(doc)?;
 
     // Mandatory test parameters --------------------------------
     let url = get_value_ok(TEST_URL_HEADER, &property_values, instruction.line_number)?
         .to_json_string(doc, false)?
         .unwrap();
-    let title = get_value_ok(TEST_TITLE_HEADER, &property_values, instruction.line_number)?
-        .to_json_string(doc, false)?
-        .unwrap();
+    get_value_ok(TEST_TITLE_HEADER, &property_values, instruction.line_number)?;
 
     // Optional test parameters --------------------------------
     let mut optional_params: ftd::Map<String> = ftd::Map::new();
@@ -458,7 +1019,6 @@ async fn execute_post_instruction(
 
     get_post_response_for_id(
         url.as_str(),
-        title.as_str(),
         optional_params,
         config,
         saved_cookies,
@@ -470,17 +1030,14 @@ async fn execute_post_instruction(
 
 async fn get_post_response_for_id(
     id: &str,
-    title: &str,
     optional_params: ftd::Map<String>,
     config: &fastn_core::Config,
     saved_cookies: &mut std::collections::HashMap<String, String>,
     doc_name: &str,
     test_parameters: &mut TestParameters,
-) -> fastn_core::Result<bool> {
+) -> fastn_core::Result<Status> {
     use actix_web::body::MessageBody;
-    use colored::Colorize;
 
-    println!("Test: {}", title.yellow());
     log_message!(test_parameters.verbose, "Test type: GET");
     log_variable!(test_parameters.verbose, &test_parameters.script);
 
@@ -579,19 +1136,16 @@ async fn get_post_response_for_id(
                 &config.ds,
             )
             .await;
-            println!("{}", "Script file created".green());
-            return Ok(true);
+            return Ok(Status::ScriptCreated);
         }
 
         let test_result = fastn_js::run_test(test_string.as_str())?;
 
         if test_result.iter().any(|v| !(*v)) {
-            println!("{}", "Test Failed".red());
-            return Ok(false);
+            return Ok(Status::Failed("assertions in `test` failed".to_string()));
         }
     }
-    println!("{}", "Test Passed".green());
-    Ok(true)
+    Ok(Status::Passed)
 }
 
 async fn execute_get_instruction(
@@ -600,16 +1154,14 @@ async fn execute_get_instruction(
     config: &fastn_core::Config,
     saved_cookies: &mut std::collections::HashMap<String, String>,
     test_parameters: &mut TestParameters,
-) -> fastn_core::Result<bool> {
+) -> fastn_core::Result<Status> {
     let property_values = instruction.get_interpreter_property_value_of_all_arguments(doc)?;
 
     // Mandatory test parameters --------------------------------
     let url = get_value_ok(TEST_URL_HEADER, &property_values, instruction.line_number)?
         .to_json_string(doc, false)?
         .unwrap();
-    let title = get_value_ok(TEST_TITLE_HEADER, &property_values, instruction.line_number)?
-        .to_json_string(doc, false)?
-        .unwrap();
+    get_value_ok(TEST_TITLE_HEADER, &property_values, instruction.line_number)?;
 
     // Optional test parameters --------------------------------
     let mut optional_params: ftd::Map<String> = ftd::Map::new();
@@ -675,7 +1227,6 @@ async fn execute_get_instruction(
 
     get_js_for_id(
         url.as_str(),
-        title.as_str(),
         optional_params,
         config,
         saved_cookies,
@@ -694,17 +1245,14 @@ fn get_content_type(response: &actix_web::HttpResponse) -> Option<String> {
 
 async fn get_js_for_id(
     id: &str,
-    title: &str,
     optional_params: ftd::Map<String>,
     config: &fastn_core::Config,
     saved_cookies: &mut std::collections::HashMap<String, String>,
     doc_name: &str,
     test_parameters: &mut TestParameters,
-) -> fastn_core::Result<bool> {
+) -> fastn_core::Result<Status> {
     use actix_web::body::MessageBody;
-    use colored::Colorize;
 
-    println!("Test: {}", title.yellow());
     log_message!(test_parameters.verbose, "Test type: GET");
     log_variable!(test_parameters.verbose, &test_parameters.script);
 
@@ -788,17 +1336,14 @@ async fn get_js_for_id(
                 &config.ds,
             )
             .await;
-            println!("{}", "Script file created".green());
-            return Ok(true);
+            return Ok(Status::ScriptCreated);
         }
         let test_result = fastn_js::run_test(test_string.as_str())?;
         if test_result.iter().any(|v| !(*v)) {
-            println!("{}", "Test Failed".red());
-            return Ok(false);
+            return Ok(Status::Failed("assertions in `test` failed".to_string()));
         }
     }
-    println!("{}", "Test Passed".green());
-    Ok(true)
+    Ok(Status::Passed)
 }
 
 fn make_test_results_variable(test_results: &ftd::Map<String>) -> String {
@@ -1057,7 +1602,7 @@ async fn execute_redirect_instruction(
     config: &fastn_core::Config,
     saved_cookies: &mut std::collections::HashMap<String, String>,
     test_parameters: &mut TestParameters,
-) -> fastn_core::Result<bool> {
+) -> fastn_core::Result<Status> {
     let property_values = instruction.get_interpreter_property_value_of_all_arguments(doc)?;
 
     let redirect = get_value_ok(
@@ -1087,11 +1632,6 @@ async fn execute_redirect_instruction(
 
     get_js_for_id(
         redirect_from_url,
-        format!(
-            "Redirecting from {} -> {}",
-            redirect_from_url, redirect_to_url,
-        )
-        .as_str(),
         params,
         config,
         saved_cookies,
@@ -1100,3 +1640,88 @@ async fn execute_redirect_instruction(
     )
     .await
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn matches_filter() {
+        use super::matches_filter;
+
+        assert!(matches_filter("login", "_tests/auth/login.test.ftd"));
+        assert!(matches_filter("auth/*", "_tests/auth/login.test.ftd"));
+        assert!(matches_filter(
+            "_tests/**/login.test.ftd",
+            "_tests/login.test.ftd"
+        ));
+        assert!(matches_filter(
+            "**/log?n.test.ftd",
+            "_tests/auth/login.test.ftd"
+        ));
+        assert!(!matches_filter("*.test.ftd", "_tests/auth/login.test.ftd"));
+        assert!(!matches_filter("signup", "_tests/auth/login.test.ftd"));
+    }
+
+    #[test]
+    fn tap() {
+        use super::{InstructionResult, Status, TestFileResult, TestReport};
+
+        let instruction = |title: &str, status| InstructionResult {
+            title: title.to_string(),
+            line_number: 1,
+            status,
+            duration: std::time::Duration::from_millis(12),
+        };
+        let report = TestReport {
+            files: vec![
+                TestFileResult {
+                    id: "_tests/a.test.ftd".to_string(),
+                    title: None,
+                    instructions: vec![
+                        instruction("get /", Status::Passed),
+                        instruction("post /", Status::Failed("status 500".to_string())),
+                        instruction("get /b", Status::Skipped),
+                    ],
+                    error: None,
+                    duration: Default::default(),
+                },
+                TestFileResult {
+                    id: "_tests/b.test.ftd".to_string(),
+                    title: None,
+                    instructions: vec![],
+                    error: Some("parse error".to_string()),
+                    duration: Default::default(),
+                },
+            ],
+            duration: Default::default(),
+        };
+
+        assert_eq!(
+            (report.passed(), report.failed(), report.skipped()),
+            (1, 2, 1)
+        );
+        assert_eq!(
+            super::tap(&report),
+            indoc::indoc! {r#"
+                TAP version 13
+                1..4
+                ok 1 - _tests/a.test.ftd - get /
+                  ---
+                  duration_ms: 12
+                  ...
+                not ok 2 - _tests/a.test.ftd - post /
+                  ---
+                  message: "status 500"
+                  duration_ms: 12
+                  ...
+                ok 3 - _tests/a.test.ftd - get /b # SKIP
+                  ---
+                  duration_ms: 12
+                  ...
+                not ok 4 - _tests/b.test.ftd
+                  ---
+                  message: "parse error"
+                  ...
+            "#}
+        );
+    }
+}
@@ -40,6 +40,17 @@ pub struct Config {
     pub ftd_external_css: Vec<String>,
     pub ftd_inline_css: Vec<String>,
     pub test_command_running: bool,
+    /// Set by `fastn serve --watch`, see `fastn_core::commands::live_reload`
+    pub live_reload: Option<fastn_core::commands::live_reload::LiveReload>,
+    /// Set by `fastn serve --cache-size`, see `fastn_core::commands::response_cache`
+    pub response_cache: Option<fastn_core::commands::response_cache::ResponseCache>,
+    /// The search index `fastn serve` builds on the first search, see `fastn_core::search`
+    pub search_index: fastn_core::search::LazyIndex,
+    /// Where `fastn_core::auth` sends emails, see `fastn_core::auth::email`
+    pub email_sink: Option<std::sync::Arc<dyn fastn_core::auth::email::EmailSink>>,
+    /// Set by `fastn lsp`, processors with side effects (`sql-execute`, `sql-batch` and `http`)
+    /// stop the interpretation instead of running
+    pub skip_side_effects: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -63,6 +74,8 @@ pub struct RequestConfig {
     pub processor_set_response: Option<ft_sys_shared::Request>,
     /// we use this to determine if the response is cacheable or not
     pub response_is_cacheable: bool,
+    /// The identities of the user of the request, see `fastn_core::auth::access`
+    pub(crate) identities: tokio::sync::OnceCell<Option<Vec<String>>>,
 }
 
 impl RequestConfig {
@@ -96,6 +109,7 @@ impl RequestConfig {
             processor_set_cookies: Default::default(),
             processor_set_response: None,
             response_is_cacheable: true,
+            identities: Default::default(),
         }
     }
 
@@ -567,6 +581,7 @@ impl Config {
             "rust-toolchain".to_string(),
             ".build".to_string(),
             "_tests".to_string(),
+            fastn_core::FASTN_LOCAL_FILE.to_string(),
         ];
         ignored_files.extend(package.ignored_paths.clone());
         Ok(self
@@ -997,12 +1012,54 @@ impl Config {
         config
     }
 
-    /// `read()` is the way to read a Config.
+    pub fn set_skip_side_effects(self) -> Self {
+        let mut config = self;
+        config.skip_side_effects = true;
+        config
+    }
+
+    pub fn add_live_reload(self, watch: bool) -> Self {
+        let mut config = self;
+        config.live_reload = watch.then(Default::default);
+        config
+    }
+
+    pub fn add_response_cache(self, cache_size: Option<usize>) -> Self {
+        let mut config = self;
+        config.response_cache = cache_size
+            .filter(|size| *size > 0)
+            .map(fastn_core::commands::response_cache::ResponseCache::new);
+        config
+    }
+
+    pub fn add_email_sink(
+        self,
+        email_sink: std::sync::Arc<dyn fastn_core::auth::email::EmailSink>,
+    ) -> Self {
+        let mut config = self;
+        config.email_sink = Some(email_sink);
+        config
+    }
+
+    /// `read()` is the way to read a Config. It applies the pending migrations too.
     #[tracing::instrument(name = "Config::read", skip_all)]
     pub async fn read(
         ds: fastn_ds::DocumentStore,
         resolve_sitemap: bool,
         session_id: &Option<String>,
+    ) -> fastn_core::Result<fastn_core::Config> {
+        let config = Config::read_without_migrations(ds, resolve_sitemap, session_id).await?;
+
+        fastn_core::migrations::migrate(&config).await?;
+
+        Ok(config)
+    }
+
+    /// `read()`, for `fastn migrate`, which applies the migrations itself.
+    pub async fn read_without_migrations(
+        ds: fastn_ds::DocumentStore,
+        resolve_sitemap: bool,
+        session_id: &Option<String>,
     ) -> fastn_core::Result<fastn_core::Config> {
         let original_directory = fastn_ds::Path::new(std::env::current_dir()?.to_str().unwrap()); // todo: remove unwrap()
         let fastn_doc =
@@ -1022,6 +1079,11 @@ impl Config {
             ftd_external_css: Default::default(),
             ftd_inline_css: Default::default(),
             test_command_running: false,
+            live_reload: None,
+            response_cache: None,
+            search_index: Default::default(),
+            email_sink: None,
+            skip_side_effects: false,
             ds,
         };
         // Update global_ids map from the current package files
@@ -1091,8 +1153,6 @@ impl Config {
             package.to_owned(),
         );
 
-        fastn_core::migrations::migrate(&config).await?;
-
         Ok(config)
     }
 
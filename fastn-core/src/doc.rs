@@ -36,7 +36,11 @@ pub async fn interpret_helper(
     preview_session_id: &Option<String>,
 ) -> ftd::interpreter::Result<ftd::interpreter::Document> {
     let doc = cached_parse(name, source, line_number)?;
-    let mut s = ftd::interpreter::interpret_with_line_number(name, doc)?;
+    let mut s = ftd::interpreter::interpret_with_settings(
+        name,
+        doc,
+        lib.config.package.datetime_settings(),
+    )?;
     lib.module_package_map.insert(
         name.trim_matches('/').to_string(),
         lib.config.package.name.to_string(),
@@ -120,6 +124,55 @@ pub async fn interpret_helper(
     Ok(document)
 }
 
+/// Interprets a document of the current package outside of any request, to read its metadata
+/// and text for feeds and the search index.
+pub(crate) async fn interpret_document(
+    config: &fastn_core::Config,
+    document: &fastn_core::Document,
+    session_id: &Option<String>,
+) -> fastn_core::Result<ftd::interpreter::Document> {
+    let req = fastn_core::http::Request::default();
+    let mut req_config = fastn_core::RequestConfig::new(config, &req, document.id.as_str(), "/");
+    req_config.current_document = Some(document.id.to_string());
+
+    // Get Prefix Body => [AutoImports + Actual Doc content]
+    let doc_content =
+        config
+            .package
+            .get_prefixed_body(document.content.as_str(), document.id.as_str(), true);
+    let doc_content = config
+        .package
+        .fix_imports_in_body(doc_content.as_str(), document.id.as_str())?;
+    let line_number = doc_content.split('\n').count() - document.content.split('\n').count();
+
+    Ok(interpret_helper(
+        document.id_with_package().as_str(),
+        doc_content.as_str(),
+        &mut req_config,
+        "/",
+        false,
+        line_number,
+        session_id,
+    )
+    .await?)
+}
+
+/// The value of `argument` (`title`, `description`, `og-image` etc.) of the `ftd.document` in
+/// `doc`, `None` if it is not set or the document does not use `ftd.document`.
+pub(crate) fn document_argument(
+    doc: &ftd::interpreter::Document,
+    argument: &str,
+) -> ftd::interpreter::Result<Option<fastn_resolved::Value>> {
+    use ftd::interpreter::ComponentExt;
+
+    Ok(match doc.get_instructions("ftd#document").pop() {
+        Some(ftd_document) => ftd_document
+            .get_interpreter_value_of_argument(argument, &doc.tdoc())?
+            .and_then(|v| v.inner()),
+        None => None,
+    })
+}
+
 // source, foreign_variable, foreign_function
 pub async fn resolve_import_2022(
     lib: &mut fastn_core::Library2022,
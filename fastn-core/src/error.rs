@@ -143,6 +143,12 @@ pub enum Error {
 
     #[error("MigrationError: {0}")]
     MigrationError(#[from] fastn_core::migrations::MigrationError),
+
+    #[error("FormatError: {0}")]
+    FormatError(#[from] fastn_core::commands::fmt::FormatError),
+
+    #[error("AuthError: {0}")]
+    AuthError(#[from] fastn_core::auth::AuthError),
 }
 
 impl From<std::convert::Infallible> for Error {
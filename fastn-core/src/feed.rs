@@ -0,0 +1,414 @@
+//! Atom and RSS 2.0 feeds declared with `-- fastn.feed:` in `FASTN.ftd`. `fastn build` writes
+//! `<path>atom.xml` and `<path>rss.xml` into `.build`, `fastn serve` generates them on request.
+//!
+//! Every document in the feed is interpreted to read the `title`, `description` and `og-image`
+//! of its `ftd.document`, and the date from the variable named by `date-variable`. Documents
+//! that do not declare the date use their modification time. A feed lists the documents the
+//! requester can read, see `fastn_core::auth::access`, the ones `fastn build` writes the
+//! documents everyone can read.
+
+pub const ATOM: &str = "atom.xml";
+pub const RSS: &str = "rss.xml";
+
+struct Item {
+    url: String,
+    title: String,
+    description: Option<String>,
+    image: Option<String>,
+    date: chrono::DateTime<chrono::Utc>,
+}
+
+/// Writes the feeds of every `-- fastn.feed:` into `build_dir`.
+pub async fn write(
+    config: &fastn_core::Config,
+    build_dir: &fastn_ds::Path,
+    session_id: &Option<String>,
+) -> fastn_core::Result<()> {
+    for feed in config.package.feeds.iter() {
+        let items = items(config, feed, None, session_id).await?;
+        for (name, content) in [
+            (ATOM, atom(&config.package, feed, items.as_slice())),
+            (RSS, rss(&config.package, feed, items.as_slice())),
+        ] {
+            let path = format!("{}{name}", directory(feed));
+            println!("Processing {}/{path} ... ", config.package.name.as_str());
+            fastn_core::utils::update(
+                &build_dir.join(path.as_str()),
+                content.as_bytes(),
+                &config.ds,
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Responds to `<path>atom.xml` and `<path>rss.xml` of every feed in `fastn serve`, returns
+/// `None` for any other path.
+pub async fn handle(
+    req_config: &fastn_core::RequestConfig,
+    session_id: &Option<String>,
+) -> Option<fastn_core::Result<fastn_core::http::Response>> {
+    let config = &req_config.config;
+    let path = req_config.request.path().trim_start_matches('/');
+    let (name, content_type) = if path.ends_with(ATOM) {
+        (ATOM, "application/atom+xml; charset=utf-8")
+    } else if path.ends_with(RSS) {
+        (RSS, "application/rss+xml; charset=utf-8")
+    } else {
+        return None;
+    };
+
+    let feed = config
+        .package
+        .feeds
+        .iter()
+        .find(|feed| format!("{}{name}", directory(feed)) == path)?;
+
+    Some(
+        items(config, feed, Some(req_config), session_id)
+            .await
+            .map(|items| {
+                let content = if name == ATOM {
+                    atom(&config.package, feed, items.as_slice())
+                } else {
+                    rss(&config.package, feed, items.as_slice())
+                };
+                actix_web::HttpResponse::Ok()
+                    .content_type(content_type)
+                    .body(content)
+            }),
+    )
+}
+
+/// `-- fastn.feed: /blog/` -> `blog/`
+fn directory(feed: &fastn_core::package::Feed) -> String {
+    fastn_core::sitemap::xml::url_path(feed.path.as_str())
+}
+
+/// The documents in the sitemap section matching the feed path, or in the feed folder, newest
+/// first. The index document of the section or folder itself is not an item. Only the
+/// documents the request can read, or everyone can if there is no `req_config`.
+async fn items(
+    config: &fastn_core::Config,
+    feed: &fastn_core::package::Feed,
+    req_config: Option<&fastn_core::RequestConfig>,
+    session_id: &Option<String>,
+) -> fastn_core::Result<Vec<Item>> {
+    let directory = directory(feed);
+    let section = config.package.sitemap.as_ref().and_then(|sitemap| {
+        sitemap
+            .sections
+            .iter()
+            .find(|section| fastn_core::utils::ids_matches(section.id.as_str(), &feed.path))
+    });
+    let urls = match section {
+        Some(section) => {
+            let mut urls = fastn_core::sitemap::xml::Urls::default();
+            fastn_core::sitemap::xml::add_section_urls(&mut urls, section);
+            urls
+        }
+        None => fastn_core::sitemap::xml::urls(config, session_id)
+            .await?
+            .into_iter()
+            .filter(|(url, _)| url.starts_with(directory.as_str()))
+            .collect(),
+    };
+
+    let mut documents = fastn_core::sitemap::xml::documents(config, urls, session_id).await?;
+    fastn_core::auth::access::retain_readable(
+        &config.package,
+        req_config,
+        &mut documents,
+        |(url, _)| url.as_str(),
+    )
+    .await?;
+
+    let base = fastn_core::sitemap::xml::base_url(&config.package);
+    let mut items = vec![];
+    for (url, document) in documents {
+        if url == directory {
+            continue;
+        }
+        match item(config, feed, &document, format!("{base}{url}"), session_id).await {
+            Ok(item) => items.push(item),
+            Err(e) => tracing::warn!(
+                msg = "failed to read feed item, skipping",
+                doc = document.id.as_str(),
+                error = e.to_string()
+            ),
+        }
+    }
+
+    items.sort_by(|a, b| b.date.cmp(&a.date));
+    Ok(items)
+}
+
+async fn item(
+    config: &fastn_core::Config,
+    feed: &fastn_core::package::Feed,
+    document: &fastn_core::Document,
+    url: String,
+    session_id: &Option<String>,
+) -> fastn_core::Result<Item> {
+    use ftd::interpreter::ValueExt;
+
+    let main_ftd_doc = fastn_core::doc::interpret_document(config, document, session_id).await?;
+    let tdoc = main_ftd_doc.tdoc();
+    let argument = |name: &str| fastn_core::doc::document_argument(&main_ftd_doc, name);
+
+    let title = match argument("title")? {
+        Some(title) => title.string(main_ftd_doc.name.as_str(), 0)?,
+        None => document.id_to_path().trim_matches('/').to_string(),
+    };
+    let description = argument("description")?
+        .map(|v| v.string(main_ftd_doc.name.as_str(), 0))
+        .transpose()?;
+    let image = argument("og-image")?
+        .and_then(|v| v.to_serde_value(&tdoc).transpose())
+        .transpose()?
+        .and_then(|v| {
+            v.get("src")
+                .and_then(|v| v.as_str())
+                .map(|src| absolute_url(&config.package, src))
+        });
+
+    let date = match main_ftd_doc
+        .get::<Option<String>>(feed.date_variable.as_str())
+        .ok()
+        .flatten()
+    {
+        Some(date) => parse_date(date.as_str()).ok_or_else(|| {
+            fastn_core::Error::GenericError(format!(
+                "`{}: {date}` is not a date, expected `2024-01-31` or RFC 3339",
+                feed.date_variable
+            ))
+        })?,
+        None => config
+            .ds
+            .modified(&document.get_full_path(), session_id)
+            .await
+            .map_err(|e| fastn_core::Error::GenericError(e.to_string()))?
+            .into(),
+    };
+
+    Ok(Item {
+        url,
+        title,
+        description,
+        image,
+        date,
+    })
+}
+
+/// `og-image` is usually relative to the package root, feed readers need the full url.
+fn absolute_url(package: &fastn_core::Package, src: &str) -> String {
+    if src.contains("://") {
+        return src.to_string();
+    }
+
+    format!(
+        "{}{}",
+        fastn_core::sitemap::xml::base_url(package),
+        src.trim_start_matches('/')
+    )
+}
+
+/// `2024-01-31` or `2024-01-31T10:00:00+05:30`
+fn parse_date(date: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    let date = date.trim();
+    if let Ok(date) = chrono::DateTime::parse_from_rfc3339(date) {
+        return Some(date.into());
+    }
+
+    chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|date| date.and_utc())
+}
+
+fn title(package: &fastn_core::Package, feed: &fastn_core::package::Feed) -> String {
+    feed.title.clone().unwrap_or_else(|| package.name.clone())
+}
+
+fn atom(package: &fastn_core::Package, feed: &fastn_core::package::Feed, items: &[Item]) -> String {
+    use fastn_core::sitemap::xml::escape;
+
+    let link = format!(
+        "{}{}",
+        fastn_core::sitemap::xml::base_url(package),
+        directory(feed)
+    );
+    let updated = items
+        .iter()
+        .map(|item| item.date)
+        .max()
+        .unwrap_or_else(chrono::Utc::now);
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <feed xmlns=\"http://www.w3.org/2005/Atom\">\n",
+    );
+    xml.push_str(format!("  <title>{}</title>\n", escape(&title(package, feed))).as_str());
+    if let Some(description) = feed.description.as_ref() {
+        xml.push_str(format!("  <subtitle>{}</subtitle>\n", escape(description)).as_str());
+    }
+    xml.push_str(
+        format!(
+            "  <link href=\"{}{ATOM}\" rel=\"self\"/>\n  <link href=\"{0}\"/>\n  <id>{0}</id>\n",
+            escape(&link)
+        )
+        .as_str(),
+    );
+    xml.push_str(format!("  <updated>{}</updated>\n", updated.to_rfc3339()).as_str());
+    for item in items {
+        xml.push_str("  <entry>\n");
+        xml.push_str(format!("    <title>{}</title>\n", escape(&item.title)).as_str());
+        xml.push_str(
+            format!(
+                "    <link href=\"{0}\"/>\n    <id>{0}</id>\n",
+                escape(&item.url)
+            )
+            .as_str(),
+        );
+        xml.push_str(format!("    <updated>{}</updated>\n", item.date.to_rfc3339()).as_str());
+        if let Some(description) = item.description.as_ref() {
+            xml.push_str(format!("    <summary>{}</summary>\n", escape(description)).as_str());
+        }
+        if let Some(image) = item.image.as_ref() {
+            xml.push_str(
+                format!(
+                    "    <link rel=\"enclosure\" type=\"{}\" href=\"{}\"/>\n",
+                    mime_guess::from_path(image).first_or_octet_stream(),
+                    escape(image)
+                )
+                .as_str(),
+            );
+        }
+        xml.push_str("  </entry>\n");
+    }
+    xml.push_str("</feed>\n");
+
+    xml
+}
+
+fn rss(package: &fastn_core::Package, feed: &fastn_core::package::Feed, items: &[Item]) -> String {
+    use fastn_core::sitemap::xml::escape;
+
+    let link = format!(
+        "{}{}",
+        fastn_core::sitemap::xml::base_url(package),
+        directory(feed)
+    );
+    let title = title(package, feed);
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">\n  <channel>\n",
+    );
+    xml.push_str(format!("    <title>{}</title>\n", escape(&title)).as_str());
+    xml.push_str(format!("    <link>{}</link>\n", escape(&link)).as_str());
+    xml.push_str(
+        format!(
+            "    <description>{}</description>\n",
+            escape(feed.description.as_ref().unwrap_or(&title))
+        )
+        .as_str(),
+    );
+    xml.push_str(
+        format!(
+            "    <atom:link href=\"{}{RSS}\" rel=\"self\" type=\"application/rss+xml\"/>\n",
+            escape(&link)
+        )
+        .as_str(),
+    );
+    for item in items {
+        xml.push_str("    <item>\n");
+        xml.push_str(format!("      <title>{}</title>\n", escape(&item.title)).as_str());
+        xml.push_str(
+            format!(
+                "      <link>{0}</link>\n      <guid>{0}</guid>\n",
+                escape(&item.url)
+            )
+            .as_str(),
+        );
+        xml.push_str(format!("      <pubDate>{}</pubDate>\n", item.date.to_rfc2822()).as_str());
+        if let Some(description) = item.description.as_ref() {
+            xml.push_str(
+                format!("      <description>{}</description>\n", escape(description)).as_str(),
+            );
+        }
+        if let Some(image) = item.image.as_ref() {
+            xml.push_str(
+                format!(
+                    "      <enclosure url=\"{}\" type=\"{}\" length=\"0\"/>\n",
+                    escape(image),
+                    mime_guess::from_path(image).first_or_octet_stream(),
+                )
+                .as_str(),
+            );
+        }
+        xml.push_str("    </item>\n");
+    }
+    xml.push_str("  </channel>\n</rss>\n");
+
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse_date() {
+        assert_eq!(
+            super::parse_date("2024-01-31").unwrap().to_rfc3339(),
+            "2024-01-31T00:00:00+00:00"
+        );
+        assert_eq!(
+            super::parse_date("2024-01-31T10:00:00+05:30")
+                .unwrap()
+                .to_rfc3339(),
+            "2024-01-31T04:30:00+00:00"
+        );
+        assert!(super::parse_date("31/01/2024").is_none());
+    }
+
+    #[test]
+    fn rss() {
+        let package = fastn_core::Package::new("foo.com");
+        let feed = fastn_core::package::Feed {
+            path: "/blog/".to_string(),
+            title: Some("Foo & Bar".to_string()),
+            description: None,
+            date_variable: "date".to_string(),
+        };
+        let items = [super::Item {
+            url: "https://foo.com/blog/hello/".to_string(),
+            title: "Hello".to_string(),
+            description: Some("<p>".to_string()),
+            image: None,
+            date: super::parse_date("2024-01-31").unwrap(),
+        }];
+
+        assert_eq!(
+            super::rss(&package, &feed, &items),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom">
+  <channel>
+    <title>Foo &amp; Bar</title>
+    <link>https://foo.com/blog/</link>
+    <description>Foo &amp; Bar</description>
+    <atom:link href="https://foo.com/blog/rss.xml" rel="self" type="application/rss+xml"/>
+    <item>
+      <title>Hello</title>
+      <link>https://foo.com/blog/hello/</link>
+      <guid>https://foo.com/blog/hello/</guid>
+      <pubDate>Wed, 31 Jan 2024 00:00:00 +0000</pubDate>
+      <description>&lt;p&gt;</description>
+    </item>
+  </channel>
+</rss>
+"#
+        );
+    }
+}
@@ -98,6 +98,103 @@ pub fn ok_with_content_type(
         .body(data)
 }
 
+/// Quoted content hash, the value we use for the `ETag` header.
+pub fn etag(content: impl AsRef<[u8]>) -> String {
+    format!("\"{}\"", fastn_core::utils::generate_hash(content))
+}
+
+/// Adds a content hash `ETag` to a successful response.
+pub async fn with_etag(
+    response: fastn_core::http::Response,
+) -> fastn_core::Result<fastn_core::http::Response> {
+    if !response.status().is_success()
+        || response
+            .headers()
+            .contains_key(actix_web::http::header::ETAG)
+    {
+        return Ok(response);
+    }
+
+    let (response, body) = response.into_parts();
+    let body = actix_web::body::to_bytes(body)
+        .await
+        .map_err(|e| fastn_core::Error::GenericError(format!("failed to read body: {e}")))?;
+    let etag = etag(&body);
+
+    let mut response = response.set_body(body).map_into_boxed_body();
+    response.headers_mut().insert(
+        actix_web::http::header::ETAG,
+        actix_web::http::header::HeaderValue::from_str(etag.as_str()).unwrap(),
+    );
+    Ok(response)
+}
+
+/// Replaces a `200 OK` response with `304 Not Modified` if the client already has it: its
+/// `If-None-Match` contains the `ETag` of the response, or, when there is no `If-None-Match`,
+/// its `If-Modified-Since` is not older than the `Last-Modified` of the response.
+pub fn not_modified(
+    req: &fastn_core::http::Request,
+    response: fastn_core::http::Response,
+) -> fastn_core::http::Response {
+    use actix_web::http::header;
+
+    if !matches!(req.method(), "GET" | "HEAD") || response.status() != StatusCode::OK {
+        return response;
+    }
+
+    let response_header = |name: header::HeaderName| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(ToString::to_string)
+    };
+    let request_header = |name: reqwest::header::HeaderName| {
+        req.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(ToString::to_string)
+    };
+
+    let is_not_modified = match request_header(reqwest::header::IF_NONE_MATCH) {
+        Some(if_none_match) => response_header(header::ETAG).is_some_and(|etag| {
+            if_none_match
+                .split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == "*" || tag == etag)
+        }),
+        None => {
+            let since = request_header(reqwest::header::IF_MODIFIED_SINCE)
+                .and_then(|v| v.parse::<header::HttpDate>().ok());
+            let last_modified = response_header(header::LAST_MODIFIED)
+                .and_then(|v| v.parse::<header::HttpDate>().ok());
+            match (since, last_modified) {
+                (Some(since), Some(last_modified)) => {
+                    std::time::SystemTime::from(last_modified) <= std::time::SystemTime::from(since)
+                }
+                _ => false,
+            }
+        }
+    };
+
+    if !is_not_modified {
+        return response;
+    }
+
+    let mut not_modified = actix_web::HttpResponse::NotModified().finish();
+    for name in [
+        header::ETAG,
+        header::LAST_MODIFIED,
+        header::CACHE_CONTROL,
+        header::VARY,
+    ] {
+        if let Some(value) = response.headers().get(&name) {
+            not_modified.headers_mut().insert(name, value.clone());
+        }
+    }
+    not_modified
+}
+
 pub(crate) struct ResponseBuilder {}
 
 impl ResponseBuilder {
@@ -6,14 +6,18 @@ extern crate self as fastn_core;
 
 #[macro_use]
 pub mod utils;
+pub mod auth;
 mod auto_import;
 pub mod commands;
 mod config;
 pub mod doc;
+pub mod feed;
 mod file;
 mod font;
 pub mod manifest;
 pub mod package;
+pub mod search;
+pub mod shared_js;
 #[macro_use]
 pub mod http;
 mod ds;
@@ -32,7 +36,14 @@ mod migrations;
 
 pub(crate) use auto_import::AutoImport;
 pub use commands::{
-    build::build, check::post_build_check, fmt::fmt, query::query, serve::listen, test::test,
+    build::build,
+    check::{check, post_build_check},
+    fmt::{fmt, fmt_stdin},
+    lsp::lsp,
+    migrate::migrate,
+    query::query,
+    serve::listen,
+    test::test,
 };
 pub use config::{config_temp, Config, ConfigTemp, FTDEdition, RequestConfig};
 pub use doc::resolve_foreign_variable2;
@@ -49,6 +60,9 @@ pub(crate) use snapshot::Snapshot;
 pub(crate) use tracker::Track;
 pub(crate) use translation::{TranslatedDocument, TranslationData};
 
+/// Gitignored overrides of `FASTN.ftd` for a local checkout, only `-- fastn.dependency:` with a
+/// `path` for now.
+pub const FASTN_LOCAL_FILE: &str = "FASTN.local.ftd";
 pub const FASTN_UI_INTERFACE: &str = "fastn-stack.github.io/fastn-ui";
 pub const PACKAGE_THEME_INTERFACE: &str = "ftd-lang.github.io/theme";
 pub const NUMBER_OF_CRS_TO_RESERVE: usize = 5;
@@ -257,6 +257,13 @@ impl Library2022 {
         let line_number = ast.line_number();
         let (_processor, variable_name, value, kind) = get_processor_data(ast, doc)?;
         match processor.as_str() {
+            "sql-execute" | "sql-batch" | "http" if self.config.skip_side_effects => {
+                ftd::interpreter::utils::e2(
+                    format!("`{processor}` {SKIPPED_PROCESSOR}"),
+                    doc.name,
+                    line_number,
+                )
+            }
             "figma-typo-token" => {
                 processor::figma_typography_tokens::process_typography_tokens(value, kind, doc)
             }
@@ -325,6 +332,10 @@ impl Library2022 {
     }
 }
 
+/// The end of the error a processor that is not run because of `Config::skip_side_effects`
+/// gives.
+pub(crate) const SKIPPED_PROCESSOR: &str = "has side effects, it is not run here";
+
 fn get_processor_data(
     ast: ftd_ast::Ast,
     doc: &mut ftd::interpreter::TDoc,
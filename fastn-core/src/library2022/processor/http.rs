@@ -126,7 +126,7 @@ pub async fn process(
     }
 
     if !req_config.config.test_command_running {
-        println!("calling `http` processor with url: {}", &url);
+        eprintln!("calling `http` processor with url: {}", &url);
     }
 
     let resp = if url.scheme() == "wasm+proxy" {
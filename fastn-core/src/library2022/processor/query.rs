@@ -1,3 +1,8 @@
+tokio::task_local! {
+    /// The documents being interpreted for the `query` processor, outermost first.
+    static INTERPRETING: Vec<String>;
+}
+
 pub async fn process(
     value: ftd_ast::VariableValue,
     kind: fastn_resolved::Kind,
@@ -16,10 +21,21 @@ pub async fn process(
         .get_optional_string_by_key("file", doc.name, value.line_number())?
         .unwrap_or_else(|| req_config.document_id.to_string());
 
-    let stage = headers
+    let stage: fastn_core::commands::query::Stage = headers
         .get_optional_string_by_key("stage", doc.name, value.line_number())?
-        .unwrap_or_else(|| "ast".to_string());
+        .as_deref()
+        .unwrap_or("ast")
+        .parse()
+        .map_err(|e: fastn_core::Error| ftd::interpreter::Error::ParseError {
+            message: e.to_string(),
+            doc_id: req_config.document_id.to_string(),
+            line_number: value.line_number(),
+        })?;
+
+    let definition =
+        headers.get_optional_string_by_key("definition", doc.name, value.line_number())?;
 
+    let current_document = req_config.current_document.clone();
     let file = req_config
         .get_file_and_package_by_id(path.as_str(), preview_session_id)
         .await
@@ -28,15 +44,50 @@ pub async fn process(
             doc_id: req_config.document_id.to_string(),
             line_number: value.line_number(),
         })?;
-    doc.from_json(
-        &fastn_core::commands::query::get_ftd_json(&file, stage.as_str()).map_err(|e| {
-            ftd::interpreter::Error::ParseError {
-                message: format!("Cannot resolve json for path: {} {:?}", path.as_str(), e),
-                doc_id: req_config.document_id.to_string(),
-                line_number: value.line_number(),
-            }
-        })?,
-        &kind,
-        &value,
-    )
+    // the processor runs in the current document, whatever it queries
+    req_config.current_document = current_document.clone();
+
+    // the later stages interpret `file`, which runs its `query` processors too
+    let mut interpreting = INTERPRETING.try_with(Clone::clone).unwrap_or_default();
+    if ![
+        fastn_core::commands::query::Stage::P1,
+        fastn_core::commands::query::Stage::Ast,
+    ]
+    .contains(&stage)
+    {
+        if interpreting.is_empty() {
+            interpreting.extend(current_document);
+        }
+        if interpreting.iter().any(|v| v == file.get_id()) {
+            interpreting.push(file.get_id().to_string());
+            return ftd::interpreter::utils::e2(
+                format!(
+                    "`query` can not interpret {} at this stage, it is being interpreted: {}",
+                    file.get_id(),
+                    interpreting.join(" -> ")
+                ),
+                doc.name,
+                value.line_number(),
+            );
+        }
+        interpreting.push(file.get_id().to_string());
+    }
+
+    let json = INTERPRETING
+        .scope(
+            interpreting,
+            fastn_core::commands::query::get_ftd_json(
+                &req_config.config,
+                &file,
+                stage,
+                definition.as_deref(),
+            ),
+        )
+        .await
+        .map_err(|e| ftd::interpreter::Error::ParseError {
+            message: format!("Cannot resolve json for path: {} {:?}", path.as_str(), e),
+            doc_id: req_config.document_id.to_string(),
+            line_number: value.line_number(),
+        })?;
+    doc.from_json(&json, &kind, &value)
 }
@@ -19,6 +19,7 @@ pub async fn process(
 
     let (query, params) = crate::library2022::processor::sqlite::extract_named_parameters(
         query.as_str(),
+        db.as_str(),
         doc,
         headers,
         value.line_number(),
@@ -82,6 +82,9 @@ fn resolve_variable_from_doc(
 fn value_to_bind(v: fastn_resolved::Value) -> ft_sys_shared::SqliteRawValue {
     match v {
         fastn_resolved::Value::String { text } => ft_sys_shared::SqliteRawValue::Text(text),
+        fastn_resolved::Value::DateTime { value } | fastn_resolved::Value::Date { value } => {
+            ft_sys_shared::SqliteRawValue::Text(value)
+        }
         fastn_resolved::Value::Integer { value } => ft_sys_shared::SqliteRawValue::Integer(value),
         fastn_resolved::Value::Decimal { value } => ft_sys_shared::SqliteRawValue::Real(value),
         fastn_resolved::Value::Optional { data, .. } => match data.as_ref() {
@@ -141,8 +144,11 @@ fn resolve_param(
         .or_else(|_| resolve_variable_from_doc(param_name, doc, line_number))
 }
 
+/// Replaces `$name` in `query` with the placeholders of the database at `db_url` (`?1` for
+/// sqlite, `$1` for postgres) and resolves their values.
 pub fn extract_named_parameters(
     query: &str,
+    db_url: &str,
     doc: &ftd::interpreter::TDoc,
     headers: ftd_ast::HeaderValues,
     line_number: usize,
@@ -150,7 +156,7 @@ pub fn extract_named_parameters(
     let mut params: Vec<ft_sys_shared::SqliteRawValue> = Vec::new();
 
     let (query, args) =
-        match fastn_utils::sql::extract_arguments(query, fastn_utils::sql::SQLITE_SUB) {
+        match fastn_utils::sql::extract_arguments(query, fastn_utils::sql::placeholder(db_url)) {
             Ok(v) => v,
             Err(e) => {
                 return ftd::interpreter::utils::e2(
@@ -3,8 +3,10 @@ pub async fn process(
     value: ftd_ast::VariableValue,
     kind: fastn_resolved::Kind,
     doc: &ftd::interpreter::TDoc<'_>,
-    req_config: &fastn_core::RequestConfig,
+    req_config: &mut fastn_core::RequestConfig,
 ) -> ftd::interpreter::Result<fastn_resolved::Value> {
+    req_config.response_is_cacheable = false;
+
     match req_config
         .config
         .ds
@@ -1,8 +1,10 @@
 pub(crate) fn fastn_migrations() -> Vec<fastn_core::package::MigrationData> {
-    vec![fastn_core::package::MigrationData {
-        number: 0,
-        name: "initial".to_string(),
-        content: r#"
+    vec![
+        fastn_core::package::MigrationData {
+            number: 0,
+            name: "initial".to_string(),
+            down: None,
+            content: r#"
             CREATE TABLE IF NOT EXISTS fastn_user
             (
                 id           INTEGER PRIMARY KEY,
@@ -50,8 +52,32 @@ pub(crate) fn fastn_migrations() -> Vec<fastn_core::package::MigrationData> {
             ) STRICT;
 
             "#
-        .to_string(),
-    }]
+            .to_string(),
+        },
+        // the codes `fastn_core::auth` emails to verify an email or to log in, `id` is the hash of
+        // the code
+        fastn_core::package::MigrationData {
+            number: 1,
+            name: "auth-token".to_string(),
+            down: Some("DROP TABLE IF EXISTS fastn_auth_token;".to_string()),
+            content: r#"
+            CREATE TABLE IF NOT EXISTS fastn_auth_token
+            (
+                id         TEXT    NOT NULL PRIMARY KEY,
+                uid        INTEGER NOT NULL,
+                kind       TEXT    NOT NULL,
+                email      TEXT    NOT NULL,
+                created_at INTEGER NOT NULL,
+                expires_at INTEGER NOT NULL,
+
+                CONSTRAINT fk_fastn_user
+                FOREIGN KEY (uid)
+                REFERENCES fastn_user (id)
+            ) STRICT;
+            "#
+            .to_string(),
+        },
+    ]
 }
 
 pub const MIGRATION_TABLE: &str = r#"
@@ -63,7 +89,41 @@ CREATE TABLE IF NOT EXISTS fastn_migration
     migration_number INTEGER NOT NULL,
     migration_name   TEXT NOT NULL,
     applied_on       INTEGER NOT NULL,
+    checksum         TEXT,
     UNIQUE (app_name, migration_number)
 ) STRICT;
 
 "#;
+
+pub const MIGRATION_TABLE_EXISTS: &str = r#"
+
+SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'fastn_migration';
+
+"#;
+
+pub const MIGRATION_TABLE_EXISTS_POSTGRES: &str = r#"
+
+SELECT table_name FROM information_schema.tables
+WHERE table_schema = current_schema() AND table_name = 'fastn_migration';
+
+"#;
+
+pub const CHECKSUM_COLUMN: &str = r#"
+
+SELECT name FROM pragma_table_info('fastn_migration') WHERE name = 'checksum';
+
+"#;
+
+pub const CHECKSUM_COLUMN_POSTGRES: &str = r#"
+
+SELECT column_name FROM information_schema.columns
+WHERE table_schema = current_schema() AND table_name = 'fastn_migration'
+    AND column_name = 'checksum';
+
+"#;
+
+pub const ADD_CHECKSUM_COLUMN: &str = r#"
+
+ALTER TABLE fastn_migration ADD COLUMN checksum TEXT;
+
+"#;
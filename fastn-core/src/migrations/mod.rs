@@ -25,7 +25,10 @@ async fn migrate_app(config: &fastn_core::Config, now: i64) -> Result<(), Migrat
     .await
 }
 
-async fn migrate_fastn(config: &fastn_core::Config, now: i64) -> Result<(), MigrationError> {
+pub(crate) async fn migrate_fastn(
+    config: &fastn_core::Config,
+    now: i64,
+) -> Result<(), MigrationError> {
     migrate_(
         config,
         fastn_migrations::fastn_migrations().as_slice(),
@@ -41,20 +44,79 @@ async fn migrate_(
     app_name: &str,
     now: i64,
 ) -> Result<(), MigrationError> {
-    let latest_applied_migration_number =
-        find_latest_applied_migration_number(config, app_name).await?;
     let migrations =
-        find_migrations_to_apply(available_migrations, latest_applied_migration_number)?;
+        pending_migrations(config, available_migrations, app_name, None, false).await?;
 
     for migration in migrations {
-        println!("Applying Migration for {app_name}: {}", migration.name);
+        eprintln!("Applying Migration for {app_name}: {}", migration.name);
         apply_migration(config, app_name, &migration, now).await?;
     }
 
     Ok(())
 }
 
-async fn apply_migration(
+/// The migrations of `app_name` that are not applied yet, up to `to` if given, once the applied
+/// ones are known to be unchanged. With `dry_run`, the checksums of migrations applied before
+/// checksums were recorded are not stored.
+pub(crate) async fn pending_migrations(
+    config: &fastn_core::Config,
+    available_migrations: &[fastn_core::package::MigrationData],
+    app_name: &str,
+    to: Option<i64>,
+    dry_run: bool,
+) -> Result<Vec<fastn_core::package::MigrationData>, MigrationError> {
+    let applied_migrations = find_applied_migrations(config, app_name).await?;
+    validate_applied_migrations(available_migrations, applied_migrations.as_slice())?;
+    if !dry_run {
+        store_missing_checksums(config, available_migrations, &applied_migrations, app_name)
+            .await?;
+    }
+
+    let latest_applied_migration_number = applied_migrations.last().map(|m| m.number);
+    let mut migrations =
+        find_migrations_to_apply(available_migrations, latest_applied_migration_number)?;
+    if let Some(to) = to {
+        migrations.retain(|m| m.number <= to);
+    }
+
+    Ok(migrations)
+}
+
+/// The applied migrations of `app_name` to revert, latest first: the ones after `to`, or only the
+/// latest one.
+pub(crate) async fn migrations_to_revert(
+    config: &fastn_core::Config,
+    available_migrations: &[fastn_core::package::MigrationData],
+    app_name: &str,
+    to: Option<i64>,
+) -> Result<Vec<fastn_core::package::MigrationData>, MigrationError> {
+    let applied_migrations = find_applied_migrations(config, app_name).await?;
+    validate_applied_migrations(available_migrations, applied_migrations.as_slice())?;
+
+    let mut migrations = vec![];
+    for applied in applied_migrations.iter().rev() {
+        match to {
+            Some(to) if applied.number <= to => break,
+            None if !migrations.is_empty() => break,
+            _ => {}
+        }
+        // validate_applied_migrations() made sure it is there
+        let migration = available_migrations
+            .iter()
+            .find(|m| m.number == applied.number)
+            .unwrap();
+        if migration.down.is_none() {
+            return Err(MigrationError::NoDownMigration {
+                name: migration.name.to_string(),
+            });
+        }
+        migrations.push(migration.clone());
+    }
+
+    Ok(migrations)
+}
+
+pub(crate) async fn apply_migration(
     config: &fastn_core::Config,
     app_name: &str,
     migration: &fastn_core::package::MigrationData,
@@ -80,6 +142,33 @@ async fn apply_migration(
     Ok(())
 }
 
+pub(crate) async fn revert_migration(
+    config: &fastn_core::Config,
+    app_name: &str,
+    migration: &fastn_core::package::MigrationData,
+) -> Result<(), MigrationError> {
+    let db = config.get_db_url().await;
+    let down = migration
+        .down
+        .as_ref()
+        .ok_or_else(|| MigrationError::NoDownMigration {
+            name: migration.name.to_string(),
+        })?;
+
+    let migration_content = format!(
+        "BEGIN;\n{}\n\n{}\nCOMMIT;",
+        down,
+        mark_migration_reverted_content(app_name, migration)
+    );
+
+    config
+        .ds
+        .sql_batch(db.as_str(), migration_content.as_str())
+        .await?;
+
+    Ok(())
+}
+
 fn find_migrations_to_apply(
     available_migrations: &[fastn_core::package::MigrationData],
     after: Option<i64>,
@@ -108,25 +197,228 @@ fn validate_migration(
     Ok(())
 }
 
+/// Every applied migration has to still be in `FASTN.ftd`, at the same place, with the content
+/// it was applied with.
+fn validate_applied_migrations(
+    available_migrations: &[fastn_core::package::MigrationData],
+    applied_migrations: &[AppliedMigration],
+) -> Result<(), MigrationError> {
+    for applied in applied_migrations {
+        let Some(migration) = available_migrations
+            .iter()
+            .find(|m| m.number == applied.number)
+        else {
+            return Err(MigrationError::AppliedMigrationDeletion {
+                name: applied.name.to_string(),
+            });
+        };
+        if migration.name != applied.name {
+            return Err(MigrationError::AppliedMigrationMismatch {
+                name: applied.name.to_string(),
+            });
+        }
+        if applied
+            .checksum
+            .as_ref()
+            .is_some_and(|checksum| *checksum != migration.checksum())
+        {
+            return Err(MigrationError::AppliedMigrationEdited {
+                name: applied.name.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Migrations applied before checksums were recorded get the checksum of their current content.
+async fn store_missing_checksums(
+    config: &fastn_core::Config,
+    available_migrations: &[fastn_core::package::MigrationData],
+    applied_migrations: &[AppliedMigration],
+    app_name: &str,
+) -> Result<(), MigrationError> {
+    let db = config.get_db_url().await;
+
+    for applied in applied_migrations.iter().filter(|m| m.checksum.is_none()) {
+        let Some(migration) = available_migrations
+            .iter()
+            .find(|m| m.number == applied.number)
+        else {
+            continue;
+        };
+        config
+            .ds
+            .sql_batch(
+                db.as_str(),
+                format!(
+                    r#"
+                        UPDATE
+                            fastn_migration
+                        SET
+                            checksum = '{}'
+                        WHERE
+                            app_name = '{app_name}' AND migration_number = {};
+                    "#,
+                    migration.checksum(),
+                    migration.number
+                )
+                .as_str(),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Where each migration of the package stands, applied ones that are not in `FASTN.ftd` anymore
+/// included.
+pub(crate) async fn migration_status(
+    config: &fastn_core::Config,
+) -> Result<Vec<MigrationStatus>, MigrationError> {
+    let applied_migrations = find_applied_migrations(config, config.package.name.as_str()).await?;
+
+    Ok(migration_statuses(
+        config.package.migrations.as_slice(),
+        applied_migrations.as_slice(),
+    ))
+}
+
+fn migration_statuses(
+    available_migrations: &[fastn_core::package::MigrationData],
+    applied_migrations: &[AppliedMigration],
+) -> Vec<MigrationStatus> {
+    let mut statuses = vec![];
+
+    for migration in available_migrations {
+        let applied = applied_migrations
+            .iter()
+            .find(|m| m.number == migration.number);
+        let state = match applied {
+            None => MigrationState::Pending,
+            Some(applied)
+                if applied.name != migration.name
+                    || applied
+                        .checksum
+                        .as_ref()
+                        .is_some_and(|checksum| *checksum != migration.checksum()) =>
+            {
+                MigrationState::Edited {
+                    applied_on: applied.applied_on,
+                }
+            }
+            Some(applied) => MigrationState::Applied {
+                applied_on: applied.applied_on,
+            },
+        };
+        statuses.push(MigrationStatus {
+            number: migration.number,
+            name: migration.name.to_string(),
+            has_down: migration.down.is_some(),
+            state,
+        });
+    }
+
+    for applied in applied_migrations {
+        if !available_migrations
+            .iter()
+            .any(|m| m.number == applied.number)
+        {
+            statuses.push(MigrationStatus {
+                number: applied.number,
+                name: applied.name.to_string(),
+                has_down: false,
+                state: MigrationState::Deleted {
+                    applied_on: applied.applied_on,
+                },
+            });
+        }
+    }
+
+    statuses.sort_by_key(|s| s.number);
+    statuses
+}
+
+/// `fastn_core::auth` needs the `fastn` tables even if the package has no migrations of its own.
 fn has_migrations(config: &fastn_core::Config) -> bool {
-    !config.package.migrations.is_empty()
+    !config.package.migrations.is_empty() || config.package.auth.is_some()
 }
 
-async fn create_migration_table(config: &fastn_core::Config) -> Result<(), fastn_utils::SqlError> {
+pub(crate) async fn create_migration_table(
+    config: &fastn_core::Config,
+) -> Result<(), fastn_utils::SqlError> {
     let db = config.get_db_url().await;
 
     config
         .ds
         .sql_batch(&db, fastn_migrations::MIGRATION_TABLE)
         .await?;
+
+    // tables created before checksums were recorded
+    if migration_table(config).await? == MigrationTable::WithoutChecksum {
+        config
+            .ds
+            .sql_batch(&db, fastn_migrations::ADD_CHECKSUM_COLUMN)
+            .await?;
+    }
+
     Ok(())
 }
 
-async fn find_latest_applied_migration_number(
+#[derive(Debug, PartialEq)]
+enum MigrationTable {
+    Missing,
+    /// Created before checksums were recorded.
+    WithoutChecksum,
+    Current,
+}
+
+/// What `fastn_migration` looks like, found without changing anything.
+async fn migration_table(
+    config: &fastn_core::Config,
+) -> Result<MigrationTable, fastn_utils::SqlError> {
+    let db = config.get_db_url().await;
+    let (table_exists, checksum_column) = if fastn_utils::sql::is_postgres_url(db.as_str()) {
+        (
+            fastn_migrations::MIGRATION_TABLE_EXISTS_POSTGRES,
+            fastn_migrations::CHECKSUM_COLUMN_POSTGRES,
+        )
+    } else {
+        (
+            fastn_migrations::MIGRATION_TABLE_EXISTS,
+            fastn_migrations::CHECKSUM_COLUMN,
+        )
+    };
+
+    if config
+        .ds
+        .sql_query(&db, table_exists, vec![])
+        .await?
+        .is_empty()
+    {
+        return Ok(MigrationTable::Missing);
+    }
+    if config
+        .ds
+        .sql_query(&db, checksum_column, vec![])
+        .await?
+        .is_empty()
+    {
+        return Ok(MigrationTable::WithoutChecksum);
+    }
+    Ok(MigrationTable::Current)
+}
+
+/// The applied migrations of `app_name`, in the order they were applied.
+async fn find_applied_migrations(
     config: &fastn_core::Config,
     app_name: &str,
-) -> Result<Option<i64>, MigrationError> {
+) -> Result<Vec<AppliedMigration>, MigrationError> {
     let db = config.get_db_url().await;
+    let checksum = match migration_table(config).await? {
+        MigrationTable::Missing => return Ok(vec![]),
+        MigrationTable::WithoutChecksum => "NULL",
+        MigrationTable::Current => "checksum",
+    };
 
     let results = config
         .ds
@@ -135,13 +427,12 @@ async fn find_latest_applied_migration_number(
             format!(
                 r#"
                     SELECT
-                        migration_number
+                        migration_number, migration_name, applied_on, {checksum}
                     FROM
                         fastn_migration
                     WHERE
                         app_name = '{app_name}'
-                    ORDER BY migration_number DESC
-                    LIMIT 1;
+                    ORDER BY migration_number ASC;
                 "#,
             )
             .as_str(),
@@ -149,13 +440,16 @@ async fn find_latest_applied_migration_number(
         )
         .await?;
 
-    match results.len() {
-        0 => Ok(None),
-        1 => Ok(Some(
-            serde_json::from_value::<i64>(results[0].first().unwrap().clone()).unwrap(),
-        )), // Unwrap is okay here
-        _ => unreachable!(),
-    }
+    // Unwrap is okay here, the columns are NOT NULL
+    Ok(results
+        .into_iter()
+        .map(|row| AppliedMigration {
+            number: row[0].as_i64().unwrap(),
+            name: row[1].as_str().unwrap().to_string(),
+            applied_on: row[2].as_i64().unwrap(),
+            checksum: row[3].as_str().map(ToString::to_string),
+        })
+        .collect())
 }
 
 fn mark_migration_applied_content(
@@ -167,23 +461,164 @@ fn mark_migration_applied_content(
         r#"
             INSERT INTO
                 fastn_migration
-                    (app_name, migration_number, migration_name, applied_on)
+                    (app_name, migration_number, migration_name, applied_on, checksum)
             VALUES
-                ('{}', {}, '{}', {});
+                ('{}', {}, '{}', {}, '{}');
         "#,
-        app_name, migration_data.number, migration_data.name, now
+        app_name,
+        migration_data.number,
+        migration_data.name,
+        now,
+        migration_data.checksum()
     )
 }
+
+fn mark_migration_reverted_content(
+    app_name: &str,
+    migration_data: &fastn_core::package::MigrationData,
+) -> String {
+    format!(
+        r#"
+            DELETE FROM
+                fastn_migration
+            WHERE
+                app_name = '{}' AND migration_number = {};
+        "#,
+        app_name, migration_data.number
+    )
+}
+
+#[derive(Debug, Clone)]
+struct AppliedMigration {
+    number: i64,
+    name: String,
+    applied_on: i64,
+    /// `None` if applied before checksums were recorded.
+    checksum: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct MigrationStatus {
+    pub number: i64,
+    pub name: String,
+    pub has_down: bool,
+    pub state: MigrationState,
+}
+
+/// `applied_on` is in nanoseconds since the epoch.
+#[derive(Debug, PartialEq)]
+pub enum MigrationState {
+    Pending,
+    Applied {
+        applied_on: i64,
+    },
+    /// Applied, but changed in `FASTN.ftd` since.
+    Edited {
+        applied_on: i64,
+    },
+    /// Applied, but not in `FASTN.ftd` anymore.
+    Deleted {
+        applied_on: i64,
+    },
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum MigrationError {
     #[error("Sql Error: {0}")]
     SqlError(#[from] fastn_utils::SqlError),
-    #[error("Cannot delete applied migration")]
-    AppliedMigrationDeletion,
-    #[error("The migration order has changed or its content has been altered")]
-    AppliedMigrationMismatch,
+    #[error("Cannot delete applied migration `{name}`")]
+    AppliedMigrationDeletion { name: String },
+    #[error("The migration order has changed, `{name}` was applied at a different position")]
+    AppliedMigrationMismatch { name: String },
+    #[error("Migration `{name}` has been edited after it was applied, add a new migration instead. Run `fastn migrate status` to see all the migrations")]
+    AppliedMigrationEdited { name: String },
+    #[error("Migration `{name}` has no `down` to revert it")]
+    NoDownMigration { name: String },
     #[error("Multiple migrations found with the same name: {name}.")]
     MigrationNameConflict { name: String },
     #[error("`{name}` is invalid migration name. It must contain only alphanumeric characters, underscores, and hyphens.")]
     InvalidMigrationName { name: String },
 }
+
+#[cfg(test)]
+mod tests {
+    fn migration(number: i64, name: &str, content: &str) -> fastn_core::package::MigrationData {
+        fastn_core::package::MigrationData {
+            number,
+            name: name.to_string(),
+            content: content.to_string(),
+            down: None,
+        }
+    }
+
+    fn applied(migration: &fastn_core::package::MigrationData) -> super::AppliedMigration {
+        super::AppliedMigration {
+            number: migration.number,
+            name: migration.name.to_string(),
+            applied_on: 1,
+            checksum: Some(migration.checksum()),
+        }
+    }
+
+    #[test]
+    fn validate_applied_migrations() {
+        let users = migration(0, "users", "CREATE TABLE users (id INTEGER);");
+        let email = migration(1, "email", "ALTER TABLE users ADD COLUMN email TEXT;");
+        let available = vec![users.clone(), email.clone()];
+
+        assert!(super::validate_applied_migrations(&available, &[applied(&users)]).is_ok());
+        assert!(super::validate_applied_migrations(
+            &available,
+            &[super::AppliedMigration {
+                checksum: None,
+                ..applied(&users)
+            }]
+        )
+        .is_ok());
+
+        let edited = migration(0, "users", "CREATE TABLE users (id INTEGER, name TEXT);");
+        assert!(matches!(
+            super::validate_applied_migrations(&available, &[applied(&edited)]),
+            Err(super::MigrationError::AppliedMigrationEdited { .. })
+        ));
+        assert!(matches!(
+            super::validate_applied_migrations(&[users.clone()], &[applied(&email)]),
+            Err(super::MigrationError::AppliedMigrationDeletion { .. })
+        ));
+        assert!(matches!(
+            super::validate_applied_migrations(
+                &available,
+                &[applied(&migration(0, "email", email.content.as_str()))]
+            ),
+            Err(super::MigrationError::AppliedMigrationMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn migration_statuses() {
+        let users = migration(0, "users", "CREATE TABLE users (id INTEGER);");
+        let email = migration(1, "email", "ALTER TABLE users ADD COLUMN email TEXT;");
+        let dropped = migration(2, "dropped", "CREATE TABLE dropped (id INTEGER);");
+        let edited = migration(0, "users", "CREATE TABLE users (id TEXT);");
+
+        let statuses =
+            super::migration_statuses(&[edited, email], &[applied(&users), applied(&dropped)])
+                .into_iter()
+                .map(|s| (s.name, s.state))
+                .collect::<Vec<_>>();
+        assert_eq!(
+            statuses,
+            vec![
+                (
+                    "users".to_string(),
+                    super::MigrationState::Edited { applied_on: 1 }
+                ),
+                ("email".to_string(), super::MigrationState::Pending),
+                (
+                    "dropped".to_string(),
+                    super::MigrationState::Deleted { applied_on: 1 }
+                ),
+            ]
+        );
+    }
+}
@@ -9,6 +9,7 @@ pub struct Dependency {
     pub implements: Vec<String>,
     pub provided_via: Option<String>,
     pub required_as: Option<String>,
+    pub path: Option<String>,
 }
 
 impl Dependency {
@@ -79,6 +80,7 @@ pub(crate) struct DependencyTemp {
     pub provided_via: Option<String>,
     #[serde(rename = "required-as")]
     pub required_as: Option<String>,
+    pub path: Option<String>,
 }
 
 impl DependencyTemp {
@@ -95,6 +97,7 @@ impl DependencyTemp {
             implements: self.implements,
             provided_via: self.provided_via,
             required_as: self.required_as,
+            path: self.path,
         })
     }
 }
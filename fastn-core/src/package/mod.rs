@@ -69,10 +69,33 @@ pub struct Package {
     pub system: Option<String>,
     pub system_is_confidential: Option<bool>,
 
+    /// Pages are rendered on the server for every request and hydrated in the browser. If
+    /// `false`, only bots get the rendered html, the browser builds the page for everyone else.
+    pub ssr: bool,
+
+    /// The offset from UTC, like `+05:30`, `ftd.format_time` and the other date and time
+    /// functions format in, UTC if `None`. See `fastn_builtins::datetime::parse_utc_offset`.
+    pub time_zone: Option<String>,
+
     pub lang: Option<Lang>,
 
+    /// The language this package is written in, see `language` in `fastn.package`
+    pub language: Option<String>,
+
     /// Migrations
     pub migrations: Vec<MigrationData>,
+
+    /// `Cache-Control` header per path prefix
+    pub cache_control: Vec<CacheControl>,
+
+    /// Body of `-- fastn.robots:`, used as `robots.txt`
+    pub robots: Option<String>,
+
+    /// Atom and RSS feeds, see `fastn_core::feed`
+    pub feeds: Vec<Feed>,
+
+    /// The built-in sign-up and login, see `fastn_core::auth`
+    pub auth: Option<Auth>,
 }
 
 impl Package {
@@ -86,6 +109,7 @@ impl Package {
             requested_language: None,
             selected_language: None,
             lang: None,
+            language: None,
             about: None,
             zip: None,
             download_base_url: None,
@@ -108,7 +132,13 @@ impl Package {
             redirects: None,
             system: None,
             system_is_confidential: None,
+            ssr: true,
+            time_zone: None,
             migrations: vec![],
+            cache_control: vec![],
+            robots: None,
+            feeds: vec![],
+            auth: None,
         }
     }
 
@@ -153,6 +183,20 @@ impl Package {
         self
     }
 
+    /// The language and the time zone of the page being served, for the date and time functions
+    /// of expressions. The language is the one the page is rendered in, not the `fastn-lang`
+    /// cookie, which the package may have no module for.
+    pub fn datetime_settings(&self) -> fastn_builtins::datetime::Settings {
+        fastn_builtins::datetime::Settings {
+            language: self.selected_language.clone(),
+            utc_offset: self
+                .time_zone
+                .as_deref()
+                .and_then(fastn_builtins::datetime::parse_utc_offset)
+                .unwrap_or_default(),
+        }
+    }
+
     pub fn current_language_meta(
         &self,
     ) -> ftd::interpreter::Result<fastn_core::library2022::processor::lang_details::LanguageMeta>
@@ -722,10 +766,27 @@ impl Package {
         package.sitemap_temp = fastn_doc.get("fastn#sitemap")?;
         package.dynamic_urls_temp = fastn_doc.get("fastn#dynamic-urls")?;
         package.migrations = get_migration_data(fastn_doc)?;
+        package.cache_control = fastn_doc.get("fastn#cache-control")?;
+        package.robots = fastn_doc
+            .get::<Option<RobotsTemp>>("fastn#robots")?
+            .map(|r| r.body);
+        package.feeds = fastn_doc.get("fastn#feed")?;
+        package.auth = fastn_doc.get("fastn#auth")?;
 
         // validation logic TODO: It should be ordered
         fastn_core::utils::validate_base_url(&package)?;
 
+        if let Some(ref time_zone) = package.time_zone {
+            if fastn_builtins::datetime::parse_utc_offset(time_zone).is_none() {
+                return Err(fastn_core::Error::PackageError {
+                    message: format!(
+                        "`time-zone: {time_zone}` of {} is not an offset from UTC like `+05:30`",
+                        package.name
+                    ),
+                });
+            }
+        }
+
         if package.import_auto_imports_from_original {
             if let Some(ref original_package) = *package.translation_of {
                 if package.auto_import.is_empty() {
@@ -743,6 +804,59 @@ impl Package {
         Ok(package)
     }
 
+    /// The dependencies to read from a local checkout instead of downloading them, with the path
+    /// of the checkout relative to the package root: the ones with a `path` in `FASTN.ftd`, and
+    /// the ones in `FASTN.local.ftd`, which override them and can name indirect dependencies too.
+    pub async fn local_paths(
+        &self,
+        ds: &fastn_ds::DocumentStore,
+    ) -> fastn_core::Result<std::collections::BTreeMap<String, String>> {
+        let mut paths: std::collections::BTreeMap<String, String> = self
+            .dependencies
+            .iter()
+            .filter_map(|d| d.path.clone().map(|path| (d.package.name.clone(), path)))
+            .collect();
+
+        let local_fastn_path = ds.root().join(fastn_core::FASTN_LOCAL_FILE);
+        if !ds.exists(&local_fastn_path, &None).await {
+            return Ok(paths);
+        }
+
+        let doc = ds.read_to_string(&local_fastn_path, &None).await?;
+        let lib = fastn_core::FastnLibrary::default();
+        let fastn_document =
+            fastn_core::doc::parse_ftd("fastn", doc.as_str(), &lib).map_err(|e| {
+                fastn_core::Error::PackageError {
+                    message: format!("failed to parse {}: {:?}", fastn_core::FASTN_LOCAL_FILE, &e),
+                }
+            })?;
+        let temp_deps: Vec<fastn_core::package::dependency::DependencyTemp> =
+            fastn_document.get("fastn#dependency")?;
+        for dependency in temp_deps {
+            let dependency = dependency.into_dependency()?;
+            let Some(path) = dependency.path else {
+                return fastn_core::usage_error(format!(
+                    "`-- fastn.dependency: {}` in {} has no `path`",
+                    dependency.package.name,
+                    fastn_core::FASTN_LOCAL_FILE
+                ));
+            };
+            paths.insert(dependency.package.name, path);
+        }
+
+        Ok(paths)
+    }
+
+    /// The `Cache-Control` header configured for `path`, if any. The longest matching
+    /// `-- fastn.cache-control:` path wins.
+    pub fn cache_control(&self, path: &str) -> Option<&str> {
+        self.cache_control
+            .iter()
+            .filter(|c| path.starts_with(c.path.as_str()))
+            .max_by_key(|c| c.path.len())
+            .map(|c| c.value.as_str())
+    }
+
     pub fn auto_import_language(
         &mut self,
         req_lang: Option<String>,
@@ -959,6 +1073,7 @@ impl PackageTempIntoPackage for fastn_package::old_fastn::PackageTemp {
             requested_language: None,
             selected_language: None,
             lang,
+            language: self.language,
             about: self.about,
             zip: self.zip,
             download_base_url: self.download_base_url.or(Some(self.name)),
@@ -981,7 +1096,13 @@ impl PackageTempIntoPackage for fastn_package::old_fastn::PackageTemp {
             redirects: None,
             system: self.system,
             system_is_confidential: self.system_is_confidential,
+            ssr: self.ssr,
+            time_zone: self.time_zone,
             migrations: vec![],
+            cache_control: vec![],
+            robots: None,
+            feeds: vec![],
+            auth: None,
         }
     }
 }
@@ -991,12 +1112,22 @@ pub struct MigrationData {
     pub number: i64,
     pub name: String,
     pub content: String,
+    /// Reverts `content`, for `fastn migrate down`.
+    pub down: Option<String>,
+}
+
+impl MigrationData {
+    /// Recorded when the migration is applied, to notice if it is edited afterwards.
+    pub fn checksum(&self) -> String {
+        fastn_core::utils::generate_hash(self.content.trim())
+    }
 }
 
 #[derive(Debug, serde::Deserialize, Clone)]
 pub struct MigrationDataTemp {
     pub name: String,
     pub content: String,
+    pub down: Option<String>,
 }
 
 impl MigrationDataTemp {
@@ -1005,6 +1136,107 @@ impl MigrationDataTemp {
             number,
             name: self.name,
             content: self.content,
+            down: self.down,
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize, Clone)]
+pub struct CacheControl {
+    pub path: String,
+    pub value: String,
+}
+
+#[derive(Debug, serde::Deserialize, Clone)]
+pub struct RobotsTemp {
+    #[serde(rename = "robots-body")]
+    pub body: String,
+}
+
+#[derive(Debug, serde::Deserialize, Clone)]
+pub struct Feed {
+    pub path: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    #[serde(rename = "date-variable")]
+    pub date_variable: String,
+}
+
+#[derive(Debug, serde::Deserialize, Clone)]
+pub struct Auth {
+    #[serde(rename = "from-address")]
+    pub from_address: String,
+    #[serde(rename = "from-name")]
+    pub from_name: String,
+    /// Anyone can create an account at `/-/auth/signup/`
+    pub signup: bool,
+    /// Users can log in with a link sent to their email instead of the password
+    #[serde(rename = "magic-link")]
+    pub magic_link: bool,
+    #[serde(rename = "session-days")]
+    pub session_days: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    #[tokio::test]
+    async fn local_paths() {
+        let root = std::env::temp_dir().join(format!("fastn-local-paths-{}", std::process::id()));
+        let ds = fastn_ds::DocumentStore::new(root.to_str().unwrap(), Default::default());
+        let mut package = fastn_core::Package::new("hello.com");
+        package.dependencies = vec![dependency("a.com", Some("../a")), dependency("b.com", None)];
+
+        // only the dependencies with a `path` without FASTN.local.ftd
+        assert_eq!(
+            package.local_paths(&ds).await.unwrap(),
+            [("a.com".to_string(), "../a".to_string())].into()
+        );
+
+        // FASTN.local.ftd overrides `path` and names indirect dependencies too
+        let local_fastn = ds.root().join(fastn_core::FASTN_LOCAL_FILE);
+        ds.write_content(
+            &local_fastn,
+            b"-- import: fastn\n\n\
+            -- fastn.dependency: a.com\npath: ../a-local\n\n\
+            -- fastn.dependency: c.com\npath: ../c\n",
+        )
+        .await
+        .unwrap();
+        let paths = package.local_paths(&ds).await;
+
+        ds.write_content(
+            &local_fastn,
+            b"-- import: fastn\n\n-- fastn.dependency: b.com\n",
+        )
+        .await
+        .unwrap();
+        let without_path = package.local_paths(&ds).await;
+        let _ = ds.remove(&ds.root()).await;
+
+        assert_eq!(
+            paths.unwrap(),
+            [
+                ("a.com".to_string(), "../a-local".to_string()),
+                ("c.com".to_string(), "../c".to_string()),
+            ]
+            .into()
+        );
+        assert!(matches!(
+            without_path,
+            Err(fastn_core::Error::UsageError { message }) if message.contains("b.com")
+        ));
+    }
+
+    fn dependency(name: &str, path: Option<&str>) -> fastn_core::package::dependency::Dependency {
+        fastn_core::package::dependency::Dependency {
+            package: fastn_core::Package::new(name),
+            version: None,
+            notes: None,
+            alias: None,
+            implements: vec![],
+            provided_via: None,
+            required_as: None,
+            path: path.map(ToString::to_string),
         }
     }
 }
@@ -513,30 +513,70 @@ pub(crate) async fn read_ftd_2023(
         return Ok(FTDResult::Redirect { url, code });
     }
 
-    let js_ast_data = ftd::js::document_into_js_ast(main_ftd_doc);
+    let js_ast_data = ftd::js::document_into_js_ast_by_module(main_ftd_doc);
     let js_document_script = fastn_js::to_js(js_ast_data.asts.as_slice(), package_name.as_str());
-    let js_ftd_script = fastn_js::to_js(
-        ftd::js::default_bag_into_js_ast().as_slice(),
-        package_name.as_str(),
-    );
+    let js_modules = js_ast_data
+        .modules
+        .iter()
+        .map(|(module, asts)| {
+            (
+                module.to_string(),
+                fastn_js::to_js(asts.as_slice(), package_name.as_str()),
+            )
+        })
+        .collect::<Vec<_>>();
+    let js_modules_script = js_modules
+        .iter()
+        .map(|(_, js)| js.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let js_ftd_script = fastn_core::shared_js::default_bag_js(package_name.as_str());
+    let js_language = fastn_core::utils::get_fastn_language_data(&config.config.package);
     let file_content = if only_js {
         fastn_js::ssr_raw_string_without_test(
             &package_name,
-            format!("{js_ftd_script}\n{js_document_script}").as_str(),
+            format!("{js_language}\n{js_ftd_script}\n{js_modules_script}\n{js_document_script}")
+                .as_str(),
         )
     } else {
-        let ssr_body = if config.request.is_bot() {
-            fastn_js::ssr_with_js_string(
+        // the browser hydrates the server rendered html, see `fastnVirtual.hydrate`
+        let ssr_body = if config.config.package.ssr || config.request.is_bot() {
+            // the browser renders the page itself when there is nothing to hydrate
+            match fastn_js::ssr_with_js_string(
                 &package_name,
-                format!("{js_ftd_script}\n{js_document_script}").as_str(),
-            )?
+                format!(
+                    "{js_language}\n{js_ftd_script}\n{js_modules_script}\n{js_document_script}"
+                )
+                .as_str(),
+            ) {
+                Ok(body) => body,
+                Err(e) => {
+                    tracing::warn!(path = ?main.id, "server side rendering failed: {e}");
+                    EMPTY_HTML_BODY.to_string()
+                }
+            }
         } else {
             EMPTY_HTML_BODY.to_string()
         };
 
+        // the modules are loaded from files the browser caches across pages, except for the ones
+        // not everyone can read, see `fastn_core::shared_js`
+        let (module_files, inlined_modules) = fastn_core::shared_js::module_files(
+            &config.config,
+            js_modules.as_slice(),
+            preview_session_id,
+        )
+        .await?;
+        let scripts = module_files
+            .iter()
+            .map(|name| format!("<script src=\"{name}\"></script>"))
+            .chain(js_ast_data.scripts)
+            .collect::<Vec<_>>()
+            .join("");
+
         fastn_core::utils::replace_markers_2023(
-            js_document_script.as_str(),
-            js_ast_data.scripts.join("").as_str(),
+            format!("{inlined_modules}\n{js_document_script}").as_str(),
+            scripts.as_str(),
             ssr_body.as_str(),
             config.config.get_font_style().as_str(),
             ftd::ftd_js_css(),
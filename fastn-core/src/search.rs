@@ -0,0 +1,506 @@
+//! Offline full-text search.
+//!
+//! `fastn build` interprets every document everyone can read (see
+//! `fastn_core::sitemap::xml::urls` and `fastn_core::auth::access`), collects the text passed to
+//! its components, and writes an inverted index to
+//! `.build/-/search/<language>.json`. A package with a `lang` module per language (see
+//! `default-language` in `fastn.package`) gets one file per language, its documents are
+//! interpreted with that language's module. `ftd.search` in `ftd.js` downloads the file of the
+//! language the page is rendered in and searches it in the browser. `fastn serve` builds the
+//! index of every document on the first search, and serves and searches only the documents the
+//! requester can read, at the same path and at `/-/search?q=&lang=`. The file watcher
+//! (`fastn serve --watch` or `--cache-size`) drops it when a file changes.
+
+/// `.build/-/search/<language>.json`
+pub const INDEX_DIR: &str = "-/search/";
+pub const QUERY_PATH: &str = "/-/search";
+
+const MIN_TERM_LENGTH: usize = 2;
+const MAX_RESULTS: usize = 20;
+/// A term in the title counts as many times in the text.
+const TITLE_WEIGHT: usize = 5;
+
+/// Headers that take urls, ids and such instead of text.
+const NON_TEXT_HEADERS: &[&str] = &[
+    "id", "link", "src", "url", "anchor", "class", "classes", "css", "js", "target", "rel",
+    "download",
+];
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct Index {
+    /// `[url, title]` of every document
+    pub documents: Vec<(String, String)>,
+    /// term -> `[document, occurrences]`, ordered by document
+    pub terms: std::collections::BTreeMap<String, Vec<(usize, usize)>>,
+}
+
+#[derive(Debug, PartialEq, serde::Serialize)]
+pub struct SearchResult {
+    pub url: String,
+    pub title: String,
+}
+
+impl Index {
+    pub fn add(&mut self, url: String, title: String, text: &str) {
+        let document = self.documents.len();
+        let mut counts: std::collections::HashMap<String, usize> = Default::default();
+        for term in terms(text) {
+            *counts.entry(term).or_default() += 1;
+        }
+        for term in terms(title.as_str()) {
+            *counts.entry(term).or_default() += TITLE_WEIGHT;
+        }
+
+        for (term, count) in counts {
+            self.terms.entry(term).or_default().push((document, count));
+        }
+        self.documents.push((url, title));
+    }
+
+    /// The index of the documents `keep` returns true for.
+    pub fn filter(&self, keep: impl Fn(&str) -> bool) -> Index {
+        // old document number -> new one
+        let mut numbers = std::collections::HashMap::new();
+        let mut index = Index::default();
+        for (number, (url, title)) in self.documents.iter().enumerate() {
+            if keep(url) {
+                numbers.insert(number, index.documents.len());
+                index.documents.push((url.clone(), title.clone()));
+            }
+        }
+
+        for (term, postings) in self.terms.iter() {
+            let postings: Vec<(usize, usize)> = postings
+                .iter()
+                .filter_map(|(document, count)| numbers.get(document).map(|d| (*d, *count)))
+                .collect();
+            if !postings.is_empty() {
+                index.terms.insert(term.clone(), postings);
+            }
+        }
+        index
+    }
+
+    /// Every term of the query has to be in the document, the last one can be a prefix, so
+    /// results show up while the query is being typed. `ftd.search` does the same.
+    pub fn search(&self, query: &str) -> Vec<SearchResult> {
+        let query: Vec<String> = terms(query).collect();
+        let Some(last) = query.last() else {
+            return vec![];
+        };
+
+        // document -> (matched terms, score)
+        let mut scores: std::collections::BTreeMap<usize, (usize, usize)> = Default::default();
+        for (i, term) in query.iter().enumerate() {
+            let matches: Vec<&Vec<(usize, usize)>> = if i == query.len() - 1 {
+                self.terms
+                    .range(last.to_string()..)
+                    .take_while(|(t, _)| t.starts_with(last.as_str()))
+                    .map(|(_, postings)| postings)
+                    .collect()
+            } else {
+                self.terms.get(term).into_iter().collect()
+            };
+
+            let mut found: std::collections::BTreeMap<usize, usize> = Default::default();
+            for (document, count) in matches.into_iter().flatten() {
+                *found.entry(*document).or_default() += count;
+            }
+            for (document, count) in found {
+                let score = scores.entry(document).or_default();
+                score.0 += 1;
+                score.1 += count;
+            }
+        }
+
+        let mut scores: Vec<(usize, usize)> = scores
+            .into_iter()
+            .filter(|(_, (terms, _))| *terms == query.len())
+            .map(|(document, (_, score))| (document, score))
+            .collect();
+        scores.sort_by(|(a, x), (b, y)| y.cmp(x).then(a.cmp(b)));
+
+        scores
+            .into_iter()
+            .take(MAX_RESULTS)
+            .map(|(document, _)| {
+                let (url, title) = self.documents[document].clone();
+                SearchResult { url, title }
+            })
+            .collect()
+    }
+}
+
+/// Lower cased words of `text`, without the one letter ones.
+fn terms(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|term| term.chars().count() >= MIN_TERM_LENGTH)
+        .map(|term| term.to_lowercase())
+}
+
+/// The language of the package's documents when none is asked for.
+pub fn language(package: &fastn_core::Package) -> String {
+    package
+        .selected_language
+        .clone()
+        .unwrap_or_else(|| "en".to_string())
+}
+
+/// Every language the package's documents are rendered in, one shard of the index each.
+pub fn languages(package: &fastn_core::Package) -> Vec<String> {
+    match package.lang.as_ref() {
+        Some(lang) => {
+            let mut languages: Vec<String> = lang.available_languages.keys().cloned().collect();
+            languages.sort();
+            languages
+        }
+        None => vec![language(package)],
+    }
+}
+
+/// `config` with the `lang` module of `language` auto imported instead of the current one.
+fn with_language(
+    config: &fastn_core::Config,
+    language: &str,
+) -> fastn_core::Result<fastn_core::Config> {
+    let mut config = config.clone();
+    let package = &mut config.package;
+    if let Some(lang) = package.lang.as_ref() {
+        let modules: Vec<String> = lang.available_languages.values().cloned().collect();
+        package
+            .auto_import
+            .retain(|a| a.alias.as_deref() != Some("lang") || !modules.contains(&a.path));
+    }
+    package.auto_import_language(Some(language.to_string()), None)?;
+    Ok(config)
+}
+
+pub async fn build_index(
+    config: &fastn_core::Config,
+    language: &str,
+    session_id: &Option<String>,
+) -> fastn_core::Result<Index> {
+    let config = &with_language(config, language)?;
+    let urls = fastn_core::sitemap::xml::urls(config, session_id).await?;
+
+    let mut index = Index::default();
+    for (url, document) in fastn_core::sitemap::xml::documents(config, urls, session_id).await? {
+        match document_text(config, &document, session_id).await {
+            Ok((title, text)) => index.add(
+                format!("/{url}"),
+                title.unwrap_or_else(|| url.trim_matches('/').to_string()),
+                text.as_str(),
+            ),
+            Err(e) => tracing::warn!(
+                msg = "failed to index document, skipping",
+                doc = document.id.as_str(),
+                error = e.to_string()
+            ),
+        }
+    }
+
+    Ok(index)
+}
+
+/// Writes the index of every language into `.build/-/search/<language>.json`.
+pub async fn write(
+    config: &fastn_core::Config,
+    build_dir: &fastn_ds::Path,
+    session_id: &Option<String>,
+) -> fastn_core::Result<()> {
+    for language in languages(&config.package) {
+        let index = build_index(config, language.as_str(), session_id)
+            .await?
+            .filter(|url| fastn_core::auth::access::is_public(&config.package, url));
+        let path = format!("{INDEX_DIR}{language}.json");
+        println!("Processing {}/{path} ... ", config.package.name.as_str());
+        fastn_core::utils::update(
+            &build_dir.join(path.as_str()),
+            serde_json::to_string(&index)?.as_bytes(),
+            &config.ds,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// The `ftd.document` title and the text of every component of `document`.
+async fn document_text(
+    config: &fastn_core::Config,
+    document: &fastn_core::Document,
+    session_id: &Option<String>,
+) -> fastn_core::Result<(Option<String>, String)> {
+    use ftd::interpreter::ValueExt;
+
+    let doc = fastn_core::doc::interpret_document(config, document, session_id).await?;
+    let title = fastn_core::doc::document_argument(&doc, "title")?
+        .map(|v| v.string(doc.name.as_str(), 0))
+        .transpose()?;
+
+    let mut text = vec![];
+    collect_text(&doc.tree, &doc.tdoc(), &mut text);
+    Ok((title, text.join("\n")))
+}
+
+fn collect_text(
+    instructions: &[fastn_resolved::ComponentInvocation],
+    doc: &ftd::interpreter::TDoc,
+    text: &mut Vec<String>,
+) {
+    use ftd::interpreter::{ComponentExt, PropertyValueExt};
+
+    for instruction in instructions {
+        for property in instruction.properties.iter() {
+            let is_text = match &property.source {
+                fastn_resolved::PropertySource::Caption | fastn_resolved::PropertySource::Body => {
+                    true
+                }
+                fastn_resolved::PropertySource::Header { name, .. } => {
+                    !NON_TEXT_HEADERS.contains(&name.as_str())
+                }
+                _ => false,
+            };
+            if !is_text {
+                continue;
+            }
+
+            // values that depend on a loop or on other components do not resolve here
+            if let Ok(Some(fastn_resolved::Value::String { text: value })) = property
+                .value
+                .clone()
+                .resolve(doc, property.line_number)
+                .map(|v| v.inner())
+            {
+                text.push(value);
+            }
+        }
+
+        collect_text(
+            &instruction.get_children(doc).unwrap_or_default(),
+            doc,
+            text,
+        );
+    }
+}
+
+/// The index of each language `fastn serve` builds on the first search in that language.
+#[derive(Debug, Clone, Default)]
+pub struct LazyIndex {
+    inner: std::sync::Arc<
+        tokio::sync::Mutex<std::collections::HashMap<String, std::sync::Arc<Index>>>,
+    >,
+}
+
+impl LazyIndex {
+    pub async fn get(
+        &self,
+        config: &fastn_core::Config,
+        language: &str,
+        session_id: &Option<String>,
+    ) -> fastn_core::Result<std::sync::Arc<Index>> {
+        let mut inner = self.inner.lock().await;
+        if let Some(index) = inner.get(language) {
+            return Ok(index.clone());
+        }
+
+        let index = std::sync::Arc::new(build_index(config, language, session_id).await?);
+        inner.insert(language.to_string(), index.clone());
+        Ok(index)
+    }
+
+    pub fn invalidate(&self) {
+        if let Ok(mut inner) = self.inner.try_lock() {
+            inner.clear();
+        } else {
+            // being built right now, clear it once that is done
+            let inner = self.inner.clone();
+            tokio::spawn(async move {
+                inner.lock().await.clear();
+            });
+        }
+    }
+}
+
+/// Responds to `/-/search?q=&lang=` and `/-/search/<language>.json` in `fastn serve`, returns
+/// `None` for any other path or a language the package is not rendered in.
+pub async fn handle(
+    req_config: &fastn_core::RequestConfig,
+    session_id: &Option<String>,
+) -> Option<fastn_core::Result<fastn_core::http::Response>> {
+    let config = &req_config.config;
+    let req = &req_config.request;
+    let languages = languages(&config.package);
+    let path = req.path();
+    if path.trim_end_matches('/') == QUERY_PATH {
+        let query = req.query();
+        let language = query
+            .get("lang")
+            .and_then(|l| l.as_str())
+            .map(ToString::to_string)
+            .unwrap_or_else(|| language(&config.package));
+        let query = query
+            .get("q")
+            .and_then(|q| q.as_str())
+            .unwrap_or_default()
+            .to_string();
+        if !languages.contains(&language) {
+            return None;
+        }
+        return Some(
+            readable_index(req_config, language.as_str(), session_id)
+                .await
+                .and_then(|index| {
+                    fastn_core::http::api_ok(index.search(query.as_str())).map_err(Into::into)
+                }),
+        );
+    }
+
+    let language = path
+        .strip_prefix('/')?
+        .strip_prefix(INDEX_DIR)?
+        .strip_suffix(".json")?;
+    if !languages.iter().any(|l| l == language) {
+        return None;
+    }
+
+    Some(
+        readable_index(req_config, language, session_id)
+            .await
+            .and_then(|index| serde_json::to_vec(index.as_ref()).map_err(Into::into))
+            .map(|index| {
+                fastn_core::http::ok_with_content_type(index, mime_guess::mime::APPLICATION_JSON)
+            }),
+    )
+}
+
+/// The index of the documents the request can read.
+async fn readable_index(
+    req_config: &fastn_core::RequestConfig,
+    language: &str,
+    session_id: &Option<String>,
+) -> fastn_core::Result<std::sync::Arc<Index>> {
+    let config = &req_config.config;
+    let index = config
+        .search_index
+        .get(config, language, session_id)
+        .await?;
+
+    let mut urls: Vec<&str> = index
+        .documents
+        .iter()
+        .map(|(url, _)| url.as_str())
+        .collect();
+    let all = urls.len();
+    fastn_core::auth::access::retain_readable(
+        &config.package,
+        Some(req_config),
+        &mut urls,
+        |url| url,
+    )
+    .await?;
+    if urls.len() == all {
+        return Ok(index);
+    }
+
+    let urls: std::collections::HashSet<&str> = urls.into_iter().collect();
+    Ok(std::sync::Arc::new(index.filter(|url| urls.contains(url))))
+}
+
+#[cfg(test)]
+mod tests {
+    fn index() -> super::Index {
+        let mut index = super::Index::default();
+        index.add(
+            "/install/".to_string(),
+            "Install fastn".to_string(),
+            "Download the installer, then run fastn serve.",
+        );
+        index.add(
+            "/serve/".to_string(),
+            "fastn serve".to_string(),
+            "Serve a package locally. Serve it again after changes.",
+        );
+        index
+    }
+
+    fn urls(results: Vec<super::SearchResult>) -> Vec<String> {
+        results.into_iter().map(|r| r.url).collect()
+    }
+
+    #[test]
+    fn search() {
+        let index = index();
+        assert_eq!(urls(index.search("serve")), vec!["/serve/", "/install/"]);
+        assert_eq!(urls(index.search("install")), vec!["/install/"]);
+        assert_eq!(urls(index.search("fastn down")), vec!["/install/"]);
+        assert_eq!(urls(index.search("Locally serve")), vec!["/serve/"]);
+        assert!(index.search("").is_empty());
+        assert!(index.search("missing").is_empty());
+    }
+
+    /// `ftd.search` in the browser finds what `Index::search` finds.
+    #[test]
+    fn search_in_javascript() {
+        let mut index = index();
+        index.add(
+            "/hindi/".to_string(),
+            "हिन्दी".to_string(),
+            "किताबें पढ़ना अच्छा है",
+        );
+        let js = |query: &str| -> Vec<String> {
+            let js = fastn_js::ssr_str(
+                format!(
+                    r#"
+                    let __fastn_package_name__ = "foo";
+                    globalThis.fetch = () => Promise.resolve({{ ok: true, json: () => ({index}) }});
+                    new Promise((resolve) => {{
+                        let results = fastn.mutableList([]);
+                        results.addClosure({{
+                            update: () => resolve(JSON.stringify(results.getList().map(
+                                (r) => fastn_utils.getStaticValue(r.item.get("url")),
+                            ))),
+                        }});
+                        ftd.search({query}, results, "hi");
+                    }});
+                    "#,
+                    index = serde_json::to_string(&index).unwrap(),
+                    query = serde_json::json!(query),
+                )
+                .as_str(),
+            )
+            .unwrap();
+            serde_json::from_str(js.as_str()).unwrap()
+        };
+
+        assert_eq!(js("किताबें"), vec!["/hindi/"]);
+        for query in [
+            "किताबें",
+            "अच्छा",
+            "पढ़ना",
+            "fastn down",
+            "serve",
+            "constructor serve",
+        ] {
+            assert_eq!(js(query), urls(index.search(query)), "{query}");
+        }
+    }
+
+    #[test]
+    fn filter() {
+        let index = index().filter(|url| url != "/install/");
+        assert_eq!(urls(index.search("serve")), vec!["/serve/"]);
+        assert!(index.search("install").is_empty());
+        assert_eq!(index.terms["serve"], vec![(0, 7)]);
+    }
+
+    #[test]
+    fn serialize() {
+        let mut index = super::Index::default();
+        index.add("/".to_string(), "Hi".to_string(), "a hello world");
+        assert_eq!(
+            serde_json::to_string(&index).unwrap(),
+            r#"{"documents":[["/","Hi"]],"terms":{"hello":[[0,1]],"hi":[[0,5]],"world":[[0,1]]}}"#
+        );
+    }
+}
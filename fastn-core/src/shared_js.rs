@@ -0,0 +1,124 @@
+//! The javascript every page shares, as content-hashed files the browser caches for good: the
+//! builtins of `ftd` with the `fastn-js` runtime, see `fastn_core::utils::hashed_default_ftd_js`,
+//! and the things of each module a document imports. A page inlines only the javascript of its
+//! own document.
+//!
+//! The module files are written to the build directory, like the pages of `fastn build`, so they
+//! outlive the server that rendered the page using them. Only components and functions go in
+//! them, see `ftd::js::document_into_js_ast_by_module`, nothing that depends on the request. They
+//! are served to anyone, so only the modules everyone can read get one, the page inlines the
+//! others.
+
+static DEFAULT_BAG_JS: once_cell::sync::OnceCell<String> = once_cell::sync::OnceCell::new();
+
+/// `ftd::js::default_bag_into_js_ast` as javascript, compiled once. A server has one package, so
+/// like `fastn_core::utils::hashed_default_ftd_js` this ignores `package_name` after the first
+/// call.
+pub fn default_bag_js(package_name: &str) -> &'static str {
+    DEFAULT_BAG_JS.get_or_init(|| {
+        fastn_js::to_js(ftd::js::default_bag_into_js_ast().as_slice(), package_name)
+    })
+}
+
+static DEFAULT_FTD_JS: once_cell::sync::OnceCell<String> = once_cell::sync::OnceCell::new();
+
+/// The content of `fastn_core::utils::hashed_default_ftd_js`, what `ftd::js::all_js_without_test`
+/// returns.
+pub fn default_ftd_js(package_name: &str) -> &'static str {
+    DEFAULT_FTD_JS.get_or_init(|| {
+        format!(
+            "{}\n{}",
+            fastn_js::all_js_without_test(),
+            default_bag_js(package_name)
+        )
+    })
+}
+
+/// The names of the files of `modules`, the javascript of each module by its name, and the
+/// javascript of the modules not everyone can read, see `fastn_core::auth::access`. Writes the
+/// files that are not in the build directory yet.
+pub async fn module_files(
+    config: &fastn_core::Config,
+    modules: &[(String, String)],
+    session_id: &Option<String>,
+) -> fastn_core::Result<(Vec<String>, String)> {
+    let build_dir = config.build_dir();
+    let mut files = vec![];
+    let mut inlined = vec![];
+    for (module, js) in modules {
+        if !fastn_core::auth::access::is_public_module(&config.package, module) {
+            inlined.push(js.as_str());
+            continue;
+        }
+        let name = module_file_name(module, js.as_str());
+        fastn_core::utils::write(
+            &build_dir,
+            name.as_str(),
+            js.as_bytes(),
+            &config.ds,
+            session_id,
+        )
+        .await?;
+        files.push(name);
+    }
+    Ok((files, inlined.join("\n")))
+}
+
+/// Serves the module file `path` ends with, `None` if it is not the name of one in the build
+/// directory.
+pub async fn handle(
+    config: &fastn_core::Config,
+    path: &str,
+    session_id: &Option<String>,
+) -> Option<fastn_core::Result<fastn_core::http::Response>> {
+    let name = path.rsplit('/').next()?;
+    if !is_module_file_name(name) {
+        return None;
+    }
+    let js = config
+        .ds
+        .read_content(&config.build_dir().join(name), session_id)
+        .await
+        .ok()?;
+    Some(Ok(actix_web::HttpResponse::Ok()
+        .content_type(mime_guess::mime::TEXT_JAVASCRIPT)
+        .append_header(("Cache-Control", "public, max-age=31536000"))
+        .body(js)))
+}
+
+fn module_file_name(module: &str, js: &str) -> String {
+    let slug: String = module
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    format!(
+        "{}-{}.js",
+        slug.trim_matches('-'),
+        fastn_core::utils::generate_hash(js)
+    )
+}
+
+fn is_module_file_name(name: &str) -> bool {
+    name.strip_suffix(".js")
+        .and_then(|stem| stem.rsplit_once('-'))
+        .is_some_and(|(_, hash)| hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn module_file_name() {
+        let name = super::module_file_name("fastn-community.github.io/doc-site/lib", "let a = 1;");
+        assert!(name.starts_with("fastn-community-github-io-doc-site-lib-"));
+        assert!(name.ends_with(".js"));
+        assert_ne!(
+            name,
+            super::module_file_name("fastn-community.github.io/doc-site/lib", "let a = 2;")
+        );
+        assert!(super::is_module_file_name(name.as_str()));
+        assert!(!super::is_module_file_name(
+            "fastn-community-github-io-doc-site-lib.js"
+        ));
+        assert!(!super::is_module_file_name("index.html"));
+    }
+}
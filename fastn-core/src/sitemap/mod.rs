@@ -17,6 +17,7 @@ pub mod dynamic_urls;
 pub mod section;
 pub mod toc;
 pub mod utils;
+pub mod xml;
 
 pub use dynamic_urls::{DynamicUrls, DynamicUrlsTemp};
 
@@ -1488,3 +1489,183 @@ pub fn resolve(
 
     Ok((None, vec![], Default::default()))
 }
+
+/// The `readers` of the sitemap or dynamic urls item of `path`, or of its closest parent with
+/// `readers`, or of `-- fastn.sitemap:` itself. Anyone can read `path` if there are none.
+pub fn readers(package: &fastn_core::Package, path: &str) -> Vec<String> {
+    readers_items(package)
+        .into_iter()
+        .find(|item| {
+            item.id
+                .is_some_and(|id| item_matches(id, item.path_parameters, path))
+        })
+        .map(|item| item.readers)
+        .unwrap_or_else(|| sitemap_readers(package))
+        .to_vec()
+}
+
+/// The `readers` of every sitemap and dynamic urls item with `document: <document>`, `document`
+/// is the path of the file in the package, like `guide/install.ftd`. The document can be read
+/// at its own url too, it has to be protected by all of them.
+pub fn document_readers(package: &fastn_core::Package, document: &str) -> Vec<Vec<String>> {
+    let document = document.trim_start_matches('/');
+    readers_items(package)
+        .into_iter()
+        .filter(|item| {
+            !item.readers.is_empty()
+                && item
+                    .document
+                    .is_some_and(|d| d.trim_start_matches('/') == document)
+        })
+        .map(|item| item.readers.to_vec())
+        .collect()
+}
+
+fn sitemap_readers(package: &fastn_core::Package) -> &[String] {
+    package
+        .sitemap
+        .as_ref()
+        .map(|sitemap| sitemap.readers.as_slice())
+        .unwrap_or_default()
+}
+
+/// A sitemap or dynamic urls item with the `readers` it has or inherits.
+struct ReadersItem<'a> {
+    id: Option<&'a str>,
+    path_parameters: &'a [PathParams],
+    document: Option<&'a str>,
+    readers: &'a [String],
+}
+
+/// Every item of the sitemap, then of the dynamic urls, parents before their children.
+fn readers_items(package: &fastn_core::Package) -> Vec<ReadersItem<'_>> {
+    let inherited = sitemap_readers(package);
+    let mut items = vec![];
+    for sections in [
+        package.sitemap.as_ref().map(|s| s.sections.as_slice()),
+        package.dynamic_urls.as_ref().map(|u| u.sections.as_slice()),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        for section in sections {
+            let readers = own_or_inherited(&section.readers, inherited);
+            items.push(ReadersItem {
+                id: Some(section.id.as_str()),
+                path_parameters: &section.path_parameters,
+                document: section.document.as_deref(),
+                readers,
+            });
+
+            for subsection in section.subsections.iter() {
+                let readers = own_or_inherited(&subsection.readers, readers);
+                items.push(ReadersItem {
+                    id: subsection.id.as_deref(),
+                    path_parameters: &subsection.path_parameters,
+                    document: subsection.document.as_deref(),
+                    readers,
+                });
+                toc_readers_items(&subsection.toc, readers, &mut items);
+            }
+        }
+    }
+    items
+}
+
+fn toc_readers_items<'a>(
+    toc: &'a [toc::TocItem],
+    inherited: &'a [String],
+    items: &mut Vec<ReadersItem<'a>>,
+) {
+    for item in toc {
+        let readers = own_or_inherited(&item.readers, inherited);
+        items.push(ReadersItem {
+            id: Some(item.id.as_str()),
+            path_parameters: &item.path_parameters,
+            document: item.document.as_deref(),
+            readers,
+        });
+        toc_readers_items(&item.children, readers, items);
+    }
+}
+
+fn own_or_inherited<'a>(own: &'a [String], inherited: &'a [String]) -> &'a [String] {
+    if own.is_empty() {
+        inherited
+    } else {
+        own
+    }
+}
+
+fn item_matches(id: &str, path_parameters: &[PathParams], path: &str) -> bool {
+    if path_parameters.is_empty() {
+        fastn_core::utils::ids_matches(id, path)
+    } else {
+        fastn_core::sitemap::utils::url_match(path, path_parameters)
+            .map(|(matches, _)| matches)
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    fn readers(readers: &[&str]) -> Vec<String> {
+        readers.iter().map(|r| r.to_string()).collect()
+    }
+
+    #[test]
+    fn inherited_readers() {
+        let mut package = fastn_core::Package::new("example.com");
+        package.sitemap = Some(super::Sitemap {
+            sections: vec![
+                super::section::Section {
+                    id: "/".to_string(),
+                    ..Default::default()
+                },
+                super::section::Section {
+                    id: "/team/".to_string(),
+                    readers: readers(&["alice@example.com"]),
+                    subsections: vec![super::section::Subsection {
+                        id: Some("/team/notes/".to_string()),
+                        toc: vec![
+                            super::toc::TocItem {
+                                id: "/team/notes/private/".to_string(),
+                                document: Some("notes/private.ftd".to_string()),
+                                ..Default::default()
+                            },
+                            super::toc::TocItem {
+                                id: "/team/notes/public/".to_string(),
+                                readers: readers(&["everyone"]),
+                                ..Default::default()
+                            },
+                        ],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            ],
+            readers: vec![],
+            writers: vec![],
+        });
+
+        assert!(super::readers(&package, "/").is_empty());
+        assert!(super::readers(&package, "/not-in-sitemap/").is_empty());
+        assert_eq!(
+            super::readers(&package, "/team/"),
+            readers(&["alice@example.com"])
+        );
+        assert_eq!(
+            super::readers(&package, "/team/notes/private/"),
+            readers(&["alice@example.com"])
+        );
+        assert_eq!(
+            super::readers(&package, "/team/notes/public/"),
+            readers(&["everyone"])
+        );
+        assert_eq!(
+            super::document_readers(&package, "notes/private.ftd"),
+            vec![readers(&["alice@example.com"])]
+        );
+        assert!(super::document_readers(&package, "index.ftd").is_empty());
+    }
+}
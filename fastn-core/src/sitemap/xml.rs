@@ -0,0 +1,339 @@
+//! `sitemap.xml` and `robots.txt` for search engines. `fastn build` writes both into `.build`,
+//! `fastn serve` generates them on request. A package that ships its own `sitemap.xml` or
+//! `robots.txt` keeps it.
+//!
+//! The urls come from `-- fastn.sitemap:`, or from every document in the package if there is
+//! no sitemap. Urls not everyone can read (see `fastn_core::auth::access`) and dynamic urls are
+//! left out. If the package has translations, every url lists the same page in each
+//! translation as an `hreflang` alternate.
+
+pub const SITEMAP: &str = "sitemap.xml";
+pub const ROBOTS: &str = "robots.txt";
+
+const DEFAULT_ROBOTS: &str = "User-agent: *\nAllow: /";
+
+/// Writes `sitemap.xml` and `robots.txt` into `build_dir`.
+pub async fn write(
+    config: &fastn_core::Config,
+    build_dir: &fastn_ds::Path,
+    session_id: &Option<String>,
+) -> fastn_core::Result<()> {
+    for (name, content) in [
+        (SITEMAP, sitemap_xml(config, session_id).await?),
+        (ROBOTS, robots_txt(&config.package)),
+    ] {
+        if config
+            .ds
+            .exists(&config.ds.root().join(name), session_id)
+            .await
+        {
+            continue;
+        }
+        println!("Processing {}/{name} ... ", config.package.name.as_str());
+        fastn_core::utils::update(&build_dir.join(name), content.as_bytes(), &config.ds).await?;
+    }
+
+    Ok(())
+}
+
+/// Responds to `/sitemap.xml` and `/robots.txt` in `fastn serve`, returns `None` for any other
+/// path or if the package has its own file.
+pub async fn handle(
+    config: &fastn_core::Config,
+    path: &str,
+    session_id: &Option<String>,
+) -> Option<fastn_core::Result<fastn_core::http::Response>> {
+    let name = path.strip_prefix('/')?;
+    if ![SITEMAP, ROBOTS].contains(&name)
+        || config
+            .ds
+            .exists(&config.ds.root().join(name), session_id)
+            .await
+    {
+        return None;
+    }
+
+    Some(if name == SITEMAP {
+        sitemap_xml(config, session_id).await.map(|xml| {
+            fastn_core::http::ok_with_content_type(xml.into_bytes(), mime_guess::mime::TEXT_XML)
+        })
+    } else {
+        Ok(fastn_core::http::ok_with_content_type(
+            robots_txt(&config.package).into_bytes(),
+            mime_guess::mime::TEXT_PLAIN_UTF_8,
+        ))
+    })
+}
+
+pub async fn sitemap_xml(
+    config: &fastn_core::Config,
+    session_id: &Option<String>,
+) -> fastn_core::Result<String> {
+    let mut urls = urls(config, session_id).await?;
+    urls.retain(|url, _| fastn_core::auth::access::is_public(&config.package, url));
+    let alternates = alternates(config, session_id).await;
+    let base = base_url(&config.package);
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\" \
+        xmlns:xhtml=\"http://www.w3.org/1999/xhtml\">\n",
+    );
+    for (path, file) in urls {
+        xml.push_str("  <url>\n");
+        xml.push_str(format!("    <loc>{}</loc>\n", escape(&format!("{base}{path}"))).as_str());
+        if let Some(file) = file {
+            if let Ok(modified) = config.ds.modified(&file, session_id).await {
+                let modified: chrono::DateTime<chrono::Utc> = modified.into();
+                xml.push_str(
+                    format!("    <lastmod>{}</lastmod>\n", modified.format("%Y-%m-%d")).as_str(),
+                );
+            }
+        }
+        for (language, base) in alternates.iter() {
+            xml.push_str(
+                format!(
+                    "    <xhtml:link rel=\"alternate\" hreflang=\"{}\" href=\"{}\"/>\n",
+                    escape(language),
+                    escape(&format!("{base}{path}"))
+                )
+                .as_str(),
+            );
+        }
+        xml.push_str("  </url>\n");
+    }
+    xml.push_str("</urlset>\n");
+
+    Ok(xml)
+}
+
+/// `-- fastn.robots:` (or a robots.txt allowing everything) with a `Sitemap:` line.
+pub fn robots_txt(package: &fastn_core::Package) -> String {
+    format!(
+        "{}\n\nSitemap: {}{SITEMAP}\n",
+        package
+            .robots
+            .as_deref()
+            .unwrap_or(DEFAULT_ROBOTS)
+            .trim_end(),
+        base_url(package)
+    )
+}
+
+/// url path (`foo/bar/`) -> the file it is rendered from
+pub(crate) type Urls = std::collections::BTreeMap<String, Option<fastn_ds::Path>>;
+
+/// Every url of the package, also the ones with `readers`.
+pub(crate) async fn urls(
+    config: &fastn_core::Config,
+    session_id: &Option<String>,
+) -> fastn_core::Result<Urls> {
+    let mut urls = Urls::default();
+    match config.package.sitemap.as_ref() {
+        Some(sitemap) => {
+            for section in sitemap.sections.iter() {
+                add_section_urls(&mut urls, section);
+            }
+        }
+        None => {
+            for file in config.get_files(&config.package, session_id).await? {
+                if let fastn_core::File::Ftd(doc) = file {
+                    if doc.id == "FASTN.ftd" {
+                        continue;
+                    }
+                    urls.insert(
+                        url_path(doc.id_to_path().as_str()),
+                        Some(doc.get_full_path()),
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(urls)
+}
+
+/// The document every url is rendered from: its file, or, as `fastn serve` does not resolve
+/// the files of the sitemap, its `document:` or the document with the same path.
+pub(crate) async fn documents(
+    config: &fastn_core::Config,
+    urls: Urls,
+    session_id: &Option<String>,
+) -> fastn_core::Result<Vec<(String, fastn_core::Document)>> {
+    let mut documents: std::collections::HashMap<String, fastn_core::Document> = config
+        .get_files(&config.package, session_id)
+        .await?
+        .into_iter()
+        .filter_map(|file| file.get_ftd_document())
+        .map(|doc| (doc.get_full_path().to_string(), doc))
+        .collect();
+    // document id and url path -> file
+    let mut files: std::collections::HashMap<String, String> = Default::default();
+    for (file, doc) in documents.iter() {
+        files.insert(doc.id.clone(), file.clone());
+        files.insert(url_path(doc.id_to_path().as_str()), file.clone());
+    }
+
+    Ok(urls
+        .into_iter()
+        .filter_map(|(url, file)| {
+            let file = match file {
+                Some(file) => file.to_string(),
+                None => config
+                    .package
+                    .sitemap
+                    .as_ref()
+                    .and_then(|sitemap| sitemap.resolve_document(format!("/{url}").as_str()))
+                    .and_then(|(document, _)| files.get(document.trim_start_matches('/')))
+                    .or_else(|| files.get(&url))?
+                    .clone(),
+            };
+            documents.remove(&file).map(|doc| (url, doc))
+        })
+        .collect())
+}
+
+pub(crate) fn add_section_urls(urls: &mut Urls, section: &fastn_core::sitemap::section::Section) {
+    add_url(
+        urls,
+        section.id.as_str(),
+        &section.file_location,
+        &section.path_parameters,
+    );
+    for subsection in section.subsections.iter() {
+        if let Some(id) = subsection.id.as_ref() {
+            add_url(
+                urls,
+                id,
+                &subsection.file_location,
+                &subsection.path_parameters,
+            );
+        }
+        add_toc_urls(urls, &subsection.toc);
+    }
+}
+
+fn add_toc_urls(urls: &mut Urls, toc: &[fastn_core::sitemap::toc::TocItem]) {
+    for item in toc {
+        add_url(
+            urls,
+            item.id.as_str(),
+            &item.file_location,
+            &item.path_parameters,
+        );
+        add_toc_urls(urls, &item.children);
+    }
+}
+
+fn add_url(
+    urls: &mut Urls,
+    id: &str,
+    file_location: &Option<fastn_ds::Path>,
+    path_parameters: &[fastn_core::sitemap::PathParams],
+) {
+    // external links and dynamic documents
+    if id.contains("://") || !path_parameters.is_empty() {
+        return;
+    }
+
+    urls.entry(url_path(id))
+        .or_insert_with(|| file_location.clone());
+}
+
+/// `/foo/bar` -> `foo/bar/`, `/` -> ``
+pub(crate) fn url_path(id: &str) -> String {
+    let id = id.trim_matches('/');
+    if id.is_empty() || id.ends_with(".html") {
+        id.to_string()
+    } else {
+        format!("{id}/")
+    }
+}
+
+/// The `canonical-url` of the package, or `https://<package-name>/`.
+pub(crate) fn base_url(package: &fastn_core::Package) -> String {
+    let base = package
+        .canonical_url
+        .clone()
+        .unwrap_or_else(|| format!("https://{}", package.name));
+    format!("{}/", base.trim_end_matches('/'))
+}
+
+/// (language, base url) of this package and its translations, empty if there are no
+/// translations. Translations are read from `.packages`, ones that are not downloaded or do not
+/// declare their `language` are skipped.
+async fn alternates(
+    config: &fastn_core::Config,
+    session_id: &Option<String>,
+) -> Vec<(String, String)> {
+    if config.package.translations.is_empty() {
+        return vec![];
+    }
+
+    let mut alternates = vec![];
+    if let Some(language) = config.package.language.as_ref() {
+        alternates.push((language.to_string(), base_url(&config.package)));
+    }
+
+    for translation in config.package.translations.iter() {
+        let root = config
+            .ds
+            .root()
+            .join(".packages")
+            .join(translation.name.as_str());
+        match translation
+            .get_and_resolve(&root, &config.ds, session_id)
+            .await
+        {
+            Ok(translation) => match translation.language.as_ref() {
+                Some(language) => alternates.push((language.to_string(), base_url(&translation))),
+                None => tracing::warn!(
+                    msg = "translation has no language, skipping in sitemap.xml",
+                    translation = translation.name
+                ),
+            },
+            Err(e) => tracing::warn!(
+                msg = "failed to read translation, skipping in sitemap.xml",
+                translation = translation.name,
+                error = e.to_string()
+            ),
+        }
+    }
+
+    alternates
+}
+
+pub(crate) fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn url_path() {
+        assert_eq!(super::url_path("/"), "");
+        assert_eq!(super::url_path("/foo/bar"), "foo/bar/");
+        assert_eq!(super::url_path("foo/"), "foo/");
+        assert_eq!(super::url_path("foo/bar.html"), "foo/bar.html");
+    }
+
+    #[test]
+    fn robots_txt() {
+        let mut package = fastn_core::Package::new("foo.com");
+        assert_eq!(
+            super::robots_txt(&package),
+            "User-agent: *\nAllow: /\n\nSitemap: https://foo.com/sitemap.xml\n"
+        );
+
+        package.canonical_url = Some("https://www.foo.com/".to_string());
+        package.robots = Some("User-agent: *\nDisallow: /drafts/\n".to_string());
+        assert_eq!(
+            super::robots_txt(&package),
+            "User-agent: *\nDisallow: /drafts/\n\nSitemap: https://www.foo.com/sitemap.xml\n"
+        );
+    }
+}
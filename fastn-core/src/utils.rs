@@ -29,6 +29,30 @@ macro_rules! warning {
     }};
 }
 
+/// `println!` for progress messages, they go to stderr once `progress_to_stderr` is called.
+#[macro_export]
+macro_rules! progress {
+    ($($t:tt)*) => {{
+        if $crate::utils::is_progress_on_stderr() {
+            eprintln!($($t)*);
+        } else {
+            println!($($t)*);
+        }
+    }};
+}
+
+static PROGRESS_ON_STDERR: std::sync::atomic::AtomicBool =
+    std::sync::atomic::AtomicBool::new(false);
+
+/// For commands whose stdout is a report other programs read, like `fastn test --format json`.
+pub fn progress_to_stderr() {
+    PROGRESS_ON_STDERR.store(true, std::sync::atomic::Ordering::Relaxed);
+}
+
+pub fn is_progress_on_stderr() -> bool {
+    PROGRESS_ON_STDERR.load(std::sync::atomic::Ordering::Relaxed)
+}
+
 fn id_to_cache_key(id: &str) -> String {
     // TODO: use MAIN_SEPARATOR here
     id.replace(['/', '\\'], "_")
@@ -716,8 +740,21 @@ pub fn get_fastn_package_data(package: &fastn_core::Package) -> String {
     format!(
         indoc::indoc! {"
         let __fastn_package_name__ = \"{package_name}\";
+        {language}
     "},
-        package_name = package.name
+        package_name = package.name,
+        language = get_fastn_language_data(package),
+    )
+}
+
+/// The language and the offset from UTC, in minutes, `ftd.format_date` and the other date
+/// functions of `ftd.js` format in, see `fastn_core::Package::datetime_settings`.
+pub fn get_fastn_language_data(package: &fastn_core::Package) -> String {
+    let settings = package.datetime_settings();
+    format!(
+        "let __fastn_language__ = {};\nlet __fastn_utc_offset__ = {};",
+        serde_json::to_string(settings.language.as_deref().unwrap_or("en")).unwrap(),
+        settings.utc_offset,
     )
 }
 
@@ -874,6 +911,25 @@ pub(crate) fn ids_matches(id1: &str, id2: &str) -> bool {
     }
 }
 
+/// `*` matches within a path component, `**` across them, `?` any one character but `/`.
+pub(crate) fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', '/', rest @ ..] => {
+            glob_match(rest, text)
+                || (0..text.len()).any(|i| text[i] == '/' && glob_match(rest, &text[i + 1..]))
+        }
+        ['*', '*', rest @ ..] => (0..=text.len()).any(|i| glob_match(rest, &text[i..])),
+        ['*', rest @ ..] => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != '/')
+            .any(|i| glob_match(rest, &text[i..])),
+        ['?', rest @ ..] => {
+            matches!(text.first(), Some(c) if *c != '/') && glob_match(rest, &text[1..])
+        }
+        [c, rest @ ..] => text.first() == Some(c) && glob_match(rest, &text[1..]),
+    }
+}
+
 /// Parse argument from CLI
 /// If CLI command: fastn serve --identities a@foo.com,foo
 /// key: --identities -> output: a@foo.com,foo
@@ -951,7 +1007,7 @@ pub fn hashed_default_ftd_js(package_name: &str) -> &'static str {
     FTD_JS_HASH.get_or_init(|| {
         format!(
             "default-{}.js",
-            generate_hash(ftd::js::all_js_without_test(package_name).as_str())
+            generate_hash(fastn_core::shared_js::default_ftd_js(package_name))
         )
     })
 }
@@ -0,0 +1,210 @@
+//! Canned responses for `DocumentStore::http`, registered by `-- fastn.mock:` in `fastn test`.
+//!
+//! Once a mock is registered, every request `DocumentStore::http` makes is answered by the first
+//! mock that matches it, and a request no mock matches is an error instead of reaching the
+//! network. Without any mock, requests go out as usual.
+
+#[derive(Debug, Clone)]
+pub struct Mock {
+    /// Any method if `None`.
+    pub method: Option<String>,
+    /// `*` matches any number of characters. The query string is only matched if the pattern has
+    /// one.
+    pub url: String,
+    /// A JSON body matches requests whose JSON body has the same values for all its keys, any
+    /// other body matches requests that contain it.
+    pub body: Option<String>,
+    pub status: u16,
+    pub content_type: String,
+    pub response: bytes::Bytes,
+    /// Number of requests the mock must answer by the end of the test.
+    pub times: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct HttpMock {
+    /// The mocks, with the number of requests each one answered.
+    mocks: std::sync::Arc<std::sync::Mutex<Vec<(Mock, usize)>>>,
+}
+
+impl HttpMock {
+    pub fn add(&self, mock: Mock) {
+        self.mocks.lock().unwrap().push((mock, 0));
+    }
+
+    /// `None` if there is no mock, the request goes to the network then.
+    pub fn respond(
+        &self,
+        method: &str,
+        url: &str,
+        body: &[u8],
+    ) -> Option<Result<fastn_ds::HttpResponse, fastn_ds::HttpError>> {
+        let mut mocks = self.mocks.lock().unwrap();
+        if mocks.is_empty() {
+            return None;
+        }
+
+        let Some((mock, calls)) = mocks
+            .iter_mut()
+            .find(|(mock, _)| mock.matches(method, url, body))
+        else {
+            return Some(Err(fastn_ds::HttpError::NoMock {
+                method: method.to_string(),
+                url: url.to_string(),
+            }));
+        };
+        *calls += 1;
+
+        Some(
+            ::http::Response::builder()
+                .status(mock.status)
+                .header(::http::header::CONTENT_TYPE, mock.content_type.as_str())
+                .body(mock.response.clone())
+                .map_err(|e| fastn_ds::HttpError::GenericError {
+                    message: format!("invalid fastn.mock for {}: {e}", mock.url),
+                }),
+        )
+    }
+
+    /// (mock, expected calls, calls) of every mock that has `times`.
+    pub fn expectations(&self) -> Vec<(Mock, usize, usize)> {
+        self.mocks
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(mock, calls)| mock.times.map(|times| (mock.clone(), times, *calls)))
+            .collect()
+    }
+}
+
+impl Mock {
+    fn matches(&self, method: &str, url: &str, body: &[u8]) -> bool {
+        if let Some(m) = self.method.as_ref() {
+            if !m.eq_ignore_ascii_case(method) {
+                return false;
+            }
+        }
+
+        // `DocumentStore::http` sends `<url>/?<query>`
+        let url = url.replacen("/?", "?", 1);
+        let url = if self.url.contains('?') {
+            url.as_str()
+        } else {
+            url.split_once('?').map_or(url.as_str(), |(url, _)| url)
+        };
+        if !wildcard_match(self.url.trim_end_matches('/'), url.trim_end_matches('/')) {
+            return false;
+        }
+
+        match self.body.as_ref() {
+            None => true,
+            Some(expected) => {
+                let body = String::from_utf8_lossy(body);
+                match (
+                    serde_json::from_str::<serde_json::Value>(expected),
+                    serde_json::from_str::<serde_json::Value>(&body),
+                ) {
+                    (Ok(expected), Ok(body)) => json_contains(&body, &expected),
+                    _ => body.contains(expected.as_str()),
+                }
+            }
+        }
+    }
+}
+
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // no `*` in the pattern
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// Objects in `expected` can leave out keys, anything else has to be equal.
+fn json_contains(value: &serde_json::Value, expected: &serde_json::Value) -> bool {
+    match (value, expected) {
+        (serde_json::Value::Object(value), serde_json::Value::Object(expected)) => expected
+            .iter()
+            .all(|(k, e)| value.get(k).map(|v| json_contains(v, e)).unwrap_or(false)),
+        _ => value == expected,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    fn mock(method: Option<&str>, url: &str, body: Option<&str>) -> super::Mock {
+        super::Mock {
+            method: method.map(ToString::to_string),
+            url: url.to_string(),
+            body: body.map(ToString::to_string),
+            status: 200,
+            content_type: "application/json".to_string(),
+            response: "{}".into(),
+            times: None,
+        }
+    }
+
+    #[test]
+    fn matches() {
+        let users = mock(Some("get"), "https://api.example.com/users/*", None);
+        assert!(users.matches("GET", "https://api.example.com/users/1", b""));
+        assert!(users.matches("GET", "https://api.example.com/users/1?page=2", b""));
+        assert!(!users.matches("POST", "https://api.example.com/users/1", b""));
+        assert!(!users.matches("GET", "https://api.example.com/teams/1", b""));
+
+        let page = mock(None, "https://api.example.com/users?page=*", None);
+        assert!(page.matches("GET", "https://api.example.com/users/?page=2", b""));
+        assert!(!page.matches("GET", "https://api.example.com/users", b""));
+
+        let create = mock(
+            None,
+            "https://api.example.com/users",
+            Some(r#"{"name": "a"}"#),
+        );
+        assert!(create.matches(
+            "POST",
+            "https://api.example.com/users/",
+            br#"{"name": "a", "age": 1}"#
+        ));
+        assert!(!create.matches("POST", "https://api.example.com/users", br#"{"name": "b"}"#));
+
+        let text = mock(None, "https://api.example.com/*", Some("hello"));
+        assert!(text.matches("POST", "https://api.example.com/echo", b"say hello world"));
+    }
+
+    #[test]
+    fn respond() {
+        let http_mock = super::HttpMock::default();
+        assert!(http_mock
+            .respond("GET", "https://example.com", b"")
+            .is_none());
+
+        http_mock.add(super::Mock {
+            times: Some(2),
+            ..mock(None, "https://example.com", None)
+        });
+        assert!(http_mock
+            .respond("GET", "https://example.com", b"")
+            .unwrap()
+            .is_ok());
+        assert!(http_mock
+            .respond("GET", "https://example.org", b"")
+            .unwrap()
+            .is_err());
+        assert_eq!(http_mock.expectations()[0].1, 2);
+        assert_eq!(http_mock.expectations()[0].2, 1);
+    }
+}
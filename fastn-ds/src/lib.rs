@@ -3,6 +3,8 @@
 
 extern crate self as fastn_ds;
 pub mod http;
+pub mod http_mock;
+mod pg;
 pub mod reqwest_util;
 mod user_data;
 mod utils;
@@ -12,6 +14,8 @@ pub use user_data::UserDataError;
 pub struct DocumentStore {
     pub wasm_modules: scc::HashMap<String, wasmtime::Module>,
     pub pg_pools: actix_web::web::Data<scc::HashMap<String, deadpool_postgres::Pool>>,
+    /// Set by `fastn test`, see `fastn_ds::http_mock`
+    pub http_mock: Option<fastn_ds::http_mock::HttpMock>,
     root: Path,
 }
 
@@ -80,6 +84,25 @@ fn package_ignores(
     overrides.build()
 }
 
+/// If `DocumentStore::get_all_file_path` skips the file at `path`, relative to the directory it
+/// walks, because of `ignore_paths`. The file is skipped if any of its parent directories is.
+pub fn is_ignored(path: &str, ignore_paths: &[String]) -> bool {
+    let Ok(overrides) = package_ignores(ignore_paths, &camino::Utf8PathBuf::new()) else {
+        return false;
+    };
+
+    let mut prefix = camino::Utf8PathBuf::new();
+    let mut parts = path.split('/').peekable();
+    while let Some(part) = parts.next() {
+        prefix.push(part);
+        let is_dir = parts.peek().is_some();
+        if overrides.matched(prefix.as_std_path(), is_dir).is_ignore() {
+            return true;
+        }
+    }
+    false
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum RemoveError {
     #[error("io error {0}")]
@@ -142,6 +165,8 @@ pub enum HttpError {
     CreatePoolError(#[from] CreatePoolError),
     #[error("sql error {0}")]
     SqlError(#[from] fastn_utils::SqlError),
+    #[error("no fastn.mock matches {method} {url}")]
+    NoMock { method: String, url: String },
 }
 
 pub type HttpResponse = ::http::Response<bytes::Bytes>;
@@ -205,10 +230,16 @@ impl DocumentStore {
         Self {
             wasm_modules: Default::default(),
             pg_pools,
+            http_mock: None,
             root: Path::new(root.as_ref().as_str()),
         }
     }
 
+    pub fn add_http_mock(mut self, http_mock: fastn_ds::http_mock::HttpMock) -> Self {
+        self.http_mock = Some(http_mock);
+        self
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn get_wasm(
         &self,
@@ -252,6 +283,10 @@ impl DocumentStore {
         query: &str,
         params: Vec<ft_sys_shared::SqliteRawValue>,
     ) -> Result<Vec<Vec<serde_json::Value>>, fastn_utils::SqlError> {
+        if fastn_utils::sql::is_postgres_url(db_url) {
+            return self.pg_query(db_url, query, params).await;
+        }
+
         let db_path = initialize_sqlite_db(db_url).await?;
         let conn = rusqlite::Connection::open_with_flags(
             db_path,
@@ -273,6 +308,10 @@ impl DocumentStore {
         query: &str,
         params: Vec<ft_sys_shared::SqliteRawValue>,
     ) -> Result<Vec<Vec<serde_json::Value>>, fastn_utils::SqlError> {
+        if fastn_utils::sql::is_postgres_url(db_url) {
+            return self.pg_execute(db_url, query, params).await;
+        }
+
         let db_path = initialize_sqlite_db(db_url).await?;
         let conn = rusqlite::Connection::open_with_flags(
             db_path,
@@ -290,6 +329,10 @@ impl DocumentStore {
         db_url: &str,
         query: &str,
     ) -> Result<Vec<Vec<serde_json::Value>>, fastn_utils::SqlError> {
+        if fastn_utils::sql::is_postgres_url(db_url) {
+            return self.pg_batch(db_url, query).await;
+        }
+
         let db_path = initialize_sqlite_db(db_url).await?;
         let conn = rusqlite::Connection::open_with_flags(
             db_path,
@@ -344,6 +387,24 @@ impl DocumentStore {
         Ok(contents)
     }
 
+    /// Last modification time of the file at `path`.
+    pub async fn modified(
+        &self,
+        path: &fastn_ds::Path,
+        _session_id: &Option<String>,
+    ) -> Result<std::time::SystemTime, ReadError> {
+        tokio::fs::metadata(self.root.join(&path.path).path)
+            .await
+            .and_then(|m| m.modified())
+            .map_err(|e| {
+                if e.kind() == std::io::ErrorKind::NotFound {
+                    ReadError::NotFound(path.to_string())
+                } else {
+                    ReadError::IOError(e, path.to_string())
+                }
+            })
+    }
+
     #[tracing::instrument]
     pub async fn read_to_string(
         &self,
@@ -361,6 +422,12 @@ impl DocumentStore {
     pub async fn copy(&self, from: &fastn_ds::Path, to: &fastn_ds::Path) -> Result<(), WriteError> {
         tracing::debug!("copy from {} to {}", from, to);
 
+        if let Some(parent) = to.path.parent() {
+            if !parent.exists() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+        }
+
         tokio::fs::copy(&from.path, &to.path).await?;
         Ok(())
     }
@@ -403,7 +470,17 @@ impl DocumentStore {
         Ok(tokio::fs::rename(&from.path, &to.path).await?)
     }
 
+    /// Removes the file or the directory at `path`, or the link if it is a symbolic link.
     pub async fn remove(&self, path: &fastn_ds::Path) -> Result<(), RemoveError> {
+        if path.path.is_symlink() {
+            #[cfg(windows)]
+            if path.path.is_dir() {
+                tokio::fs::remove_dir(&path.path).await?;
+                return Ok(());
+            }
+            tokio::fs::remove_file(&path.path).await?;
+            return Ok(());
+        }
         if !path.path.exists() {
             return Ok(());
         }
@@ -411,12 +488,39 @@ impl DocumentStore {
             tokio::fs::remove_file(&path.path).await?;
         } else if path.path.is_dir() {
             tokio::fs::remove_dir_all(&path.path).await?
-        } else if path.path.is_symlink() {
-            todo!("symlinks are not handled yet")
         }
         Ok(())
     }
 
+    /// Creates `link`, a symbolic link to the directory `target`.
+    pub async fn symlink_dir(
+        &self,
+        target: &fastn_ds::Path,
+        link: &fastn_ds::Path,
+    ) -> Result<(), WriteError> {
+        tracing::debug!("symlink from {} to {}", link, target);
+
+        if let Some(parent) = link.path.parent() {
+            if !parent.exists() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+        }
+
+        #[cfg(unix)]
+        tokio::fs::symlink(&target.path, &link.path).await?;
+        #[cfg(windows)]
+        tokio::fs::symlink_dir(&target.path, &link.path).await?;
+        Ok(())
+    }
+
+    /// Where `path` points to, if it is a symbolic link.
+    pub async fn read_link(&self, path: &fastn_ds::Path) -> Option<fastn_ds::Path> {
+        let target = tokio::fs::read_link(&path.path).await.ok()?;
+        camino::Utf8PathBuf::from_path_buf(target)
+            .ok()
+            .map(|path| fastn_ds::Path { path })
+    }
+
     pub async fn get_all_file_path(
         &self,
         path: &fastn_ds::Path,
@@ -440,6 +544,67 @@ impl DocumentStore {
             .collect::<Vec<fastn_ds::Path>>()
     }
 
+    /// Polls all files under `path` (skipping `ignore_paths`) every `interval`, and sends the
+    /// paths, relative to the root, that were created, modified or deleted since the last poll.
+    /// The watcher stops once the returned receiver is dropped.
+    pub fn watch(
+        &self,
+        path: &fastn_ds::Path,
+        ignore_paths: Vec<String>,
+        interval: std::time::Duration,
+    ) -> tokio::sync::mpsc::Receiver<Vec<fastn_ds::Path>> {
+        let (sender, receiver) = tokio::sync::mpsc::channel(16);
+        let ds = self.clone();
+        let path = path.clone();
+
+        tokio::spawn(async move {
+            let mut previous = ds.modification_times(&path, &ignore_paths).await;
+            loop {
+                tokio::time::sleep(interval).await;
+                let current = ds.modification_times(&path, &ignore_paths).await;
+
+                let changed: Vec<fastn_ds::Path> = current
+                    .iter()
+                    .filter(|(path, modified)| previous.get(*path) != Some(*modified))
+                    .chain(
+                        previous
+                            .iter()
+                            .filter(|(path, _)| !current.contains_key(*path)),
+                    )
+                    .filter_map(|(path, _)| {
+                        fastn_ds::Path { path: path.clone() }.strip_prefix(&ds.root)
+                    })
+                    .collect();
+                previous = current;
+
+                if changed.is_empty() {
+                    continue;
+                }
+
+                if sender.send(changed).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        receiver
+    }
+
+    async fn modification_times(
+        &self,
+        path: &fastn_ds::Path,
+        ignore_paths: &[String],
+    ) -> std::collections::HashMap<camino::Utf8PathBuf, std::time::SystemTime> {
+        self.get_all_file_path(path, ignore_paths)
+            .await
+            .into_iter()
+            .filter_map(|p| {
+                let modified = p.path.metadata().and_then(|m| m.modified()).ok()?;
+                Some((p.path, modified))
+            })
+            .collect()
+    }
+
     pub async fn exists(&self, path: &fastn_ds::Path, _session_id: &Option<String>) -> bool {
         path.path.exists()
     }
@@ -579,6 +744,17 @@ impl DocumentStore {
             proxy_request.headers_mut().remove(header);
         }
 
+        if let Some(response) = self.http_mock.as_ref().and_then(|http_mock| {
+            http_mock.respond(
+                proxy_request.method().as_str(),
+                proxy_request.url().as_str(),
+                req.body(),
+            )
+        }) {
+            tracing::info!(url = ?proxy_request.url(), "mocked");
+            return response;
+        }
+
         tracing::info!(
             url = ?proxy_request.url(),
             method = ?proxy_request.method(),
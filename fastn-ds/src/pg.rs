@@ -0,0 +1,201 @@
+//! `sql-query`, `sql-execute` and `sql-batch` against `postgres://` databases.
+
+type Param = Box<dyn tokio_postgres::types::ToSql + Sync + Send>;
+
+impl fastn_ds::DocumentStore {
+    /// The pool for `db_url`, created on first use and kept in `pg_pools`.
+    async fn pg_pool(
+        &self,
+        db_url: &str,
+    ) -> Result<deadpool_postgres::Pool, fastn_utils::SqlError> {
+        if let Some(p) = self.pg_pools.get(db_url) {
+            return Ok(p.get().clone());
+        }
+
+        let pool = fastn_wasm::pg::create_pool(db_url)
+            .await
+            .map_err(fastn_utils::SqlError::PgCreatePool)?;
+        fastn_wasm::insert_or_update(&self.pg_pools, db_url.to_string(), pool.clone());
+
+        Ok(pool)
+    }
+
+    pub(crate) async fn pg_query(
+        &self,
+        db_url: &str,
+        query: &str,
+        params: Vec<ft_sys_shared::SqliteRawValue>,
+    ) -> Result<Vec<Vec<serde_json::Value>>, fastn_utils::SqlError> {
+        let client = self.pg_client(db_url).await?;
+        let stmt = client
+            .prepare_cached(query)
+            .await
+            .map_err(fastn_utils::SqlError::Postgres)?;
+        let params = to_params(params, stmt.params())?;
+
+        let rows = client
+            .query(&stmt, &as_refs(&params))
+            .await
+            .map_err(fastn_utils::SqlError::Postgres)?;
+        fastn_utils::pg_rows_to_json(&rows)
+    }
+
+    pub(crate) async fn pg_execute(
+        &self,
+        db_url: &str,
+        query: &str,
+        params: Vec<ft_sys_shared::SqliteRawValue>,
+    ) -> Result<Vec<Vec<serde_json::Value>>, fastn_utils::SqlError> {
+        let client = self.pg_client(db_url).await?;
+        let stmt = client
+            .prepare_cached(query)
+            .await
+            .map_err(fastn_utils::SqlError::Postgres)?;
+        let params = to_params(params, stmt.params())?;
+
+        Ok(vec![vec![client
+            .execute(&stmt, &as_refs(&params))
+            .await
+            .map_err(fastn_utils::SqlError::Postgres)?
+            .into()]])
+    }
+
+    pub(crate) async fn pg_batch(
+        &self,
+        db_url: &str,
+        query: &str,
+    ) -> Result<Vec<Vec<serde_json::Value>>, fastn_utils::SqlError> {
+        self.pg_client(db_url)
+            .await?
+            .batch_execute(query)
+            .await
+            .map_err(fastn_utils::SqlError::Postgres)?;
+
+        // same as sqlite, see `sql_batch`
+        Ok(vec![vec![1.into()]])
+    }
+
+    async fn pg_client(
+        &self,
+        db_url: &str,
+    ) -> Result<deadpool_postgres::Object, fastn_utils::SqlError> {
+        self.pg_pool(db_url)
+            .await?
+            .get()
+            .await
+            .map_err(fastn_utils::SqlError::PgPool)
+    }
+}
+
+fn as_refs(params: &[Param]) -> Vec<&(dyn tokio_postgres::types::ToSql + Sync)> {
+    params
+        .iter()
+        .map(|p| p.as_ref() as &(dyn tokio_postgres::types::ToSql + Sync))
+        .collect()
+}
+
+/// Parameters are resolved from `ftd` values and processor headers, headers are always text, so
+/// they are converted to the types postgres inferred for the statement.
+fn to_params(
+    params: Vec<ft_sys_shared::SqliteRawValue>,
+    types: &[tokio_postgres::types::Type],
+) -> Result<Vec<Param>, fastn_utils::SqlError> {
+    params
+        .into_iter()
+        .zip(types)
+        .enumerate()
+        .map(|(i, (value, ty))| {
+            to_param(value, ty)
+                .map_err(|message| fastn_utils::SqlError::PgParam(i + 1, ty.clone(), message))
+        })
+        .collect()
+}
+
+fn to_param(
+    value: ft_sys_shared::SqliteRawValue,
+    ty: &tokio_postgres::types::Type,
+) -> Result<Param, String> {
+    use ft_sys_shared::SqliteRawValue as V;
+    use tokio_postgres::types::{ToSql, Type};
+
+    fn parse<T>(value: &str) -> Result<T, String>
+    where
+        T: std::str::FromStr,
+        T::Err: std::fmt::Display,
+    {
+        value.trim().parse().map_err(|e: T::Err| e.to_string())
+    }
+
+    fn int<T: TryFrom<i64>>(value: i64) -> Result<T, String> {
+        T::try_from(value).map_err(|_| format!("{value} is out of range"))
+    }
+
+    /// `value`, a `datetime` like `2024-01-02T03:04:05+00:00` or a `date` like `2024-01-02`, in UTC.
+    fn timestamp(value: &str) -> Result<chrono::NaiveDateTime, String> {
+        let value = value.trim();
+        value
+            .parse::<chrono::DateTime<chrono::Utc>>()
+            .map(|v| v.naive_utc())
+            .or_else(|_| value.parse::<chrono::NaiveDateTime>())
+            .or_else(|_| {
+                value
+                    .parse::<chrono::NaiveDate>()
+                    .map(|v| v.and_time(chrono::NaiveTime::MIN))
+            })
+            .map_err(|e| format!("{value} is not ISO 8601: {e}"))
+    }
+
+    Ok(match (ty, value) {
+        (_, V::Null) => Box::new(Null),
+        (&Type::BOOL, V::Integer(v)) => Box::new(v != 0),
+        (&Type::BOOL, V::Text(v)) => Box::new(parse::<bool>(&v)?),
+        (&Type::INT2, V::Integer(v)) => Box::new(int::<i16>(v)?),
+        (&Type::INT2, V::Text(v)) => Box::new(parse::<i16>(&v)?),
+        (&Type::INT4, V::Integer(v)) => Box::new(int::<i32>(v)?),
+        (&Type::INT4, V::Text(v)) => Box::new(parse::<i32>(&v)?),
+        (&Type::INT8, V::Integer(v)) => Box::new(v),
+        (&Type::INT8, V::Text(v)) => Box::new(parse::<i64>(&v)?),
+        (&Type::FLOAT4, V::Integer(v)) => Box::new(v as f32),
+        (&Type::FLOAT4, V::Real(v)) => Box::new(v as f32),
+        (&Type::FLOAT4, V::Text(v)) => Box::new(parse::<f32>(&v)?),
+        (&Type::FLOAT8, V::Integer(v)) => Box::new(v as f64),
+        (&Type::FLOAT8, V::Real(v)) => Box::new(v),
+        (&Type::FLOAT8, V::Text(v)) => Box::new(parse::<f64>(&v)?),
+        (&Type::JSON | &Type::JSONB, V::Integer(v)) => Box::new(serde_json::Value::from(v)),
+        (&Type::JSON | &Type::JSONB, V::Real(v)) => Box::new(serde_json::Value::from(v)),
+        (&Type::JSON | &Type::JSONB, V::Text(v)) => Box::new(
+            serde_json::from_str::<serde_json::Value>(&v).unwrap_or(serde_json::Value::String(v)),
+        ),
+        (&Type::BYTEA, V::Blob(v)) => Box::new(v),
+        (&Type::TIMESTAMP, V::Text(v)) => Box::new(timestamp(&v)?),
+        (&Type::TIMESTAMPTZ, V::Text(v)) => Box::new(timestamp(&v)?.and_utc()),
+        (&Type::DATE, V::Text(v)) => Box::new(timestamp(&v)?.date()),
+        (ty, V::Text(v)) if <String as ToSql>::accepts(ty) => Box::new(v),
+        (ty, V::Integer(v)) if <String as ToSql>::accepts(ty) => Box::new(v.to_string()),
+        (ty, V::Real(v)) if <String as ToSql>::accepts(ty) => Box::new(v.to_string()),
+        (_, V::Integer(_)) => return Err("found an integer".to_string()),
+        (_, V::Real(_)) => return Err("found a decimal".to_string()),
+        (_, V::Text(_)) => return Err("found a string".to_string()),
+        (_, V::Blob(_)) => return Err("found a blob".to_string()),
+    })
+}
+
+/// `NULL` for a parameter of any type.
+#[derive(Debug)]
+struct Null;
+
+impl tokio_postgres::types::ToSql for Null {
+    fn to_sql(
+        &self,
+        _ty: &tokio_postgres::types::Type,
+        _out: &mut bytes::BytesMut,
+    ) -> Result<tokio_postgres::types::IsNull, Box<dyn std::error::Error + Sync + Send>> {
+        Ok(tokio_postgres::types::IsNull::Yes)
+    }
+
+    fn accepts(_ty: &tokio_postgres::types::Type) -> bool {
+        true
+    }
+
+    tokio_postgres::types::to_sql_checked!();
+}
@@ -34,8 +34,18 @@ impl fastn_ds::DocumentStore {
             WHERE
                 fastn_session.id = $1
                 AND fastn_user.id = fastn_session.uid
+                AND (fastn_session.expires_at IS NULL OR fastn_session.expires_at > $2)
             "#,
-            vec![sid.as_str().into()],
+            vec![
+                sid.as_str().into(),
+                // fastn stores timestamps in nanoseconds
+                ft_sys_shared::SqliteRawValue::Integer(
+                    std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap()
+                        .as_nanos() as i64,
+                ),
+            ],
         ).await?;
 
         let mut row = match rows.len() {
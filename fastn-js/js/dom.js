@@ -877,6 +877,10 @@ class Node2 {
             this.#parent = parentOrSibiling;
             return;
         }
+        if (hydrating && this.#node.parentNode === this.#parent) {
+            // the server rendered node is in place already
+            return;
+        }
         if (sibiling) {
             this.#parent.insertBefore(
                 this.#node,
@@ -967,6 +971,11 @@ class Node2 {
     updateTagName(name) {
         if (ssr) {
             this.#node.updateTagName(name);
+        } else if (
+            hydrating &&
+            this.#node.tagName.toLowerCase() === name.toLowerCase()
+        ) {
+            // the server rendered it with this tag already
         } else {
             let newElement = document.createElement(name);
             newElement.innerHTML = this.#node.innerHTML;
@@ -996,8 +1005,14 @@ class Node2 {
         if (node_kind === fastn_dom.ElementKind.Image) {
             let anchorElement = document.createElement("a");
             anchorElement.href = url;
-            anchorElement.appendChild(this.#node);
-            this.#parent.appendChild(anchorElement);
+            if (hydrating) {
+                // keep the server rendered image where it is
+                this.#node.replaceWith(anchorElement);
+                anchorElement.appendChild(this.#node);
+            } else {
+                anchorElement.appendChild(this.#node);
+                this.#parent.appendChild(anchorElement);
+            }
             this.#node = anchorElement;
         } else {
             this.updateTagName("a");
@@ -2961,8 +2976,13 @@ class ForLoop {
                 this.#nodes[index - 1],
             );
         }
-        let v = this.#list.get(index);
-        let node = this.#node_constructor(parentWithSibiling, v.item, v.index);
+        let v = this.#list.getList()[index];
+        let node = this.#node_constructor(
+            parentWithSibiling,
+            v.item,
+            v.index,
+            v.key,
+        );
         this.#nodes.splice(index, 0, node);
         if (resizeBodyHeight) {
             fastn_utils.setFullHeight();
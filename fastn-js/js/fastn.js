@@ -350,13 +350,17 @@ const fastn = (function (fastn) {
             });
         }
 
-        insertAt(index, value) {
+        insertAt(index, value, key) {
             index = fastn_utils.getFlattenStaticValue(index);
             let mutable = fastn.wrapMutable(value);
-            this.#list.splice(index, 0, {
+            let entry = {
                 item: mutable,
                 index: new Mutable(index),
-            });
+            };
+            if (key !== undefined) {
+                entry.key = new Mutable(key);
+            }
+            this.#list.splice(index, 0, entry);
             // for every item after the inserted item, update the index
             for (let i = index + 1; i < this.#list.length; i++) {
                 this.#list[i].index.set(i);
@@ -413,6 +417,94 @@ const fastn = (function (fastn) {
         }
     }
 
+    // A map keeps its entries as a list so that `for` loops over it work the
+    // same way as over a list, each entry additionally carries its `key`. It
+    // is built from `[key, value]` pairs, not an object, whose integer keys
+    // would be reordered and whose `__proto__` key is not an entry.
+    class MutableMap extends MutableList {
+        constructor(entries) {
+            super([]);
+            let list = this.getList();
+            for (let [key, value] of entries) {
+                list.push({
+                    item: fastn.wrapMutable(value),
+                    index: new Mutable(list.length),
+                    key: new Mutable(String(key)),
+                });
+            }
+        }
+
+        #indexOf(key) {
+            key = String(fastn_utils.getFlattenStaticValue(key));
+            return this.getList().findIndex((obj) => obj.key.get() === key);
+        }
+
+        get(key) {
+            if (fastn_utils.isNull(key)) {
+                return this.getList();
+            }
+            let index = this.#indexOf(key);
+            return index === -1 ? null : this.getList()[index].item;
+        }
+
+        has(key) {
+            return this.#indexOf(key) !== -1;
+        }
+
+        keys() {
+            return this.getList().map((obj) => obj.key.get());
+        }
+
+        set(key, value) {
+            if (value === undefined) {
+                value = key;
+                if (!(value instanceof MutableMap)) {
+                    value = new MutableMap(
+                        fastn_utils.isNull(value)
+                            ? []
+                            : Array.isArray(value)
+                              ? value
+                              : Object.entries(value),
+                    );
+                }
+                super.set(value);
+                return;
+            }
+            let index = this.#indexOf(key);
+            if (index === -1) {
+                this.insertAt(
+                    this.getLength(),
+                    value,
+                    fastn_utils.getFlattenStaticValue(key),
+                );
+            } else {
+                super.set(index, value);
+            }
+        }
+
+        delete(key) {
+            let index = this.#indexOf(key);
+            if (index !== -1) {
+                this.deleteAt(index);
+            }
+        }
+
+        toObject() {
+            return Object.fromEntries(
+                this.getList().map((entry) => [entry.key.get(), entry.item]),
+            );
+        }
+
+        getClone() {
+            return new MutableMap(
+                this.getList().map((entry) => [
+                    entry.key.get(),
+                    fastn_utils.clone(entry.item),
+                ]),
+            );
+        }
+    }
+
     fastn.mutable = function (val) {
         return new Mutable(val);
     };
@@ -462,6 +554,11 @@ const fastn = (function (fastn) {
         return new MutableList(list);
     };
 
+    // `entries` are `[key, value]` pairs
+    fastn.mutableMap = function (entries) {
+        return new MutableMap(entries);
+    };
+
     class RecordInstance {
         #fields;
         #closures;
@@ -605,6 +702,7 @@ const fastn = (function (fastn) {
 
     fastn.mutableClass = Mutable;
     fastn.mutableListClass = MutableList;
+    fastn.mutableMapClass = MutableMap;
     fastn.recordInstanceClass = RecordInstance;
     fastn.module = function (name, global) {
         return new Module(name, global);
@@ -195,6 +195,547 @@ const ftd = (function () {
         list.set(value);
     };
 
+    // The string, list and number functions of expressions, like
+    // `ftd.upper(name)`. They work like `fastn_builtins::functions` does on the
+    // server, keep the two in sync: `ftd/t/functions.json` has the cases both
+    // are tested against.
+
+    function listItems(list) {
+        if (Array.isArray(list)) {
+            return list.map((item) => fastn_utils.getFlattenStaticValue(item));
+        }
+        list = fastn_utils.getFlattenStaticValue(list);
+        return fastn_utils.isNull(list) ? [] : list;
+    }
+
+    // a number or a boolean as a string, `null` is the empty string
+    function stringValue(value) {
+        value = fastn_utils.getFlattenStaticValue(value);
+        if (fastn_utils.isNull(value)) return "";
+        if (typeof value === "number") return plainNumber(value);
+        if (typeof value === "object") {
+            throw new Error(`expected a string, found ${JSON.stringify(value)}`);
+        }
+        return String(value);
+    }
+
+    // the shortest digits that read back as `n`, never an exponent, like
+    // `f64::to_string`: `1e21` is `1000000000000000000000`
+    function plainNumber(n) {
+        let [mantissa, exponent] = String(n).split("e");
+        if (exponent === undefined) return mantissa;
+        let sign = "";
+        if (mantissa.startsWith("-")) {
+            sign = "-";
+            mantissa = mantissa.slice(1);
+        }
+        let [integer, fraction = ""] = mantissa.split(".");
+        let digits = integer + fraction;
+        let point = integer.length + Number(exponent);
+        if (point <= 0) return `${sign}0.${"0".repeat(-point)}${digits}`;
+        if (point >= digits.length) {
+            return sign + digits + "0".repeat(point - digits.length);
+        }
+        return `${sign}${digits.slice(0, point)}.${digits.slice(point)}`;
+    }
+
+    exports.upper = (s) => stringValue(s).toUpperCase();
+    exports.lower = (s) => stringValue(s).toLowerCase();
+    exports.trim = (s) => stringValue(s).trim();
+    exports.split = (s, separator) => {
+        // `Array.from` splits by character, not by UTF-16 code unit
+        separator = stringValue(separator);
+        let parts =
+            separator === ""
+                ? Array.from(stringValue(s))
+                : stringValue(s).split(separator);
+        return fastn.mutableList(parts);
+    };
+    exports.join = (list, separator) =>
+        listItems(list)
+            .map(stringValue)
+            .join(stringValue(separator));
+    // an empty `from` replaces nothing
+    exports.replace = (s, from, to) => {
+        from = stringValue(from);
+        if (from === "") return stringValue(s);
+        return stringValue(s).split(from).join(stringValue(to));
+    };
+    exports.substring = (s, start, end) => {
+        let chars = Array.from(stringValue(s));
+        return sliceRange(chars, start, end).join("");
+    };
+    exports.contains = (value, item) => {
+        item = fastn_utils.getFlattenStaticValue(item);
+        let flat = fastn_utils.getFlattenStaticValue(value);
+        if (typeof flat === "string") {
+            return flat.includes(stringValue(item));
+        }
+        return listItems(value).includes(item);
+    };
+    exports.starts_with = (s, prefix) =>
+        stringValue(s).startsWith(stringValue(prefix));
+    exports.ends_with = (s, suffix) =>
+        stringValue(s).endsWith(stringValue(suffix));
+    exports.format_number = (n, decimals) => {
+        n = fastn_utils.getFlattenStaticValue(n);
+        decimals = fastn_utils.getFlattenStaticValue(decimals);
+        if (typeof n !== "number" || !isFinite(n)) {
+            throw new Error(`ftd.format_number: can not format ${n}`);
+        }
+        let formatted;
+        if (fastn_utils.isNull(decimals)) {
+            formatted = plainNumber(n);
+        } else {
+            if (!Number.isInteger(decimals)) {
+                throw new Error(
+                    `ftd.format_number: decimals must be an integer, found ${decimals}`,
+                );
+            }
+            if (decimals < 0) {
+                throw new Error(
+                    `ftd.format_number: decimals can not be negative, found ${decimals}`,
+                );
+            }
+            let scale = Math.pow(10, decimals);
+            // halves away from zero, like `f64::round`
+            let rounded = isFinite(Math.abs(n) * scale)
+                ? (Math.sign(n) * Math.round(Math.abs(n) * scale)) / scale
+                : n;
+            let [integer, fraction = ""] = plainNumber(rounded).split(".");
+            formatted =
+                decimals === 0
+                    ? integer
+                    : `${integer}.${fraction.padEnd(decimals, "0")}`;
+        }
+        let sign = "";
+        if (formatted.startsWith("-")) {
+            sign = "-";
+            formatted = formatted.slice(1);
+        }
+        let [integer, fraction] = formatted.split(".");
+        integer = integer.replace(/\B(?=(\d{3})+(?!\d))/g, ",");
+        fraction = fraction === undefined ? "" : `.${fraction}`;
+        // `-0` after rounding a small negative number
+        if (!/[1-9]/.test(integer + fraction)) sign = "";
+        return `${sign}${integer}${fraction}`;
+    };
+    exports.slice = (list, start, end) =>
+        fastn.mutableList(sliceRange(listItems(list), start, end));
+    exports.reverse = (list) => fastn.mutableList(listItems(list).reverse());
+    exports.sort = (list) => {
+        let items = listItems(list);
+        if (items.every((item) => typeof item === "number")) {
+            items.sort((a, b) => a - b);
+        } else if (items.every((item) => typeof item === "string")) {
+            // not by locale, like `str::cmp`
+            items.sort((a, b) => (a < b ? -1 : a > b ? 1 : 0));
+        } else {
+            throw new Error(
+                "ftd.sort: can only sort a list of strings or of numbers",
+            );
+        }
+        return fastn.mutableList(items);
+    };
+    exports.index_of = (list, item) =>
+        listItems(list).indexOf(fastn_utils.getFlattenStaticValue(item));
+
+    // `null` is the empty map, like in `fastn_builtins::functions`
+    function mapValue(map) {
+        while (map instanceof fastn.mutableClass) {
+            map = map.get();
+        }
+        return fastn_utils.isNull(map) ? fastn.mutableMap([]) : map;
+    }
+
+    exports.get = (map, key) =>
+        fastn_utils.getStaticValue(mapValue(map).get(stringValue(key)));
+    exports.has_key = (map, key) => mapValue(map).has(stringValue(key));
+    exports.keys = (map) => fastn.mutableList(mapValue(map).keys());
+
+    function sliceRange(items, start, end) {
+        start = fastn_utils.getFlattenStaticValue(start);
+        end = fastn_utils.getFlattenStaticValue(end);
+        return items.slice(start, fastn_utils.isNull(end) ? undefined : end);
+    }
+
+    // The `datetime` and `date` functions of expressions, like
+    // `ftd.format_date(created-on)`. They work like `fastn_builtins::datetime`
+    // does on the server, keep the two in sync. `Intl` is not used, server side
+    // rendering has none, so the two render the same. The language is the one
+    // of the page, unless the last argument names one, and the time is at the
+    // `time-zone` of the package, `__fastn_utc_offset__` minutes east of UTC.
+
+    // The patterns have `{day}`, `{dd}` (with a leading zero), `{month}`,
+    // `{mm}`, `{month_name}`, `{year}`, `{weekday}`, `{hour}` (from 00 to 23),
+    // `{hour12}`, `{minute}`, `{am_pm}`, `{zone}` (like `UTC` or `UTC+05:30`),
+    // and `{date}` and `{time}` in `datetime`. `units` are the singular and
+    // plural of seconds, minutes, hours, days, weeks, months and years, only
+    // the browser has them, see `relative_time`.
+    const locales = {
+        en: {
+            months: [
+                "January",
+                "February",
+                "March",
+                "April",
+                "May",
+                "June",
+                "July",
+                "August",
+                "September",
+                "October",
+                "November",
+                "December",
+            ],
+            weekdays: [
+                "Sunday",
+                "Monday",
+                "Tuesday",
+                "Wednesday",
+                "Thursday",
+                "Friday",
+                "Saturday",
+            ],
+            short_date: "{month}/{day}/{year}",
+            long_date: "{month_name} {day}, {year}",
+            full_date: "{weekday}, {month_name} {day}, {year}",
+            time: "{hour12}:{minute} {am_pm} {zone}",
+            am_pm: ["AM", "PM"],
+            datetime: "{date}, {time}",
+            units: [
+                ["second", "seconds"],
+                ["minute", "minutes"],
+                ["hour", "hours"],
+                ["day", "days"],
+                ["week", "weeks"],
+                ["month", "months"],
+                ["year", "years"],
+            ],
+            past: "{n} {unit} ago",
+            future: "in {n} {unit}",
+        },
+        es: {
+            months: [
+                "enero",
+                "febrero",
+                "marzo",
+                "abril",
+                "mayo",
+                "junio",
+                "julio",
+                "agosto",
+                "septiembre",
+                "octubre",
+                "noviembre",
+                "diciembre",
+            ],
+            weekdays: [
+                "domingo",
+                "lunes",
+                "martes",
+                "miércoles",
+                "jueves",
+                "viernes",
+                "sábado",
+            ],
+            short_date: "{day}/{month}/{year}",
+            long_date: "{day} de {month_name} de {year}",
+            full_date: "{weekday}, {day} de {month_name} de {year}",
+            time: "{hour}:{minute} {zone}",
+            am_pm: ["a. m.", "p. m."],
+            datetime: "{date}, {time}",
+            units: [
+                ["segundo", "segundos"],
+                ["minuto", "minutos"],
+                ["hora", "horas"],
+                ["día", "días"],
+                ["semana", "semanas"],
+                ["mes", "meses"],
+                ["año", "años"],
+            ],
+            past: "hace {n} {unit}",
+            future: "dentro de {n} {unit}",
+        },
+        fr: {
+            months: [
+                "janvier",
+                "février",
+                "mars",
+                "avril",
+                "mai",
+                "juin",
+                "juillet",
+                "août",
+                "septembre",
+                "octobre",
+                "novembre",
+                "décembre",
+            ],
+            weekdays: [
+                "dimanche",
+                "lundi",
+                "mardi",
+                "mercredi",
+                "jeudi",
+                "vendredi",
+                "samedi",
+            ],
+            short_date: "{dd}/{mm}/{year}",
+            long_date: "{day} {month_name} {year}",
+            full_date: "{weekday} {day} {month_name} {year}",
+            time: "{hour}:{minute} {zone}",
+            am_pm: ["AM", "PM"],
+            datetime: "{date} à {time}",
+            units: [
+                ["seconde", "secondes"],
+                ["minute", "minutes"],
+                ["heure", "heures"],
+                ["jour", "jours"],
+                ["semaine", "semaines"],
+                ["mois", "mois"],
+                ["an", "ans"],
+            ],
+            past: "il y a {n} {unit}",
+            future: "dans {n} {unit}",
+        },
+        de: {
+            months: [
+                "Januar",
+                "Februar",
+                "März",
+                "April",
+                "Mai",
+                "Juni",
+                "Juli",
+                "August",
+                "September",
+                "Oktober",
+                "November",
+                "Dezember",
+            ],
+            weekdays: [
+                "Sonntag",
+                "Montag",
+                "Dienstag",
+                "Mittwoch",
+                "Donnerstag",
+                "Freitag",
+                "Samstag",
+            ],
+            short_date: "{dd}.{mm}.{year}",
+            long_date: "{day}. {month_name} {year}",
+            full_date: "{weekday}, {day}. {month_name} {year}",
+            time: "{hour}:{minute} {zone}",
+            am_pm: ["AM", "PM"],
+            datetime: "{date}, {time}",
+            // dative, `vor` and `in` take it
+            units: [
+                ["Sekunde", "Sekunden"],
+                ["Minute", "Minuten"],
+                ["Stunde", "Stunden"],
+                ["Tag", "Tagen"],
+                ["Woche", "Wochen"],
+                ["Monat", "Monaten"],
+                ["Jahr", "Jahren"],
+            ],
+            past: "vor {n} {unit}",
+            future: "in {n} {unit}",
+        },
+        hi: {
+            months: [
+                "जनवरी",
+                "फ़रवरी",
+                "मार्च",
+                "अप्रैल",
+                "मई",
+                "जून",
+                "जुलाई",
+                "अगस्त",
+                "सितंबर",
+                "अक्तूबर",
+                "नवंबर",
+                "दिसंबर",
+            ],
+            weekdays: [
+                "रविवार",
+                "सोमवार",
+                "मंगलवार",
+                "बुधवार",
+                "गुरुवार",
+                "शुक्रवार",
+                "शनिवार",
+            ],
+            short_date: "{day}/{month}/{year}",
+            long_date: "{day} {month_name} {year}",
+            full_date: "{weekday}, {day} {month_name} {year}",
+            time: "{hour12}:{minute} {am_pm} {zone}",
+            am_pm: ["am", "pm"],
+            datetime: "{date}, {time}",
+            units: [
+                ["सेकंड", "सेकंड"],
+                ["मिनट", "मिनट"],
+                ["घंटा", "घंटे"],
+                ["दिन", "दिन"],
+                ["सप्ताह", "सप्ताह"],
+                ["महीना", "महीने"],
+                ["वर्ष", "वर्ष"],
+            ],
+            past: "{n} {unit} पहले",
+            future: "{n} {unit} में",
+        },
+    };
+
+    const ISO_8601 =
+        /^(\d{4})-(\d{2})-(\d{2})(?:[Tt ](\d{2}):(\d{2})(?::(\d{2})(?:\.(\d+))?)?)?\s*([Zz]|[+-]\d{2}:?\d{2})?$/;
+
+    // A `Date` in UTC, and if `value` has a time, a `date` does not.
+    // `undefined` if `value` is not ISO 8601.
+    function parseDatetime(value) {
+        let match = ISO_8601.exec(stringValue(value).trim());
+        if (!match) return undefined;
+        let [year, month, day, hour, minute, second] = match
+            .slice(1, 7)
+            .map((part) => (part === undefined ? 0 : Number(part)));
+        // a `Date` has milliseconds, the server keeps nanoseconds
+        let milliseconds = Number(((match[7] || "") + "000").slice(0, 3));
+        let date = new Date(0);
+        // not `Date.UTC`, it takes years below 100 to be in the 1900s
+        date.setUTCFullYear(year, month - 1, day);
+        date.setUTCHours(hour, minute, second, milliseconds);
+        if (
+            date.getUTCMonth() !== month - 1 ||
+            date.getUTCDate() !== day ||
+            hour > 23 ||
+            minute > 59 ||
+            second > 59
+        ) {
+            return undefined;
+        }
+        let offset = match[8];
+        if (offset !== undefined && offset.toUpperCase() !== "Z") {
+            let digits = offset.slice(1).replace(":", "");
+            let minutes =
+                Number(digits.slice(0, 2)) * 60 + Number(digits.slice(2));
+            date = new Date(
+                date.getTime() - (offset[0] === "-" ? -1 : 1) * minutes * 60000,
+            );
+        }
+        return { date, hasTime: match[4] !== undefined };
+    }
+
+    // The `datetime` or `date`, in a `Date` whose UTC fields are the ones at
+    // the offset of the page, and the offset. A `date` is a day, not a time in
+    // UTC, it is not moved to another day.
+    function datetimeArgument(name, value) {
+        let parsed = parseDatetime(value);
+        if (parsed === undefined) {
+            throw new Error(
+                `ftd.${name}: \`${stringValue(value)}\` is not a datetime or a date`,
+            );
+        }
+        let offset =
+            parsed.hasTime && typeof __fastn_utc_offset__ !== "undefined"
+                ? __fastn_utc_offset__
+                : 0;
+        return {
+            date: new Date(parsed.date.getTime() + offset * 60000),
+            offset,
+        };
+    }
+
+    // `UTC`, or `UTC+05:30` for an offset of 330 minutes
+    function zone(offset) {
+        if (offset === 0) return "UTC";
+        let minutes = Math.abs(offset);
+        let pad = (n) => String(n).padStart(2, "0");
+        let hours = pad(Math.floor(minutes / 60));
+        return `UTC${offset < 0 ? "-" : "+"}${hours}:${pad(minutes % 60)}`;
+    }
+
+    function locale(language) {
+        language = stringValue(language);
+        if (language === "" && typeof __fastn_language__ !== "undefined") {
+            language = __fastn_language__;
+        }
+        language = language.split(/[-_]/)[0].toLowerCase();
+        return locales[language] || locales.en;
+    }
+
+    function datePattern(name, style, locale) {
+        style = stringValue(style) || "long";
+        if (!["short", "long", "full"].includes(style)) {
+            throw new Error(
+                `ftd.${name}: unknown date style \`${style}\`, expected \`short\`, \`long\` or \`full\``,
+            );
+        }
+        return locale[`${style}_date`];
+    }
+
+    function formatDatetime(pattern, { date, offset }, locale) {
+        let pad = (n) => String(n).padStart(2, "0");
+        let hour = date.getUTCHours();
+        return pattern
+            .replace("{month_name}", locale.months[date.getUTCMonth()])
+            .replace("{weekday}", locale.weekdays[date.getUTCDay()])
+            .replace("{dd}", pad(date.getUTCDate()))
+            .replace("{day}", String(date.getUTCDate()))
+            .replace("{mm}", pad(date.getUTCMonth() + 1))
+            .replace("{month}", String(date.getUTCMonth() + 1))
+            .replace("{year}", String(date.getUTCFullYear()))
+            .replace("{hour12}", String(hour % 12 === 0 ? 12 : hour % 12))
+            .replace("{hour}", pad(hour))
+            .replace("{minute}", pad(date.getUTCMinutes()))
+            .replace("{am_pm}", locale.am_pm[hour < 12 ? 0 : 1])
+            .replace("{zone}", zone(offset));
+    }
+
+    // `seconds` is how long ago, negative for the future. A month is 30 days
+    // and a year 365.
+    function relativeTime(seconds, locale) {
+        const units = [1, 60, 3600, 86400, 604800, 2592000, 31536000];
+        let abs = Math.abs(seconds);
+        let unit = 0;
+        units.forEach((u, i) => {
+            if (abs >= u) unit = i;
+        });
+        let n = Math.floor(abs / units[unit]);
+        let [singular, plural] = locale.units[unit];
+        return (seconds >= 0 ? locale.past : locale.future)
+            .replace("{n}", String(n))
+            .replace("{unit}", n === 1 ? singular : plural);
+    }
+
+    exports.format_date = (d, style, language) => {
+        let date = datetimeArgument("format_date", d);
+        let l = locale(language);
+        return formatDatetime(datePattern("format_date", style, l), date, l);
+    };
+    exports.format_time = (d, language) => {
+        let date = datetimeArgument("format_time", d);
+        let l = locale(language);
+        return formatDatetime(l.time, date, l);
+    };
+    exports.format_datetime = (d, style, language) => {
+        let date = datetimeArgument("format_datetime", d);
+        let l = locale(language);
+        let pattern = l.datetime
+            .replace("{date}", datePattern("format_datetime", style, l))
+            .replace("{time}", l.time);
+        return formatDatetime(pattern, date, l);
+    };
+    // The server does not know when the page is read, there it is the
+    // `format_datetime` of `d`, which the browser replaces when it hydrates
+    // the page, like `fastn_builtins::datetime::relative_time`.
+    exports.relative_time = (d, language) => {
+        let { date, offset } = datetimeArgument("relative_time", d);
+        if (ssr) return exports.format_datetime(d, "long", language);
+        return relativeTime(
+            Math.trunc((Date.now() - date.getTime() + offset * 60000) / 1000),
+            locale(language),
+        );
+    };
+
     exports.http = function (url, method, headers, ...body) {
         if (url instanceof fastn.mutableClass) url = url.get();
         if (method instanceof fastn.mutableClass) method = method.get();
@@ -482,6 +1023,102 @@ const ftd = (function () {
         return fastn_utils.private.getCookie("fastn-lang");
     };
 
+    // Search related functions -----------------------------------------------
+    // The index is written by `fastn build`, and served by `fastn serve`, at
+    // `/-/search/<language>.json`. It looks like:
+    // {"documents": [[url, title], ...], "terms": {term: [[document, count], ...]}}
+    // The terms are kept in a `Map`, so a term like `constructor` is not found
+    // on the prototype of an object.
+    const searchIndexes = {};
+
+    // Same as `fastn_core::search::terms`, `char::is_alphanumeric` is
+    // `Alphabetic` or `N`, which includes the vowel signs of Indic scripts.
+    function searchTerms(text) {
+        return text
+            .toLowerCase()
+            .split(/[^\p{Alphabetic}\p{N}]+/u)
+            .filter((term) => [...term].length >= 2);
+    }
+
+    function loadSearchIndex(language) {
+        if (!searchIndexes[language]) {
+            searchIndexes[language] = fetch(`/-/search/${language}.json`)
+                .then((res) => {
+                    if (!res.ok) {
+                        delete searchIndexes[language];
+                        throw new Error(
+                            "[search]: Failed to load index: " + res.status,
+                        );
+                    }
+                    return res.json();
+                })
+                .then((index) => ({
+                    documents: index.documents,
+                    terms: new Map(Object.entries(index.terms)),
+                }));
+        }
+        return searchIndexes[language];
+    }
+
+    // Every term of the query has to be in the document, the last one can be
+    // a prefix, so results show up while the query is being typed. Same as
+    // `fastn_core::search::Index::search`.
+    function searchIndex(index, query) {
+        const terms = searchTerms(query);
+        if (terms.length === 0) {
+            return [];
+        }
+        const scores = {};
+        terms.forEach((term, i) => {
+            const matches =
+                i === terms.length - 1
+                    ? [...index.terms.keys()].filter((t) => t.startsWith(term))
+                    : index.terms.has(term)
+                      ? [term]
+                      : [];
+            const found = {};
+            for (const match of matches) {
+                for (const [document, count] of index.terms.get(match)) {
+                    found[document] = (found[document] || 0) + count;
+                }
+            }
+            for (const [document, count] of Object.entries(found)) {
+                if (!scores[document]) {
+                    scores[document] = { terms: 0, score: 0 };
+                }
+                scores[document].terms += 1;
+                scores[document].score += count;
+            }
+        });
+        return Object.entries(scores)
+            .filter(([_, s]) => s.terms === terms.length)
+            .sort(([a, x], [b, y]) => y.score - x.score || a - b)
+            .slice(0, 20)
+            .map(([document, _]) => {
+                const [url, title] = index.documents[document];
+                return { url, title };
+            });
+    }
+
+    // Searches the index of the language the page is rendered in, which is not
+    // the `fastn-lang` cookie if the package has no module for that language.
+    exports.search = function (query, results, language) {
+        query = fastn_utils.getStaticValue(query);
+        language =
+            fastn_utils.getStaticValue(language) ||
+            (typeof __fastn_language__ !== "undefined" && __fastn_language__) ||
+            "en";
+
+        loadSearchIndex(language)
+            .then((index) => {
+                let found = fastn_utils.staticToMutables(
+                    searchIndex(index, query),
+                );
+                results.set(found);
+            })
+            .catch(console.error);
+    };
+
     exports.submit_form = function (url, ...args) {
         if (url instanceof fastn.mutableClass) url = url.get();
 
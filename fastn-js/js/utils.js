@@ -157,6 +157,15 @@ let fastn_utils = {
     mutableToStaticValue(obj) {
         if (obj instanceof fastn.mutableClass) {
             return this.mutableToStaticValue(obj.get());
+        } else if (obj instanceof fastn.mutableMapClass) {
+            return Object.fromEntries(
+                obj
+                    .getList()
+                    .map((entry) => [
+                        entry.key.get(),
+                        this.mutableToStaticValue(entry.item),
+                    ]),
+            );
         } else if (obj instanceof fastn.mutableListClass) {
             let list = obj.getList();
             return list.map((func) => this.mutableToStaticValue(func.item));
@@ -181,6 +190,9 @@ let fastn_utils = {
         return value;
     },
     getFlattenStaticValue(obj) {
+        if (fastn_utils.getter(obj) instanceof fastn.mutableMapClass) {
+            return fastn_utils.mutableToStaticValue(obj);
+        }
         let staticValue = fastn_utils.getStaticValue(obj);
         if (Array.isArray(staticValue)) {
             return staticValue.map((func) =>
@@ -208,7 +220,8 @@ let fastn_utils = {
     getterByKey(value, index) {
         if (
             value instanceof fastn.mutableClass ||
-            value instanceof fastn.recordInstanceClass
+            value instanceof fastn.recordInstanceClass ||
+            value instanceof fastn.mutableMapClass
         ) {
             return value.get(index);
         } else if (value instanceof fastn.mutableListClass) {
@@ -3,6 +3,13 @@ let fastnVirtual = {};
 let id_counter = 0;
 let ssr = false;
 let doubleBuffering = false;
+/**
+ * While hydrating, `fastnVirtual.document.createElement` hands out the
+ * server rendered element with the same `data-id` instead of a new one, see
+ * `fastnVirtual.hydrate`.
+ */
+let hydrating = false;
+let hydratableNodes = {};
 
 class ClassList {
     #classes = [];
@@ -125,6 +132,10 @@ class Document2 {
             return window.document.body;
         }
 
+        if (hydrating && !fastn_utils.isWrapperNode(tagName)) {
+            return hydratableNode(id_counter, tagName);
+        }
+
         if (fastn_utils.isWrapperNode(tagName)) {
             return window.document.createComment(fastn_dom.commentMessage);
         }
@@ -137,6 +148,37 @@ class Document2 {
 
 fastnVirtual.document = new Document2();
 
+class HydrationMismatch extends Error {}
+
+/**
+ * The server rendered element `data-id` of the element being created, without
+ * the classes of its css properties, which the browser adds again. The
+ * `<comment>` elements of the server are replaced by comment nodes.
+ */
+function hydratableNode(dataId, tagName) {
+    let node = hydratableNodes[dataId];
+    if (fastn_utils.isNull(node)) {
+        throw new HydrationMismatch(`no server rendered node ${dataId}`);
+    }
+    delete hydratableNodes[dataId];
+
+    if (fastn_utils.isCommentNode(tagName)) {
+        if (node.tagName.toLowerCase() !== fastn_dom.commentNode) {
+            throw new HydrationMismatch(`node ${dataId} is not a comment`);
+        }
+        let comment = window.document.createComment(fastn_dom.commentMessage);
+        node.replaceWith(comment);
+        return comment;
+    }
+
+    for (const className of [...node.classList]) {
+        if (className.startsWith("__")) {
+            node.classList.remove(className);
+        }
+    }
+    return node;
+}
+
 function addClosureToBreakpointWidth() {
     let closure = fastn.closureWithoutExecute(function () {
         let current = ftd.get_device();
@@ -153,15 +195,85 @@ function addClosureToBreakpointWidth() {
 
 fastnVirtual.doubleBuffer = function (main) {
     addClosureToBreakpointWidth();
-    let parent = document.createElement("div");
     let current_device = ftd.get_device();
     ftd.device = fastn.mutable(current_device);
+    renderDoubleBuffered(main);
+};
+
+function renderDoubleBuffered(main) {
+    let parent = document.createElement("div");
     doubleBuffering = true;
     fastnVirtual.root = parent;
     main(parent);
     fastn_utils.replaceBodyStyleAndChildren(parent);
     doubleBuffering = false;
     fastnVirtual.root = document.body;
+}
+
+/**
+ * Attaches `main` to the html `fastnVirtual.ssr` rendered on the server: the
+ * nodes are created in the same order, so each one takes over the element
+ * with its `data-id` and only gets its properties and event handlers set.
+ *
+ * The server renders for the default `ftd.device`, the page is hydrated for
+ * that device and then switched to the device of the browser, which updates
+ * what depends on it like a resize does. The page is built from scratch, like
+ * `fastnVirtual.doubleBuffer` does, if there is no server rendered html or if
+ * the nodes do not match it, including when server rendered nodes are left
+ * over.
+ */
+fastnVirtual.hydrate = function (main) {
+    hydratableNodes = {};
+    for (const node of document.body.querySelectorAll("[data-id]")) {
+        hydratableNodes[node.getAttribute("data-id")] = node;
+    }
+
+    if (
+        Object.keys(hydratableNodes).length === 0 ||
+        fastn_utils.isNull(ftd.device)
+    ) {
+        hydratableNodes = {};
+        fastnVirtual.doubleBuffer(main);
+        return;
+    }
+
+    let current_device = ftd.get_device();
+    ftd.device = fastn.mutable(ftd.device.get());
+    doubleBuffering = true;
+    hydrating = true;
+    fastnVirtual.root = document.body;
+    try {
+        // the server renders into a `body` node, which takes the first id
+        main(fastnVirtual.document.createElement("body"));
+        let unclaimed = Object.keys(hydratableNodes);
+        if (unclaimed.length > 0) {
+            throw new HydrationMismatch(
+                `no node for the server rendered nodes ${unclaimed.join(", ")}`,
+            );
+        }
+        document.getElementById("styles").innerHTML =
+            fastn_dom.getClassesAsStringWithoutStyleTag();
+    } catch (e) {
+        if (!(e instanceof HydrationMismatch)) {
+            throw e;
+        }
+        console.warn("rendering the page again,", e.message);
+        hydrating = false;
+        id_counter = 0;
+        fastn_dom.classes = {};
+        fastn_dom.unsanitised_classes = {};
+        fastn_dom.class_count = 0;
+        fastn_dom.styleClasses = "";
+        fastn_dom.webComponent = [];
+        renderDoubleBuffered(main);
+    } finally {
+        hydrating = false;
+        doubleBuffering = false;
+        hydratableNodes = {};
+    }
+
+    addClosureToBreakpointWidth();
+    ftd.device.set(current_device);
 };
 
 fastnVirtual.ssr = function (main) {
@@ -198,6 +198,9 @@ pub enum Value {
     List {
         value: Vec<SetPropertyValue>,
     },
+    Map {
+        value: Vec<(String, SetPropertyValue)>,
+    },
     Record {
         fields: Vec<(String, SetPropertyValue)>,
         other_references: Vec<String>,
@@ -244,6 +247,17 @@ impl Value {
                     .map(|v| v.to_js_with_element_name(element_name))
                     .join(", ")
             ),
+            Value::Map { value } => format!(
+                "fastn.mutableMap([{}])",
+                value
+                    .iter()
+                    .map(|(k, v)| format!(
+                        "[{}, {}]",
+                        Value::String(k.to_string()).to_js(element_name),
+                        v.to_js_with_element_name(element_name)
+                    ))
+                    .join(", ")
+            ),
             Value::Record {
                 fields,
                 other_references,
@@ -303,6 +317,7 @@ impl Value {
                 .map(|v| v.is_local_value_dependent())
                 .unwrap_or_default(),
             Value::List { value } => value.iter().any(|v| v.is_local_value_dependent()),
+            Value::Map { value } => value.iter().any(|v| v.1.is_local_value_dependent()),
             Value::Record { fields, .. } => fields.iter().any(|v| v.1.is_local_value_dependent()),
             Value::UI { .. } => {
                 //Todo: Check for UI
@@ -406,7 +406,7 @@ impl fastn_js::ForLoop {
         .append(text(self.parent.as_str()))
         .append(comma())
         .append(space())
-        .append(text("function (root, item, index) {"))
+        .append(text("function (root, item, index, key) {"))
         .append(
             pretty::RcDoc::intersperse(
                 self.statements.iter().map(|v| v.to_js()),
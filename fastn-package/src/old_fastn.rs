@@ -102,6 +102,12 @@ pub struct PackageTemp {
     pub system: Option<String>,
     #[serde(rename = "system-is-confidential")]
     pub system_is_confidential: Option<bool>,
+    pub ssr: bool,
+    #[serde(rename = "time-zone")]
+    pub time_zone: Option<String>,
+    /// The language this package is written in, used for the `hreflang` alternates in
+    /// `sitemap.xml`
+    pub language: Option<String>,
     #[serde(rename = "default-language")]
     pub default_language: Option<String>,
     pub lang: Option<String>,
@@ -5,6 +5,10 @@ pub enum Kind {
     Integer,
     Decimal,
     Boolean,
+    /// A moment in time, see `fastn_resolved::Value::DateTime`
+    DateTime,
+    /// A day, see `fastn_resolved::Value::Date`
+    Date,
     Record {
         name: String,
     }, // the full name of the record (full document name.record name)
@@ -16,6 +20,10 @@ pub enum Kind {
     List {
         kind: Box<Kind>,
     },
+    /// Entries keyed by string, see `fastn_resolved::Value::Map`
+    Map {
+        kind: Box<Kind>,
+    },
     Optional {
         kind: Box<Kind>,
     },
@@ -39,8 +47,11 @@ impl Kind {
             Kind::Integer { .. } => "integer".to_string(),
             Kind::Boolean { .. } => "boolean".to_string(),
             Kind::Decimal { .. } => "decimal".to_string(),
+            Kind::DateTime => "datetime".to_string(),
+            Kind::Date => "date".to_string(),
             Kind::Constant { .. } => "constant".to_string(),
             Kind::List { .. } => "list".to_string(),
+            Kind::Map { .. } => "map".to_string(),
             Kind::Object { .. } => "object".to_string(),
             Kind::OrType { name, .. } => name.clone(),
             Kind::Optional { .. } => "optional".to_string(),
@@ -59,6 +70,7 @@ impl Kind {
             (Self::Optional { kind, .. }, _) => kind.is_same_as(other),
             (_, Self::Optional { kind: other, .. }) => self.is_same_as(other),
             (Self::List { kind: k1 }, Self::List { kind: k2 }) => k1.is_same_as(k2),
+            (Self::Map { kind: k1 }, Self::Map { kind: k2 }) => k1.is_same_as(k2),
             _ => self.eq(other),
         }
     }
@@ -83,6 +95,14 @@ impl Kind {
         Kind::Boolean
     }
 
+    pub fn datetime() -> Kind {
+        Kind::DateTime
+    }
+
+    pub fn date() -> Kind {
+        Kind::Date
+    }
+
     pub fn module() -> Kind {
         Kind::Module
     }
@@ -159,6 +179,12 @@ impl Kind {
         }
     }
 
+    pub fn into_map(self) -> Kind {
+        Kind::Map {
+            kind: Box::new(self),
+        }
+    }
+
     pub fn into_optional(self) -> Kind {
         Kind::Optional {
             kind: Box::new(self),
@@ -204,6 +230,17 @@ impl Kind {
         matches!(self, Kind::List { .. })
     }
 
+    pub fn inner_map(self) -> Kind {
+        match self {
+            Kind::Map { kind } => kind.as_ref().to_owned(),
+            t => t,
+        }
+    }
+
+    pub fn is_map(&self) -> bool {
+        matches!(self, Kind::Map { .. })
+    }
+
     pub fn is_subsection_ui(&self) -> bool {
         matches!(
             self,
@@ -254,6 +291,14 @@ impl Kind {
         matches!(self, Kind::Decimal { .. })
     }
 
+    pub fn is_datetime(&self) -> bool {
+        matches!(self, Kind::DateTime)
+    }
+
+    pub fn is_date(&self) -> bool {
+        matches!(self, Kind::Date)
+    }
+
     pub fn is_void(&self) -> bool {
         matches!(self, Kind::Void { .. })
     }
@@ -327,6 +372,10 @@ impl KindData {
         self.kind.is_list()
     }
 
+    pub fn is_map(&self) -> bool {
+        self.kind.is_map()
+    }
+
     pub fn is_or_type(&self) -> bool {
         self.kind.is_or_type()
     }
@@ -375,6 +424,14 @@ impl KindData {
         self.kind.is_decimal()
     }
 
+    pub fn is_datetime(&self) -> bool {
+        self.kind.is_datetime()
+    }
+
+    pub fn is_date(&self) -> bool {
+        self.kind.is_date()
+    }
+
     pub fn is_void(&self) -> bool {
         self.kind.is_void()
     }
@@ -403,6 +460,16 @@ impl KindData {
         }
     }
 
+    pub fn map(self) -> KindData {
+        KindData {
+            kind: Kind::Map {
+                kind: Box::new(self.kind),
+            },
+            caption: self.caption,
+            body: self.body,
+        }
+    }
+
     pub fn constant(self) -> KindData {
         KindData {
             kind: Kind::Constant {
@@ -425,6 +492,18 @@ impl KindData {
         }
     }
 
+    pub fn inner_map(self) -> KindData {
+        let kind = match self.kind {
+            Kind::Map { kind } => kind.as_ref().to_owned(),
+            t => t,
+        };
+        KindData {
+            kind,
+            caption: self.caption,
+            body: self.body,
+        }
+    }
+
     pub fn inner(self) -> KindData {
         let kind = match self.kind {
             Kind::Optional { kind } => kind.as_ref().to_owned(),
@@ -162,6 +162,15 @@ pub enum Value {
     Boolean {
         value: bool,
     },
+    /// In UTC, like `2024-01-02T03:04:05+00:00`, so two of them compare as strings, see
+    /// `fastn_builtins::datetime::parse_datetime`
+    DateTime {
+        value: String,
+    },
+    /// Like `2024-01-02`
+    Date {
+        value: String,
+    },
     Object {
         values: fastn_resolved::Map<PropertyValue>,
     },
@@ -182,6 +191,11 @@ pub enum Value {
         data: Vec<PropertyValue>,
         kind: fastn_resolved::KindData,
     },
+    /// Entries in key order, `kind` is the kind of the values
+    Map {
+        data: fastn_resolved::Map<PropertyValue>,
+        kind: fastn_resolved::KindData,
+    },
     Optional {
         data: Box<Option<Value>>,
         kind: fastn_resolved::KindData,
@@ -246,10 +260,13 @@ impl Value {
             Value::Integer { .. } => fastn_resolved::Kind::integer(),
             Value::Decimal { .. } => fastn_resolved::Kind::decimal(),
             Value::Boolean { .. } => fastn_resolved::Kind::boolean(),
+            Value::DateTime { .. } => fastn_resolved::Kind::datetime(),
+            Value::Date { .. } => fastn_resolved::Kind::date(),
             Value::Object { .. } => fastn_resolved::Kind::object(),
             Value::Record { name, .. } => fastn_resolved::Kind::record(name),
             Value::KwArgs { .. } => fastn_resolved::Kind::kwargs(),
             Value::List { kind, .. } => kind.kind.clone().into_list(),
+            Value::Map { kind, .. } => kind.kind.clone().into_map(),
             Value::Optional { kind, .. } => fastn_resolved::Kind::Optional {
                 kind: Box::new(kind.kind.clone()),
             },
@@ -313,12 +330,11 @@ impl Value {
     }
 
     pub fn is_empty(&self) -> bool {
-        if let Self::List { data, .. } = self {
-            if data.is_empty() {
-                return true;
-            }
+        match self {
+            Self::List { data, .. } => data.is_empty(),
+            Self::Map { data, .. } => data.is_empty(),
+            _ => false,
         }
-        false
     }
 
     pub fn is_equal(&self, other: &Self) -> bool {
@@ -132,6 +132,9 @@ impl fastn_runtime::extensions::ValueExt for fastn_resolved::Value {
             fastn_resolved::Value::String { text } => {
                 fastn_js::SetPropertyValue::Value(fastn_js::Value::String(text.to_string()))
             }
+            fastn_resolved::Value::DateTime { value } | fastn_resolved::Value::Date { value } => {
+                fastn_js::SetPropertyValue::Value(fastn_js::Value::String(value.to_string()))
+            }
             fastn_resolved::Value::Integer { value } => {
                 fastn_js::SetPropertyValue::Value(fastn_js::Value::Integer(*value))
             }
@@ -172,6 +175,24 @@ impl fastn_runtime::extensions::ValueExt for fastn_resolved::Value {
                         .collect_vec(),
                 })
             }
+            fastn_resolved::Value::Map { data, .. } => {
+                fastn_js::SetPropertyValue::Value(fastn_js::Value::Map {
+                    value: data
+                        .iter()
+                        .map(|(k, v)| {
+                            (
+                                k.to_string(),
+                                v.to_fastn_js_value_with_ui(
+                                    doc,
+                                    rdata,
+                                    has_rive_components,
+                                    should_return,
+                                ),
+                            )
+                        })
+                        .collect_vec(),
+                })
+            }
             fastn_resolved::Value::Record {
                 fields: record_fields,
                 name,
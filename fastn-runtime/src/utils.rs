@@ -168,6 +168,10 @@ pub(crate) fn update_reference(reference: &str, rdata: &fastn_runtime::ResolverD
         return "index".to_string();
     }
 
+    if name.contains(fastn_builtins::constants::FTD_LOOP_KEY) {
+        return "key".to_string();
+    }
+
     if is_ftd_thing(name.as_str()) {
         return name.replace("ftd#", "ftd.");
     }
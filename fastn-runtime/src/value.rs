@@ -174,6 +174,10 @@ impl fastn_runtime::extensions::ExpressionExt for fastn_resolved::Expression {
                     operator = fastn_resolved::evalexpr::Operator::VariableIdentifierRead {
                         identifier: "index".to_string(),
                     }
+                } else if format!("${}", fastn_builtins::constants::FTD_LOOP_KEY).eq(identifier) {
+                    operator = fastn_resolved::evalexpr::Operator::VariableIdentifierRead {
+                        identifier: "key".to_string(),
+                    }
                 } else if let Some(loop_counter_alias) = rdata.loop_counter_alias {
                     if loop_counter_alias.eq(identifier.trim_start_matches('$')) {
                         operator = fastn_resolved::evalexpr::Operator::VariableIdentifierRead {
@@ -209,6 +213,11 @@ impl fastn_runtime::extensions::ArgumentExt for fastn_resolved::Argument {
                 data: vec![],
                 kind: self.kind.clone(),
             }))
+        } else if self.kind.is_map() {
+            Some(fastn_runtime::Value::Data(fastn_resolved::Value::Map {
+                data: Default::default(),
+                kind: self.kind.clone(),
+            }))
         } else if self.kind.is_optional() {
             Some(fastn_runtime::Value::Data(
                 fastn_resolved::Value::Optional {
@@ -433,6 +442,8 @@ pub(crate) fn ftd_to_js_variant(
                     fastn_resolved::Value::Decimal { value } => (value.to_string(), false),
                     fastn_resolved::Value::String { text } => (format!("\"{}\"", text), false),
                     fastn_resolved::Value::Boolean { value } => (value.to_string(), false),
+                    fastn_resolved::Value::DateTime { value }
+                    | fastn_resolved::Value::Date { value } => (format!("\"{}\"", value), false),
                     _ => todo!("{} {}", t, variant),
                 };
             }
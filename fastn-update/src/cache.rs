@@ -0,0 +1,229 @@
+//! Packages downloaded by any fastn package on this machine, unpacked in
+//! `<home>/.fastn/cache/packages/<manifest checksum>/`, next to `<manifest checksum>.json`, their
+//! manifest. `fastn update` fills `.packages/<package>` with copies of the files here, so a
+//! package is downloaded once however many packages depend on it, and editing a file in
+//! `.packages` does not change the cache. The manifest is written
+//! again every time the package is used, `fastn cache prune` removes the ones not used for long.
+
+use snafu::ResultExt;
+
+pub fn cache_dir(ds: &fastn_ds::DocumentStore) -> fastn_ds::Path {
+    ds.home().join(".fastn").join("cache").join("packages")
+}
+
+/// The manifest of the cached package with `checksum`, if it is cached and every file of the
+/// manifest is there with the content it had when it was downloaded. A package that is not is
+/// removed from the cache, so it is downloaded again.
+pub(crate) async fn get(
+    ds: &fastn_ds::DocumentStore,
+    checksum: &str,
+) -> Option<fastn_core::Manifest> {
+    if checksum.is_empty() {
+        return None;
+    }
+
+    let dir = cache_dir(ds);
+    let entry = dir.join(checksum);
+    if !ds.exists(&entry, &None).await {
+        return None;
+    }
+    let bytes = ds
+        .read_content(&dir.join(format!("{checksum}.json")), &None)
+        .await
+        .ok()?;
+    let manifest: fastn_core::Manifest = serde_json::from_slice(&bytes).ok()?;
+
+    for (path, file) in manifest.files.iter() {
+        let unchanged = match ds.read_content(&entry.join(path), &None).await {
+            Ok(content) => fastn_core::utils::generate_hash(content).eq(&file.checksum),
+            Err(_) => false,
+        };
+        if !unchanged {
+            tracing::warn!("{checksum} is corrupt in the package cache, removing it");
+            let _ = ds.remove(&entry).await;
+            return None;
+        }
+    }
+
+    Some(manifest)
+}
+
+/// Adds the package, `contents` are the files by their path in the package. They are unpacked in
+/// a temporary directory first, so a package is in the cache with all its files or not at all,
+/// even with `fastn update` running in many packages at once.
+pub(crate) async fn add(
+    ds: &fastn_ds::DocumentStore,
+    manifest: &fastn_core::Manifest,
+    contents: Vec<(String, Vec<u8>)>,
+    package_name: &str,
+) -> Result<(), fastn_update::UpdateError> {
+    let dir = cache_dir(ds);
+    let entry = dir.join(&manifest.checksum);
+    let tmp = dir.join(format!(".{}-{}", manifest.checksum, std::process::id()));
+
+    for (path, content) in contents {
+        ds.write_content(&tmp.join(path), &content).await.context(
+            fastn_update::WriteArchiveContentSnafu {
+                package: package_name,
+            },
+        )?;
+    }
+
+    if let Err(fastn_ds::RenameError::IOError(e)) = ds.rename(&tmp, &entry).await {
+        let _ = ds.remove(&tmp).await;
+        // unless some other `fastn update` has added it in the meantime
+        if !ds.exists(&entry, &None).await {
+            return Err(fastn_update::ArchiveError::WriteArchiveContent {
+                package: package_name.to_string(),
+                source: fastn_ds::WriteError::IOError(e),
+            }
+            .into());
+        }
+    }
+
+    mark_used(ds, manifest, package_name).await
+}
+
+/// Replaces `dependency_path` with the cached package.
+pub(crate) async fn populate(
+    ds: &fastn_ds::DocumentStore,
+    manifest: &fastn_core::Manifest,
+    dependency_path: &fastn_ds::Path,
+    package_name: &str,
+    check: bool,
+) -> Result<(), fastn_update::UpdateError> {
+    if check {
+        return Err(fastn_update::CheckError::WriteDuringCheck {
+            package: package_name.to_string(),
+            file: dependency_path.to_string(),
+        }
+        .into());
+    }
+
+    let entry = cache_dir(ds).join(&manifest.checksum);
+    let files = files(ds, &entry)
+        .await
+        .context(fastn_update::ReadCacheSnafu {
+            package: package_name,
+        })?;
+
+    ds.remove(dependency_path)
+        .await
+        .context(fastn_update::RemoveArchiveContentSnafu {
+            package: package_name,
+        })?;
+    for file in files {
+        let Some(path) = file.strip_prefix(&entry) else {
+            continue;
+        };
+        ds.copy(&file, &dependency_path.join(path.to_string()))
+            .await
+            .context(fastn_update::WriteArchiveContentSnafu {
+                package: package_name,
+            })?;
+    }
+
+    mark_used(ds, manifest, package_name).await
+}
+
+async fn mark_used(
+    ds: &fastn_ds::DocumentStore,
+    manifest: &fastn_core::Manifest,
+    package_name: &str,
+) -> Result<(), fastn_update::UpdateError> {
+    let path = cache_dir(ds).join(format!("{}.json", manifest.checksum));
+    // a `Manifest` is only strings and numbers, it always serializes
+    let content = serde_json::to_vec(manifest).unwrap();
+
+    Ok(ds
+        .write_content(&path, &content)
+        .await
+        .context(fastn_update::WriteArchiveContentSnafu {
+            package: package_name,
+        })?)
+}
+
+/// All the files under `dir`, hidden ones too.
+async fn files(
+    ds: &fastn_ds::DocumentStore,
+    dir: &fastn_ds::Path,
+) -> std::io::Result<Vec<fastn_ds::Path>> {
+    let mut files = vec![];
+    let mut dirs = vec![dir.clone()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = ds.read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = dir.join(entry.file_name().to_string_lossy());
+            if entry.file_type().await?.is_dir() {
+                dirs.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Removes the packages not used in `older_than`, all of them if `None`, and whatever an
+/// interrupted `fastn update` left behind. Returns the number of packages removed.
+pub async fn prune(
+    ds: &fastn_ds::DocumentStore,
+    older_than: Option<std::time::Duration>,
+) -> fastn_core::Result<usize> {
+    let dir = cache_dir(ds);
+    if !ds.exists(&dir, &None).await {
+        return Ok(0);
+    }
+
+    let is_old = |modified: Option<std::time::SystemTime>| match (older_than, modified) {
+        (None, _) => true,
+        (Some(older_than), Some(modified)) => modified
+            .elapsed()
+            .map(|elapsed| elapsed > older_than)
+            .unwrap_or(false),
+        (Some(_), None) => true,
+    };
+
+    let mut removed = 0;
+    let mut entries = ds.read_dir(&dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let path = dir.join(name.as_str());
+
+        if let Some(checksum) = name.strip_suffix(".json") {
+            // the manifest of a package that is not there anymore
+            if !ds.exists(&dir.join(checksum), &None).await {
+                ds.remove(&path).await.map_err(remove_error)?;
+            }
+            continue;
+        }
+
+        let last_used = if name.starts_with('.') {
+            // unpacked by a `fastn update` that did not finish, or is running right now
+            ds.modified(&path, &None).await.ok()
+        } else {
+            ds.modified(&dir.join(format!("{name}.json")), &None)
+                .await
+                .ok()
+        };
+        if !is_old(last_used) {
+            continue;
+        }
+
+        ds.remove(&path).await.map_err(remove_error)?;
+        if !name.starts_with('.') {
+            ds.remove(&dir.join(format!("{name}.json")))
+                .await
+                .map_err(remove_error)?;
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
+fn remove_error(e: fastn_ds::RemoveError) -> fastn_core::Error {
+    match e {
+        fastn_ds::RemoveError::IOError(e) => e.into(),
+    }
+}
@@ -0,0 +1,464 @@
+//! `fastn add` and `fastn remove`: edit the `-- fastn.dependency:` sections of `FASTN.ftd` in
+//! place, keeping the rest of the file as it is, then resolve the dependencies again.
+
+use snafu::ResultExt;
+
+const FASTN_FILE: &str = "FASTN.ftd";
+const DEPENDENCY_SECTION: &str = "fastn.dependency";
+
+/// Adds `package` to the dependencies in `FASTN.ftd`, after the last one, and runs
+/// `fastn update`. `FASTN.ftd` is left as it was if the update fails.
+pub async fn add(
+    ds: &fastn_ds::DocumentStore,
+    package: &str,
+    alias: Option<&str>,
+    provided_via: Option<&str>,
+) -> fastn_core::Result<()> {
+    let path = ds.root().join(FASTN_FILE);
+    let content = read_fastn(ds, &path).await?;
+    let new_content =
+        add_dependency(content.as_str(), package, alias, provided_via).map_err(edit_error)?;
+
+    write_and_update(ds, &path, content.as_str(), new_content.as_str()).await?;
+    println!("Added {package} to the dependencies.");
+    Ok(())
+}
+
+/// Removes `package` from the dependencies in `FASTN.ftd` and runs `fastn update`. Refuses to if
+/// any document of the package, or an auto-import, still imports from it, or from the module it is
+/// `provided-via`.
+pub async fn remove(ds: &fastn_ds::DocumentStore, package: &str) -> fastn_core::Result<()> {
+    let path = ds.root().join(FASTN_FILE);
+    let content = read_fastn(ds, &path).await?;
+    let dependency = find_dependency(content.as_str(), package)
+        .map_err(edit_error)?
+        .ok_or_else(|| {
+            edit_error(fastn_update::EditError::MissingDependency {
+                package: package.to_string(),
+            })
+        })?;
+
+    let imports = find_imports(ds, dependency.modules(package).as_slice())
+        .await
+        .map_err(edit_error)?;
+    if !imports.is_empty() {
+        return Err(edit_error(fastn_update::EditError::StillImported {
+            package: package.to_string(),
+            imports,
+        }));
+    }
+
+    let new_content = remove_lines(content.as_str(), dependency.lines);
+    write_and_update(ds, &path, content.as_str(), new_content.as_str()).await?;
+
+    // only if no other dependency depends on it too
+    let still_needed = fastn_update::Lock::read(ds)
+        .await
+        .ok()
+        .flatten()
+        .is_some_and(|lock| lock.packages.contains_key(package));
+    let package_path = ds.root().join(".packages").join(package);
+    if !still_needed && !ds.exists(&package_path.join(".is-local"), &None).await {
+        ds.remove(&package_path)
+            .await
+            .context(fastn_update::RemovePackageSnafu { package })
+            .map_err(edit_error)?;
+    }
+
+    println!("Removed {package} from the dependencies.");
+    Ok(())
+}
+
+async fn read_fastn(
+    ds: &fastn_ds::DocumentStore,
+    path: &fastn_ds::Path,
+) -> fastn_core::Result<String> {
+    ds.read_to_string(path, &None)
+        .await
+        .context(fastn_update::ReadFastnSnafu)
+        .map_err(edit_error)
+}
+
+async fn write_and_update(
+    ds: &fastn_ds::DocumentStore,
+    path: &fastn_ds::Path,
+    content: &str,
+    new_content: &str,
+) -> fastn_core::Result<()> {
+    ds.write_content(path, new_content.as_bytes())
+        .await
+        .context(fastn_update::WriteFastnSnafu)
+        .map_err(edit_error)?;
+
+    if let Err(e) = fastn_update::update(ds, false, &fastn_update::Upgrade::Locked).await {
+        ds.write_content(path, content.as_bytes())
+            .await
+            .context(fastn_update::WriteFastnSnafu)
+            .map_err(edit_error)?;
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+fn edit_error(e: fastn_update::EditError) -> fastn_core::Error {
+    fastn_core::Error::UpdateError {
+        message: e.to_string(),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct DependencySection {
+    alias: Option<String>,
+    provided_via: Option<String>,
+    /// The lines of the section, with the comments above it.
+    lines: std::ops::Range<usize>,
+}
+
+impl DependencySection {
+    /// What the documents of the package import to use the dependency `package`: the package, its
+    /// alias and the module it is `provided-via`.
+    fn modules<'a>(&'a self, package: &'a str) -> Vec<&'a str> {
+        std::iter::once(package)
+            .chain(self.alias.as_deref())
+            .chain(self.provided_via.as_deref())
+            .collect()
+    }
+}
+
+/// The line range of every top level section in `content`, commented ones too, with the comments
+/// right above it.
+fn sections(
+    content: &str,
+) -> Result<Vec<(ftd_p1::Section, std::ops::Range<usize>)>, ftd_p1::Error> {
+    let lines: Vec<&str> = content.lines().collect();
+    let sections = ftd_p1::parse(content, FASTN_FILE)?;
+
+    let starts: Vec<usize> = sections
+        .iter()
+        .map(|s| s.line_number.saturating_sub(1))
+        .collect();
+    Ok(sections
+        .into_iter()
+        .enumerate()
+        .map(|(i, section)| {
+            let mut start = starts[i];
+            let mut end = starts.get(i + 1).copied().unwrap_or(lines.len());
+            // comments and empty lines before a section go with it
+            while end > start + 1 && {
+                let line = lines[end - 1].trim();
+                line.is_empty() || line.starts_with(";;")
+            } {
+                end -= 1;
+            }
+            // and so do the comments right above it
+            while start > 0 && lines[start - 1].trim_start().starts_with(";;") {
+                start -= 1;
+            }
+            (section, start..end)
+        })
+        .collect())
+}
+
+/// `package` and its alias from the caption of a `-- fastn.dependency: <package> [as <alias>]`.
+fn dependency_name(section: &ftd_p1::Section) -> Option<(String, Option<String>)> {
+    if section.is_commented || section.name != DEPENDENCY_SECTION {
+        return None;
+    }
+    let caption = section.caption.as_ref()?.get_value(FASTN_FILE).ok()??;
+    Some(match caption.split_once(" as ") {
+        Some((package, alias)) => (package.trim().to_string(), Some(alias.trim().to_string())),
+        None => (caption.trim().to_string(), None),
+    })
+}
+
+fn find_dependency(
+    content: &str,
+    package: &str,
+) -> Result<Option<DependencySection>, fastn_update::EditError> {
+    Ok(sections(content)
+        .context(fastn_update::ParseFileSnafu { file: FASTN_FILE })?
+        .into_iter()
+        .find_map(|(section, lines)| match dependency_name(&section) {
+            Some((name, alias)) if name == package => Some(DependencySection {
+                alias,
+                provided_via: section
+                    .headers
+                    .find("provided-via")
+                    .first()
+                    .and_then(|header| header.get_value(FASTN_FILE).ok().flatten()),
+                lines,
+            }),
+            _ => None,
+        }))
+}
+
+fn add_dependency(
+    content: &str,
+    package: &str,
+    alias: Option<&str>,
+    provided_via: Option<&str>,
+) -> Result<String, fastn_update::EditError> {
+    let sections = sections(content).context(fastn_update::ParseFileSnafu { file: FASTN_FILE })?;
+    if sections
+        .iter()
+        .any(|(section, _)| matches!(dependency_name(section), Some((name, _)) if name == package))
+    {
+        return fastn_update::AlreadyADependencySnafu { package }.fail();
+    }
+
+    // after the last dependency, or else after `-- fastn.package:`
+    let after = sections
+        .iter()
+        .rfind(|(section, _)| dependency_name(section).is_some())
+        .or_else(|| {
+            sections
+                .iter()
+                .find(|(section, _)| !section.is_commented && section.name == "fastn.package")
+        })
+        .map(|(_, lines)| lines.end)
+        .ok_or(fastn_update::EditError::NoPackageSection)?;
+
+    let mut dependency = format!("-- {DEPENDENCY_SECTION}: {package}");
+    if let Some(alias) = alias {
+        dependency.push_str(format!(" as {alias}").as_str());
+    }
+    if let Some(provided_via) = provided_via {
+        dependency.push_str(format!("\nprovided-via: {provided_via}").as_str());
+    }
+
+    let mut lines: Vec<&str> = content.lines().collect();
+    let has_next = after < lines.len();
+    let mut insert = vec!["", dependency.as_str()];
+    if has_next && !lines[after].trim().is_empty() {
+        insert.push("");
+    }
+    lines.splice(after..after, insert);
+
+    Ok(join_lines(content, lines))
+}
+
+/// `content` without `range`, and without the empty line after it if it is between empty lines.
+fn remove_lines(content: &str, range: std::ops::Range<usize>) -> String {
+    let mut lines: Vec<&str> = content.lines().collect();
+    let is_empty = |i: usize, lines: &[&str]| lines.get(i).is_none_or(|l| l.trim().is_empty());
+
+    let mut end = range.end;
+    if range.start == 0 || is_empty(range.start - 1, &lines) {
+        while end < lines.len() && is_empty(end, &lines) {
+            end += 1;
+        }
+    }
+    lines.drain(range.start..end);
+
+    join_lines(content, lines)
+}
+
+fn join_lines(content: &str, lines: Vec<&str>) -> String {
+    let mut content_ = lines.join("\n");
+    if content.ends_with('\n') {
+        content_.push('\n');
+    }
+    content_
+}
+
+/// `<file>:<line>` of every import from one of `modules` in the documents of the current package,
+/// and of the auto-imports in `FASTN.ftd`.
+async fn find_imports(
+    ds: &fastn_ds::DocumentStore,
+    modules: &[&str],
+) -> Result<Vec<String>, fastn_update::EditError> {
+    let root = ds.root();
+    let ignored = [".packages".to_string(), ".build".to_string()];
+    let mut imports = vec![];
+
+    let mut files: Vec<fastn_ds::Path> = ds
+        .get_all_file_path(&root, &ignored)
+        .await
+        .into_iter()
+        .filter(|path| path.extension().as_deref() == Some("ftd"))
+        .collect();
+    files.sort_by_key(|path| path.to_string());
+
+    for path in files {
+        let file = path
+            .strip_prefix(&root)
+            .map_or_else(|| path.to_string(), |p| p.to_string());
+        let Ok(content) = ds.read_to_string(&path, &None).await else {
+            continue;
+        };
+        imports.extend(file_imports(file.as_str(), content.as_str(), modules)?);
+    }
+
+    Ok(imports)
+}
+
+/// `<file>:<line>` of every import from one of `modules` in `content`, the content of `file`.
+fn file_imports(
+    file: &str,
+    content: &str,
+    modules: &[&str],
+) -> Result<Vec<String>, fastn_update::EditError> {
+    let sections = ftd_p1::parse(content, file).context(fastn_update::ParseFileSnafu { file })?;
+
+    Ok(sections
+        .into_iter()
+        .filter(|section| {
+            !section.is_commented
+                && (section.name == "import"
+                    || (file == FASTN_FILE && section.name == "fastn.auto-import"))
+        })
+        .filter_map(|section| {
+            let caption = section.caption.as_ref()?.get_value(file).ok()??;
+            let module = caption
+                .split_once(" as ")
+                .map_or(caption.as_str(), |(module, _)| module)
+                .trim();
+            modules
+                .iter()
+                .any(|m| is_from(module, m))
+                .then(|| format!("{file}:{}", section.line_number))
+        })
+        .collect())
+}
+
+fn is_from(module: &str, package: &str) -> bool {
+    module == package
+        || module
+            .strip_prefix(package)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    const FASTN: &str = "-- import: fastn
+
+-- fastn.package: hello.com
+
+;; the design system
+-- fastn.dependency: fastn-community.github.io/doc-site as ds
+provided-via: hello.com/ds
+
+-- fastn.dependency: fifthtry.github.io/fastn-ui
+
+-- fastn.auto-import: hello.com/ds
+";
+
+    #[test]
+    fn add_dependency() {
+        assert_eq!(
+            super::add_dependency(FASTN, "a.com", Some("a"), Some("hello.com/a")).unwrap(),
+            "-- import: fastn
+
+-- fastn.package: hello.com
+
+;; the design system
+-- fastn.dependency: fastn-community.github.io/doc-site as ds
+provided-via: hello.com/ds
+
+-- fastn.dependency: fifthtry.github.io/fastn-ui
+
+-- fastn.dependency: a.com as a
+provided-via: hello.com/a
+
+-- fastn.auto-import: hello.com/ds
+"
+        );
+
+        assert_eq!(
+            super::add_dependency(
+                "-- import: fastn\n\n-- fastn.package: hello.com\n",
+                "a.com",
+                None,
+                None
+            )
+            .unwrap(),
+            "-- import: fastn\n\n-- fastn.package: hello.com\n\n-- fastn.dependency: a.com\n"
+        );
+
+        assert!(super::add_dependency(FASTN, "fifthtry.github.io/fastn-ui", None, None).is_err());
+    }
+
+    #[test]
+    fn remove_dependency() {
+        let dependency = super::find_dependency(FASTN, "fastn-community.github.io/doc-site")
+            .unwrap()
+            .unwrap();
+        assert_eq!(dependency.alias.as_deref(), Some("ds"));
+        assert_eq!(
+            super::remove_lines(FASTN, dependency.lines),
+            "-- import: fastn
+
+-- fastn.package: hello.com
+
+-- fastn.dependency: fifthtry.github.io/fastn-ui
+
+-- fastn.auto-import: hello.com/ds
+"
+        );
+
+        let dependency = super::find_dependency(FASTN, "fifthtry.github.io/fastn-ui")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            super::remove_lines(FASTN, dependency.lines),
+            "-- import: fastn
+
+-- fastn.package: hello.com
+
+;; the design system
+-- fastn.dependency: fastn-community.github.io/doc-site as ds
+provided-via: hello.com/ds
+
+-- fastn.auto-import: hello.com/ds
+"
+        );
+
+        assert!(super::find_dependency(FASTN, "a.com").unwrap().is_none());
+    }
+
+    #[test]
+    fn file_imports() {
+        // `ds` is only imported through the module it is provided via
+        let dependency = super::find_dependency(FASTN, "fastn-community.github.io/doc-site")
+            .unwrap()
+            .unwrap();
+        let modules = dependency.modules("fastn-community.github.io/doc-site");
+        assert_eq!(
+            modules,
+            ["fastn-community.github.io/doc-site", "ds", "hello.com/ds"]
+        );
+        assert_eq!(
+            super::file_imports("FASTN.ftd", FASTN, &modules).unwrap(),
+            ["FASTN.ftd:11"]
+        );
+        assert_eq!(
+            super::file_imports(
+                "index.ftd",
+                "-- import: hello.com/ds\n\n-- import: hello.com/dsx\n\n\
+                -- import: fastn-community.github.io/doc-site/page as page\n",
+                &modules
+            )
+            .unwrap(),
+            ["index.ftd:1", "index.ftd:5"]
+        );
+
+        let dependency = super::find_dependency(FASTN, "fifthtry.github.io/fastn-ui")
+            .unwrap()
+            .unwrap();
+        assert!(super::file_imports(
+            "FASTN.ftd",
+            FASTN,
+            &dependency.modules("fifthtry.github.io/fastn-ui")
+        )
+        .unwrap()
+        .is_empty());
+    }
+
+    #[test]
+    fn is_from() {
+        assert!(super::is_from("a.com", "a.com"));
+        assert!(super::is_from("a.com/x/y", "a.com"));
+        assert!(!super::is_from("a.com.au/x", "a.com"));
+    }
+}
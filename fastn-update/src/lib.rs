@@ -4,8 +4,14 @@ use snafu::prelude::*;
 
 extern crate self as fastn_update;
 
+pub mod cache;
+mod dependency;
+mod lock;
 mod utils;
 
+pub use dependency::{add, remove};
+pub use lock::{Lock, LockedPackage, Upgrade, LOCK_FILE};
+
 #[derive(Snafu, Debug)]
 pub enum ManifestError {
     #[snafu(display("Failed to download manifest.json for package '{package}'"))]
@@ -53,6 +59,21 @@ pub enum ArchiveError {
         package: String,
         source: fastn_ds::WriteError,
     },
+    #[snafu(display("Failed to remove the old content of package '{package}'"))]
+    RemoveArchiveContent {
+        package: String,
+        source: fastn_ds::RemoveError,
+    },
+    #[snafu(display("Failed to read the cached archive content for package '{package}'"))]
+    ReadCache {
+        package: String,
+        source: std::io::Error,
+    },
+    #[snafu(display("Failed to read FASTN.ftd in the archive of package '{package}'"))]
+    ReadArchiveFastn {
+        package: String,
+        source: fastn_core::Error,
+    },
 }
 
 #[derive(Snafu, Debug)]
@@ -62,11 +83,80 @@ pub enum DependencyError {
         package: String,
         source: fastn_core::Error,
     },
+    #[snafu(display(
+        "Dependency '{package}' is read from '{path}', but there is no FASTN.ftd there"
+    ))]
+    LocalPackageNotFound { package: String, path: String },
+}
+
+#[derive(Snafu, Debug)]
+pub enum LockError {
+    #[snafu(display("Failed to read {}", LOCK_FILE))]
+    ReadLock { source: fastn_ds::ReadError },
+    #[snafu(display("Failed to deserialize {}", LOCK_FILE))]
+    DeserializeLock { source: serde_json::Error },
+    #[snafu(display("Failed to serialize {}", LOCK_FILE))]
+    SerializeLock { source: serde_json::Error },
+    #[snafu(display("Failed to write {}", LOCK_FILE))]
+    WriteLock { source: fastn_ds::WriteError },
+    #[snafu(display(
+        "The archive {zip_url} of package '{package}' changed, {} has checksum {locked} but it \
+        now is {found}. Run `fastn update --upgrade {package}` to use the new version",
+        LOCK_FILE
+    ))]
+    LockedArchiveChanged {
+        package: String,
+        zip_url: String,
+        locked: String,
+        found: String,
+    },
+    #[snafu(display("'{package}' is not a dependency of this package"))]
+    NotADependency { package: String },
+    #[snafu(display("Failed to resolve the locked commit of package '{package}'"))]
+    ResolveCommit {
+        package: String,
+        source: fastn_core::Error,
+    },
+    #[snafu(display("Failed to deserialize the locked commit of package '{package}'"))]
+    DeserializeCommit {
+        package: String,
+        source: serde_json::Error,
+    },
+}
+
+#[derive(Snafu, Debug)]
+pub enum EditError {
+    #[snafu(display("Failed to read FASTN.ftd"))]
+    ReadFastn { source: fastn_ds::ReadStringError },
+    #[snafu(display("Failed to write FASTN.ftd"))]
+    WriteFastn { source: fastn_ds::WriteError },
+    #[snafu(display("Failed to parse {file}"))]
+    ParseFile { file: String, source: ftd_p1::Error },
+    #[snafu(display("No `-- fastn.package:` in FASTN.ftd"))]
+    NoPackageSection,
+    #[snafu(display("'{package}' is already a dependency of this package"))]
+    AlreadyADependency { package: String },
+    #[snafu(display("'{package}' is not a dependency in FASTN.ftd"))]
+    MissingDependency { package: String },
+    #[snafu(display(
+        "'{package}' is still imported, remove these imports first:\n{}",
+        imports.join("\n")
+    ))]
+    StillImported {
+        package: String,
+        imports: Vec<String>,
+    },
+    #[snafu(display("Failed to remove .packages/{package}"))]
+    RemovePackage {
+        package: String,
+        source: fastn_ds::RemoveError,
+    },
 }
 
 #[derive(Debug)]
 pub enum CheckError {
     WriteDuringCheck { package: String, file: String },
+    LockOutOfSync { problems: Vec<String> },
 }
 
 impl std::fmt::Display for CheckError {
@@ -84,6 +174,15 @@ impl std::fmt::Display for CheckError {
                     "Write Attempt".yellow()
                 )
             }
+            CheckError::LockOutOfSync { problems } => {
+                write!(
+                    f,
+                    "{}\n\nThe {} file is out of sync with the FASTN.ftd file.\n\n{}\n\nRun `fastn update` to update it.",
+                    "Error: Out of Sync Lock".red().bold(),
+                    LOCK_FILE,
+                    problems.join("\n")
+                )
+            }
         }
     }
 }
@@ -104,73 +203,118 @@ pub enum UpdateError {
     Config(#[from] fastn_core::config_temp::Error),
     #[error("Invalid package {0}")]
     InvalidPackage(String),
+    #[error("Lock error: {0}")]
+    Lock(#[from] LockError),
 }
 
+/// Number of packages downloaded at a time.
+const MAX_CONCURRENT_DOWNLOADS: usize = 8;
+
+/// A dependency, once it is in `.packages`.
+struct UpdatedDependency {
+    name: String,
+    manifest: fastn_core::Manifest,
+    /// `None` for `fastn_core::FASTN_UI_INTERFACE`, its dependencies are not resolved.
+    package: Option<fastn_core::Package>,
+    /// Read from a local checkout, `manifest` is empty.
+    is_local: bool,
+}
+
+/// Resolves the dependency graph one level at a time, the packages of a level are downloaded
+/// concurrently.
+#[allow(clippy::too_many_arguments)]
 async fn update_dependencies(
     ds: &fastn_ds::DocumentStore,
     packages_root: fastn_ds::Path,
     current_package: &fastn_core::Package,
     pb: &indicatif::ProgressBar,
     check: bool,
-) -> Result<usize, UpdateError> {
-    let mut stack = vec![current_package.clone()];
+    lock: Option<&Lock>,
+    upgrade: &Upgrade,
+    local_paths: &std::collections::BTreeMap<String, String>,
+) -> Result<(usize, Lock), UpdateError> {
+    use futures::{StreamExt, TryStreamExt};
+
     let mut resolved = std::collections::HashSet::new();
     resolved.insert(current_package.name.to_string());
     let mut all_packages: Vec<(String, fastn_core::Manifest)> = vec![];
-    let mut updated_packages: usize = 0;
+    let updated_packages = std::sync::atomic::AtomicUsize::new(0);
+    let mut new_lock = Lock {
+        package: current_package.name.clone(),
+        dependencies: current_package
+            .dependencies
+            .iter()
+            .map(|d| d.package.name.clone())
+            .collect(),
+        packages: Default::default(),
+    };
 
-    while let Some(package) = stack.pop() {
-        for dependency in package.dependencies {
-            if resolved.contains(&dependency.package.name) {
-                continue;
-            }
+    let mut level: Vec<fastn_core::package::dependency::Dependency> = current_package
+        .dependencies
+        .iter()
+        .filter(|d| resolved.insert(d.package.name.to_string()))
+        .cloned()
+        .collect();
 
-            let dep_package = &dependency.package;
-            let package_name = dep_package.name.clone();
-            let dependency_path = packages_root.join(&package_name);
-            if ds.exists(&dependency_path.join(".is-local"), &None).await {
-                all_packages.push((
-                    package_name.to_string(),
-                    update_local_package_manifest(&dependency_path).await?,
-                ));
-                // explicitly not updating updated_packages as we did not actually update anything
-            } else if is_fifthtry_site_package(package_name.as_str()) {
-                update_fifthtry_site_dependency(
-                    &dependency,
+    while !level.is_empty() {
+        let updated: Vec<UpdatedDependency> = futures::stream::iter(level)
+            .map(|dependency| {
+                let locked = lock
+                    .filter(|_| upgrade.is_locked(dependency.package.name.as_str()))
+                    .and_then(|lock| lock.packages.get(&dependency.package.name));
+                let local_path = local_paths
+                    .get(&dependency.package.name)
+                    .map(String::as_str);
+                update_dependency(
+                    dependency,
                     ds,
-                    packages_root.clone(),
+                    &packages_root,
                     pb,
-                    &mut updated_packages,
-                    &mut all_packages,
+                    &updated_packages,
                     check,
+                    locked,
+                    local_path,
                 )
-                .await?;
+            })
+            .buffered(MAX_CONCURRENT_DOWNLOADS)
+            .try_collect()
+            .await?;
+
+        level = vec![];
+        for dependency in updated {
+            let (checksum, zip_url) = if dependency.is_local {
+                locked_upstream(ds, lock, &dependency.name, pb).await?
             } else {
-                update_github_dependency(
-                    &dependency,
-                    ds,
-                    packages_root.clone(),
-                    pb,
-                    &mut updated_packages,
-                    &mut all_packages,
-                    check,
+                (
+                    dependency.manifest.checksum.clone(),
+                    dependency.manifest.zip_url.clone(),
                 )
-                .await?;
-            }
+            };
+            let zip_url = pinned_zip_url(ds, lock, &dependency.name, &checksum, zip_url).await?;
+            let dependencies = dependency
+                .package
+                .map(|p| p.dependencies)
+                .unwrap_or_default();
+            new_lock.packages.insert(
+                dependency.name.clone(),
+                LockedPackage {
+                    checksum,
+                    zip_url,
+                    dependencies: dependencies
+                        .iter()
+                        .map(|d| d.package.name.clone())
+                        .collect(),
+                },
+            );
+            all_packages.push((dependency.name, dependency.manifest));
 
-            if package_name.eq(&fastn_core::FASTN_UI_INTERFACE) {
-                resolved.insert(package_name.to_string());
-                continue;
+            for dependency in dependencies {
+                if resolved.insert(dependency.package.name.to_string()) {
+                    pb.inc_length(1);
+                    level.push(dependency);
+                }
             }
-
-            let dep_package =
-                utils::resolve_dependency_package(ds, &dependency, &dependency_path).await?;
-            resolved.insert(package_name.to_string());
-            pb.inc_length(1);
-            stack.push(dep_package);
         }
-
-        pb.inc(1);
     }
 
     fastn_core::ConfigTemp::write(
@@ -180,7 +324,142 @@ async fn update_dependencies(
     )
     .await?;
 
-    Ok(updated_packages)
+    Ok((updated_packages.into_inner(), new_lock))
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn update_dependency(
+    dependency: fastn_core::package::dependency::Dependency,
+    ds: &fastn_ds::DocumentStore,
+    packages_root: &fastn_ds::Path,
+    pb: &indicatif::ProgressBar,
+    updated_packages: &std::sync::atomic::AtomicUsize,
+    check: bool,
+    locked: Option<&LockedPackage>,
+    local_path: Option<&str>,
+) -> Result<UpdatedDependency, UpdateError> {
+    let package_name = dependency.package.name.clone();
+    let dependency_path = packages_root.join(&package_name);
+
+    // still linked to the local checkout it was read from, it is downloaded instead now
+    if local_path.is_none() && ds.read_link(&dependency_path).await.is_some() {
+        if check {
+            return Err(CheckError::WriteDuringCheck {
+                package: package_name,
+                file: dependency_path.to_string(),
+            }
+            .into());
+        }
+        ds.remove(&dependency_path)
+            .await
+            .context(RemoveArchiveContentSnafu {
+                package: package_name.as_str(),
+            })?;
+    }
+
+    let is_local =
+        local_path.is_some() || ds.exists(&dependency_path.join(".is-local"), &None).await;
+    let manifest = if let Some(local_path) = local_path {
+        link_local_package(ds, &dependency_path, local_path, &package_name, check).await?
+    } else if is_local {
+        // explicitly not updating updated_packages as we did not actually update anything
+        update_local_package_manifest(&dependency_path).await?
+    } else if is_fifthtry_site_package(package_name.as_str()) {
+        update_fifthtry_site_dependency(
+            &dependency,
+            ds,
+            packages_root.clone(),
+            pb,
+            updated_packages,
+            check,
+            locked,
+        )
+        .await?
+    } else {
+        update_github_dependency(
+            &dependency,
+            ds,
+            packages_root.clone(),
+            pb,
+            updated_packages,
+            check,
+            locked,
+        )
+        .await?
+    };
+
+    let package = if package_name.eq(&fastn_core::FASTN_UI_INTERFACE) {
+        None
+    } else {
+        Some(utils::resolve_dependency_package(ds, &dependency, &dependency_path).await?)
+    };
+    pb.inc(1);
+
+    Ok(UpdatedDependency {
+        name: package_name,
+        manifest,
+        package,
+        is_local,
+    })
+}
+
+/// The checksum and the archive `fastn.lock` records for a package read from a local checkout,
+/// the checkout is not published so it is what the lock had, or else what upstream has now.
+async fn locked_upstream(
+    ds: &fastn_ds::DocumentStore,
+    lock: Option<&Lock>,
+    package_name: &str,
+    pb: &indicatif::ProgressBar,
+) -> Result<(String, String), UpdateError> {
+    if let Some(locked) = lock
+        .and_then(|lock| lock.packages.get(package_name))
+        .filter(|locked| !locked.checksum.is_empty())
+    {
+        return Ok((locked.checksum.clone(), locked.zip_url.clone()));
+    }
+
+    pb.set_message(format!("Resolving upstream {package_name} for {LOCK_FILE}"));
+    let manifest = if is_fifthtry_site_package(package_name) {
+        let site_slug = package_name.trim_end_matches(".fifthtry.site");
+        let site_zip_url = fastn_core::utils::fifthtry_site_zip_url(site_slug);
+        download_unpack_zip_and_get_manifest(
+            site_zip_url.as_str(),
+            ds,
+            package_name,
+            Archive::FifthTry,
+            None,
+            pb,
+        )
+        .await?
+        .0
+    } else {
+        utils::get_manifest(ds, package_name).await?.0
+    };
+
+    Ok((manifest.checksum, manifest.zip_url))
+}
+
+/// The archive `fastn.lock` records for a package. A GitHub branch archive follows the branch, so
+/// it is pinned to the commit the branch is at now, unless the lock already pins this version.
+async fn pinned_zip_url(
+    ds: &fastn_ds::DocumentStore,
+    lock: Option<&Lock>,
+    package_name: &str,
+    checksum: &str,
+    zip_url: String,
+) -> Result<String, UpdateError> {
+    if let Some(locked) = lock
+        .and_then(|lock| lock.packages.get(package_name))
+        .filter(|locked| {
+            locked.checksum.eq(checksum) && utils::github_branch_archive(&locked.zip_url).is_none()
+        })
+    {
+        return Ok(locked.zip_url.clone());
+    }
+
+    Ok(utils::pin_github_archive(ds, package_name, &zip_url)
+        .await?
+        .unwrap_or(zip_url))
 }
 
 async fn update_github_dependency(
@@ -188,10 +467,10 @@ async fn update_github_dependency(
     ds: &fastn_ds::DocumentStore,
     packages_root: fastn_ds::Path,
     pb: &indicatif::ProgressBar,
-    updated_packages: &mut usize,
-    all_packages: &mut Vec<(String, fastn_core::Manifest)>,
+    updated_packages: &std::sync::atomic::AtomicUsize,
     check: bool,
-) -> Result<(), fastn_update::UpdateError> {
+    locked: Option<&LockedPackage>,
+) -> Result<fastn_core::Manifest, fastn_update::UpdateError> {
     let dep_package = &dependency.package;
     let package_name = dep_package.name.clone();
     let dependency_path = &packages_root.join(&package_name);
@@ -202,10 +481,66 @@ async fn update_github_dependency(
         )));
     }
 
+    let manifest_path = dependency_path.join(fastn_core::manifest::MANIFEST_FILE);
+
+    // The locked version is already here, or in the cache, no need to ask upstream
+    if let Some(locked) = locked {
+        if let Ok(existing_manifest_bytes) = ds.read_content(&manifest_path, &None).await {
+            let existing_manifest: fastn_core::Manifest =
+                utils::read_manifest(&existing_manifest_bytes, &package_name)?;
+            if existing_manifest.checksum.eq(&locked.checksum) {
+                pb.set_message(format!(
+                    "Skipping download for package \"{}\" as it is locked.",
+                    &package_name
+                ));
+                return Ok(existing_manifest);
+            }
+        }
+
+        if let Some(manifest) = cache::get(ds, &locked.checksum).await {
+            pb.set_message(format!("Copying {} from the cache", &package_name));
+            cache::populate(ds, &manifest, dependency_path, &package_name, check).await?;
+            updated_packages.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            return Ok(manifest);
+        }
+    }
+
     pb.set_message(format!("Resolving {}/manifest.json", &package_name));
-    let (manifest, manifest_bytes) = utils::get_manifest(ds, &package_name).await?;
+    let (mut manifest, manifest_bytes) = utils::get_manifest(ds, &package_name).await?;
 
-    let manifest_path = dependency_path.join(fastn_core::manifest::MANIFEST_FILE);
+    if let Some(locked) = locked {
+        if manifest.checksum.ne(&locked.checksum) {
+            // upstream moved on, the locked version is only in the archive fastn.lock points to
+            if check {
+                return Err(CheckError::WriteDuringCheck {
+                    package: package_name,
+                    file: dependency_path.to_string(),
+                }
+                .into());
+            }
+
+            pb.set_message(format!("Downloading the locked {} archive", &package_name));
+            let (manifest, mut contents) = download_unpack_zip_and_get_manifest(
+                locked.zip_url.as_str(),
+                ds,
+                package_name.as_str(),
+                Archive::GithubWithoutManifest,
+                Some(locked.checksum.as_str()),
+                pb,
+            )
+            .await?;
+            // a `Manifest` is only strings and numbers, it always serializes
+            contents.push((
+                fastn_core::manifest::MANIFEST_FILE.to_string(),
+                serde_json::to_vec(&manifest).unwrap(),
+            ));
+            cache::add(ds, &manifest, contents, &package_name).await?;
+            cache::populate(ds, &manifest, dependency_path, &package_name, check).await?;
+            updated_packages.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            return Ok(manifest);
+        }
+        manifest.zip_url = locked.zip_url.clone();
+    }
 
     // Download the archive if:
     // 1. The package does not yet exist
@@ -231,27 +566,40 @@ async fn update_github_dependency(
             "Skipping download for package \"{}\" as it already exists.",
             &package_name
         ));
-    } else {
-        pb.set_message(format!("Downloading {} archive", &package_name));
+        return Ok(manifest);
+    }
 
-        download_unpack_zip_and_get_manifest(
-            dependency_path.clone(),
+    if cache::get(ds, &manifest.checksum).await.is_none() {
+        // nothing is written during check, so no need to download either
+        if check {
+            return Err(CheckError::WriteDuringCheck {
+                package: package_name,
+                file: dependency_path.to_string(),
+            }
+            .into());
+        }
+
+        pb.set_message(format!("Downloading {} archive", &package_name));
+        let (_, mut contents) = download_unpack_zip_and_get_manifest(
             manifest.zip_url.as_str(),
             ds,
             package_name.as_str(),
-            Some(&manifest),
+            Archive::Github(&manifest),
+            None,
             pb,
-            check,
         )
         .await?;
-
-        write_archive_content(ds, &manifest_path, &manifest_bytes, &package_name, check).await?;
-
-        *updated_packages += 1;
+        contents.push((
+            fastn_core::manifest::MANIFEST_FILE.to_string(),
+            manifest_bytes.to_vec(),
+        ));
+        cache::add(ds, &manifest, contents, &package_name).await?;
     }
 
-    all_packages.push((package_name.to_string(), manifest));
-    Ok(())
+    cache::populate(ds, &manifest, dependency_path, &package_name, check).await?;
+    updated_packages.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+
+    Ok(manifest)
 }
 
 async fn update_fifthtry_site_dependency(
@@ -259,10 +607,10 @@ async fn update_fifthtry_site_dependency(
     ds: &fastn_ds::DocumentStore,
     packages_root: fastn_ds::Path,
     pb: &indicatif::ProgressBar,
-    updated_packages: &mut usize,
-    all_packages: &mut Vec<(String, fastn_core::Manifest)>,
+    updated_packages: &std::sync::atomic::AtomicUsize,
     check: bool,
-) -> Result<(), fastn_update::UpdateError> {
+    locked: Option<&LockedPackage>,
+) -> Result<fastn_core::Manifest, fastn_update::UpdateError> {
     let dep_package = &dependency.package;
     let package_name = dep_package.name.clone();
 
@@ -276,20 +624,102 @@ async fn update_fifthtry_site_dependency(
     let dependency_path = &packages_root.join(&package_name);
     let site_zip_url = fastn_core::utils::fifthtry_site_zip_url(site_slug);
 
-    let manifest = download_unpack_zip_and_get_manifest(
-        dependency_path.clone(),
-        site_zip_url.as_str(),
+    // fifthtry sites have no manifest.json, the one computed from the archive last time is in
+    // `.fastn/config.json`
+    if let Some(locked) = locked {
+        if let Some(existing_manifest) = fastn_core::ConfigTemp::read(ds, &None)
+            .await
+            .ok()
+            .and_then(|config| config.all_packages.get(&package_name).cloned())
+        {
+            if existing_manifest.checksum.eq(&locked.checksum)
+                && ds.exists(dependency_path, &None).await
+            {
+                pb.set_message(format!(
+                    "Skipping download for package \"{}\" as it is locked.",
+                    &package_name
+                ));
+                return Ok(existing_manifest);
+            }
+        }
+
+        if let Some(manifest) = cache::get(ds, &locked.checksum).await {
+            pb.set_message(format!("Copying {} from the cache", &package_name));
+            cache::populate(ds, &manifest, dependency_path, &package_name, check).await?;
+            updated_packages.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            return Ok(manifest);
+        }
+    }
+
+    if check {
+        return Err(CheckError::WriteDuringCheck {
+            package: package_name,
+            file: dependency_path.to_string(),
+        }
+        .into());
+    }
+
+    pb.set_message(format!("Downloading {} archive", &package_name));
+    let (manifest, contents) = download_unpack_zip_and_get_manifest(
+        locked.map_or(site_zip_url.as_str(), |l| l.zip_url.as_str()),
         ds,
         package_name.as_str(),
-        None,
+        Archive::FifthTry,
+        locked.map(|l| l.checksum.as_str()),
         pb,
-        check,
     )
     .await?;
 
-    all_packages.push((package_name.to_string(), manifest));
-    *updated_packages += 1;
-    Ok(())
+    // the archive may not have changed since some other project downloaded it
+    if cache::get(ds, &manifest.checksum).await.is_none() {
+        cache::add(ds, &manifest, contents, &package_name).await?;
+    }
+    cache::populate(ds, &manifest, dependency_path, &package_name, check).await?;
+    updated_packages.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+
+    Ok(manifest)
+}
+
+/// Makes `.packages/<package>` a link to the local checkout at `local_path`, the package is read
+/// from there, as it is, by everything that reads `.packages`.
+async fn link_local_package(
+    ds: &fastn_ds::DocumentStore,
+    dependency_path: &fastn_ds::Path,
+    local_path: &str,
+    package_name: &str,
+    check: bool,
+) -> Result<fastn_core::Manifest, fastn_update::UpdateError> {
+    let target = ds.root().join(local_path);
+    if !ds.exists(&target.join("FASTN.ftd"), &None).await {
+        return Err(DependencyError::LocalPackageNotFound {
+            package: package_name.to_string(),
+            path: local_path.to_string(),
+        }
+        .into());
+    }
+
+    if ds.read_link(dependency_path).await.as_ref() != Some(&target) {
+        if check {
+            return Err(CheckError::WriteDuringCheck {
+                package: package_name.to_string(),
+                file: dependency_path.to_string(),
+            }
+            .into());
+        }
+
+        ds.remove(dependency_path)
+            .await
+            .context(RemoveArchiveContentSnafu {
+                package: package_name,
+            })?;
+        ds.symlink_dir(&target, dependency_path)
+            .await
+            .context(WriteArchiveContentSnafu {
+                package: package_name,
+            })?;
+    }
+
+    update_local_package_manifest(dependency_path).await
 }
 
 async fn update_local_package_manifest(
@@ -302,21 +732,35 @@ async fn update_local_package_manifest(
     })
 }
 
+/// How to read the files of a package archive, and its checksum.
+#[derive(Clone, Copy)]
+enum Archive<'a> {
+    /// A github archive, the files are those in the package's manifest.json.
+    Github(&'a fastn_core::Manifest),
+    /// A github archive of a version that is no longer the one in manifest.json. The files and
+    /// the checksum are computed from the archive the way `fastn build` computes them, see
+    /// `is_manifest_file`.
+    GithubWithoutManifest,
+    /// A fifthtry site archive, every file is in the package and the checksum is of all of them,
+    /// in archive order.
+    FifthTry,
+}
+
+/// The manifest, and the content of every file of the package, by its path in the package.
 async fn download_unpack_zip_and_get_manifest(
-    dependency_path: fastn_ds::Path,
     zip_url: &str,
     ds: &fastn_ds::DocumentStore,
     package_name: &str,
-    manifest: Option<&fastn_core::Manifest>,
+    kind: Archive<'_>,
+    expected_checksum: Option<&str>,
     pb: &indicatif::ProgressBar,
-    check: bool,
-) -> Result<fastn_core::Manifest, fastn_update::UpdateError> {
+) -> Result<(fastn_core::Manifest, Vec<(String, Vec<u8>)>), fastn_update::UpdateError> {
     use sha2::digest::FixedOutput;
     use sha2::Digest;
 
-    let mut files: std::collections::BTreeMap<String, fastn_core::manifest::File> = match manifest {
-        Some(manifest) => manifest.files.clone(),
-        None => Default::default(),
+    let mut files: std::collections::BTreeMap<String, fastn_core::manifest::File> = match kind {
+        Archive::Github(manifest) => manifest.files.clone(),
+        _ => Default::default(),
     };
 
     let mut archive = utils::download_archive(ds, zip_url.to_string())
@@ -326,6 +770,7 @@ async fn download_unpack_zip_and_get_manifest(
         })?;
 
     let mut hasher = sha2::Sha256::new();
+    let mut contents = vec![];
     for i in 0..archive.len() {
         let mut entry = archive.by_index(i).context(ArchiveEntryReadSnafu {
             package: package_name,
@@ -344,7 +789,6 @@ async fn download_unpack_zip_and_get_manifest(
             let path_string = path.to_string_lossy().into_owned();
             let path_normalized = path_string.replace('\\', "/");
 
-            let file_name = path_normalized.clone();
             let file_hash = fastn_core::utils::generate_hash(buffer.clone());
             let file_size = buffer.len();
 
@@ -352,79 +796,151 @@ async fn download_unpack_zip_and_get_manifest(
             // `package-doc-<commit-id>` which contains all files, so path for `FASTN.ftd` becomes
             // `package-doc-<commit-id>/FASTN.ftd` while fifthtry package zip doesn't have any
             // such folder, so path becomes `FASTN.ftd`
-            let path_without_prefix = if manifest.is_some() {
-                // For github
-                match path_normalized.split_once('/') {
-                    Some((_, path)) => path,
-                    None => &path_normalized,
+            let path_without_prefix = match kind {
+                Archive::Github(_) | Archive::GithubWithoutManifest => {
+                    match path_normalized.split_once('/') {
+                        Some((_, path)) => path,
+                        None => &path_normalized,
+                    }
                 }
-            } else {
-                // For fifthtry packages
-                &path_normalized
+                Archive::FifthTry => &path_normalized,
             };
 
-            if manifest.is_some() {
-                if !files.contains_key(path_without_prefix) {
-                    continue;
+            match kind {
+                Archive::Github(_) => {
+                    if !files.contains_key(path_without_prefix) {
+                        continue;
+                    }
+                }
+                // the files are known once FASTN.ftd, with `fastn.ignore`, is read
+                Archive::GithubWithoutManifest => {}
+                Archive::FifthTry => {
+                    hasher.update(&buffer);
+                    // Creating file entry for manifest using archive files
+                    files.insert(
+                        path_normalized.clone(),
+                        fastn_core::manifest::File::new(
+                            path_normalized.clone(),
+                            file_hash,
+                            file_size,
+                        ),
+                    );
                 }
-            } else {
-                hasher.update(&buffer);
-                // Creating file entry for manifest using archive files
-                files.insert(
-                    file_name.clone(),
-                    fastn_core::manifest::File::new(file_name, file_hash, file_size),
-                );
             }
-            let output_path = &dependency_path.join(path_without_prefix);
-            write_archive_content(ds, output_path, &buffer, package_name, check).await?;
+            contents.push((path_without_prefix.to_string(), buffer));
             pb.tick();
         }
     }
 
-    let manifest = match manifest {
-        Some(manifest) => manifest.clone(),
-        None => {
+    let manifest = match kind {
+        Archive::Github(manifest) => manifest.clone(),
+        Archive::GithubWithoutManifest => {
+            let ignored_paths = match contents.iter().find(|(path, _)| path.eq("FASTN.ftd")) {
+                Some((_, fastn)) => {
+                    utils::package_from_ftd(ds, String::from_utf8_lossy(fastn).as_ref())
+                        .context(ReadArchiveFastnSnafu {
+                            package: package_name,
+                        })?
+                        .ignored_paths
+                }
+                None => vec![],
+            };
+            contents.retain(|(path, _)| is_manifest_file(path, &ignored_paths));
+            for (path, content) in &contents {
+                files.insert(
+                    path.clone(),
+                    fastn_core::manifest::File::new(
+                        path.clone(),
+                        fastn_core::utils::generate_hash(content),
+                        content.len(),
+                    ),
+                );
+            }
+
+            // `fastn build` hashes the files in the order of their path
+            contents.sort_by(|a, b| a.0.cmp(&b.0));
+            for (_, content) in &contents {
+                hasher.update(content);
+            }
+            let checksum = format!("{:X}", hasher.finalize_fixed());
+            fastn_core::Manifest::new(files, zip_url.to_string(), checksum)
+        }
+        Archive::FifthTry => {
             let checksum = format!("{:X}", hasher.finalize_fixed());
             fastn_core::Manifest::new(files, zip_url.to_string(), checksum)
         }
     };
 
-    Ok(manifest)
+    if let Some(expected_checksum) = expected_checksum {
+        if manifest.checksum.ne(expected_checksum) {
+            return Err(LockError::LockedArchiveChanged {
+                package: package_name.to_string(),
+                zip_url: zip_url.to_string(),
+                locked: expected_checksum.to_string(),
+                found: manifest.checksum,
+            }
+            .into());
+        }
+    }
+
+    Ok((manifest, contents))
+}
+
+/// If `fastn build` puts the file at `path` in manifest.json, it skips hidden files and what
+/// `Config::get_all_file_paths` ignores, `ignored_paths` are the package's `fastn.ignore`.
+fn is_manifest_file(path: &str, ignored_paths: &[String]) -> bool {
+    const IGNORED: [&str; 5] = [
+        "fastn",
+        "rust-toolchain",
+        "_tests",
+        fastn_core::FASTN_LOCAL_FILE,
+        fastn_core::manifest::MANIFEST_FILE,
+    ];
+
+    !path.split('/').any(|part| part.starts_with('.'))
+        && path
+            .split('/')
+            .next()
+            .is_some_and(|first| !IGNORED.contains(&first))
+        && !fastn_ds::is_ignored(path, ignored_paths)
 }
 
 fn is_fifthtry_site_package(package_name: &str) -> bool {
     package_name.ends_with(".fifthtry.site")
 }
 
-async fn write_archive_content(
+/// Downloads the dependencies into `.packages`, at the versions in `fastn.lock` unless `upgrade`
+/// says otherwise, and writes the versions used to `fastn.lock`. With `check`, exits with an error
+/// instead of writing anything that is out of sync.
+#[tracing::instrument(skip_all)]
+pub async fn update(
     ds: &fastn_ds::DocumentStore,
-    output_path: &fastn_ds::Path,
-    buffer: &[u8],
-    package_name: &str,
     check: bool,
-) -> Result<(), UpdateError> {
-    if check {
-        return Err(UpdateError::Check(CheckError::WriteDuringCheck {
-            package: package_name.to_string(),
-            file: output_path.to_string(),
-        }));
-    }
-
-    Ok(ds
-        .write_content(output_path, buffer)
-        .await
-        .context(WriteArchiveContentSnafu {
-            package: package_name,
-        })?)
-}
-
-#[tracing::instrument(skip_all)]
-pub async fn update(ds: &fastn_ds::DocumentStore, check: bool) -> fastn_core::Result<()> {
+    upgrade: &Upgrade,
+) -> fastn_core::Result<()> {
     let packages_root = ds.root().join(".packages");
     let current_package = utils::read_current_package(ds).await?;
+    let local_paths = current_package.local_paths(ds).await?;
+    let lock = Lock::read(ds)
+        .await
+        .map_err(|e| fastn_core::Error::UpdateError {
+            message: e.to_string(),
+        })?;
+
+    if check {
+        let problems = match lock.as_ref() {
+            Some(lock) => lock.problems(&current_package),
+            None if current_package.dependencies.is_empty() => vec![],
+            None => vec![format!("{LOCK_FILE} not found")],
+        };
+        if !problems.is_empty() {
+            eprintln!("{}", CheckError::LockOutOfSync { problems });
+            std::process::exit(7);
+        }
+    }
 
     if current_package.dependencies.is_empty() {
-        println!("No dependencies to update.");
+        fastn_core::progress!("No dependencies to update.");
 
         // Creating Empty config file for packages with no dependencies
         fastn_core::ConfigTemp::write(ds, current_package.name.clone(), Default::default()).await?;
@@ -441,27 +957,320 @@ pub async fn update(ds: &fastn_ds::DocumentStore, check: bool) -> fastn_core::Re
     pb.set_style(spinner_style);
     pb.set_prefix("Updating dependencies");
 
-    let updated_packages =
-        match update_dependencies(ds, packages_root, &current_package, &pb, check).await {
-            Ok(n) => n,
-            Err(UpdateError::Check(e)) => {
-                eprintln!("{}", e);
-                std::process::exit(7);
-            }
-            Err(e) => {
-                return Err(fastn_core::Error::UpdateError {
-                    message: e.to_string(),
-                });
+    let result = update_dependencies(
+        ds,
+        packages_root,
+        &current_package,
+        &pb,
+        check,
+        lock.as_ref(),
+        upgrade,
+        &local_paths,
+    )
+    .await
+    .and_then(|(n, new_lock)| {
+        if let Upgrade::Package(package) = upgrade {
+            if !new_lock.packages.contains_key(package) {
+                return Err(LockError::NotADependency {
+                    package: package.to_string(),
+                }
+                .into());
             }
-        };
+        }
+        Ok((n, new_lock))
+    });
+
+    let (updated_packages, new_lock) = match result {
+        Ok(v) => v,
+        Err(UpdateError::Check(e)) => {
+            eprintln!("{}", e);
+            std::process::exit(7);
+        }
+        Err(e) => {
+            return Err(fastn_core::Error::UpdateError {
+                message: e.to_string(),
+            });
+        }
+    };
 
     pb.finish_and_clear();
 
+    if lock.as_ref() != Some(&new_lock) {
+        if check {
+            eprintln!(
+                "{}",
+                CheckError::LockOutOfSync {
+                    problems: vec![format!("{LOCK_FILE} does not have the resolved versions")]
+                }
+            );
+            std::process::exit(7);
+        }
+
+        new_lock
+            .write(ds)
+            .await
+            .map_err(|e| fastn_core::Error::UpdateError {
+                message: e.to_string(),
+            })?;
+    }
+
     match updated_packages {
-        0 => println!("No packages updated."),
-        1 => println!("Updated package dependency."),
-        n => println!("Updated {} dependencies.", n),
+        0 => fastn_core::progress!("No packages updated."),
+        1 => fastn_core::progress!("Updated package dependency."),
+        n => fastn_core::progress!("Updated {} dependencies.", n),
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn is_manifest_file() {
+        assert!(super::is_manifest_file("FASTN.ftd", &[]));
+        assert!(super::is_manifest_file("blog/index.ftd", &[]));
+        assert!(!super::is_manifest_file(
+            ".github/workflows/deploy.yml",
+            &[]
+        ));
+        assert!(!super::is_manifest_file("blog/.draft.ftd", &[]));
+        assert!(!super::is_manifest_file("manifest.json", &[]));
+        assert!(!super::is_manifest_file("_tests/01-hello.test.ftd", &[]));
+        assert!(super::is_manifest_file("blog/manifest.json", &[]));
+
+        // `-- fastn.ignore: drafts` and `-- fastn.ignore: *.md`
+        let ignored_paths = ["drafts".to_string(), "*.md".to_string()];
+        assert!(!super::is_manifest_file("drafts/post.ftd", &ignored_paths));
+        assert!(!super::is_manifest_file(
+            "blog/drafts/post.ftd",
+            &ignored_paths
+        ));
+        assert!(!super::is_manifest_file("README.md", &ignored_paths));
+        assert!(!super::is_manifest_file("blog/notes.md", &ignored_paths));
+        assert!(super::is_manifest_file("blog/post.ftd", &ignored_paths));
+        assert!(super::is_manifest_file("drafts.ftd", &ignored_paths));
+    }
+
+    const BRANCH_ZIP_URL: &str = "https://codeload.github.com/hello/world/zip/refs/heads/main";
+    const PINNED_ZIP_URL: &str = "https://codeload.github.com/hello/world/zip/c0ffee";
+
+    #[tokio::test]
+    async fn pinned_zip_url() {
+        let (ds, http_mock) = document_store("pinned_zip_url");
+        http_mock.add(fastn_ds::http_mock::Mock {
+            times: Some(1),
+            ..mock(
+                "https://api.github.com/repos/hello/world/commits/main",
+                r#"{"sha": "c0ffee", "commit": {}}"#.into(),
+            )
+        });
+
+        assert_eq!(
+            super::pinned_zip_url(
+                &ds,
+                None,
+                "hello.github.io/world",
+                "A1",
+                BRANCH_ZIP_URL.into()
+            )
+            .await
+            .unwrap(),
+            PINNED_ZIP_URL
+        );
+        assert_eq!(
+            super::pinned_zip_url(
+                &ds,
+                None,
+                "hello.fifthtry.site",
+                "A1",
+                "https://x.com/a".into()
+            )
+            .await
+            .unwrap(),
+            "https://x.com/a"
+        );
+
+        // an already pinned version is kept, the branch is not resolved again
+        let lock = lock(PINNED_ZIP_URL);
+        assert_eq!(
+            super::pinned_zip_url(
+                &ds,
+                Some(&lock),
+                "hello.github.io/world",
+                "A1",
+                BRANCH_ZIP_URL.into()
+            )
+            .await
+            .unwrap(),
+            PINNED_ZIP_URL
+        );
+        assert_eq!(http_mock.expectations()[0].2, 1);
+    }
+
+    #[tokio::test]
+    async fn locked_package_is_downloaded_from_the_pinned_archive() {
+        use sha2::digest::FixedOutput;
+        use sha2::Digest;
+
+        let content = b"-- import: fastn\n\n-- fastn.package: hello.github.io/world\n\n\
+            -- fastn.ignore: *.md\n";
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+        for (path, content) in [("FASTN.ftd", &content[..]), ("README.md", b"# world")] {
+            zip.start_file(
+                format!("world-c0ffee/{path}"),
+                zip::write::SimpleFileOptions::default(),
+            )
+            .unwrap();
+            std::io::Write::write_all(&mut zip, content).unwrap();
+        }
+        let zip = zip.finish().unwrap().into_inner();
+        let checksum = format!(
+            "{:X}",
+            sha2::Sha256::new().chain_update(content).finalize_fixed()
+        );
+
+        let (ds, http_mock) = document_store("locked_package");
+        // upstream moved on since the package was locked
+        http_mock.add(mock(
+            "https://hello.github.io/world/manifest.json",
+            serde_json::to_vec(&fastn_core::Manifest::new(
+                Default::default(),
+                BRANCH_ZIP_URL.to_string(),
+                "B2".to_string(),
+            ))
+            .unwrap()
+            .into(),
+        ));
+        http_mock.add(fastn_ds::http_mock::Mock {
+            times: Some(1),
+            ..mock(PINNED_ZIP_URL, zip.into())
+        });
+
+        let mut locked = lock(PINNED_ZIP_URL)
+            .packages
+            .remove("hello.github.io/world");
+        if let Some(locked) = locked.as_mut() {
+            locked.checksum.clone_from(&checksum);
+        }
+        let manifest = super::update_github_dependency(
+            &dependency("hello.github.io/world"),
+            &ds,
+            ds.root().join(".packages"),
+            &indicatif::ProgressBar::hidden(),
+            &Default::default(),
+            false,
+            locked.as_ref(),
+        )
+        .await;
+
+        let cache_entry = super::cache::cache_dir(&ds).join(&checksum);
+        let _ = ds.remove(&cache_entry).await;
+        let _ = ds.remove(&cache_entry.with_extension("json")).await;
+        let _ = ds.remove(&ds.root()).await;
+
+        let manifest = manifest.unwrap();
+        assert_eq!(manifest.checksum, checksum);
+        assert_eq!(manifest.zip_url, PINNED_ZIP_URL);
+        assert_eq!(manifest.files.keys().collect::<Vec<_>>(), ["FASTN.ftd"]);
+        assert_eq!(http_mock.expectations()[0].2, 1);
+    }
+
+    #[tokio::test]
+    async fn stale_local_link_is_removed() {
+        let (ds, http_mock) = document_store("stale_local_link");
+        http_mock.add(fastn_ds::http_mock::Mock {
+            status: 404,
+            ..mock("https://hello.github.io/world/manifest.json", "".into())
+        });
+        let checkout = ds.root().join("world");
+        let checkout_fastn = checkout.join("FASTN.ftd");
+        ds.write_content(&checkout_fastn, b"-- import: fastn")
+            .await
+            .unwrap();
+        let packages_root = ds.root().join(".packages");
+        let link = packages_root.join("hello.github.io/world");
+        ds.symlink_dir(&checkout, &link).await.unwrap();
+
+        let pb = indicatif::ProgressBar::hidden();
+        let updated_packages = Default::default();
+        let update = |check| {
+            super::update_dependency(
+                dependency("hello.github.io/world"),
+                &ds,
+                &packages_root,
+                &pb,
+                &updated_packages,
+                check,
+                None,
+                None,
+            )
+        };
+        let checked = update(true).await;
+        let linked_after_check = ds.read_link(&link).await.is_some();
+        // the package is downloaded, not written in the checkout
+        let updated = update(false).await;
+        let linked_after_update = ds.read_link(&link).await.is_some();
+        let checkout_kept = ds.exists(&checkout_fastn, &None).await;
+        let _ = ds.remove(&ds.root()).await;
+
+        assert!(matches!(
+            checked,
+            Err(super::UpdateError::Check(
+                super::CheckError::WriteDuringCheck { .. }
+            ))
+        ));
+        assert!(linked_after_check);
+        assert!(matches!(updated, Err(super::UpdateError::Manifest(_))));
+        assert!(!linked_after_update);
+        assert!(checkout_kept);
+    }
+
+    fn dependency(name: &str) -> fastn_core::package::dependency::Dependency {
+        fastn_core::package::dependency::Dependency {
+            package: fastn_core::Package::new(name),
+            version: None,
+            notes: None,
+            alias: None,
+            implements: vec![],
+            provided_via: None,
+            required_as: None,
+            path: None,
+        }
+    }
+
+    fn document_store(name: &str) -> (fastn_ds::DocumentStore, fastn_ds::http_mock::HttpMock) {
+        let root = std::env::temp_dir().join(format!("fastn-update-{name}-{}", std::process::id()));
+        let http_mock = fastn_ds::http_mock::HttpMock::default();
+        let ds = fastn_ds::DocumentStore::new(root.to_str().unwrap(), Default::default())
+            .add_http_mock(http_mock.clone());
+        (ds, http_mock)
+    }
+
+    fn mock(url: &str, response: bytes::Bytes) -> fastn_ds::http_mock::Mock {
+        fastn_ds::http_mock::Mock {
+            method: None,
+            url: url.to_string(),
+            body: None,
+            status: 200,
+            content_type: "application/octet-stream".to_string(),
+            response,
+            times: None,
+        }
+    }
+
+    fn lock(zip_url: &str) -> super::Lock {
+        super::Lock {
+            package: "hello.com".to_string(),
+            dependencies: vec!["hello.github.io/world".to_string()],
+            packages: [(
+                "hello.github.io/world".to_string(),
+                super::LockedPackage {
+                    checksum: "A1".to_string(),
+                    zip_url: zip_url.to_string(),
+                    dependencies: vec![],
+                },
+            )]
+            .into(),
+        }
+    }
+}
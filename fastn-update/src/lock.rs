@@ -0,0 +1,167 @@
+//! `fastn.lock` records what every dependency, direct or not, resolved to: the checksum of its
+//! `manifest.json` and the archive it was downloaded from. `fastn update` (and so `fastn build`,
+//! `fastn serve` and `fastn test`) keeps locked packages at their locked checksum, only
+//! `fastn update --upgrade [package]` moves them to what upstream has now.
+
+use snafu::ResultExt;
+
+pub const LOCK_FILE: &str = "fastn.lock";
+
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Lock {
+    pub package: String,
+    /// The dependencies in `FASTN.ftd`
+    pub dependencies: Vec<String>,
+    pub packages: std::collections::BTreeMap<String, LockedPackage>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct LockedPackage {
+    /// For packages read from a local checkout, the version upstream had when they were locked.
+    pub checksum: String,
+    /// For GitHub packages, the archive of the locked commit, not of the branch.
+    pub zip_url: String,
+    pub dependencies: Vec<String>,
+}
+
+impl Lock {
+    pub async fn read(
+        ds: &fastn_ds::DocumentStore,
+    ) -> Result<Option<Lock>, fastn_update::LockError> {
+        let bytes = match ds.read_content(&ds.root().join(LOCK_FILE), &None).await {
+            Ok(v) => v,
+            Err(fastn_ds::ReadError::NotFound(_)) => return Ok(None),
+            Err(e) => return Err(e).context(fastn_update::ReadLockSnafu),
+        };
+
+        serde_json::from_slice(&bytes).context(fastn_update::DeserializeLockSnafu)
+    }
+
+    pub async fn write(&self, ds: &fastn_ds::DocumentStore) -> Result<(), fastn_update::LockError> {
+        let mut content =
+            serde_json::to_string_pretty(self).context(fastn_update::SerializeLockSnafu)?;
+        content.push('\n');
+
+        ds.write_content(&ds.root().join(LOCK_FILE), content.as_bytes())
+            .await
+            .context(fastn_update::WriteLockSnafu)
+    }
+
+    /// The ways the lock disagrees with `FASTN.ftd` of `package`, without looking at upstream.
+    pub fn problems(&self, package: &fastn_core::Package) -> Vec<String> {
+        let mut problems = vec![];
+
+        if self.package != package.name {
+            problems.push(format!(
+                "fastn.lock is for '{}', FASTN.ftd is for '{}'",
+                self.package, package.name
+            ));
+        }
+
+        for dependency in package.dependencies.iter() {
+            if !self.dependencies.contains(&dependency.package.name) {
+                problems.push(format!(
+                    "'{}' is a dependency in FASTN.ftd, but not in fastn.lock",
+                    dependency.package.name
+                ));
+            }
+        }
+        for name in self.dependencies.iter() {
+            if !package.dependencies.iter().any(|d| d.package.name.eq(name)) {
+                problems.push(format!(
+                    "'{name}' is a dependency in fastn.lock, but not in FASTN.ftd"
+                ));
+            }
+        }
+
+        let mut reachable = std::collections::BTreeSet::new();
+        let mut stack: Vec<&String> = self.dependencies.iter().collect();
+        while let Some(name) = stack.pop() {
+            if !reachable.insert(name.as_str()) {
+                continue;
+            }
+            match self.packages.get(name) {
+                Some(locked) => stack.extend(locked.dependencies.iter()),
+                None => problems.push(format!("'{name}' is not locked in fastn.lock")),
+            }
+        }
+        for name in self.packages.keys() {
+            if !reachable.contains(name.as_str()) {
+                problems.push(format!(
+                    "'{name}' in fastn.lock is not a dependency anymore"
+                ));
+            }
+        }
+
+        problems
+    }
+}
+
+/// Which locked packages `fastn update` moves to their current `manifest.json`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Upgrade {
+    /// Keep every locked package as locked, only resolve the ones not in the lock.
+    #[default]
+    Locked,
+    All,
+    Package(String),
+}
+
+impl Upgrade {
+    pub(crate) fn is_locked(&self, package: &str) -> bool {
+        match self {
+            Upgrade::Locked => true,
+            Upgrade::All => false,
+            Upgrade::Package(p) => p != package,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    fn locked(dependencies: &[&str]) -> super::LockedPackage {
+        super::LockedPackage {
+            checksum: "C".to_string(),
+            zip_url: "https://example.com/a.zip".to_string(),
+            dependencies: dependencies.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    #[test]
+    fn problems() {
+        let mut package = fastn_core::Package::new("site.com");
+        package.dependencies = vec![fastn_core::package::dependency::Dependency {
+            package: fastn_core::Package::new("a.com"),
+            version: None,
+            notes: None,
+            alias: None,
+            implements: vec![],
+            provided_via: None,
+            required_as: None,
+            path: None,
+        }];
+
+        let mut lock = super::Lock {
+            package: "site.com".to_string(),
+            dependencies: vec!["a.com".to_string()],
+            packages: std::collections::BTreeMap::from([
+                ("a.com".to_string(), locked(&["b.com"])),
+                ("b.com".to_string(), locked(&[])),
+            ]),
+        };
+        assert!(lock.problems(&package).is_empty());
+
+        lock.packages.remove("b.com");
+        lock.packages.insert("c.com".to_string(), locked(&[]));
+        lock.dependencies.push("c.com".to_string());
+        assert_eq!(
+            lock.problems(&package),
+            vec![
+                "'c.com' is a dependency in fastn.lock, but not in FASTN.ftd",
+                "'b.com' is not locked in fastn.lock",
+            ]
+        );
+    }
+}
@@ -5,8 +5,16 @@ pub async fn from_fastn_doc(
     fastn_path: &fastn_ds::Path,
 ) -> fastn_core::Result<fastn_core::Package> {
     let doc = ds.read_to_string(fastn_path, &None).await?;
+    package_from_ftd(ds, doc.as_str())
+}
+
+/// The package defined by `doc`, the content of a FASTN.ftd.
+pub(crate) fn package_from_ftd(
+    ds: &fastn_ds::DocumentStore,
+    doc: &str,
+) -> fastn_core::Result<fastn_core::Package> {
     let lib = fastn_core::FastnLibrary::default();
-    let fastn_doc = match fastn_core::doc::parse_ftd("fastn", doc.as_str(), &lib) {
+    let fastn_doc = match fastn_core::doc::parse_ftd("fastn", doc, &lib) {
         Ok(v) => Ok(v),
         Err(e) => Err(fastn_core::Error::PackageError {
             message: format!("failed to parse FASTN.ftd 3: {:?}", &e),
@@ -73,6 +81,49 @@ pub(crate) async fn get_manifest(
     Ok((manifest, manifest_bytes))
 }
 
+/// `<user>/<repo>` and the branch of a `https://codeload.github.com/<user>/<repo>/zip/refs/heads/<branch>`
+/// archive, the url `fastn build` puts in manifest.json for GitHub packages.
+pub(crate) fn github_branch_archive(zip_url: &str) -> Option<(&str, &str)> {
+    zip_url
+        .strip_prefix("https://codeload.github.com/")?
+        .split_once("/zip/refs/heads/")
+}
+
+/// The archive of the commit the branch of a GitHub branch archive is at now,
+/// `https://codeload.github.com/<user>/<repo>/zip/<sha>`. `None` for any other archive.
+pub(crate) async fn pin_github_archive(
+    ds: &fastn_ds::DocumentStore,
+    package_name: &str,
+    zip_url: &str,
+) -> Result<Option<String>, fastn_update::LockError> {
+    #[derive(serde::Deserialize)]
+    struct Commit {
+        sha: String,
+    }
+
+    let Some((repository, branch)) = github_branch_archive(zip_url) else {
+        return Ok(None);
+    };
+
+    let commit_bytes = fastn_core::http::http_get(
+        ds,
+        &format!("https://api.github.com/repos/{repository}/commits/{branch}"),
+    )
+    .await
+    .context(fastn_update::ResolveCommitSnafu {
+        package: package_name,
+    })?;
+    let commit: Commit =
+        serde_json::from_slice(&commit_bytes).context(fastn_update::DeserializeCommitSnafu {
+            package: package_name,
+        })?;
+
+    Ok(Some(format!(
+        "https://codeload.github.com/{repository}/zip/{}",
+        commit.sha
+    )))
+}
+
 pub(crate) async fn resolve_dependency_package(
     ds: &fastn_ds::DocumentStore,
     dependency: &fastn_core::package::dependency::Dependency,
@@ -19,6 +19,18 @@ pub enum SqlError {
     FoundBlob,
     #[error("unknown db error")]
     UnknownDB,
+    #[error("postgres pool error {0}")]
+    PgPool(deadpool_postgres::PoolError),
+    #[error("postgres create pool error {0}")]
+    PgCreatePool(deadpool_postgres::CreatePoolError),
+    #[error("postgres error {0}")]
+    Postgres(tokio_postgres::Error),
+    #[error("postgres column error {0}: {1}")]
+    PgColumn(usize, tokio_postgres::Error),
+    #[error("column {0} is of type {1}, which is not supported, cast it to text")]
+    PgColumnType(usize, tokio_postgres::types::Type),
+    #[error("parameter ${0} can not be passed as {1}: {2}")]
+    PgParam(usize, tokio_postgres::types::Type, String),
 }
 
 pub fn rows_to_json(
@@ -55,4 +67,188 @@ pub fn row_to_json(r: &rusqlite::Row, count: usize) -> Result<Vec<serde_json::Va
     Ok(row)
 }
 
+pub fn pg_rows_to_json(
+    rows: &[tokio_postgres::Row],
+) -> Result<Vec<Vec<serde_json::Value>>, SqlError> {
+    rows.iter().map(pg_row_to_json).collect()
+}
+
+/// Converts a row to the json values `sqlite` rows are converted to, so both go through the
+/// same `result_to_value`. `NULL` of any type is `null`, timestamps and dates are the ISO 8601
+/// strings of `datetime` and `date`, timestamps without a time zone are taken to be in UTC.
+/// `NUMERIC` is an `integer` if it has no fractional digits and fits, a `decimal` otherwise.
+pub fn pg_row_to_json(r: &tokio_postgres::Row) -> Result<Vec<serde_json::Value>, SqlError> {
+    use tokio_postgres::types::Type;
+
+    fn get<'a, T: tokio_postgres::types::FromSql<'a> + Into<serde_json::Value>>(
+        r: &'a tokio_postgres::Row,
+        i: usize,
+    ) -> Result<serde_json::Value, SqlError> {
+        r.try_get::<usize, Option<T>>(i)
+            .map(|v| v.map(Into::into).unwrap_or(serde_json::Value::Null))
+            .map_err(|e| SqlError::PgColumn(i, e))
+    }
+
+    fn get_formatted<'a, T: tokio_postgres::types::FromSql<'a>>(
+        r: &'a tokio_postgres::Row,
+        i: usize,
+        format: impl Fn(T) -> String,
+    ) -> Result<serde_json::Value, SqlError> {
+        r.try_get::<usize, Option<T>>(i)
+            .map(|v| {
+                v.map(format)
+                    .map(Into::into)
+                    .unwrap_or(serde_json::Value::Null)
+            })
+            .map_err(|e| SqlError::PgColumn(i, e))
+    }
+
+    let mut row: Vec<serde_json::Value> = Vec::with_capacity(r.len());
+    for (i, column) in r.columns().iter().enumerate() {
+        row.push(match *column.type_() {
+            Type::BOOL => get::<bool>(r, i)?,
+            Type::INT2 => get::<i16>(r, i)?,
+            Type::INT4 => get::<i32>(r, i)?,
+            Type::INT8 => get::<i64>(r, i)?,
+            Type::OID => get::<u32>(r, i)?,
+            Type::FLOAT4 => get::<f32>(r, i)?,
+            Type::FLOAT8 => get::<f64>(r, i)?,
+            Type::TEXT | Type::VARCHAR | Type::BPCHAR | Type::NAME | Type::UNKNOWN => {
+                get::<String>(r, i)?
+            }
+            Type::JSON | Type::JSONB => get::<serde_json::Value>(r, i)?,
+            Type::TIMESTAMP => get_formatted(r, i, |v: chrono::NaiveDateTime| {
+                v.and_utc()
+                    .to_rfc3339_opts(chrono::SecondsFormat::AutoSi, false)
+            })?,
+            Type::TIMESTAMPTZ => get_formatted(r, i, |v: chrono::DateTime<chrono::Utc>| {
+                v.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, false)
+            })?,
+            Type::DATE => get_formatted(r, i, |v: chrono::NaiveDate| {
+                v.format("%Y-%m-%d").to_string()
+            })?,
+            Type::NUMERIC => get::<Numeric>(r, i)?,
+            ref t => return Err(SqlError::PgColumnType(i, t.clone())),
+        });
+    }
+    Ok(row)
+}
+
+/// A postgres `NUMERIC`, read from its binary format: the number of base 10000 digits, the
+/// weight of the first digit, the sign, the number of decimal digits after the point, and then
+/// the digits.
+struct Numeric(serde_json::Value);
+
+impl<'a> tokio_postgres::types::FromSql<'a> for Numeric {
+    fn from_sql(
+        _ty: &tokio_postgres::types::Type,
+        raw: &'a [u8],
+    ) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        let word = |i: usize| -> Result<u16, Box<dyn std::error::Error + Sync + Send>> {
+            raw.get(i * 2..i * 2 + 2)
+                .map(|w| u16::from_be_bytes([w[0], w[1]]))
+                .ok_or_else(|| "invalid numeric".into())
+        };
+
+        let count = word(0)? as usize;
+        let weight = word(1)? as i16 as i64;
+        let sign = word(2)?;
+        let scale = word(3)? as usize;
+        let digits = (0..count)
+            .map(|i| word(4 + i))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let negative = match sign {
+            0x0000 => false,
+            0x4000 => true,
+            0xC000 => return Ok(Numeric("NaN".into())),
+            0xD000 => return Ok(Numeric("Infinity".into())),
+            0xF000 => return Ok(Numeric("-Infinity".into())),
+            _ => return Err(format!("invalid numeric sign {sign:#x}").into()),
+        };
+
+        let digit = |i: i64| {
+            usize::try_from(i)
+                .ok()
+                .and_then(|i| digits.get(i))
+                .copied()
+                .unwrap_or(0)
+        };
+
+        let mut text = String::new();
+        if negative {
+            text.push('-');
+        }
+        if weight < 0 {
+            text.push('0');
+        } else {
+            text.push_str(&digit(0).to_string());
+            for i in 1..=weight {
+                text.push_str(&format!("{:04}", digit(i)));
+            }
+        }
+        if scale > 0 {
+            let mut fraction = String::new();
+            let mut i = weight + 1;
+            while fraction.len() < scale {
+                fraction.push_str(&format!("{:04}", digit(i)));
+                i += 1;
+            }
+            fraction.truncate(scale);
+            text.push('.');
+            text.push_str(&fraction);
+        }
+
+        if scale == 0 {
+            if let Ok(v) = text.parse::<i64>() {
+                return Ok(Numeric(v.into()));
+            }
+        }
+        Ok(Numeric(
+            text.parse::<f64>()
+                .ok()
+                .and_then(serde_json::Number::from_f64)
+                .map(serde_json::Value::Number)
+                .unwrap_or(serde_json::Value::String(text)),
+        ))
+    }
+
+    fn accepts(ty: &tokio_postgres::types::Type) -> bool {
+        *ty == tokio_postgres::types::Type::NUMERIC
+    }
+}
+
+impl From<Numeric> for serde_json::Value {
+    fn from(value: Numeric) -> Self {
+        value.0
+    }
+}
+
 pub const FASTN_MOUNTPOINT: &str = "x-fastn-mountpoint";
+
+#[cfg(test)]
+mod test {
+    #[track_caller]
+    fn n(words: &[u16], o: serde_json::Value) {
+        use tokio_postgres::types::FromSql;
+
+        let raw: Vec<u8> = words.iter().flat_map(|w| w.to_be_bytes()).collect();
+        let value = super::Numeric::from_sql(&tokio_postgres::types::Type::NUMERIC, &raw).unwrap();
+        assert_eq!(value.0, o);
+    }
+
+    #[test]
+    fn numeric() {
+        // 12345678
+        n(&[2, 1, 0, 0, 1234, 5678], serde_json::json!(12345678));
+        // -12.50
+        n(&[2, 0, 0x4000, 2, 12, 5000], serde_json::json!(-12.5));
+        // 0.0001
+        n(&[1, 0xFFFF, 0, 4, 1], serde_json::json!(0.0001));
+        // 100000000, trailing zero digits are not sent
+        n(&[1, 2, 0, 0, 1], serde_json::json!(100000000));
+        // 0
+        n(&[0, 0, 0, 0], serde_json::json!(0));
+        n(&[0, 0, 0xC000, 0], serde_json::json!("NaN"));
+    }
+}
@@ -4,6 +4,19 @@ const BACKSLASH: char = '\\';
 pub const SQLITE_SUB: char = '?';
 pub const POSTGRES_SUB: char = '$';
 
+pub fn is_postgres_url(db_url: &str) -> bool {
+    db_url.starts_with("postgres://") || db_url.starts_with("postgresql://")
+}
+
+/// `$1` for postgres, `?1` for sqlite.
+pub fn placeholder(db_url: &str) -> char {
+    if is_postgres_url(db_url) {
+        POSTGRES_SUB
+    } else {
+        SQLITE_SUB
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum QueryError {
     #[error("Invalid query, quote left open")]
@@ -237,4 +250,10 @@ mod test {
             vec![("name".to_string(), None)],
         );
     }
+    #[test]
+    fn placeholder() {
+        assert_eq!(super::placeholder("postgres://amitu@localhost/amitu"), '$');
+        assert_eq!(super::placeholder("postgresql://localhost/db"), '$');
+        assert_eq!(super::placeholder("sqlite:///fastn.sqlite"), '?');
+    }
 }
@@ -34,8 +34,9 @@ async fn async_main() -> Result<(), Error> {
 
     set_env_vars(matches.subcommand_matches("test").is_some());
 
+    // boxed, the future of every command inlined into this one is too big for rustc to lay out
     futures::try_join!(
-        fastn_core_commands(&matches),
+        Box::pin(fastn_core_commands(&matches)),
         check_for_update_cmd(&matches)
     )?;
 
@@ -62,9 +63,56 @@ async fn fastn_core_commands(matches: &clap::ArgMatches) -> fastn_core::Result<(
     let current_dir: camino::Utf8PathBuf = std::env::current_dir()?.canonicalize()?.try_into()?;
     let ds = fastn_ds::DocumentStore::new(current_dir, pg_pools);
 
+    if let Some(prune) = matches
+        .subcommand_matches("cache")
+        .and_then(|cache| cache.subcommand_matches("prune"))
+    {
+        let older_than = if prune.get_flag("all") {
+            None
+        } else {
+            let days = prune.value_of_("older-than").unwrap_or("30");
+            match days
+                .parse::<u64>()
+                .ok()
+                .and_then(|v| v.checked_mul(24 * 60 * 60))
+            {
+                Some(seconds) => Some(std::time::Duration::from_secs(seconds)),
+                None => {
+                    eprintln!("Provided --older-than {days} is not a valid number of days.");
+                    std::process::exit(1);
+                }
+            }
+        };
+        let removed = fastn_update::cache::prune(&ds, older_than).await?;
+        println!(
+            "Removed {removed} package(s) from {}",
+            fastn_update::cache::cache_dir(&ds)
+        );
+        return Ok(());
+    }
+
+    if let Some(add) = matches.subcommand_matches("add") {
+        return fastn_update::add(
+            &ds,
+            add.value_of_("package").unwrap(),
+            add.value_of_("alias"),
+            add.value_of_("provided-via"),
+        )
+        .await;
+    }
+
+    if let Some(remove) = matches.subcommand_matches("remove") {
+        return fastn_update::remove(&ds, remove.value_of_("package").unwrap()).await;
+    }
+
     if let Some(update) = matches.subcommand_matches("update") {
         let check = update.get_flag("check");
-        return fastn_update::update(&ds, check).await;
+        let upgrade = match update.get_one::<String>("upgrade") {
+            Some(package) if package.is_empty() => fastn_update::Upgrade::All,
+            Some(package) => fastn_update::Upgrade::Package(package.to_string()),
+            None => fastn_update::Upgrade::Locked,
+        };
+        return fastn_update::update(&ds, check, &upgrade).await;
     }
 
     if let Some(serve) = matches.subcommand_matches("serve") {
@@ -83,9 +131,19 @@ async fn fastn_core_commands(matches: &clap::ArgMatches) -> fastn_core::Result<(
         let external_css = serve.values_of_("external-css");
         let inline_css = serve.values_of_("css");
         let offline = serve.get_flag("offline");
+        let watch = serve.get_flag("watch");
+        let cache_size = serve
+            .value_of_("cache-size")
+            .map(|s| match s.parse::<usize>() {
+                Ok(v) => v,
+                Err(_) => {
+                    eprintln!("Provided cache size {s} is not a valid number.");
+                    std::process::exit(1);
+                }
+            });
 
         if cfg!(feature = "use-config-json") && !offline {
-            fastn_update::update(&ds, false).await?;
+            fastn_update::update(&ds, false, &fastn_update::Upgrade::Locked).await?;
         }
 
         let config = fastn_core::Config::read(ds, false, &None)
@@ -94,7 +152,9 @@ async fn fastn_core_commands(matches: &clap::ArgMatches) -> fastn_core::Result<(
             .add_external_js(external_js.clone())
             .add_inline_js(inline_js.clone())
             .add_external_css(external_css.clone())
-            .add_inline_css(inline_css.clone());
+            .add_inline_css(inline_css.clone())
+            .add_live_reload(watch)
+            .add_response_cache(cache_size);
 
         return fastn_core::listen(std::sync::Arc::new(config), bind.as_str(), port).await;
     }
@@ -106,9 +166,24 @@ async fn fastn_core_commands(matches: &clap::ArgMatches) -> fastn_core::Result<(
         let external_css = test.values_of_("external-css");
         let inline_css = test.values_of_("css");
         let offline: bool = test.get_flag("offline");
+        let format = test.value_of_("format").unwrap_or("human").parse()?;
+        let jobs = match test.value_of_("jobs") {
+            Some(j) => match j.parse::<usize>() {
+                Ok(v) => v,
+                Err(_) => {
+                    eprintln!("Provided jobs {j} is not a valid number.");
+                    std::process::exit(1);
+                }
+            },
+            // the test files share the database
+            None => 1,
+        };
+        if format != fastn_core::commands::test::Format::Human {
+            fastn_core::utils::progress_to_stderr();
+        }
 
         if !offline {
-            fastn_update::update(&ds, false).await?;
+            fastn_update::update(&ds, false, &fastn_update::Upgrade::Locked).await?;
         }
 
         let mut config = fastn_core::Config::read(ds, true, &None).await?;
@@ -123,11 +198,13 @@ async fn fastn_core_commands(matches: &clap::ArgMatches) -> fastn_core::Result<(
 
         return fastn_core::test(
             &config,
-            test.value_of_("file"), // TODO: handle more than one files
+            test.values_of_("file").as_slice(),
             test.value_of_("base").unwrap_or("/"),
             test.get_flag("headless"),
             test.get_flag("script"),
             test.get_flag("verbose"),
+            jobs,
+            format,
         )
         .await;
     }
@@ -146,7 +223,7 @@ async fn fastn_core_commands(matches: &clap::ArgMatches) -> fastn_core::Result<(
         let offline: bool = build.get_flag("offline");
 
         if !offline {
-            fastn_update::update(&ds, false).await?;
+            fastn_update::update(&ds, false, &fastn_update::Upgrade::Locked).await?;
         }
 
         let mut config = fastn_core::Config::read(ds, true, &None).await?;
@@ -171,10 +248,55 @@ async fn fastn_core_commands(matches: &clap::ArgMatches) -> fastn_core::Result<(
         .await;
     }
 
+    if let Some(migrate) = matches.subcommand_matches("migrate") {
+        let action = match migrate.subcommand() {
+            Some(("status", _)) => fastn_core::commands::migrate::Action::Status,
+            Some(("down", down)) => fastn_core::commands::migrate::Action::Down {
+                to: migration_number(down),
+            },
+            Some(("up", up)) => fastn_core::commands::migrate::Action::Up {
+                to: migration_number(up),
+            },
+            _ => fastn_core::commands::migrate::Action::Up { to: None },
+        };
+
+        let config = fastn_core::Config::read_without_migrations(ds, false, &None).await?;
+        return fastn_core::migrate(&config, action, migrate.get_flag("dry-run")).await;
+    }
+
+    if let Some(check) = matches.subcommand_matches("check") {
+        let format = check.value_of_("format").unwrap_or("human").parse()?;
+        // an entry without a document is one of the problems `fastn check` reports, resolving
+        // the sitemap would fail on it instead, and a check does not change the database
+        let config = fastn_core::Config::read_without_migrations(ds, false, &None).await?;
+        return fastn_core::check(&config, format).await;
+    }
+
+    // stdin is formatted on its own, it can be outside of any package
+    if let Some(fmt) = matches
+        .subcommand_matches("fmt")
+        .filter(|fmt| fmt.values_of_("file") == ["-"])
+    {
+        return fastn_core::fmt_stdin(fmt.get_flag("noidentation"), fmt.get_flag("check"));
+    }
+
+    if matches.subcommand_matches("lsp").is_some() {
+        // stdout is the protocol, applying migrations would write to it, and change the database
+        // from inside an editor
+        let config = fastn_core::Config::read_without_migrations(ds, true, &None).await?;
+        return fastn_core::lsp(&config.set_skip_side_effects()).await;
+    }
+
     let config = fastn_core::Config::read(ds, true, &None).await?;
 
     if let Some(fmt) = matches.subcommand_matches("fmt") {
-        return fastn_core::fmt(&config, fmt.value_of_("file"), fmt.get_flag("noidentation")).await;
+        return fastn_core::fmt(
+            &config,
+            fmt.values_of_("file").as_slice(),
+            fmt.get_flag("noidentation"),
+            fmt.get_flag("check"),
+        )
+        .await;
     }
 
     if let Some(wasmc) = matches.subcommand_matches("wasmc") {
@@ -190,17 +312,26 @@ async fn fastn_core_commands(matches: &clap::ArgMatches) -> fastn_core::Result<(
             query.value_of_("stage").unwrap(),
             query.value_of_("path"),
             query.get_flag("null"),
+            query.value_of_("definition"),
         )
         .await;
     }
 
-    if matches.subcommand_matches("check").is_some() {
-        return fastn_core::post_build_check(&config).await;
-    }
-
     Ok(())
 }
 
+fn migration_number(matches: &clap::ArgMatches) -> Option<i64> {
+    use fastn_core::utils::ValueOf;
+
+    matches.value_of_("to").map(|to| match to.parse::<i64>() {
+        Ok(v) => v,
+        Err(_) => {
+            eprintln!("Provided --to {to} is not a valid migration number.");
+            std::process::exit(1);
+        }
+    })
+}
+
 async fn check_for_update_cmd(matches: &clap::ArgMatches) -> fastn_core::Result<()> {
     let env_var_set = {
         if let Ok(val) = std::env::var("FASTN_CHECK_FOR_UPDATES") {
@@ -281,8 +412,9 @@ fn app(version: &'static str) -> clap::Command {
         .subcommand(
             clap::Command::new("fmt")
                 .about("Format the fastn package")
-                .arg(clap::arg!(file: [FILE]... "The file to format").required(false))
+                .arg(clap::arg!(file: [FILE]... "The files to format, by id, path or glob, `-` formats stdin to stdout").required(false))
                 .arg(clap::arg!(-i --noidentation "No identation added to file/package").required(false))
+                .arg(clap::arg!(--check "Print the diff of the files that are not formatted and fail, without writing them").required(false))
         )
         .subcommand(
             clap::Command::new("wasmc")
@@ -292,9 +424,13 @@ fn app(version: &'static str) -> clap::Command {
         .subcommand(
             clap::Command::new("test")
                 .about("Run the test files in `_tests` folder")
-                .arg(clap::arg!(file: [FILE]... "The file to build (if specified only these are built, else entire package is built)"))
+                .arg(clap::arg!(file: [FILE]... "The test files to run, by name or glob (if specified only these are run, else all of them are run)"))
                 .arg(clap::arg!(-b --base [BASE] "The base path.").default_value("/"))
                 .arg(clap::arg!(--"headless" "Run the test in headless mode"))
+                .arg(clap::arg!(-j --jobs <JOBS> "Number of test files to run at a time, they share the database [default: 1]"))
+                .arg(clap::arg!(--format <FORMAT> "How to report the results")
+                    .value_parser(["human", "junit", "json", "tap"])
+                    .default_value("human"))
                 .arg(clap::arg!(--"external-js" <URL> "Script added in ftd files")
                     .action(clap::ArgAction::Append))
                 .arg(clap::arg!(--"js" <URL> "Script text added in ftd files")
@@ -311,20 +447,71 @@ fn app(version: &'static str) -> clap::Command {
         .subcommand(
             clap::Command::new("query")
                 .about("JSON Dump in various stages")
-                .arg(clap::arg!(--stage <STAGE> "The stage. Currently supported (p1)").required
+                .arg(clap::arg!(--stage <STAGE> "The stage: p1, ast, interpreter, executor, node, js or html").required
                 (true))
                 .arg(clap::arg!(-p --path [PATH] "The path of the file"))
                 .arg(clap::arg!(-n --null "JSON with null and empty list"))
+                .arg(clap::arg!(-d --definition [NAME] "Only the component, record, variable etc. with this name (ast and interpreter stages)"))
         )
         .subcommand(
             clap::Command::new("check")
-                .about("Check if everything is fine with current fastn package")
-                .hide(true) // hidden since the feature is not being released yet.
+                .about("Check the package for broken links, missing images, unused imports and definitions, and sitemap mismatches")
+                .arg(clap::arg!(--format <FORMAT> "How to report the problems")
+                    .value_parser(["human", "json"])
+                    .default_value("human"))
+        )
+        .subcommand(
+            clap::Command::new("lsp")
+                .about("Run the language server for .ftd files, over stdio")
         )
         .subcommand(
             clap::Command::new("update")
                 .about("Update dependency packages for this fastn package")
-                .arg(clap::arg!(--check "Check if packages are in sync with FASTN.ftd without performing updates."))
+                .arg(clap::arg!(--check "Check if packages and fastn.lock are in sync with FASTN.ftd without performing updates."))
+                .arg(clap::arg!(--upgrade [PACKAGE] "Update the package (all packages if not specified) to its latest version, instead of the one in fastn.lock")
+                    .default_missing_value(""))
+        )
+        .subcommand(
+            clap::Command::new("migrate")
+                .about("Apply or revert the migrations in FASTN.ftd, applies the pending ones without a subcommand")
+                .arg(clap::arg!(--"dry-run" "Print the SQL that would run instead of running it").global(true))
+                .subcommand(
+                    clap::Command::new("status")
+                        .about("List the migrations, with the ones that are pending or were edited after being applied")
+                )
+                .subcommand(
+                    clap::Command::new("up")
+                        .about("Apply the pending migrations")
+                        .arg(clap::arg!(--to <N> "Apply the migrations up to, and including, number N"))
+                )
+                .subcommand(
+                    clap::Command::new("down")
+                        .about("Revert the latest applied migration using its `down`")
+                        .arg(clap::arg!(--to <N> "Revert all the migrations after number N"))
+                )
+        )
+        .subcommand(
+            clap::Command::new("add")
+                .about("Add a dependency to FASTN.ftd and download it")
+                .arg(clap::arg!(<package> "The package to add, e.g. fastn-community.github.io/doc-site"))
+                .arg(clap::arg!(--alias <ALIAS> "Import the package with this name instead"))
+                .arg(clap::arg!(--"provided-via" <PACKAGE> "The package of this package that provides it"))
+        )
+        .subcommand(
+            clap::Command::new("remove")
+                .about("Remove a dependency from FASTN.ftd, if nothing imports it anymore")
+                .arg(clap::arg!(<package> "The package to remove"))
+        )
+        .subcommand(
+            clap::Command::new("cache")
+                .about("Manage the packages downloaded by fastn update, shared by all fastn packages")
+                .subcommand_required(true)
+                .subcommand(
+                    clap::Command::new("prune")
+                        .about("Remove the downloaded packages that have not been used recently")
+                        .arg(clap::arg!(--"older-than" <DAYS> "Remove the packages not used in these many days [default: 30]"))
+                        .arg(clap::arg!(--all "Remove all the downloaded packages"))
+                )
         )
         .subcommand(sub_command::serve())
 }
@@ -348,7 +535,9 @@ mod sub_command {
             .arg(clap::arg!(--"css" <URL> "CSS text added in ftd files")
                 .action(clap::ArgAction::Append))
             .arg(clap::arg!(--"download-base-url" <URL> "If running without files locally, download needed files from here"))
-            .arg(clap::arg!(--offline "Disables automatic package update checks to operate in offline mode"));
+            .arg(clap::arg!(--offline "Disables automatic package update checks to operate in offline mode"))
+            .arg(clap::arg!(--watch "Reload open pages when the files they are rendered from change"))
+            .arg(clap::arg!(--"cache-size" <SIZE> "Number of rendered pages to keep in memory [default: 0, no caching]"));
         serve
                 .arg(
                     clap::arg!(identities: --identities <IDENTITIES> "Http request identities, fastn allows these identities to access documents")
@@ -7,12 +7,14 @@ pub struct VariableKind {
 #[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
 pub enum VariableModifier {
     List,
+    Map,
     Optional,
     Constant,
 }
 
 pub const OPTIONAL: &str = "optional";
 pub const LIST: &str = "list";
+pub const MAP: &str = "map";
 pub const CONSTANT: &str = "constant";
 
 impl VariableModifier {
@@ -24,6 +26,10 @@ impl VariableModifier {
         expr.eq(LIST)
     }
 
+    pub(crate) fn is_map_from_expr(expr: &str) -> bool {
+        expr.eq(MAP)
+    }
+
     pub(crate) fn is_constant_from_expr(expr: &str) -> bool {
         expr.eq(CONSTANT)
     }
@@ -32,6 +38,10 @@ impl VariableModifier {
         matches!(self, VariableModifier::List)
     }
 
+    fn is_map(&self) -> bool {
+        matches!(self, VariableModifier::Map)
+    }
+
     fn is_optional(&self) -> bool {
         matches!(self, VariableModifier::Optional)
     }
@@ -43,6 +53,8 @@ impl VariableModifier {
                 return Some(VariableModifier::Optional);
             } else if VariableModifier::is_list_from_expr(expr.last().unwrap()) {
                 return Some(VariableModifier::List);
+            } else if VariableModifier::is_map_from_expr(expr.last().unwrap()) {
+                return Some(VariableModifier::Map);
             } else if VariableModifier::is_constant_from_expr(expr.first().unwrap()) {
                 return Some(VariableModifier::Constant);
             }
@@ -73,6 +85,7 @@ impl VariableKind {
         let kind = match modifier {
             Some(VariableModifier::Optional) if expr.len() >= 2 => expr[1..].join(" "),
             Some(VariableModifier::List) if expr.len() >= 2 => expr[..expr.len() - 1].join(" "),
+            Some(VariableModifier::Map) if expr.len() >= 2 => expr[..expr.len() - 1].join(" "),
             Some(VariableModifier::Constant) if expr.len() >= 2 => expr[1..].join(" "),
             None => expr.join(" "),
             _ => {
@@ -626,6 +639,52 @@ impl VariableValue {
                     )
                 }
             }
+            Some(modifier) if modifier.is_map() => {
+                if self.is_null() {
+                    return Ok(VariableValue::List {
+                        value: vec![],
+                        line_number: self.line_number(),
+                        condition: self.condition().clone(),
+                    });
+                }
+                match self {
+                    VariableValue::String { ref value, .. } if value.starts_with('$') => Ok(self),
+                    // Processors fill the map in later, so keep the value as is
+                    t if has_processor => Ok(t),
+                    VariableValue::Record {
+                        caption,
+                        headers,
+                        body: None,
+                        values,
+                        line_number,
+                        condition,
+                        ..
+                    } if caption.is_none() && values.is_empty() => {
+                        // Each header is one entry, the header key being the map key
+                        Ok(VariableValue::List {
+                            value: headers
+                                .0
+                                .into_iter()
+                                .map(|header| VariableKeyValue {
+                                    key: header.key,
+                                    value: header.value.set_condition(
+                                        header.condition.map(|c| {
+                                            ftd_ast::Condition::new(&c, header.line_number)
+                                        }),
+                                    ),
+                                })
+                                .collect(),
+                            line_number,
+                            condition,
+                        })
+                    }
+                    t => ftd_ast::parse_error(
+                        format!("Expected Map found: `{:?}`", t),
+                        doc_id,
+                        t.line_number(),
+                    ),
+                }
+            }
             Some(modifier) if modifier.is_optional() => Ok(self.into_optional()),
             _ => Ok(self),
         }
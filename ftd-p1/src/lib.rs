@@ -13,7 +13,7 @@ mod parser;
 mod section;
 pub mod utils;
 
-pub use header::{AccessModifier, BlockRecordHeader, Header, Headers, SectionHeader, KV};
+pub use header::{AccessModifier, BlockRecordHeader, Header, Headers, KVSource, SectionHeader, KV};
 pub use parser::{parse, parse_with_line_number};
 pub use section::Body;
 pub use section::Section;
@@ -373,7 +373,7 @@ impl ExecuteDoc<'_> {
             fastn_resolved::ComponentInvocation,
         )>,
     > {
-        use ftd::interpreter::LoopExt;
+        use ftd::interpreter::{LoopExt, PropertyValueExt};
 
         let iteration = if let Some(iteration) = instruction.iteration.as_ref() {
             iteration
@@ -393,6 +393,15 @@ impl ExecuteDoc<'_> {
         .0;
 
         let children_length = iteration.children(&doc.itdoc())?.0.len();
+        // The items of a map are its values, a key can have a `.` so they are not looked up
+        let entries = match iteration
+            .on
+            .clone()
+            .resolve(&doc.itdoc(), iteration.line_number)?
+        {
+            fastn_resolved::Value::Map { data, .. } => Some(data.into_iter().collect::<Vec<_>>()),
+            _ => None,
+        };
         let reference_name =
             iteration
                 .on
@@ -416,6 +425,9 @@ impl ExecuteDoc<'_> {
                 instruction,
                 doc,
                 index,
+                entries
+                    .as_ref()
+                    .map(|entries| (entries[index].0.as_str(), &entries[index].1)),
                 iteration.alias.as_str(),
                 reference_name,
                 inherited_variables,
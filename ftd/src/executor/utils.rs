@@ -46,6 +46,7 @@ pub(crate) fn update_instruction_for_loop_element(
     instruction: &fastn_resolved::ComponentInvocation,
     doc: &mut ftd::executor::TDoc,
     index_in_loop: usize,
+    entry_in_loop: Option<(&str, &fastn_resolved::PropertyValue)>,
     alias: &str,
     reference_name: &str,
     inherited_variables: &mut ftd::VecMap<(String, Vec<usize>)>,
@@ -57,18 +58,33 @@ pub(crate) fn update_instruction_for_loop_element(
     let mut instruction = instruction.clone();
     let reference_replace_pattern = fastn_resolved::PropertyValueSource::Loop(alias.to_string())
         .get_reference_name(alias, &doc.itdoc());
-    let replace_with = format!("{}.{}", reference_name, index_in_loop);
-    let map =
-        std::iter::IntoIterator::into_iter([(reference_replace_pattern, replace_with)]).collect();
-    let replace_property_value = std::iter::IntoIterator::into_iter([(
-        doc.itdoc()
-            .resolve_name(format!("{}#{}", doc_name, ftd::interpreter::FTD_LOOP_COUNTER).as_str()),
-        fastn_resolved::Value::Integer {
-            value: index_in_loop as i64,
-        }
-        .into_property_value(false, instruction.line_number),
-    )])
-    .collect();
+    let mut map: ftd::Map<String> = Default::default();
+    if entry_in_loop.is_none() {
+        map.insert(
+            reference_replace_pattern.clone(),
+            format!("{}.{}", reference_name, index_in_loop),
+        );
+    }
+    let mut replace_property_value: ftd::Map<fastn_resolved::PropertyValue> =
+        std::iter::IntoIterator::into_iter([(
+            doc.itdoc().resolve_name(
+                format!("{}#{}", doc_name, ftd::interpreter::FTD_LOOP_COUNTER).as_str(),
+            ),
+            fastn_resolved::Value::Integer {
+                value: index_in_loop as i64,
+            }
+            .into_property_value(false, instruction.line_number),
+        )])
+        .collect();
+    if let Some((key, value)) = entry_in_loop {
+        replace_property_value.insert(
+            doc.itdoc()
+                .resolve_name(format!("{}#{}", doc_name, ftd::interpreter::FTD_LOOP_KEY).as_str()),
+            fastn_resolved::Value::new_string(key)
+                .into_property_value(false, instruction.line_number),
+        );
+        replace_property_value.insert(reference_replace_pattern, value.clone());
+    }
 
     update_local_variable_references_in_component(
         &mut instruction,
@@ -423,6 +439,18 @@ fn update_local_variable_reference_in_property_value(
 
     if let Some(replace_with) = replace_property_value.get(reference_or_clone.as_str()) {
         replace_with.clone_into(property_value);
+    } else if let Some(part) = replace_property_value.iter().find_map(|(k, v)| {
+        reference_or_clone
+            .strip_prefix(format!("{}.", k).as_str())
+            .and_then(|path| property_value_at(v, path))
+    }) {
+        match part {
+            fastn_resolved::PropertyValue::Reference { name, .. }
+            | fastn_resolved::PropertyValue::Clone { name, .. } => {
+                property_value.set_reference_or_clone(name.as_str())
+            }
+            part => *property_value = part,
+        }
     }
 
     if !is_children {
@@ -436,6 +464,36 @@ fn update_local_variable_reference_in_property_value(
     }
 }
 
+/// The part of `value` at `path`, like `name` in `$item.name` where `item` is `value`: a field of
+/// a record, an entry of a map or an item of a list, or the reference to the same path of the
+/// variable `value` refers to.
+fn property_value_at(
+    value: &fastn_resolved::PropertyValue,
+    path: &str,
+) -> Option<fastn_resolved::PropertyValue> {
+    let (p1, p2) = ftd::interpreter::utils::split_at(path, ".");
+    let part = match value {
+        fastn_resolved::PropertyValue::Reference { name, .. }
+        | fastn_resolved::PropertyValue::Clone { name, .. } => {
+            let mut value = value.clone();
+            value.set_reference_or_clone(format!("{}.{}", name, path).as_str());
+            return Some(value);
+        }
+        fastn_resolved::PropertyValue::Value { value, .. } => match value {
+            fastn_resolved::Value::Record { fields, .. }
+            | fastn_resolved::Value::Object { values: fields } => fields.get(p1.as_str())?,
+            fastn_resolved::Value::Map { data, .. } => data.get(p1.as_str())?,
+            fastn_resolved::Value::List { data, .. } => data.get(p1.parse::<usize>().ok()?)?,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    match p2 {
+        Some(p2) => property_value_at(part, p2.as_str()),
+        None => Some(part.clone()),
+    }
+}
+
 fn update_inherited_reference_in_property_value(
     property_value: &mut fastn_resolved::PropertyValue,
     reference_or_clone: &str,
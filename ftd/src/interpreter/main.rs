@@ -28,6 +28,9 @@ use ftd::interpreter::{ComponentExt, VariableExt};
 ///
 /// - `instructions`: a `Vec` of `fastn_resolved::Component`s that represents the instructions
 ///   that the interpreter has processed.
+///
+/// - `settings`: the language and the time zone the date and time functions of expressions
+///   format in, see `fastn_builtins::datetime::Settings`.
 #[derive(Debug, Clone, Default, PartialEq)]
 pub struct InterpreterState {
     pub id: String,
@@ -39,6 +42,7 @@ pub struct InterpreterState {
     pub parsed_libs: ftd::Map<ParsedDocument>,
     pub instructions: Vec<fastn_resolved::ComponentInvocation>,
     pub in_process: Vec<(String, usize, ftd_ast::Ast)>,
+    pub settings: ftd::interpreter::default::datetime::Settings,
 }
 
 #[derive(Debug, Clone, Default, PartialEq)]
@@ -952,10 +956,21 @@ pub fn interpret(id: &str, source: &str) -> ftd::interpreter::Result<Interpreter
 pub fn interpret_with_line_number(
     id: &str,
     document: ParsedDocument,
+) -> ftd::interpreter::Result<Interpreter> {
+    interpret_with_settings(id, document, Default::default())
+}
+
+/// Like `interpret_with_line_number`, with the expressions evaluated in the language and the
+/// time zone of `settings`.
+pub fn interpret_with_settings(
+    id: &str,
+    document: ParsedDocument,
+    settings: ftd::interpreter::default::datetime::Settings,
 ) -> ftd::interpreter::Result<Interpreter> {
     use itertools::Itertools;
 
     let mut s = InterpreterState::new(id.to_string());
+    s.settings = settings;
     s.parsed_libs.insert(id.to_string(), document);
     s.to_process.stack.push((
         id.to_string(),
@@ -1336,7 +1351,11 @@ impl Document {
             fastn_resolved::Value::String { text, .. } => {
                 serde_json::Value::String(text.to_owned())
             }
-            fastn_resolved::Value::Record { fields, .. } => self.object_to_json(fields)?,
+            fastn_resolved::Value::DateTime { value } | fastn_resolved::Value::Date { value } => {
+                serde_json::Value::String(value.to_owned())
+            }
+            fastn_resolved::Value::Record { fields, .. }
+            | fastn_resolved::Value::Map { data: fields, .. } => self.object_to_json(fields)?,
             fastn_resolved::Value::OrType { variant, value, .. } => {
                 let mut map = serde_json::Map::new();
                 map.insert(
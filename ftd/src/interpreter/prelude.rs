@@ -1,6 +1,7 @@
 pub use fastn_builtins::constants::*;
 pub use ftd::interpreter::main::{
-    interpret, interpret_with_line_number, Document, Interpreter, InterpreterState,
+    interpret, interpret_with_line_number, interpret_with_settings, Document, Interpreter,
+    InterpreterState,
     InterpreterWithoutState, ParsedDocument, PendingImportItem, StateWithThing, ToProcess,
     ToProcessItem,
 };
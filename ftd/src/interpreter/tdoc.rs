@@ -50,6 +50,18 @@ impl<'a> TDoc<'a> {
         }
     }
 
+    /// The context expressions are evaluated in, with the functions of
+    /// `ftd::interpreter::default` in the settings of the interpreter, the default ones for a
+    /// `Bag`.
+    pub fn evalexpr_context(
+        &self,
+    ) -> ftd::interpreter::Result<fastn_resolved::evalexpr::HashMapContext> {
+        Ok(match &self.bag {
+            BagOrState::Bag(_) => ftd::interpreter::default::default_context()?,
+            BagOrState::State(s) => ftd::interpreter::default::default_context_with(&s.settings)?,
+        })
+    }
+
     pub fn resolve_module_name(&self, name: &str) -> String {
         ftd::interpreter::utils::resolve_module_name(name, self.name, self.aliases)
     }
@@ -313,6 +325,27 @@ impl<'a> TDoc<'a> {
                     }
                     Ok(value)
                 }
+                fastn_resolved::Value::Map { data, kind } => {
+                    let value = data
+                        .get(p1.as_str())
+                        .ok_or(ftd::interpreter::Error::ParseError {
+                            message: format!("Can't find key `{}` in map of kind `{:?}`", p1, kind),
+                            doc_id: doc.name.to_string(),
+                            line_number,
+                        })?
+                        .clone()
+                        .resolve_with_inherited(doc, line_number, inherited_variables)?;
+                    if let Some(p2) = p2 {
+                        return resolve_(
+                            p2.as_str(),
+                            &value,
+                            line_number,
+                            doc,
+                            inherited_variables,
+                        );
+                    }
+                    Ok(value)
+                }
                 t => ftd::interpreter::utils::e2(
                     format!("Expected record found `{:?}`", t).as_str(),
                     doc.name,
@@ -684,7 +717,7 @@ impl<'a> TDoc<'a> {
                         Ok(ftd::interpreter::StateWithThing::new_thing(field_kind))
                     }
                 }
-                fastn_resolved::Kind::List { kind } => {
+                fastn_resolved::Kind::List { kind } | fastn_resolved::Kind::Map { kind } => {
                     if let Some(remaining) = remaining {
                         get_kind_(*kind, &remaining, doc, line_number)
                     } else {
@@ -2076,6 +2109,29 @@ impl<'a> TDoc<'a> {
                     }
                 },
             },
+            fastn_resolved::Kind::DateTime | fastn_resolved::Kind::Date => {
+                let datetime = match json {
+                    serde_json::Value::String(s) => fastn_builtins::datetime::parse_datetime(s),
+                    serde_json::Value::Number(n) => n
+                        .as_i64()
+                        .and_then(fastn_builtins::datetime::datetime_from_timestamp),
+                    serde_json::Value::Object(o) => {
+                        return self.handle_object(kind, o, default_value, record_name, line_number)
+                    }
+                    _ => None,
+                };
+                let Some(datetime) = datetime else {
+                    return ftd::interpreter::utils::e2(
+                        format!("Can't parse to {}, found: {json}", kind.get_name()),
+                        self.name,
+                        line_number,
+                    );
+                };
+                match fastn_builtins::datetime::parse_date(&datetime) {
+                    Some(date) if kind.is_date() => fastn_resolved::Value::Date { value: date },
+                    _ => fastn_resolved::Value::DateTime { value: datetime },
+                }
+            }
             fastn_resolved::Kind::Record { name, .. } => {
                 let rec_fields = self.get_record(name, line_number)?.fields;
                 let mut fields: ftd::Map<fastn_resolved::PropertyValue> = Default::default();
@@ -2085,6 +2141,9 @@ impl<'a> TDoc<'a> {
                             Some(v) => v.to_owned(),
                             None if field.kind.is_optional() => serde_json::Value::Null,
                             None if field.kind.is_list() => serde_json::Value::Array(vec![]),
+                            None if field.kind.is_map() => {
+                                serde_json::Value::Object(Default::default())
+                            }
                             None => {
                                 return ftd::interpreter::utils::e2(
                                     format!("key not found: {}", field.name.as_str()),
@@ -2167,6 +2226,30 @@ impl<'a> TDoc<'a> {
                     kind: kind.to_owned().into_kind_data(),
                 }
             }
+            fastn_resolved::Kind::Map { kind } => {
+                let serde_json::Value::Object(o) = json else {
+                    return ftd::interpreter::utils::e2(
+                        format!("expected object of map type, found: {}", json),
+                        self.name,
+                        line_number,
+                    );
+                };
+                let mut data: ftd::Map<fastn_resolved::PropertyValue> = Default::default();
+                for (key, item) in o {
+                    data.insert(
+                        key.to_string(),
+                        fastn_resolved::PropertyValue::Value {
+                            value: self.as_json_(kind, item, None, None, line_number)?,
+                            is_mutable: false,
+                            line_number,
+                        },
+                    );
+                }
+                fastn_resolved::Value::Map {
+                    data,
+                    kind: kind.as_ref().to_owned().into_kind_data(),
+                }
+            }
             fastn_resolved::Kind::Optional { kind, .. } => {
                 let kind = kind.as_ref();
                 match json {
@@ -1550,9 +1550,11 @@ impl LoopExt for fastn_resolved::Loop {
     fn loop_object_kind(&self, doc_id: &str) -> ftd::interpreter::Result<fastn_resolved::Kind> {
         let kind = self.on.kind();
         match kind {
-            fastn_resolved::Kind::List { kind } => Ok(kind.as_ref().to_owned()),
+            fastn_resolved::Kind::List { kind } | fastn_resolved::Kind::Map { kind } => {
+                Ok(kind.as_ref().to_owned())
+            }
             t => ftd::interpreter::utils::e2(
-                format!("Expected list kind, found: {:?}", t),
+                format!("Expected list or map kind, found: {:?}", t),
                 doc_id,
                 self.line_number,
             ),
@@ -1586,6 +1588,8 @@ impl LoopExt for fastn_resolved::Loop {
         let value = self.on.clone().resolve(doc, self.line_number)?;
         if let fastn_resolved::Value::List { data, kind } = value {
             Ok((data, kind))
+        } else if let fastn_resolved::Value::Map { data, kind } = value {
+            Ok((data.into_values().collect(), kind))
         } else {
             ftd::interpreter::utils::e2(
                 format!("Expected list type data, found: {:?}", self.on),
@@ -218,7 +218,7 @@ impl ExpressionExt for fastn_resolved::Expression {
             );
         }
         let node = update_node_with_value(&self.expression, &values);
-        let mut context = ftd::interpreter::default::default_context()?;
+        let mut context = doc.evalexpr_context()?;
         Ok(node.eval_boolean_with_context_mut(&mut context)?)
     }
 
@@ -162,7 +162,7 @@ impl FunctionExt for fastn_resolved::Function {
             );
         }
 
-        let mut evalexpr_context = ftd::interpreter::default::default_context()?;
+        let mut evalexpr_context = doc.evalexpr_context()?;
         for (key, context) in context.iter() {
             evalexpr_context.set_value(key.to_string(), context.value.to_owned())?;
         }
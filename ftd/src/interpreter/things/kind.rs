@@ -47,8 +47,8 @@ impl KindDataExt for fastn_resolved::KindData {
     ) -> ftd::interpreter::Result<()> {
         let ast_kind = var_kind.kind;
         match ast_kind.as_ref() {
-            "string" | "object" | "integer" | "decimal" | "boolean" | "void" | "ftd.ui"
-            | "children" => Ok(()),
+            "string" | "object" | "integer" | "decimal" | "boolean" | "datetime" | "date"
+            | "void" | "ftd.ui" | "children" => Ok(()),
             k if known_kinds.contains_key(k) => Ok(()),
             k => doc.scan_thing(k, line_number),
         }
@@ -90,6 +90,8 @@ impl KindDataExt for fastn_resolved::KindData {
             "integer" => fastn_resolved::Kind::integer(),
             "decimal" => fastn_resolved::Kind::decimal(),
             "boolean" => fastn_resolved::Kind::boolean(),
+            "datetime" => fastn_resolved::Kind::datetime(),
+            "date" => fastn_resolved::Kind::date(),
             "void" => fastn_resolved::Kind::void(),
             "ftd.ui" => fastn_resolved::Kind::ui(),
             "module" => fastn_resolved::Kind::module(),
@@ -148,6 +150,7 @@ impl KindDataExt for fastn_resolved::KindData {
         match modifier {
             ftd_ast::VariableModifier::Optional => self.optional(),
             ftd_ast::VariableModifier::List => self.list(),
+            ftd_ast::VariableModifier::Map => self.map(),
             ftd_ast::VariableModifier::Constant => self.constant(),
         }
     }
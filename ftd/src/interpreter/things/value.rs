@@ -708,6 +708,45 @@ impl PropertyValueExt for fastn_resolved::PropertyValue {
                         line_number: value.line_number(),
                     },
                 ),
+                fastn_resolved::Kind::DateTime => {
+                    let text = value.string(doc.name)?;
+                    let Some(datetime) = fastn_builtins::datetime::parse_datetime(&text) else {
+                        return ftd::interpreter::utils::e2(
+                            format!(
+                                "Can't parse `{text}` as datetime, expected ISO 8601 like \
+                                2024-01-02T03:04:05Z"
+                            ),
+                            doc.name,
+                            value.line_number(),
+                        );
+                    };
+                    ftd::interpreter::StateWithThing::new_thing(
+                        fastn_resolved::PropertyValue::Value {
+                            value: fastn_resolved::Value::DateTime { value: datetime },
+                            is_mutable,
+                            line_number: value.line_number(),
+                        },
+                    )
+                }
+                fastn_resolved::Kind::Date => {
+                    let text = value.string(doc.name)?;
+                    let Some(date) = fastn_builtins::datetime::parse_date(&text) else {
+                        return ftd::interpreter::utils::e2(
+                            format!(
+                                "Can't parse `{text}` as date, expected ISO 8601 like 2024-01-02"
+                            ),
+                            doc.name,
+                            value.line_number(),
+                        );
+                    };
+                    ftd::interpreter::StateWithThing::new_thing(
+                        fastn_resolved::PropertyValue::Value {
+                            value: fastn_resolved::Value::Date { value: date },
+                            is_mutable,
+                            line_number: value.line_number(),
+                        },
+                    )
+                }
                 fastn_resolved::Kind::List { kind } => {
                     let line_number = value.line_number();
                     let value_list = value.into_list(doc.name, kind.get_name())?;
@@ -757,6 +796,48 @@ impl PropertyValueExt for fastn_resolved::PropertyValue {
                         },
                     )
                 }
+                fastn_resolved::Kind::Map { kind } => {
+                    let line_number = value.line_number();
+                    let ftd_ast::VariableValue::List { value: entries, .. } = value else {
+                        return ftd::interpreter::utils::e2(
+                            format!("Expected map of `{:?}`, found: `{:?}`", kind, value),
+                            doc.name,
+                            line_number,
+                        );
+                    };
+                    let mut data: ftd::Map<fastn_resolved::PropertyValue> = Default::default();
+                    for entry in entries {
+                        let (key, value) = (entry.key, entry.value);
+                        if data.contains_key(&key) {
+                            return ftd::interpreter::utils::e2(
+                                format!("Duplicate key `{}` in map", key),
+                                doc.name,
+                                value.line_number(),
+                            );
+                        }
+                        let value = try_ok_state!(fastn_resolved::PropertyValue::from_ast_value(
+                            value,
+                            doc,
+                            is_mutable,
+                            Some(&fastn_resolved::KindData {
+                                kind: kind.as_ref().clone(),
+                                caption: expected_kind.caption,
+                                body: expected_kind.body,
+                            }),
+                        )?);
+                        data.insert(key, value);
+                    }
+                    ftd::interpreter::StateWithThing::new_thing(
+                        fastn_resolved::PropertyValue::Value {
+                            value: fastn_resolved::Value::Map {
+                                data,
+                                kind: expected_kind.clone().inner_map(),
+                            },
+                            is_mutable,
+                            line_number,
+                        },
+                    )
+                }
                 fastn_resolved::Kind::Record { name } if value.is_record() || value.is_string() => {
                     let record = try_ok_state!(doc.search_record(name, value.line_number())?);
                     fastn_resolved::PropertyValue::from_record(
@@ -1693,6 +1774,9 @@ impl ValueExt for fastn_resolved::Value {
             fastn_resolved::Value::Boolean { value } => {
                 Ok(fastn_resolved::evalexpr::Value::Boolean(value))
             }
+            fastn_resolved::Value::DateTime { value } | fastn_resolved::Value::Date { value } => {
+                Ok(fastn_resolved::evalexpr::Value::String(value))
+            }
             fastn_resolved::Value::Optional { data, .. } => {
                 if let Some(data) = data.as_ref() {
                     data.clone().into_evalexpr_value(doc)
@@ -1719,6 +1803,17 @@ impl ValueExt for fastn_resolved::Value {
                 }
                 Ok(fastn_resolved::evalexpr::Value::Tuple(values))
             }
+            fastn_resolved::Value::Map { data, .. } => {
+                let mut entries = vec![];
+                for (key, item) in data {
+                    let line_number = item.line_number();
+                    entries.push(fastn_resolved::evalexpr::Value::Tuple(vec![
+                        fastn_resolved::evalexpr::Value::String(key),
+                        item.resolve(doc, line_number)?.into_evalexpr_value(doc)?,
+                    ]));
+                }
+                Ok(fastn_resolved::evalexpr::Value::Tuple(entries))
+            }
             t => unimplemented!("{:?}", t),
         }
     }
@@ -1741,6 +1836,9 @@ impl ValueExt for fastn_resolved::Value {
             fastn_resolved::Value::Boolean { value } => {
                 fastn_resolved::evalexpr::Value::Boolean(*value)
             }
+            fastn_resolved::Value::DateTime { value } | fastn_resolved::Value::Date { value } => {
+                fastn_resolved::evalexpr::Value::String(value.to_string())
+            }
             fastn_resolved::Value::List { data, .. } => {
                 let mut values = vec![];
                 for value in data {
@@ -1752,6 +1850,21 @@ impl ValueExt for fastn_resolved::Value {
                 }
                 fastn_resolved::evalexpr::Value::Tuple(values)
             }
+            // A tuple of `(key, value)` tuples, see `fastn_builtins::functions::get`
+            fastn_resolved::Value::Map { data, .. } => {
+                let mut entries = vec![];
+                for (key, value) in data {
+                    let v = value
+                        .clone()
+                        .resolve(doc, line_number)?
+                        .to_evalexpr_value(doc, value.line_number())?;
+                    entries.push(fastn_resolved::evalexpr::Value::Tuple(vec![
+                        fastn_resolved::evalexpr::Value::String(key.to_string()),
+                        v,
+                    ]));
+                }
+                fastn_resolved::evalexpr::Value::Tuple(entries)
+            }
             fastn_resolved::Value::Optional { data, .. } => {
                 if let Some(data) = data.as_ref() {
                     data.to_evalexpr_value(doc, line_number)?
@@ -1782,6 +1895,30 @@ impl ValueExt for fastn_resolved::Value {
             fastn_resolved::evalexpr::Value::Boolean(value) if expected_kind.is_boolean() => {
                 fastn_resolved::Value::Boolean { value }
             }
+            fastn_resolved::evalexpr::Value::String(text) if expected_kind.is_datetime() => {
+                match fastn_builtins::datetime::parse_datetime(&text) {
+                    Some(value) => fastn_resolved::Value::DateTime { value },
+                    None => {
+                        return ftd::interpreter::utils::e2(
+                            format!("Can't parse `{text}` as datetime"),
+                            doc_name,
+                            line_number,
+                        )
+                    }
+                }
+            }
+            fastn_resolved::evalexpr::Value::String(text) if expected_kind.is_date() => {
+                match fastn_builtins::datetime::parse_date(&text) {
+                    Some(value) => fastn_resolved::Value::Date { value },
+                    None => {
+                        return ftd::interpreter::utils::e2(
+                            format!("Can't parse `{text}` as date"),
+                            doc_name,
+                            line_number,
+                        )
+                    }
+                }
+            }
             fastn_resolved::evalexpr::Value::Tuple(data) if expected_kind.is_list() => {
                 let mut values = vec![];
                 let val_kind = expected_kind.list_type(doc_name, line_number)?;
@@ -1802,6 +1939,49 @@ impl ValueExt for fastn_resolved::Value {
                     kind: fastn_resolved::KindData::new(val_kind),
                 }
             }
+            fastn_resolved::evalexpr::Value::Tuple(entries) if expected_kind.is_map() => {
+                let val_kind = expected_kind.clone().inner_map();
+                let mut data: ftd::Map<fastn_resolved::PropertyValue> = Default::default();
+                for entry in entries {
+                    let (key, value) = match entry {
+                        fastn_resolved::evalexpr::Value::Tuple(pair) if pair.len() == 2 => {
+                            let mut pair = pair.into_iter();
+                            (pair.next().unwrap(), pair.next().unwrap())
+                        }
+                        t => {
+                            return ftd::interpreter::utils::e2(
+                                format!("Expected map entry `(key, value)`, found: `{:?}`", t),
+                                doc_name,
+                                line_number,
+                            )
+                        }
+                    };
+                    let fastn_resolved::evalexpr::Value::String(key) = key else {
+                        return ftd::interpreter::utils::e2(
+                            format!("Expected map key to be string, found: `{:?}`", key),
+                            doc_name,
+                            line_number,
+                        );
+                    };
+                    data.insert(
+                        key,
+                        fastn_resolved::PropertyValue::Value {
+                            value: fastn_resolved::Value::from_evalexpr_value(
+                                value,
+                                &val_kind,
+                                doc_name,
+                                line_number,
+                            )?,
+                            is_mutable: false,
+                            line_number,
+                        },
+                    );
+                }
+                fastn_resolved::Value::Map {
+                    data,
+                    kind: fastn_resolved::KindData::new(val_kind),
+                }
+            }
             fastn_resolved::evalexpr::Value::Empty if expected_kind.is_optional() => {
                 fastn_resolved::Value::Optional {
                     data: Box::new(None),
@@ -1835,6 +2015,7 @@ impl ValueExt for fastn_resolved::Value {
             }
             fastn_resolved::Value::Record { fields, .. }
             | fastn_resolved::Value::Object { values: fields, .. }
+            | fastn_resolved::Value::Map { data: fields, .. }
             | fastn_resolved::Value::KwArgs {
                 arguments: fields, ..
             } => {
@@ -1921,6 +2102,9 @@ impl ValueExt for fastn_resolved::Value {
             fastn_resolved::Value::Boolean { value } => {
                 Ok(Some(serde_json::Value::Bool(value.to_owned())))
             }
+            fastn_resolved::Value::DateTime { value } | fastn_resolved::Value::Date { value } => {
+                Ok(Some(serde_json::Value::String(value.to_owned())))
+            }
             fastn_resolved::Value::Optional { data, .. } => {
                 if let Some(data) = data.as_ref() {
                     data.to_serde_value(doc)
@@ -1948,7 +2132,8 @@ impl ValueExt for fastn_resolved::Value {
                 }
                 Ok(Some(serde_json::to_value(&new_values)?))
             }
-            fastn_resolved::Value::Record { fields, .. } => {
+            fastn_resolved::Value::Record { fields, .. }
+            | fastn_resolved::Value::Map { data: fields, .. } => {
                 let mut new_values: ftd::Map<serde_json::Value> = Default::default();
                 for (k, v) in fields {
                     let resolved_value = v.clone().resolve(doc, 0)?;
@@ -2006,6 +2191,13 @@ impl ValueExt for fastn_resolved::Value {
             fastn_resolved::Value::Integer { value } => Ok(Some(value.to_string())),
             fastn_resolved::Value::Decimal { value } => Ok(Some(value.to_string())),
             fastn_resolved::Value::Boolean { value } => Ok(Some(value.to_string())),
+            fastn_resolved::Value::DateTime { value } | fastn_resolved::Value::Date { value } => {
+                if use_quotes {
+                    Ok(Some(format!("\"{}\"", value)))
+                } else {
+                    Ok(Some(value.to_string()))
+                }
+            }
             fastn_resolved::Value::Optional { data, .. } => {
                 if let Some(data) = data.as_ref() {
                     data.to_json_string(doc, use_quotes)
@@ -2016,6 +2208,7 @@ impl ValueExt for fastn_resolved::Value {
             fastn_resolved::Value::Object { .. }
             | fastn_resolved::Value::Record { .. }
             | fastn_resolved::Value::List { .. }
+            | fastn_resolved::Value::Map { .. }
             | fastn_resolved::Value::KwArgs { .. } => {
                 Ok(Some(serde_json::to_string(&self.to_serde_value(doc)?)?))
             }
@@ -381,6 +381,30 @@ pub fn get_argument_for_reference_and_remaining(
                 fastn_resolved::PropertyValueSource::Loop(loop_name.to_string()),
             )));
         }
+        if name.starts_with(format!("{}#{}", doc.name, ftd::interpreter::FTD_LOOP_KEY).as_str()) {
+            if !loop_argument
+                .value
+                .as_ref()
+                .is_some_and(|on| on.kind().inner().is_map())
+            {
+                return ftd::interpreter::utils::e2(
+                    format!(
+                        "`${}` is only available in a loop over a map",
+                        ftd::interpreter::FTD_LOOP_KEY
+                    ),
+                    doc.name,
+                    line_number,
+                );
+            }
+            return Ok(Some((
+                fastn_resolved::Field::default(
+                    ftd::interpreter::FTD_LOOP_KEY,
+                    fastn_resolved::Kind::string().into_kind_data(),
+                ),
+                None,
+                fastn_resolved::PropertyValueSource::Loop(loop_name.to_string()),
+            )));
+        }
 
         if let Some(loop_counter_alias) = loop_counter_alias {
             if name.starts_with(loop_counter_alias.as_str()) {
@@ -545,7 +569,13 @@ pub(crate) fn get_value(
         Some(fastn_resolved::Value::Integer { value }) => serde_json::to_value(value).ok(),
         Some(fastn_resolved::Value::String { text: value, .. }) => serde_json::to_value(value).ok(),
         Some(fastn_resolved::Value::Decimal { value, .. }) => serde_json::to_value(value).ok(),
-        Some(fastn_resolved::Value::Record { fields, .. }) => {
+        Some(fastn_resolved::Value::DateTime { value } | fastn_resolved::Value::Date { value }) => {
+            serde_json::to_value(value).ok()
+        }
+        Some(
+            fastn_resolved::Value::Record { fields, .. }
+            | fastn_resolved::Value::Map { data: fields, .. },
+        ) => {
             let mut value_fields = ftd::Map::new();
             for (k, v) in fields {
                 if let Some(value) = get_value(doc, &v.clone().resolve(doc, v.line_number())?)? {
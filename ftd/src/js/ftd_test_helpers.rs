@@ -355,3 +355,99 @@ fn filename_with_second_last_extension_replaced_with_json(
         path.with_file_name(format!("{}.error", stem)),
     )
 }
+
+/// The cases of `t/functions.json`, `[function, arguments, result]`, run against
+/// `fastn_builtins::functions` and `fastn_builtins::datetime`, and the same functions of
+/// `fastn-js/js/ftd.js`, which have to agree. A `result` of `{"error": true}` is an error. Both
+/// run in a page in German at `UTC+05:30`, the javascript in server side rendering.
+#[test]
+fn functions_parity() {
+    use fastn_resolved::evalexpr::{Context, ContextWithMutableFunctions, HashMapContext, Value};
+
+    fn to_value(json: &serde_json::Value) -> Value {
+        match json {
+            serde_json::Value::Null => Value::Empty,
+            serde_json::Value::Bool(b) => Value::Boolean(*b),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => Value::Int(i),
+                None => Value::Float(n.as_f64().unwrap()),
+            },
+            serde_json::Value::String(s) => Value::String(s.to_string()),
+            serde_json::Value::Array(items) => Value::Tuple(items.iter().map(to_value).collect()),
+            serde_json::Value::Object(_) => unreachable!(),
+        }
+    }
+
+    fn to_json(value: Value) -> serde_json::Value {
+        match value {
+            Value::Empty => serde_json::Value::Null,
+            Value::Boolean(b) => b.into(),
+            Value::Int(i) => (i as f64).into(),
+            Value::Float(f) => f.into(),
+            Value::String(s) => s.into(),
+            Value::Tuple(items) => items.into_iter().map(to_json).collect(),
+        }
+    }
+
+    // `1` and `1.0` are the same number in javascript
+    fn normalize(json: serde_json::Value) -> serde_json::Value {
+        match json {
+            serde_json::Value::Number(n) => n.as_f64().unwrap().into(),
+            serde_json::Value::Array(items) => items.into_iter().map(normalize).collect(),
+            json => json,
+        }
+    }
+
+    let table = std::fs::read_to_string("./t/functions.json").unwrap();
+    let cases: Vec<(String, Vec<serde_json::Value>, serde_json::Value)> =
+        serde_json::from_str(table.as_str()).unwrap();
+
+    let mut context = HashMapContext::new();
+    let settings = fastn_builtins::datetime::Settings {
+        language: Some("de".to_string()),
+        utc_offset: 330,
+    };
+    for (key, function) in fastn_builtins::default_functions_with(&settings) {
+        context.set_function(key, function).unwrap();
+    }
+    let rust = cases.iter().map(|(function, arguments, _)| {
+        let argument = match arguments.as_slice() {
+            [argument] => to_value(argument),
+            arguments => Value::Tuple(arguments.iter().map(to_value).collect()),
+        };
+        match context.call_function(format!("ftd.{function}").as_str(), &argument) {
+            Ok(value) => to_json(value),
+            Err(_) => serde_json::json!({"error": true}),
+        }
+    });
+
+    let js = fastn_js::ssr_str(
+        format!(
+            r#"
+            let __fastn_package_name__ = "foo";
+            let __fastn_language__ = "de";
+            let __fastn_utc_offset__ = 330;
+            ssr = true;
+            JSON.stringify({table}.map(([f, args]) => {{
+                try {{
+                    args = args.map((a) => (Array.isArray(a) ? fastn.mutableList(a) : a));
+                    return fastn_utils.getFlattenStaticValue(ftd[f](...args));
+                }} catch (e) {{
+                    return {{ error: true }};
+                }}
+            }}));
+            "#
+        )
+        .as_str(),
+    )
+    .unwrap();
+    let js: Vec<serde_json::Value> = serde_json::from_str(js.as_str()).unwrap();
+    assert_eq!(js.len(), cases.len());
+
+    for (((function, arguments, expected), rust), js) in cases.iter().zip(rust).zip(js) {
+        let case = format!("ftd.{function} {}", serde_json::json!(arguments));
+        let expected = normalize(expected.clone());
+        assert_eq!(normalize(rust), expected, "{case} in rust");
+        assert_eq!(normalize(js), expected, "{case} in javascript");
+    }
+}
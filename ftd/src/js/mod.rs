@@ -80,9 +80,23 @@ pub struct JSAstData {
     /// This contains external scripts provided by user and also `ftd`
     /// internally supports (like rive).
     pub scripts: Vec<String>,
+    /// Asts of the components and functions of each module the document imports, by module name.
+    /// Empty unless `document_into_js_ast_by_module` is used, `asts` has them otherwise.
+    pub modules: ftd::Map<Vec<fastn_js::Ast>>,
 }
 
 pub fn document_into_js_ast(document: ftd::interpreter::Document) -> JSAstData {
+    document_into_js_ast_(document, false)
+}
+
+/// Like `document_into_js_ast`, but the components and functions of the imported modules are in
+/// `modules`, so they can be shipped once for all the documents using them. The variables of the
+/// modules stay in `asts`, their values can depend on the request.
+pub fn document_into_js_ast_by_module(document: ftd::interpreter::Document) -> JSAstData {
+    document_into_js_ast_(document, true)
+}
+
+fn document_into_js_ast_(document: ftd::interpreter::Document, by_module: bool) -> JSAstData {
     use fastn_runtime::extensions::*;
     use itertools::Itertools;
 
@@ -101,11 +115,27 @@ pub fn document_into_js_ast(document: ftd::interpreter::Document) -> JSAstData {
         .collect_vec();
 
     let mut export_asts = vec![];
+    let mut modules: ftd::Map<Vec<fastn_js::Ast>> = Default::default();
 
     for (key, thing) in document.data.iter() {
         if default_thing_name.contains(&key) {
             continue;
         }
+        let document_asts = match key.split_once('#') {
+            Some((module, _))
+                if by_module
+                    && module != document.name
+                    && matches!(
+                        thing,
+                        ftd::interpreter::Thing::Component(_)
+                            | ftd::interpreter::Thing::WebComponent(_)
+                            | ftd::interpreter::Thing::Function(_)
+                    ) =>
+            {
+                modules.entry(module.to_string()).or_default()
+            }
+            _ => &mut document_asts,
+        };
         if let ftd::interpreter::Thing::Component(c) = thing {
             document_asts.push(c.to_ast(&doc, &mut has_rive_components));
         } else if let ftd::interpreter::Thing::Variable(v) = thing {
@@ -168,5 +198,6 @@ pub fn document_into_js_ast(document: ftd::interpreter::Document) -> JSAstData {
     JSAstData {
         asts: document_asts,
         scripts,
+        modules,
     }
 }